@@ -80,6 +80,7 @@ impl ContainerBoxHeader {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ContainerBoxType(pub [u8; 4]);
 
 impl ContainerBoxType {
@@ -95,3 +96,54 @@ impl ContainerBoxType {
     pub const PARTIAL_CODESTREAM: Self = Self(*b"jxlp");
     pub const JPEG_RECONSTRUCTION: Self = Self(*b"jbrd");
 }
+
+/// Location and size of a box in the container.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContainerBoxInfo {
+    ty: ContainerBoxType,
+    offset: u64,
+    header_size: u64,
+    size: Option<u64>,
+}
+
+impl ContainerBoxInfo {
+    #[inline]
+    pub(crate) fn new(
+        ty: ContainerBoxType,
+        offset: u64,
+        header_size: u64,
+        size: Option<u64>,
+    ) -> Self {
+        Self {
+            ty,
+            offset,
+            header_size,
+            size,
+        }
+    }
+
+    /// Returns the type of the box.
+    #[inline]
+    pub fn box_type(&self) -> ContainerBoxType {
+        self.ty
+    }
+
+    /// Returns the offset of the box in the file, including the box header.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the size of the box header.
+    #[inline]
+    pub fn header_size(&self) -> u64 {
+        self.header_size
+    }
+
+    /// Returns the size of the box payload, or `None` if the box extends to the end of the file.
+    #[inline]
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+}
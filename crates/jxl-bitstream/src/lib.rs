@@ -6,6 +6,8 @@ mod error;
 mod macros;
 mod memory;
 mod reader;
+#[cfg(feature = "serde")]
+pub mod serde_array;
 
 pub use container::*;
 pub use error::{Error, Result};
@@ -47,6 +49,8 @@ where
 
 /// Name type which is read by some JPEG XL headers.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Name(String);
 
 impl<Ctx> Bundle<Ctx> for Name {
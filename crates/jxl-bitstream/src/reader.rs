@@ -7,6 +7,8 @@ pub struct ContainerDetectingReader {
     buf: Vec<u8>,
     codestream: Vec<u8>,
     aux_boxes: Vec<(ContainerBoxType, Vec<u8>)>,
+    boxes: Vec<ContainerBoxInfo>,
+    bytes_fed: u64,
     next_jxlp_index: u32,
 }
 
@@ -39,6 +41,7 @@ enum DetectState {
 
 /// Structure of the decoded bitstream.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BitstreamKind {
     /// Decoder can't determine structure of the bitstream.
     Unknown,
@@ -68,10 +71,26 @@ impl ContainerDetectingReader {
         }
     }
 
+    /// Returns the boxes found so far, in file order, including the signature box.
+    ///
+    /// The list is empty if the bitstream is a bare codestream.
+    pub fn boxes(&self) -> &[ContainerBoxInfo] {
+        &self.boxes
+    }
+
+    /// Returns the types and contents of auxiliary boxes read so far, in file order.
+    ///
+    /// Codestream boxes are not included. Contents of the last box are available after
+    /// [`finish`][Self::finish] if it extends to the end of the file.
+    pub fn aux_boxes(&self) -> &[(ContainerBoxType, Vec<u8>)] {
+        &self.aux_boxes
+    }
+
     pub fn feed_bytes(&mut self, input: &[u8]) -> std::io::Result<()> {
         let state = &mut self.state;
         let buf = &mut self.buf;
         buf.extend_from_slice(input);
+        self.bytes_fed += input.len() as u64;
 
         loop {
             match state {
@@ -88,6 +107,12 @@ impl ContainerDetectingReader {
                         tracing::debug!("Container signature found");
                         *state = DetectState::WaitingBoxHeader;
                         buf.drain(..Self::CONTAINER_SIG.len());
+                        self.boxes.push(ContainerBoxInfo::new(
+                            ContainerBoxType::JXL,
+                            0,
+                            8,
+                            Some(4),
+                        ));
                         continue;
                     }
                     if !Self::CODESTREAM_SIG.starts_with(buf)
@@ -104,6 +129,13 @@ impl ContainerDetectingReader {
                 }
                 DetectState::WaitingBoxHeader => match ContainerBoxHeader::parse(buf)? {
                     HeaderParseResult::Done { header, size } => {
+                        let offset = self.bytes_fed - buf.len() as u64;
+                        self.boxes.push(ContainerBoxInfo::new(
+                            header.box_type(),
+                            offset,
+                            size as u64,
+                            header.size(),
+                        ));
                         buf.drain(..size);
                         let tbox = header.box_type();
                         if tbox == ContainerBoxType::CODESTREAM {
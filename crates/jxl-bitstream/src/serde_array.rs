@@ -0,0 +1,55 @@
+//! Serde helpers for fixed-size arrays, which serde supports only up to 32 elements.
+//!
+//! Use with `#[serde(with = "jxl_bitstream::serde_array")]`.
+
+use std::marker::PhantomData;
+
+use serde::{
+    de::{Error, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+pub fn serialize<S, T, const N: usize>(value: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    let mut tuple = serializer.serialize_tuple(N)?;
+    for item in value {
+        tuple.serialize_element(item)?;
+    }
+    tuple.end()
+}
+
+pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for ArrayVisitor<T, N> {
+        type Value = [T; N];
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "an array of length {N}")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut items = Vec::with_capacity(N);
+            while let Some(item) = seq.next_element()? {
+                if items.len() == N {
+                    return Err(A::Error::invalid_length(N + 1, &self));
+                }
+                items.push(item);
+            }
+            let len = items.len();
+            items
+                .try_into()
+                .map_err(|_| A::Error::invalid_length(len, &self))
+        }
+    }
+
+    deserializer.deserialize_tuple(N, ArrayVisitor::<T, N>(PhantomData))
+}
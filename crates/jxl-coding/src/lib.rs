@@ -359,7 +359,9 @@ impl IntegerConfig {
 
 #[derive(Debug, Clone)]
 struct DecoderInner {
-    clusters: Vec<u8>,           // num_dist, [0, num_clusters)
+    // Shared between clones, so that the cluster map sized by the bitstream is not copied when
+    // the decoder is cloned for each group.
+    clusters: Arc<[u8]>,         // num_dist, [0, num_clusters)
     configs: Vec<IntegerConfig>, // num_clusters
     code: Coder,
 }
@@ -407,7 +409,7 @@ impl DecoderInner {
             }
         };
         Ok(Self {
-            clusters,
+            clusters: clusters.into(),
             configs,
             code,
         })
@@ -1,7 +1,11 @@
+use jxl_bitstream::BundleDefault;
+
 use crate::{
-    ciexyz::*, consts::*, icc::colour_encoding_to_icc, tf, ColorManagementSystem, ColourEncoding,
-    ColourSpace, EnumColourEncoding, Error, OpsinInverseMatrix, RenderingIntent, Result,
-    ToneMapping, TransferFunction,
+    ciexyz::*,
+    consts::*,
+    icc::{colour_encoding_to_icc, IccMatchTolerance},
+    tf, ColorManagementSystem, ColourEncoding, ColourSpace, EnumColourEncoding, Error,
+    OpsinInverseMatrix, RenderingIntent, Result, ToneMapping, TransferFunction,
 };
 
 mod gamut_map;
@@ -37,11 +41,23 @@ impl ColorEncodingWithProfile {
 
     /// Creates a color encoding from ICC profile.
     ///
+    /// The profile is represented as an enum color encoding only if it matches one exactly. Use
+    /// [`with_icc_tolerance`][Self::with_icc_tolerance] to match approximately.
+    ///
     /// # Errors
     /// This function will return an error if it cannot parse the ICC profile.
     pub fn with_icc(icc_profile: &[u8]) -> Result<Self> {
-        match crate::icc::parse_icc(icc_profile) {
-            Ok(encoding) => Ok(Self::new(encoding)),
+        Self::with_icc_tolerance(icc_profile, IccMatchTolerance::EXACT)
+    }
+
+    /// Creates a color encoding from ICC profile, matching it to an enum color encoding within
+    /// the given tolerance.
+    ///
+    /// # Errors
+    /// This function will return an error if it cannot parse the ICC profile.
+    pub fn with_icc_tolerance(icc_profile: &[u8], tolerance: IccMatchTolerance) -> Result<Self> {
+        match crate::icc::match_enum_encoding(icc_profile, tolerance) {
+            Ok(matched) => Ok(Self::new(matched.encoding)),
             Err(Error::UnsupportedIccProfile) => {
                 let raw = crate::icc::parse_icc_raw(icc_profile)?;
                 Ok(Self {
@@ -113,6 +129,9 @@ impl ColorEncodingWithProfile {
 pub struct ColorTransformBuilder {
     detect_peak: bool,
     srgb_icc: bool,
+    extended_linear: Option<f32>,
+    hlg_display_luminance: Option<f32>,
+    hlg_scene_referred: bool,
 }
 
 impl Default for ColorTransformBuilder {
@@ -127,6 +146,9 @@ impl ColorTransformBuilder {
         Self {
             detect_peak: false,
             srgb_icc: false,
+            extended_linear: None,
+            hlg_display_luminance: None,
+            hlg_scene_referred: false,
         }
     }
 
@@ -140,6 +162,37 @@ impl ColorTransformBuilder {
         self
     }
 
+    /// Sets whether to output unclamped linear samples in absolute luminance.
+    ///
+    /// If `Some(reference_white)` is given, linear sample value of 1.0 in the output represents
+    /// `reference_white` nits, and clipping, gamut mapping and tone mapping are skipped. Samples
+    /// may be negative or larger than 1.0. Use `80.0` for scRGB.
+    ///
+    /// The target color encoding should be an enum color encoding with linear transfer function.
+    /// Note that ICC to ICC transformation done by CMS may still clamp the samples.
+    pub fn set_extended_linear(&mut self, reference_white: Option<f32>) -> &mut Self {
+        self.extended_linear = reference_white;
+        self
+    }
+
+    /// Sets the nominal peak luminance of the display, in nits, used by HLG OOTF.
+    ///
+    /// System gamma of HLG OOTF is derived from this value as described in Rec. ITU-R BT.2100,
+    /// Table 5, Note 5e. If `None` is given, intensity target of the image is used.
+    pub fn set_hlg_display_luminance(&mut self, luminance: Option<f32>) -> &mut Self {
+        self.hlg_display_luminance = luminance;
+        self
+    }
+
+    /// Sets whether HLG signals are treated as scene-referred.
+    ///
+    /// If `true`, HLG OOTF (and its inverse) is not applied, and linear samples are converted to
+    /// and from HLG signals using only the OETF.
+    pub fn set_hlg_scene_referred(&mut self, value: bool) -> &mut Self {
+        self.hlg_scene_referred = value;
+        self
+    }
+
     pub fn build(
         self,
         from: &ColorEncodingWithProfile,
@@ -193,6 +246,9 @@ impl ColorTransform {
         let ColorTransformBuilder {
             detect_peak,
             srgb_icc,
+            extended_linear,
+            hlg_display_luminance,
+            hlg_scene_referred,
         } = builder;
         let connecting_tf = if srgb_icc {
             TransferFunction::Srgb
@@ -202,6 +258,16 @@ impl ColorTransform {
 
         let intensity_target = tone_mapping.intensity_target;
         let min_nits = tone_mapping.min_nits;
+        let hlg_display_luminance = if hlg_scene_referred {
+            None
+        } else if let Some(luminance) = hlg_display_luminance {
+            if !(luminance.is_finite() && luminance > 0.0) {
+                return Err(Error::InvalidDisplayLuminance(luminance));
+            }
+            Some(luminance)
+        } else {
+            Some(intensity_target)
+        };
 
         let begin_channels = match &from.encoding {
             ColourEncoding::Enum(EnumColourEncoding {
@@ -223,7 +289,23 @@ impl ColorTransform {
             }
         };
 
-        if from.is_equivalent(to) {
+        if let Some(reference_white) = extended_linear {
+            if !matches!(
+                &to.encoding,
+                ColourEncoding::Enum(EnumColourEncoding {
+                    colour_space: ColourSpace::Rgb | ColourSpace::Grey,
+                    tf: TransferFunction::Linear,
+                    ..
+                })
+            ) {
+                return Err(Error::UnsupportedColorEncoding);
+            }
+            if !(reference_white.is_finite() && reference_white > 0.0) {
+                return Err(Error::InvalidReferenceWhite(reference_white));
+            }
+        }
+
+        if from.is_equivalent(to) && extended_linear.is_none() {
             return Ok(Self {
                 begin_channels,
                 ops: Vec::new(),
@@ -303,6 +385,7 @@ impl ColorTransform {
                         luminances,
                         intensity_target,
                         min_nits,
+                        hlg_display_luminance,
                     },
                     inverse: true,
                 });
@@ -330,6 +413,7 @@ impl ColorTransform {
                         luminances,
                         intensity_target,
                         min_nits,
+                        hlg_display_luminance,
                     },
                     inverse: true,
                 });
@@ -369,6 +453,7 @@ impl ColorTransform {
                     luminances: [0.0, 0.0, 0.0],
                     intensity_target,
                     min_nits,
+                    hlg_display_luminance,
                 },
                 inverse: true,
             });
@@ -380,20 +465,23 @@ impl ColorTransform {
             || (current_encoding.colour_space == ColourSpace::Rgb
                 && current_encoding.primaries != target_encoding.primaries)
         {
-            if current_encoding.rendering_intent == RenderingIntent::Perceptual {
-                let illuminant = current_encoding.white_point.as_chromaticity();
-                let mat = crate::ciexyz::primaries_to_xyz_mat(
-                    current_encoding.primaries.as_chromaticity(),
-                    illuminant,
-                );
-                let luminances = [mat[3], mat[4], mat[5]];
+            // Out-of-gamut samples are kept as-is if extended range output is requested.
+            if extended_linear.is_none() {
+                if current_encoding.rendering_intent == RenderingIntent::Perceptual {
+                    let illuminant = current_encoding.white_point.as_chromaticity();
+                    let mat = crate::ciexyz::primaries_to_xyz_mat(
+                        current_encoding.primaries.as_chromaticity(),
+                        illuminant,
+                    );
+                    let luminances = [mat[3], mat[4], mat[5]];
 
-                ops.push(ColorTransformOp::GamutMap {
-                    luminances,
-                    saturation_factor: 0.3,
-                });
-            } else {
-                ops.push(ColorTransformOp::Clip);
+                    ops.push(ColorTransformOp::GamutMap {
+                        luminances,
+                        saturation_factor: 0.3,
+                    });
+                } else {
+                    ops.push(ColorTransformOp::Clip);
+                }
             }
 
             match current_encoding.colour_space {
@@ -460,9 +548,16 @@ impl ColorTransform {
             luminances,
             intensity_target,
             min_nits,
+            hlg_display_luminance,
         };
 
-        if intensity_target > 255.0 && !target_encoding.is_hdr() {
+        if let Some(reference_white) = extended_linear {
+            // Linear sample value of 1.0 represents `intensity_target` nits at this point.
+            let scale = intensity_target / reference_white;
+            if scale != 1.0 {
+                ops.push(ColorTransformOp::Scale(scale));
+            }
+        } else if intensity_target > 255.0 && !target_encoding.is_hdr() {
             if current_encoding.colour_space == ColourSpace::Grey {
                 ops.push(ColorTransformOp::ToneMapLumaRec2408 {
                     hdr_params,
@@ -647,6 +742,32 @@ impl ColorTransform {
     }
 }
 
+/// Converts samples from a color encoding to another in place.
+///
+/// Tone mapping and gamut mapping are done as while rendering, assuming that sample value 1.0 is
+/// `intensity_target` nits. If `from` is grayscale, only the first channel is read as input, and
+/// if `to` is grayscale, only the first channel is written as output.
+///
+/// # Errors
+/// Returns an error if the conversion is not supported, or the CMS failed to convert between ICC
+/// profiles.
+pub fn convert_color<Cms: ColorManagementSystem + ?Sized>(
+    from: &ColorEncodingWithProfile,
+    to: &ColorEncodingWithProfile,
+    channels: [&mut [f32]; 3],
+    intensity_target: f32,
+    cms: &Cms,
+) -> Result<()> {
+    let oim = OpsinInverseMatrix::default_with_context(());
+    let mut tone_mapping = ToneMapping::default_with_context(());
+    tone_mapping.intensity_target = intensity_target;
+    let transform = ColorTransform::new(from, to, &oim, &tone_mapping)?;
+
+    let [a, b, c] = channels;
+    transform.run(&mut [a, b, c], cms)?;
+    Ok(())
+}
+
 #[derive(Clone)]
 enum ColorTransformOp {
     XybToMixedLms {
@@ -658,6 +779,7 @@ enum ColorTransformOp {
     },
     XyzToLuma,
     Matrix([f32; 9]),
+    Scale(f32),
     TransferFunction {
         tf: TransferFunction,
         hdr_params: HdrParams,
@@ -704,6 +826,7 @@ impl std::fmt::Debug for ColorTransformOp {
                 .finish(),
             Self::XyzToLuma => f.debug_struct("XyzToLuma").finish(),
             Self::Matrix(arg0) => f.debug_tuple("Matrix").field(arg0).finish(),
+            Self::Scale(arg0) => f.debug_tuple("Scale").field(arg0).finish(),
             Self::TransferFunction {
                 tf,
                 hdr_params,
@@ -768,6 +891,7 @@ impl ColorTransformOp {
             ColorTransformOp::XybToMixedLms { .. } | ColorTransformOp::Matrix(_) => Some(3),
             ColorTransformOp::LumaToXyz { .. } => Some(1),
             ColorTransformOp::XyzToLuma => Some(3),
+            ColorTransformOp::Scale(_) => None,
             ColorTransformOp::TransferFunction {
                 tf: TransferFunction::Hlg,
                 ..
@@ -788,6 +912,7 @@ impl ColorTransformOp {
             ColorTransformOp::XybToMixedLms { .. } | ColorTransformOp::Matrix(_) => Some(3),
             ColorTransformOp::LumaToXyz { .. } => Some(3),
             ColorTransformOp::XyzToLuma => Some(1),
+            ColorTransformOp::Scale(_) => None,
             ColorTransformOp::TransferFunction {
                 tf: TransferFunction::Hlg,
                 ..
@@ -860,6 +985,14 @@ impl ColorTransformOp {
                 }
                 3
             }
+            Self::Scale(scale) => {
+                for buf in &mut channels[..num_input_channels] {
+                    for v in buf.iter_mut() {
+                        *v *= scale;
+                    }
+                }
+                num_input_channels
+            }
             Self::TransferFunction {
                 tf,
                 hdr_params,
@@ -930,6 +1063,8 @@ struct HdrParams {
     luminances: [f32; 3],
     intensity_target: f32,
     min_nits: f32,
+    /// Nominal peak luminance of the display used by HLG OOTF; `None` skips OOTF.
+    hlg_display_luminance: Option<f32>,
 }
 
 fn apply_transfer_function(
@@ -988,7 +1123,14 @@ fn apply_transfer_function(
             let luminances = hdr_params.luminances;
             let intensity_target = hdr_params.intensity_target;
 
-            tf::hlg_inverse_oo([r, g, b], luminances, intensity_target);
+            if let Some(display_luminance) = hdr_params.hlg_display_luminance {
+                // Linear sample value of 1.0 should represent peak luminance of the display.
+                scale_samples(
+                    [&mut *r, &mut *g, &mut *b],
+                    intensity_target / display_luminance,
+                );
+                tf::hlg_inverse_oo([r, g, b], luminances, display_luminance);
+            }
             tf::linear_to_hlg(r);
             tf::linear_to_hlg(g);
             tf::linear_to_hlg(b);
@@ -1055,7 +1197,213 @@ fn apply_inverse_transfer_function(
             tf::hlg_to_linear(r);
             tf::hlg_to_linear(g);
             tf::hlg_to_linear(b);
-            tf::hlg_oo([r, g, b], luminances, intensity_target);
+            if let Some(display_luminance) = hdr_params.hlg_display_luminance {
+                tf::hlg_oo([&mut *r, &mut *g, &mut *b], luminances, display_luminance);
+                // Linear sample value of 1.0 should represent `intensity_target` nits.
+                scale_samples([r, g, b], display_luminance / intensity_target);
+            }
+        }
+    }
+}
+
+fn scale_samples(channels: [&mut [f32]; 3], scale: f32) {
+    if scale == 1.0 {
+        return;
+    }
+    for ch in channels {
+        for v in ch {
+            *v *= scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jxl_bitstream::BundleDefault;
+
+    use super::*;
+
+    #[test]
+    fn extended_linear_keeps_out_of_gamut() {
+        let from = ColorEncodingWithProfile::new(EnumColourEncoding {
+            tf: TransferFunction::Linear,
+            ..EnumColourEncoding::bt2100_pq(RenderingIntent::Relative)
+        });
+        let to = ColorEncodingWithProfile::new(EnumColourEncoding::srgb_linear(
+            RenderingIntent::Relative,
+        ));
+        let oim = OpsinInverseMatrix::default_with_context(());
+        let tone_mapping = ToneMapping::default_with_context(());
+
+        let mut builder = ColorTransform::builder();
+        builder.set_extended_linear(Some(255.0));
+        let transform = builder.build(&from, &to, &oim, &tone_mapping).unwrap();
+
+        let mut r = [0.0f32, 1.0];
+        let mut g = [1.0f32, 1.0];
+        let mut b = [0.0f32, 1.0];
+        transform
+            .run(&mut [&mut r, &mut g, &mut b], &crate::NullCms)
+            .unwrap();
+
+        // BT.2020 green is outside of sRGB gamut.
+        assert!(r[0] < 0.0);
+        assert!(g[0] > 1.0);
+        assert!(b[0] < 0.0);
+        // White stays white.
+        for v in [r[1], g[1], b[1]] {
+            assert!((v - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn extended_linear_scale() {
+        let encoding = ColorEncodingWithProfile::new(EnumColourEncoding::srgb_linear(
+            RenderingIntent::Relative,
+        ));
+        let oim = OpsinInverseMatrix::default_with_context(());
+        let mut tone_mapping = ToneMapping::default_with_context(());
+        tone_mapping.intensity_target = 1000.0;
+
+        let mut builder = ColorTransform::builder();
+        builder.set_extended_linear(Some(80.0));
+        let transform = builder
+            .build(&encoding, &encoding, &oim, &tone_mapping)
+            .unwrap();
+
+        let mut r = [0.5f32];
+        let mut g = [1.0f32];
+        let mut b = [-0.1f32];
+        transform
+            .run(&mut [&mut r, &mut g, &mut b], &crate::NullCms)
+            .unwrap();
+        assert_eq!(r[0], 6.25);
+        assert_eq!(g[0], 12.5);
+        assert_eq!(b[0], -1.25);
+    }
+
+    #[test]
+    fn extended_linear_needs_linear_target() {
+        let from =
+            ColorEncodingWithProfile::new(EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let to = ColorEncodingWithProfile::new(EnumColourEncoding::display_p3(
+            RenderingIntent::Relative,
+        ));
+        let oim = OpsinInverseMatrix::default_with_context(());
+        let tone_mapping = ToneMapping::default_with_context(());
+
+        let mut builder = ColorTransform::builder();
+        builder.set_extended_linear(Some(80.0));
+        assert!(builder.build(&from, &to, &oim, &tone_mapping).is_err());
+    }
+
+    fn hlg_to_linear_transform(
+        hlg_display_luminance: Option<f32>,
+        hlg_scene_referred: bool,
+    ) -> ColorTransform {
+        let from = ColorEncodingWithProfile::new(EnumColourEncoding::bt2100_hlg(
+            RenderingIntent::Relative,
+        ));
+        let to = ColorEncodingWithProfile::new(EnumColourEncoding {
+            tf: TransferFunction::Linear,
+            ..EnumColourEncoding::bt2100_hlg(RenderingIntent::Relative)
+        });
+        let oim = OpsinInverseMatrix::default_with_context(());
+        let mut tone_mapping = ToneMapping::default_with_context(());
+        tone_mapping.intensity_target = 1000.0;
+
+        let mut builder = ColorTransform::builder();
+        builder
+            .set_extended_linear(Some(1000.0))
+            .set_hlg_display_luminance(hlg_display_luminance)
+            .set_hlg_scene_referred(hlg_scene_referred);
+        builder.build(&from, &to, &oim, &tone_mapping).unwrap()
+    }
+
+    #[test]
+    fn hlg_display_luminance() {
+        let transform = hlg_to_linear_transform(Some(400.0), false);
+
+        let mut r = [0.5f32, 1.0];
+        let mut g = [0.5f32, 1.0];
+        let mut b = [0.5f32, 1.0];
+        transform
+            .run(&mut [&mut r, &mut g, &mut b], &crate::NullCms)
+            .unwrap();
+
+        // BT.2100 Note 5e
+        let gamma = 1.2f32 * 1.111f32.powf((400.0f32 / 1000.0).log2());
+        let expected = (1.0f32 / 12.0).powf(gamma) * 0.4;
+        for v in [r[0], g[0], b[0]] {
+            assert!((v - expected).abs() < 1e-4, "{v} != {expected}");
         }
+        // Peak signal maps to peak luminance of the display.
+        for v in [r[1], g[1], b[1]] {
+            assert!((v - 0.4).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn hlg_scene_referred() {
+        let transform = hlg_to_linear_transform(Some(400.0), true);
+
+        let mut r = [0.5f32];
+        let mut g = [0.25f32];
+        let mut b = [1.0f32];
+        transform
+            .run(&mut [&mut r, &mut g, &mut b], &crate::NullCms)
+            .unwrap();
+
+        assert!((r[0] - 1.0 / 12.0).abs() < 1e-4);
+        assert!((g[0] - 1.0 / 48.0).abs() < 1e-4);
+        assert!((b[0] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hlg_display_luminance_roundtrip() {
+        let linear = ColorEncodingWithProfile::new(EnumColourEncoding {
+            tf: TransferFunction::Linear,
+            ..EnumColourEncoding::bt2100_hlg(RenderingIntent::Relative)
+        });
+        let hlg = ColorEncodingWithProfile::new(EnumColourEncoding::bt2100_hlg(
+            RenderingIntent::Relative,
+        ));
+        let oim = OpsinInverseMatrix::default_with_context(());
+        let mut tone_mapping = ToneMapping::default_with_context(());
+        tone_mapping.intensity_target = 1000.0;
+
+        let mut builder = ColorTransform::builder();
+        builder.set_hlg_display_luminance(Some(2000.0));
+        let forward = builder.build(&linear, &hlg, &oim, &tone_mapping).unwrap();
+
+        let original = [0.02f32, 0.3, 0.9];
+        let mut r = [original[0]];
+        let mut g = [original[1]];
+        let mut b = [original[2]];
+        forward
+            .run(&mut [&mut r, &mut g, &mut b], &crate::NullCms)
+            .unwrap();
+
+        let inverse = hlg_to_linear_transform(Some(2000.0), false);
+        inverse
+            .run(&mut [&mut r, &mut g, &mut b], &crate::NullCms)
+            .unwrap();
+        for (v, expected) in [r[0], g[0], b[0]].into_iter().zip(original) {
+            assert!((v - expected).abs() < 1e-3, "{v} != {expected}");
+        }
+    }
+
+    #[test]
+    fn invalid_hlg_display_luminance() {
+        let from = ColorEncodingWithProfile::new(EnumColourEncoding::bt2100_hlg(
+            RenderingIntent::Relative,
+        ));
+        let to = ColorEncodingWithProfile::new(EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let oim = OpsinInverseMatrix::default_with_context(());
+        let tone_mapping = ToneMapping::default_with_context(());
+
+        let mut builder = ColorTransform::builder();
+        builder.set_hlg_display_luminance(Some(0.0));
+        assert!(builder.build(&from, &to, &oim, &tone_mapping).is_err());
     }
 }
@@ -775,6 +775,7 @@ mod tests {
             luminances: [0.2126, 0.7152, 0.0722],
             intensity_target: 10000.0,
             min_nits: 0.0,
+            hlg_display_luminance: None,
         };
         tone_map(&mut r, &mut g, &mut b, &hdr_params, 255.0, false);
 
@@ -804,6 +805,7 @@ mod tests {
             luminances: [0.2126, 0.7152, 0.0722],
             intensity_target: 10000.0,
             min_nits: 0.0,
+            hlg_display_luminance: None,
         };
         tone_map(&mut r, &mut g, &mut b, &hdr_params, 255.0, true);
 
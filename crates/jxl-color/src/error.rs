@@ -3,12 +3,15 @@
 pub enum Error {
     Bitstream(jxl_bitstream::Error),
     Decoder(jxl_coding::Error),
+    Buffer(jxl_grid::Error),
     InvalidIccStream(&'static str),
     IccParseFailure(&'static str),
     UnsupportedColorEncoding,
     UnsupportedIccProfile,
     IccProfileEmbedded,
     InvalidEnumColorspace,
+    InvalidReferenceWhite(f32),
+    InvalidDisplayLuminance(f32),
     CmsNotAvailable,
     CmsFailure(Box<dyn std::error::Error + Send + Sync + 'static>),
 }
@@ -25,6 +28,12 @@ impl From<jxl_coding::Error> for Error {
     }
 }
 
+impl From<jxl_grid::Error> for Error {
+    fn from(err: jxl_grid::Error) -> Self {
+        Self::Buffer(err)
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Error::*;
@@ -32,12 +41,15 @@ impl std::fmt::Display for Error {
         match self {
             Bitstream(err) => write!(f, "bitstream error: {}", err),
             Decoder(err) => write!(f, "entropy decoder error: {}", err),
+            Buffer(err) => write!(f, "{}", err),
             InvalidIccStream(s) => write!(f, "invalid ICC stream: {s}"),
             IccParseFailure(s) => write!(f, "parsing ICC profile failed: {s}"),
             UnsupportedColorEncoding => write!(f, "unsupported color encoding"),
             UnsupportedIccProfile => write!(f, "unsupported ICC profile"),
             IccProfileEmbedded => write!(f, "embedded ICC profile is signalled, use it instead"),
             InvalidEnumColorspace => write!(f, "unknown colorspace without embedded ICC profile"),
+            InvalidReferenceWhite(nits) => write!(f, "invalid reference white luminance {nits}"),
+            InvalidDisplayLuminance(nits) => write!(f, "invalid display peak luminance {nits}"),
             CmsNotAvailable => write!(f, "color management system is not available"),
             CmsFailure(err) => write!(f, "color management system error: {err}"),
         }
@@ -51,6 +63,7 @@ impl std::error::Error for Error {
         match self {
             Bitstream(err) => Some(err),
             Decoder(err) => Some(err),
+            Buffer(err) => Some(err),
             CmsFailure(err) => Some(&**err),
             _ => None,
         }
@@ -7,6 +7,7 @@ use crate::consts::*;
 
 /// Color encoding, either represented by enum values, or a signal of existence of ICC profile.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColourEncoding {
     /// Color encoding is represented by enum values.
     Enum(EnumColourEncoding),
@@ -111,6 +112,7 @@ impl ColourEncoding {
 
 /// "Enum color encoding" represented by JPEG XL enum values.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumColourEncoding {
     pub colour_space: ColourSpace,
     pub white_point: WhitePoint,
@@ -157,7 +159,8 @@ impl EnumColourEncoding {
         }
     }
 
-    pub(crate) fn srgb_linear(rendering_intent: RenderingIntent) -> Self {
+    /// Creates an sRGB color encoding with linear transfer function.
+    pub fn srgb_linear(rendering_intent: RenderingIntent) -> Self {
         Self {
             colour_space: ColourSpace::Rgb,
             white_point: WhitePoint::D65,
@@ -313,6 +316,7 @@ define_bundle! {
     ///
     /// Coordinate values are scaled by `1e6` (`1_000_000`).
     #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Customxy {
         pub x: ty(U32(u(19), 524288 + u(19), 1048576 + u(20), 2097152 + u(21)); UnpackSigned),
         pub y: ty(U32(u(19), 524288 + u(19), 1048576 + u(20), 2097152 + u(21)); UnpackSigned),
@@ -320,6 +324,7 @@ define_bundle! {
 
     /// HDR tone mapping metadata.
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct ToneMapping {
         all_default: ty(Bool) default(true),
         pub intensity_target: ty(F16) cond(!all_default) default(255.0),
@@ -340,6 +345,7 @@ impl Customxy {
 /// Color space type.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColourSpace {
     /// Tristimulus RGB.
     ///
@@ -395,6 +401,7 @@ impl TryFrom<u32> for WhitePointDiscriminator {
 /// White point.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum WhitePoint {
     /// CIE Standard Illuminant D65.
     #[default]
@@ -464,6 +471,7 @@ impl TryFrom<u32> for PrimariesDiscriminator {
 /// RGB primaries.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Primaries {
     /// sRGB primaries (same as BT.709).
     #[default]
@@ -527,6 +535,7 @@ impl Primaries {
 /// Rendering intent, defined by ICC specification.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RenderingIntent {
     /// Perceptual; vendor-specific.
     Perceptual = 0,
@@ -556,6 +565,7 @@ impl TryFrom<u32> for RenderingIntent {
 /// Transfer function (tone curve).
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TransferFunction {
     /// Pure gamma curve.
     Gamma {
@@ -636,6 +646,7 @@ impl TransferFunction {
 define_bundle! {
     /// Opsin inverse metadata.
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct OpsinInverseMatrix {
         all_default: ty(Bool) default(true),
         pub inv_mat: ty(Array[Array[F16]; 3]; 3) cond(!all_default) default([
@@ -3,21 +3,62 @@
 //! - [`read_icc`] and [`decode_icc`] can be used to read embedded ICC profile from the bitstream.
 //! - [`colour_encoding_to_icc`] can be used to create an ICC profile to embed into the decoded
 //!   image file, or to be used by the color management system for various purposes.
+//!   [`colour_encoding_to_icc_with_options`] can create ICCv2 profiles, or ICCv4 profiles with
+//!   tone mapped LUTs for HDR encodings.
+//! - [`inspect_icc`] can be used to read the header and tags of an ICC profile, with validation.
+//! - [`match_enum_encoding`] can be used to find an enum color encoding which approximates an ICC
+//!   profile.
 
 mod decode;
+mod inspect;
 mod parse;
 mod synthesize;
 
-pub use decode::{decode_icc, read_icc};
-pub(crate) use parse::parse_icc;
+pub use decode::{decode_icc, decode_icc_with_tracker, read_icc, read_icc_with_tracker};
+pub use inspect::*;
 pub(crate) use parse::parse_icc_raw;
-pub use synthesize::colour_encoding_to_icc;
+pub use parse::{match_enum_encoding, IccEncodingMatch, IccMatchTolerance};
+pub use synthesize::{
+    colour_encoding_to_icc, colour_encoding_to_icc_with_options, IccSynthesisOptions, IccVersion,
+};
 
-#[derive(Debug)]
+/// Header of an ICC profile.
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct IccHeader {
+    /// Profile size in bytes.
+    pub size: u32,
+    /// Preferred CMM type.
+    pub cmm_type: [u8; 4],
+    /// Profile version, in `(major, minor, bugfix)`.
+    pub version: (u8, u8, u8),
+    /// Profile/device class, such as `mntr`.
+    pub device_class: [u8; 4],
+    /// Color space of data, such as `RGB `.
     pub color_space: [u8; 4],
+    /// Profile connection space, either `XYZ ` or `Lab `.
+    pub pcs: [u8; 4],
+    /// Date and time the profile was created, in `[year, month, day, hour, minute, second]`.
+    pub creation_date: [u16; 6],
+    /// Profile file signature, should be `acsp`.
+    pub signature: [u8; 4],
+    /// Primary platform signature.
+    pub platform: [u8; 4],
+    /// Profile flags.
+    pub flags: u32,
+    /// Device manufacturer signature.
+    pub manufacturer: [u8; 4],
+    /// Device model signature.
+    pub model: [u8; 4],
+    /// Device attributes.
+    pub attributes: u64,
     pub rendering_intent: crate::RenderingIntent,
+    /// XYZ values of the illuminant of the PCS.
+    pub illuminant: [f32; 3],
+    /// Profile creator signature.
+    pub creator: [u8; 4],
+    /// Profile ID, which is MD5 checksum of the profile. All zero if not computed.
+    pub profile_id: [u8; 16],
 }
 
 #[derive(Debug)]
@@ -2,11 +2,24 @@ use std::io::prelude::*;
 use std::io::Cursor;
 
 use jxl_bitstream::Bitstream;
+use jxl_grid::{AllocHandle, AllocTracker};
 
 use crate::{Error, Result};
 
 /// Reads the encoded ICC profile stream from the given bitstream.
 pub fn read_icc(bitstream: &mut Bitstream) -> Result<Vec<u8>> {
+    read_icc_with_tracker(bitstream, None).map(|(stream, _)| stream)
+}
+
+/// Reads the encoded ICC profile stream from the given bitstream, checking the size of the stream
+/// against the allocation tracker.
+///
+/// The stream is returned with the allocation handle recording it, which should be kept alive as
+/// long as the stream.
+pub fn read_icc_with_tracker(
+    bitstream: &mut Bitstream,
+    tracker: Option<&AllocTracker>,
+) -> Result<(Vec<u8>, Option<AllocHandle>)> {
     let enc_size = jxl_bitstream::read_bits!(bitstream, U64)?;
     tracing::trace!(enc_size);
 
@@ -62,21 +75,25 @@ pub fn read_icc(bitstream: &mut Bitstream) -> Result<Vec<u8>> {
         ));
     }
 
-    // Read remaining data
-    encoded_icc.resize(enc_size as usize, 0);
-    for (idx, b) in encoded_icc.iter_mut().enumerate().skip(max_size_header_len) {
+    let alloc_handle = tracker
+        .map(|tracker| tracker.alloc::<u8>(enc_size as usize))
+        .transpose()?;
+
+    // Read remaining data. The buffer grows as data is decoded, since `enc_size` is not trusted.
+    for idx in max_size_header_len..enc_size as usize {
         let sym = decoder.read_varint(bitstream, get_icc_ctx(idx, b1, b2))?;
         if sym >= 256 {
             return Err(Error::InvalidIccStream("decoded value out of range"));
         }
-        *b = sym as u8;
+        let b = sym as u8;
+        encoded_icc.push(b);
 
         b2 = b1;
-        b1 = *b;
+        b1 = b;
     }
 
     decoder.finalize()?;
-    Ok(encoded_icc)
+    Ok((encoded_icc, alloc_handle))
 }
 
 fn get_icc_ctx(idx: usize, b1: u8, b2: u8) -> u32 {
@@ -189,6 +206,18 @@ fn shuffle4(bytes: &[u8]) -> Vec<u8> {
 
 /// Decodes the given ICC profile stream.
 pub fn decode_icc(stream: &[u8]) -> Result<Vec<u8>> {
+    decode_icc_with_tracker(stream, None).map(|(icc, _)| icc)
+}
+
+/// Decodes the given ICC profile stream, checking the size of the output against the allocation
+/// tracker.
+///
+/// The profile is returned with the allocation handle recording it, which should be kept alive as
+/// long as the profile.
+pub fn decode_icc_with_tracker(
+    stream: &[u8],
+    tracker: Option<&AllocTracker>,
+) -> Result<(Vec<u8>, Option<AllocHandle>)> {
     use std::num::Wrapping;
 
     const COMMON_TAGS: [&[u8]; 19] = [
@@ -219,7 +248,11 @@ pub fn decode_icc(stream: &[u8]) -> Result<Vec<u8>> {
     }
     let (header_data, mut data) = data.split_at(header_size);
     let mut commands_stream = Cursor::new(commands);
-    let mut out = Vec::with_capacity(output_size as usize);
+    let alloc_handle = tracker
+        .map(|tracker| tracker.alloc::<u8>(output_size as usize))
+        .transpose()?;
+    // `output_size` is not trusted; the output grows as the stream is decoded.
+    let mut out = Vec::with_capacity((output_size as usize).min(stream.len()));
 
     // Header
     for (idx, &e) in header_data.iter().enumerate() {
@@ -227,7 +260,7 @@ pub fn decode_icc(stream: &[u8]) -> Result<Vec<u8>> {
         out.push(p.wrapping_add(e));
     }
     if output_size <= 128 {
-        return Ok(out);
+        return Ok((out, alloc_handle));
     }
 
     // Tag
@@ -248,7 +281,7 @@ pub fn decode_icc(stream: &[u8]) -> Result<Vec<u8>> {
                 .read_exact(std::slice::from_mut(&mut command))
                 .is_err()
             {
-                return Ok(out);
+                return Ok((out, alloc_handle));
             }
             let tagcode = command & 63;
             let tag = match tagcode {
@@ -419,5 +452,40 @@ pub fn decode_icc(stream: &[u8]) -> Result<Vec<u8>> {
     if out.len() != output_size as usize {
         return Err(Error::InvalidIccStream("decoded ICC profile size mismatch"));
     }
-    Ok(out)
+    Ok((out, alloc_handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_size_exceeding_limit() {
+        // output_size = 2^27, commands_size = 0, followed by the header.
+        let mut stream = vec![0x80, 0x80, 0x80, 0x40, 0x00];
+        stream.resize(stream.len() + 128, 0);
+
+        let tracker = AllocTracker::with_limit(1 << 20);
+        let err = decode_icc_with_tracker(&stream, Some(&tracker)).unwrap_err();
+        assert!(matches!(err, Error::Buffer(_)));
+        assert_eq!(tracker.stats().bytes_left, 1 << 20);
+
+        // Without a tracker, the truncated stream is rejected.
+        assert!(decode_icc(&stream).is_err());
+    }
+
+    #[test]
+    fn handle_outlives_decoding() {
+        // output_size = 64, commands_size = 0, followed by the header.
+        let mut stream = vec![64, 0];
+        stream.resize(stream.len() + 64, 0);
+
+        let tracker = AllocTracker::with_limit(1 << 20);
+        let (icc, handle) = decode_icc_with_tracker(&stream, Some(&tracker)).unwrap();
+        assert_eq!(icc.len(), 64);
+        assert_eq!(tracker.stats().bytes_left, (1 << 20) - 64);
+
+        drop(handle);
+        assert_eq!(tracker.stats().bytes_left, 1 << 20);
+    }
 }
@@ -0,0 +1,988 @@
+use crate::{Error, Result};
+
+use super::IccHeader;
+
+/// ICC profile with its header and decoded tags, returned by [`inspect_icc`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct IccInspection {
+    /// Header of the profile.
+    pub header: IccHeader,
+    /// Tags in the tag table, in the order they appear.
+    pub tags: Vec<IccTagEntry>,
+}
+
+impl IccInspection {
+    /// Returns the first tag with the given signature.
+    pub fn tag(&self, signature: [u8; 4]) -> Option<&IccTagEntry> {
+        self.tags.iter().find(|tag| tag.signature == signature)
+    }
+
+    /// Returns the decoded value of the first tag with the given signature, if it's valid.
+    pub fn tag_value(&self, signature: [u8; 4]) -> Option<&IccTagValue> {
+        self.tag(signature)?.value.as_ref().ok()
+    }
+
+    /// Returns the profile description (`desc` tag).
+    pub fn description(&self) -> Option<&str> {
+        self.tag_value(*b"desc")?.text()
+    }
+
+    /// Returns the copyright information (`cprt` tag).
+    pub fn copyright(&self) -> Option<&str> {
+        self.tag_value(*b"cprt")?.text()
+    }
+
+    /// Returns the media white point (`wtpt` tag) in XYZ.
+    pub fn media_white_point(&self) -> Option<[f32; 3]> {
+        self.tag_value(*b"wtpt")?.xyz()
+    }
+
+    /// Returns the luminance of the display in cd/m² (`lumi` tag).
+    pub fn luminance(&self) -> Option<f32> {
+        self.tag_value(*b"lumi")?.xyz().map(|[_, y, _]| y)
+    }
+
+    /// Returns the CICP tag (`cicp` tag), if there's any.
+    pub fn cicp(&self) -> Option<IccCicp> {
+        match self.tag_value(*b"cicp")? {
+            IccTagValue::Cicp(cicp) => Some(*cicp),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over tags which failed to decode or validate, with their errors.
+    pub fn tag_errors(&self) -> impl Iterator<Item = (&[u8; 4], &Error)> + '_ {
+        self.tags
+            .iter()
+            .filter_map(|tag| tag.value.as_ref().err().map(|e| (&tag.signature, e)))
+    }
+
+    /// Returns whether the profile signature is valid and all tags are decoded successfully.
+    pub fn is_valid(&self) -> bool {
+        &self.header.signature == b"acsp" && self.tag_errors().next().is_none()
+    }
+}
+
+/// Entry in the tag table of an ICC profile.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct IccTagEntry {
+    /// Tag signature, such as `desc`.
+    pub signature: [u8; 4],
+    /// Offset to the tag data from the beginning of the profile.
+    pub offset: u32,
+    /// Size of the tag data.
+    pub size: u32,
+    /// Decoded tag data, or an error if the tag is malformed.
+    pub value: Result<IccTagValue>,
+}
+
+/// Decoded data of an ICC tag.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum IccTagValue {
+    /// `textType` or ASCII part of `textDescriptionType`.
+    Text(String),
+    /// `multiLocalizedUnicodeType`.
+    MultiLocalizedText(Vec<IccLocalizedText>),
+    /// `XYZType`.
+    Xyz(Vec<[f32; 3]>),
+    /// `s15Fixed16ArrayType`.
+    S15Fixed16Array(Vec<f32>),
+    /// `signatureType`.
+    Signature([u8; 4]),
+    /// `curveType` or `parametricCurveType`.
+    Curve(IccCurve),
+    /// `cicpType`.
+    Cicp(IccCicp),
+    /// `lut8Type`.
+    Lut8(IccLut),
+    /// `lut16Type`.
+    Lut16(IccLut),
+    /// `lutAToBType`.
+    LutAToB(IccLutAb),
+    /// `lutBToAType`.
+    LutBToA(IccLutAb),
+    /// Tag type not decoded by this crate.
+    Unknown([u8; 4]),
+}
+
+impl IccTagValue {
+    /// Returns the text of the tag, preferring English if there are multiple localized texts.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::MultiLocalizedText(texts) => texts
+                .iter()
+                .find(|text| &text.language == b"en")
+                .or_else(|| texts.first())
+                .map(|text| &*text.text),
+            _ => None,
+        }
+    }
+
+    /// Returns the first XYZ value of the tag.
+    pub fn xyz(&self) -> Option<[f32; 3]> {
+        match self {
+            Self::Xyz(xyz) => xyz.first().copied(),
+            _ => None,
+        }
+    }
+}
+
+/// Localized text in `multiLocalizedUnicodeType`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IccLocalizedText {
+    /// ISO 639-1 language code.
+    pub language: [u8; 2],
+    /// ISO 3166-1 country code.
+    pub country: [u8; 2],
+    pub text: String,
+}
+
+/// One-dimensional curve in an ICC profile.
+#[derive(Debug, Clone)]
+pub enum IccCurve {
+    /// `curveType` with no entries.
+    Identity,
+    /// `curveType` with single gamma value.
+    Gamma(f32),
+    /// `curveType` with lookup table, or input/output table of `lut8Type` and `lut16Type`.
+    ///
+    /// Values of `lut8Type` are scaled to 16-bit range.
+    Table(Vec<u16>),
+    /// `parametricCurveType`.
+    Parametric {
+        function_type: u16,
+        params: Vec<f32>,
+    },
+}
+
+impl IccCurve {
+    /// Evaluates the curve at `x`, where input and output are normalized to `[0, 1]`.
+    ///
+    /// Tables are interpolated linearly.
+    pub fn eval(&self, x: f32) -> f32 {
+        match self {
+            Self::Identity => x,
+            Self::Gamma(g) => x.max(0.0).powf(*g),
+            Self::Table(table) => {
+                let Some(last) = table.len().checked_sub(1).filter(|&last| last > 0) else {
+                    return table.first().map(|&v| v as f32 / 65535.0).unwrap_or(x);
+                };
+                let pos = x.clamp(0.0, 1.0) * last as f32;
+                let idx = (pos as usize).min(last - 1);
+                let frac = pos - idx as f32;
+                let a = table[idx] as f32;
+                let b = table[idx + 1] as f32;
+                (a + (b - a) * frac) / 65535.0
+            }
+            Self::Parametric {
+                function_type,
+                params,
+            } => {
+                let p = |idx: usize| params.get(idx).copied().unwrap_or(0.0);
+                let (g, a, b, c, d, e, f) = (p(0), p(1), p(2), p(3), p(4), p(5), p(6));
+                match function_type {
+                    0 => x.max(0.0).powf(g),
+                    1 if x >= -b / a => (a * x + b).max(0.0).powf(g),
+                    1 => 0.0,
+                    2 if x >= -b / a => (a * x + b).max(0.0).powf(g) + c,
+                    2 => c,
+                    3 if x >= d => (a * x + b).max(0.0).powf(g),
+                    3 => c * x,
+                    4 if x >= d => (a * x + b).max(0.0).powf(g) + e,
+                    4 => c * x + f,
+                    _ => x,
+                }
+            }
+        }
+    }
+}
+
+/// Coding-independent code points stored in `cicpType`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IccCicp {
+    pub color_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+    pub video_full_range: bool,
+}
+
+/// Multidimensional lookup table in an ICC profile.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IccClut {
+    /// Number of grid points in each input dimension.
+    pub grid_points: Vec<u8>,
+    /// Number of bytes of each value in the profile, either 1 or 2.
+    pub precision: u8,
+    /// Values of the table, scaled to 16-bit range.
+    pub values: Vec<u16>,
+}
+
+/// `lut8Type` or `lut16Type`.
+///
+/// Samples are processed in the order of matrix, input curves, CLUT and output curves.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IccLut {
+    pub input_channels: u8,
+    pub output_channels: u8,
+    pub matrix: [f32; 9],
+    pub input_curves: Vec<IccCurve>,
+    pub clut: IccClut,
+    pub output_curves: Vec<IccCurve>,
+}
+
+/// `lutAToBType` or `lutBToAType`.
+///
+/// Samples are processed in the order of A curves, CLUT, M curves, matrix and B curves for
+/// `lutAToBType`, and in reverse order for `lutBToAType`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IccLutAb {
+    pub input_channels: u8,
+    pub output_channels: u8,
+    pub a_curves: Option<Vec<IccCurve>>,
+    pub clut: Option<IccClut>,
+    pub m_curves: Option<Vec<IccCurve>>,
+    /// 3x3 matrix followed by 3 offsets.
+    pub matrix: Option<[f32; 12]>,
+    pub b_curves: Vec<IccCurve>,
+}
+
+/// Reads the header and tags of an ICC profile.
+///
+/// Known tags are decoded and validated; tag-level errors are stored in
+/// [`IccTagEntry::value`] instead of failing the whole profile.
+///
+/// # Errors
+/// This function will return an error if the header or the tag table is malformed.
+pub fn inspect_icc(profile: &[u8]) -> Result<IccInspection> {
+    let header = super::parse::parse_icc_header(profile)?;
+    let size = profile.len();
+    if size < 0x84 {
+        return Ok(IccInspection {
+            header,
+            tags: Vec::new(),
+        });
+    }
+
+    let tag_count = read_u32(profile, 0x80).unwrap() as usize;
+    let tag_table_end = tag_count
+        .checked_mul(12)
+        .and_then(|len| len.checked_add(0x84))
+        .filter(|&end| end <= size)
+        .ok_or(Error::IccParseFailure(
+            "unexpected end of profile while reading tag list",
+        ))?;
+
+    let device_channels = match &header.color_space {
+        b"GRAY" => Some(1),
+        b"RGB " | b"XYZ " | b"Lab " | b"Luv " | b"YCbr" | b"Yxy " | b"HSV " | b"HLS " | b"CMY "
+        | b"3CLR" => Some(3),
+        b"CMYK" | b"4CLR" => Some(4),
+        _ => None,
+    };
+
+    let tag_bytes = &profile[0x84..tag_table_end];
+    let mut tags = Vec::<IccTagEntry>::with_capacity(tag_count);
+    for raw_tag in tag_bytes.chunks_exact(12) {
+        let signature = [raw_tag[0], raw_tag[1], raw_tag[2], raw_tag[3]];
+        let offset = read_u32(raw_tag, 4).unwrap();
+        let tag_size = read_u32(raw_tag, 8).unwrap();
+
+        let value = if tags.iter().any(|tag| tag.signature == signature) {
+            Err(Error::IccParseFailure("duplicate tag"))
+        } else {
+            let start = offset as usize;
+            let end = start
+                .checked_add(tag_size as usize)
+                .filter(|&end| end <= size);
+            match end {
+                _ if start < tag_table_end => {
+                    Err(Error::IccParseFailure("tag data overlaps with tag table"))
+                }
+                None => Err(Error::IccParseFailure(
+                    "unexpected end of profile while reading tag data",
+                )),
+                Some(end) => decode_tag(signature, &profile[start..end], device_channels),
+            }
+        };
+
+        tags.push(IccTagEntry {
+            signature,
+            offset,
+            size: tag_size,
+            value,
+        });
+    }
+
+    Ok(IccInspection { header, tags })
+}
+
+fn decode_tag(signature: [u8; 4], data: &[u8], device_channels: Option<u8>) -> Result<IccTagValue> {
+    if data.len() < 8 {
+        return Err(Error::IccParseFailure("tag data is too short"));
+    }
+
+    let type_signature = [data[0], data[1], data[2], data[3]];
+    let allowed_types: &[&[u8; 4]] = match &signature {
+        b"desc" | b"dmnd" | b"dmdd" | b"cprt" => &[b"desc", b"mluc", b"text"],
+        b"wtpt" | b"bkpt" | b"lumi" | b"rXYZ" | b"gXYZ" | b"bXYZ" => &[b"XYZ "],
+        b"chad" => &[b"sf32"],
+        b"rTRC" | b"gTRC" | b"bTRC" | b"kTRC" => &[b"curv", b"para"],
+        b"cicp" => &[b"cicp"],
+        b"tech" => &[b"sig "],
+        b"A2B0" | b"A2B1" | b"A2B2" => &[b"mft1", b"mft2", b"mAB "],
+        b"B2A0" | b"B2A1" | b"B2A2" | b"gamt" | b"pre0" | b"pre1" | b"pre2" => {
+            &[b"mft1", b"mft2", b"mBA "]
+        }
+        _ => &[],
+    };
+    if !allowed_types.is_empty() && !allowed_types.contains(&&type_signature) {
+        return Err(Error::IccParseFailure("unexpected tag type"));
+    }
+
+    let value = match &type_signature {
+        b"text" => {
+            let text = &data[8..];
+            let len = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+            IccTagValue::Text(String::from_utf8_lossy(&text[..len]).into_owned())
+        }
+        b"desc" => {
+            let count = read_u32(data, 8).ok_or(Error::IccParseFailure("invalid desc tag"))?;
+            let text = data
+                .get(12..)
+                .and_then(|text| text.get(..count as usize))
+                .ok_or(Error::IccParseFailure("invalid desc tag"))?;
+            let len = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+            IccTagValue::Text(String::from_utf8_lossy(&text[..len]).into_owned())
+        }
+        b"mluc" => IccTagValue::MultiLocalizedText(decode_mluc(data)?),
+        b"XYZ " => {
+            let xyz = data[8..]
+                .chunks_exact(12)
+                .map(|xyz| std::array::from_fn(|idx| read_s15f16(xyz, idx * 4).unwrap()))
+                .collect::<Vec<_>>();
+            if xyz.is_empty() {
+                return Err(Error::IccParseFailure("invalid XYZType"));
+            }
+            IccTagValue::Xyz(xyz)
+        }
+        b"sf32" => IccTagValue::S15Fixed16Array(
+            data[8..]
+                .chunks_exact(4)
+                .map(|v| read_s15f16(v, 0).unwrap())
+                .collect(),
+        ),
+        b"sig " => {
+            let sig = read_u32(data, 8).ok_or(Error::IccParseFailure("invalid signatureType"))?;
+            IccTagValue::Signature(sig.to_be_bytes())
+        }
+        b"curv" | b"para" => {
+            let (curve, _) = decode_curve(data)?;
+            IccTagValue::Curve(curve)
+        }
+        b"cicp" => {
+            if data.len() < 12 {
+                return Err(Error::IccParseFailure("invalid cicpType"));
+            }
+            IccTagValue::Cicp(IccCicp {
+                color_primaries: data[8],
+                transfer_characteristics: data[9],
+                matrix_coefficients: data[10],
+                video_full_range: data[11] != 0,
+            })
+        }
+        b"mft1" => IccTagValue::Lut8(decode_lut(data, false)?),
+        b"mft2" => IccTagValue::Lut16(decode_lut(data, true)?),
+        b"mAB " => IccTagValue::LutAToB(decode_lut_ab(data, false)?),
+        b"mBA " => IccTagValue::LutBToA(decode_lut_ab(data, true)?),
+        _ => IccTagValue::Unknown(type_signature),
+    };
+
+    match (&signature, &value) {
+        (b"chad", IccTagValue::S15Fixed16Array(mat)) => {
+            let Ok(mat) = <[f32; 9]>::try_from(&**mat) else {
+                return Err(Error::IccParseFailure("invalid chad tag"));
+            };
+            if crate::ciexyz::matinv(&mat).iter().any(|v| !v.is_finite()) {
+                return Err(Error::IccParseFailure("invalid chad tag"));
+            }
+        }
+        (b"wtpt" | b"rXYZ" | b"gXYZ" | b"bXYZ", IccTagValue::Xyz(xyz)) => {
+            let [x, y, z] = xyz[0];
+            let sum = x + y + z;
+            if [x, y, z].iter().any(|v| !(v / sum).is_finite()) {
+                return Err(Error::IccParseFailure("invalid XYZType"));
+            }
+        }
+        _ => {}
+    }
+
+    let lut_channels = match &value {
+        IccTagValue::Lut8(lut) | IccTagValue::Lut16(lut) => {
+            Some((lut.input_channels, lut.output_channels))
+        }
+        IccTagValue::LutAToB(lut) | IccTagValue::LutBToA(lut) => {
+            Some((lut.input_channels, lut.output_channels))
+        }
+        _ => None,
+    };
+    let is_device_to_pcs = matches!(signature, [b'A', b'2', b'B', _]);
+    let is_pcs_to_device = matches!(signature, [b'B', b'2', b'A', _]);
+    if let Some((input_channels, output_channels)) = lut_channels {
+        let (device, pcs) = if is_device_to_pcs {
+            (input_channels, output_channels)
+        } else {
+            (output_channels, input_channels)
+        };
+        if (is_device_to_pcs || is_pcs_to_device)
+            && (pcs != 3 || device_channels.is_some_and(|c| c != device))
+        {
+            return Err(Error::IccParseFailure("channel count mismatch"));
+        }
+    }
+
+    Ok(value)
+}
+
+fn decode_mluc(data: &[u8]) -> Result<Vec<IccLocalizedText>> {
+    const ERR: Error = Error::IccParseFailure("invalid multiLocalizedUnicodeType");
+
+    let count = read_u32(data, 8).ok_or(ERR)? as usize;
+    let record_size = read_u32(data, 12).ok_or(ERR)? as usize;
+    if record_size < 12 {
+        return Err(ERR);
+    }
+
+    let mut out = Vec::new();
+    for idx in 0..count {
+        let record = idx
+            .checked_mul(record_size)
+            .and_then(|offset| data.get(16 + offset..))
+            .and_then(|record| record.get(..12))
+            .ok_or(ERR)?;
+        let len = read_u32(record, 4).unwrap() as usize;
+        let offset = read_u32(record, 8).unwrap() as usize;
+        if !len.is_multiple_of(2) {
+            return Err(ERR);
+        }
+        let text = data
+            .get(offset..)
+            .and_then(|text| text.get(..len))
+            .ok_or(ERR)?;
+        let text = text
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+
+        out.push(IccLocalizedText {
+            language: [record[0], record[1]],
+            country: [record[2], record[3]],
+            text: String::from_utf16_lossy(&text),
+        });
+    }
+
+    Ok(out)
+}
+
+/// Decodes `curveType` or `parametricCurveType`, returning the curve and the number of bytes
+/// read.
+pub(super) fn decode_curve(data: &[u8]) -> Result<(IccCurve, usize)> {
+    match data.get(..4) {
+        Some(b"curv") => {
+            const ERR: Error = Error::IccParseFailure("invalid curveType");
+            let count = read_u32(data, 8).ok_or(ERR)? as usize;
+            let len = count.checked_mul(2).and_then(|len| len.checked_add(12));
+            let table = len.and_then(|len| data.get(12..len)).ok_or(ERR)?;
+            let curve = match count {
+                0 => IccCurve::Identity,
+                1 => IccCurve::Gamma(u16::from_be_bytes([table[0], table[1]]) as f32 / 256.0),
+                _ => IccCurve::Table(
+                    table
+                        .chunks_exact(2)
+                        .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                        .collect(),
+                ),
+            };
+            Ok((curve, 12 + count * 2))
+        }
+        Some(b"para") => {
+            const ERR: Error = Error::IccParseFailure("invalid parametricCurveType");
+            let function_type = read_u16(data, 8).ok_or(ERR)?;
+            let num_params = match function_type {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return Err(ERR),
+            };
+            let params = (0..num_params)
+                .map(|idx| read_s15f16(data, 12 + idx * 4))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ERR)?;
+            Ok((
+                IccCurve::Parametric {
+                    function_type,
+                    params,
+                },
+                12 + num_params * 4,
+            ))
+        }
+        _ => Err(Error::IccParseFailure("invalid curve")),
+    }
+}
+
+/// Decodes `count` curves stored back to back, each aligned to 4 bytes.
+fn decode_curves(data: &[u8], offset: u32, count: u8) -> Result<Vec<IccCurve>> {
+    let mut data = data
+        .get(offset as usize..)
+        .ok_or(Error::IccParseFailure("invalid curve offset"))?;
+    let mut out = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (curve, len) = decode_curve(data)?;
+        out.push(curve);
+        data = data.get((len + 3) & !3..).unwrap_or(&[]);
+    }
+    Ok(out)
+}
+
+fn decode_clut(
+    data: &[u8],
+    grid_points: &[u8],
+    output_channels: u8,
+    precision: u8,
+) -> Result<IccClut> {
+    const ERR: Error = Error::IccParseFailure("invalid CLUT");
+    if grid_points.contains(&0) || !(precision == 1 || precision == 2) {
+        return Err(ERR);
+    }
+
+    let count = grid_points
+        .iter()
+        .try_fold(output_channels as usize, |acc, &g| {
+            acc.checked_mul(g as usize)
+        })
+        .ok_or(ERR)?;
+    let len = count.checked_mul(precision as usize).ok_or(ERR)?;
+    let bytes = data.get(..len).ok_or(ERR)?;
+    let values = if precision == 1 {
+        bytes.iter().map(|&v| v as u16 * 257).collect()
+    } else {
+        bytes
+            .chunks_exact(2)
+            .map(|v| u16::from_be_bytes([v[0], v[1]]))
+            .collect()
+    };
+
+    Ok(IccClut {
+        grid_points: grid_points.to_vec(),
+        precision,
+        values,
+    })
+}
+
+fn decode_lut(data: &[u8], is_16bit: bool) -> Result<IccLut> {
+    const ERR: Error = Error::IccParseFailure("invalid lut8Type or lut16Type");
+    if data.len() < 52 {
+        return Err(ERR);
+    }
+
+    let input_channels = data[8];
+    let output_channels = data[9];
+    let grid_points = data[10];
+    if !(1..=15).contains(&input_channels) || output_channels == 0 {
+        return Err(ERR);
+    }
+    let matrix = std::array::from_fn(|idx| read_s15f16(data, 12 + idx * 4).unwrap());
+
+    let (input_entries, output_entries, precision, mut offset) = if is_16bit {
+        let input_entries = read_u16(data, 48).unwrap() as usize;
+        let output_entries = read_u16(data, 50).unwrap() as usize;
+        if !(2..=4096).contains(&input_entries) || !(2..=4096).contains(&output_entries) {
+            return Err(ERR);
+        }
+        (input_entries, output_entries, 2u8, 52usize)
+    } else {
+        (256, 256, 1u8, 48usize)
+    };
+
+    let input_curves =
+        decode_lut_tables(data, &mut offset, input_channels, input_entries, is_16bit)?;
+    let clut = decode_clut(
+        data.get(offset..).unwrap_or(&[]),
+        &vec![grid_points; input_channels as usize],
+        output_channels,
+        precision,
+    )?;
+    offset += clut.values.len() * precision as usize;
+    let output_curves =
+        decode_lut_tables(data, &mut offset, output_channels, output_entries, is_16bit)?;
+
+    Ok(IccLut {
+        input_channels,
+        output_channels,
+        matrix,
+        input_curves,
+        clut,
+        output_curves,
+    })
+}
+
+fn decode_lut_tables(
+    data: &[u8],
+    offset: &mut usize,
+    channels: u8,
+    entries: usize,
+    is_16bit: bool,
+) -> Result<Vec<IccCurve>> {
+    let len = if is_16bit { entries * 2 } else { entries };
+    let mut out = Vec::with_capacity(channels as usize);
+    for _ in 0..channels {
+        let bytes = data
+            .get(*offset..)
+            .and_then(|d| d.get(..len))
+            .ok_or(Error::IccParseFailure("invalid lut8Type or lut16Type"))?;
+        *offset += len;
+        let table = if is_16bit {
+            bytes
+                .chunks_exact(2)
+                .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                .collect()
+        } else {
+            bytes.iter().map(|&v| v as u16 * 257).collect()
+        };
+        out.push(IccCurve::Table(table));
+    }
+    Ok(out)
+}
+
+fn decode_lut_ab(data: &[u8], b_to_a: bool) -> Result<IccLutAb> {
+    const ERR: Error = Error::IccParseFailure("invalid lutAToBType or lutBToAType");
+    if data.len() < 32 {
+        return Err(ERR);
+    }
+
+    let input_channels = data[8];
+    let output_channels = data[9];
+    if !(1..=15).contains(&input_channels) || !(1..=15).contains(&output_channels) {
+        return Err(ERR);
+    }
+    let [b_offset, matrix_offset, m_offset, clut_offset, a_offset] =
+        std::array::from_fn(|idx| read_u32(data, 12 + idx * 4).unwrap());
+
+    // B curves are on the PCS side, A curves are on the device side.
+    let (a_channels, pcs_channels) = if b_to_a {
+        (output_channels, input_channels)
+    } else {
+        (input_channels, output_channels)
+    };
+
+    if b_offset == 0 {
+        return Err(ERR);
+    }
+    let b_curves = decode_curves(data, b_offset, pcs_channels)?;
+    let matrix = if matrix_offset == 0 {
+        None
+    } else {
+        if pcs_channels != 3 {
+            return Err(ERR);
+        }
+        let matrix = (0..12)
+            .map(|idx| read_s15f16(data, matrix_offset as usize + idx * 4))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ERR)?;
+        Some(std::array::from_fn(|idx| matrix[idx]))
+    };
+    let m_curves = if m_offset == 0 {
+        None
+    } else {
+        Some(decode_curves(data, m_offset, pcs_channels)?)
+    };
+    let clut = if clut_offset == 0 {
+        None
+    } else {
+        let clut_data = data.get(clut_offset as usize..).ok_or(ERR)?;
+        if clut_data.len() < 20 {
+            return Err(ERR);
+        }
+        Some(decode_clut(
+            &clut_data[20..],
+            &clut_data[..input_channels as usize],
+            output_channels,
+            clut_data[16],
+        )?)
+    };
+    let a_curves = if a_offset == 0 {
+        None
+    } else {
+        Some(decode_curves(data, a_offset, a_channels)?)
+    };
+
+    if (a_curves.is_some() != clut.is_some()) || (m_curves.is_some() != matrix.is_some()) {
+        return Err(ERR);
+    }
+    if clut.is_none() && input_channels != output_channels {
+        return Err(ERR);
+    }
+
+    Ok(IccLutAb {
+        input_channels,
+        output_channels,
+        a_curves,
+        clut,
+        m_curves,
+        matrix,
+        b_curves,
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..)?.get(..2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..)?.get(..4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_s15f16(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(|v| v as i32 as f32 / 65536.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        icc::{
+            colour_encoding_to_icc, colour_encoding_to_icc_with_options, match_enum_encoding,
+            IccMatchTolerance, IccSynthesisOptions, IccVersion,
+        },
+        ColourSpace, EnumColourEncoding, RenderingIntent,
+    };
+
+    #[test]
+    fn synthesized_srgb() {
+        let profile = colour_encoding_to_icc(&EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let inspection = inspect_icc(&profile).unwrap();
+        dbg!(&inspection);
+
+        assert!(inspection.is_valid());
+        assert_eq!(&inspection.header.device_class, b"mntr");
+        assert_eq!(&inspection.header.color_space, b"RGB ");
+        assert_eq!(inspection.header.version, (4, 4, 0));
+        assert_eq!(inspection.copyright(), Some("CC0, generated by jxl-oxide"));
+        assert!(inspection.description().unwrap().starts_with("Rgb_"));
+        assert!(matches!(
+            inspection.tag_value(*b"rTRC"),
+            Some(IccTagValue::Curve(IccCurve::Parametric {
+                function_type: 3,
+                ..
+            }))
+        ));
+        let [x, y, z] = inspection.media_white_point().unwrap();
+        assert!((x - 0.9642).abs() < 1e-4);
+        assert!((y - 1.0).abs() < 1e-4);
+        assert!((z - 0.8249).abs() < 1e-4);
+        assert!(inspection.cicp().is_none());
+    }
+
+    #[test]
+    fn synthesized_pq() {
+        let profile =
+            colour_encoding_to_icc(&EnumColourEncoding::bt2100_pq(RenderingIntent::Relative));
+        let inspection = inspect_icc(&profile).unwrap();
+
+        assert!(inspection.is_valid());
+        assert_eq!(
+            inspection.cicp(),
+            Some(IccCicp {
+                color_primaries: 9,
+                transfer_characteristics: 16,
+                matrix_coefficients: 0,
+                video_full_range: true,
+            })
+        );
+        let Some(IccTagValue::Curve(IccCurve::Table(table))) = inspection.tag_value(*b"gTRC")
+        else {
+            panic!()
+        };
+        assert_eq!(table.len(), 4096);
+    }
+
+    #[test]
+    fn synthesized_v2() {
+        let options = IccSynthesisOptions {
+            version: IccVersion::V2,
+            ..Default::default()
+        };
+        let encodings = [
+            EnumColourEncoding::srgb(RenderingIntent::Relative),
+            EnumColourEncoding::srgb_gamma22(RenderingIntent::Relative),
+            EnumColourEncoding::srgb_linear(RenderingIntent::Relative),
+            EnumColourEncoding::bt709(RenderingIntent::Perceptual),
+            EnumColourEncoding::display_p3(RenderingIntent::Relative),
+            EnumColourEncoding::gray_gamma22(RenderingIntent::Relative),
+        ];
+
+        for encoding in encodings {
+            let profile = colour_encoding_to_icc_with_options(&encoding, options);
+            let inspection = inspect_icc(&profile).unwrap();
+            assert!(inspection.is_valid(), "{encoding:?}");
+            assert_eq!(inspection.header.version, (2, 4, 0));
+            assert!(matches!(
+                inspection.tag_value(*b"desc"),
+                Some(IccTagValue::Text(_))
+            ));
+            assert!(inspection.tag(*b"chad").is_none());
+            assert!(inspection.cicp().is_none());
+
+            let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+            let matched = matched.encoding;
+            assert_eq!(matched.colour_space, encoding.colour_space);
+            assert_eq!(matched.white_point, encoding.white_point);
+            assert_eq!(matched.tf, encoding.tf, "{encoding:?}");
+            assert_eq!(matched.rendering_intent, encoding.rendering_intent);
+            if encoding.colour_space == ColourSpace::Rgb {
+                assert_eq!(matched.primaries, encoding.primaries);
+            }
+        }
+    }
+
+    #[test]
+    fn synthesized_hdr_lut() {
+        let encoding = EnumColourEncoding::bt2100_pq(RenderingIntent::Relative);
+        let options = IccSynthesisOptions {
+            hdr_lut: true,
+            hdr_peak_luminance: Some(1000.0),
+            ..Default::default()
+        };
+        let profile = colour_encoding_to_icc_with_options(&encoding, options);
+        let inspection = inspect_icc(&profile).unwrap();
+
+        assert!(inspection.is_valid());
+        assert_eq!(inspection.header.version, (4, 4, 0));
+        assert!(inspection.cicp().is_some());
+        let Some(IccTagValue::LutAToB(lut)) = inspection.tag_value(*b"A2B0") else {
+            panic!()
+        };
+        let clut = lut.clut.as_ref().unwrap();
+        assert_eq!(clut.grid_points, [17, 17, 17]);
+        assert_eq!(clut.values.len(), 17 * 17 * 17 * 3);
+
+        // Black stays near black, and signals brighter than the peak are clipped to SDR white.
+        assert!(clut.values[..3].iter().all(|&v| v < 1024));
+        let white = &clut.values[clut.values.len() - 3..];
+        assert!(white.iter().all(|&v| v > 65000), "{white:?}");
+        // Neutral ramp is monotonic.
+        let ramp = (0..17)
+            .map(|idx| clut.values[(idx * 17 * 17 + idx * 17 + idx) * 3 + 1])
+            .collect::<Vec<_>>();
+        assert!(ramp.windows(2).all(|w| w[0] <= w[1]), "{ramp:?}");
+
+        // Matrix maps white to D50 in PCS XYZ encoding.
+        let matrix = lut.matrix.unwrap();
+        let y = (matrix[3] + matrix[4] + matrix[5]) * 65535.0 / 32768.0;
+        assert!((y - 1.0).abs() < 1e-3, "{y}");
+
+        // LUT is not written by default, nor in ICCv2 profiles.
+        let profile = colour_encoding_to_icc(&encoding);
+        assert!(inspect_icc(&profile).unwrap().tag(*b"A2B0").is_none());
+        let options = IccSynthesisOptions {
+            version: IccVersion::V2,
+            ..options
+        };
+        let profile = colour_encoding_to_icc_with_options(&encoding, options);
+        assert!(inspect_icc(&profile).unwrap().tag(*b"A2B0").is_none());
+    }
+
+    #[test]
+    fn external_profile() {
+        let inspection =
+            inspect_icc(include_bytes!("./test-profiles/prophoto-gamma18-rel.icc")).unwrap();
+        dbg!(&inspection);
+        assert!(inspection.is_valid());
+        assert!(inspection.description().is_some());
+    }
+
+    #[test]
+    fn tag_errors() {
+        let mut profile =
+            colour_encoding_to_icc(&EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let tag_count = read_u32(&profile, 0x80).unwrap() as usize;
+        for idx in 0..tag_count {
+            let entry = 0x84 + idx * 12;
+            match &profile[entry..][..4] {
+                // Point rXYZ to the data of desc tag.
+                b"rXYZ" => {
+                    let desc_offset = read_u32(&profile, 0x88).unwrap();
+                    profile[entry + 4..][..4].copy_from_slice(&desc_offset.to_be_bytes());
+                }
+                // Make bXYZ point outside of the profile.
+                b"bXYZ" => {
+                    profile[entry + 4..][..4].copy_from_slice(&u32::MAX.to_be_bytes());
+                }
+                _ => {}
+            }
+        }
+
+        let inspection = inspect_icc(&profile).unwrap();
+        assert!(!inspection.is_valid());
+        let errors = inspection
+            .tag_errors()
+            .map(|(sig, _)| *sig)
+            .collect::<Vec<_>>();
+        assert_eq!(errors, [*b"rXYZ", *b"bXYZ"]);
+        assert!(inspection.tag_value(*b"gXYZ").is_some());
+    }
+
+    #[test]
+    fn lut_ab() {
+        let mut tag = Vec::new();
+        tag.extend_from_slice(b"mAB \0\0\0\0");
+        tag.extend_from_slice(&[3, 3, 0, 0]);
+        // B curves at 32, no matrix and M curves, CLUT at 68, A curves at 112
+        for offset in [32u32, 0, 0, 68, 112] {
+            tag.extend_from_slice(&offset.to_be_bytes());
+        }
+        for _ in 0..3 {
+            tag.extend_from_slice(b"curv\0\0\0\0\0\0\0\0");
+        }
+        let mut clut = [0u8; 20];
+        clut[..3].copy_from_slice(&[2, 2, 2]);
+        clut[16] = 1;
+        tag.extend_from_slice(&clut);
+        tag.extend((0..24).map(|v| v as u8));
+        for _ in 0..3 {
+            tag.extend_from_slice(b"para\0\0\0\0\0\0\0\0\0\x02\x00\x00");
+        }
+
+        let value = decode_tag(*b"A2B0", &tag, Some(3)).unwrap();
+        let IccTagValue::LutAToB(lut) = value else {
+            panic!()
+        };
+        assert_eq!(lut.b_curves.len(), 3);
+        assert!(lut.matrix.is_none());
+        let clut = lut.clut.unwrap();
+        assert_eq!(clut.grid_points, [2, 2, 2]);
+        assert_eq!(clut.values.len(), 24);
+        assert_eq!(clut.values[1], 257);
+        let a_curves = lut.a_curves.unwrap();
+        assert!(matches!(
+            a_curves[0],
+            IccCurve::Parametric { function_type: 0, ref params } if params == &[2.0]
+        ));
+
+        // Device has four channels, but the LUT takes three.
+        assert!(decode_tag(*b"A2B0", &tag, Some(4)).is_err());
+        // mAB is not allowed in B2A tags.
+        assert!(decode_tag(*b"B2A0", &tag, Some(3)).is_err());
+    }
+}
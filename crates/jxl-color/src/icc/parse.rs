@@ -3,14 +3,64 @@ use crate::{
     TransferFunction, WhitePoint,
 };
 
+use super::IccCurve;
+
+/// Tolerance used when matching ICC profiles to enum color encodings.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct IccMatchTolerance {
+    /// Maximum absolute error of tone curves, with input and output normalized to `[0, 1]`.
+    pub trc: f32,
+    /// Maximum difference of CIE xy chromaticity coordinates of primaries and white point.
+    pub chromaticity: f32,
+    /// Whether to ignore informational tags which don't affect colorimetry, such as `lumi`, `meas`
+    /// and `view`.
+    pub ignore_informational_tags: bool,
+}
+
+impl Default for IccMatchTolerance {
+    /// Returns [`IccMatchTolerance::EXACT`].
+    fn default() -> Self {
+        Self::EXACT
+    }
+}
+
+impl IccMatchTolerance {
+    /// Tolerance which only matches tone curves with exact parameters.
+    pub const EXACT: Self = Self {
+        trc: 0.0,
+        chromaticity: 1e-4,
+        ignore_informational_tags: false,
+    };
+
+    /// Tolerance which also matches sampled tone curves and slightly different parameters, found
+    /// in profiles from common cameras and editors.
+    pub const APPROXIMATE: Self = Self {
+        trc: 2e-3,
+        chromaticity: 2e-3,
+        ignore_informational_tags: true,
+    };
+}
+
+/// Result of matching an ICC profile to an enum color encoding.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IccEncodingMatch {
+    /// Matched color encoding.
+    pub encoding: EnumColourEncoding,
+    /// Maximum absolute error of tone curves.
+    pub trc_error: f32,
+    /// Maximum difference of CIE xy chromaticity coordinates of primaries and white point.
+    pub chromaticity_error: f32,
+}
+
 #[derive(Debug)]
 pub struct IccProfileInfo {
     color_space: [u8; 4],
     rendering_intent: RenderingIntent,
-    chad: [i32; 9],
-    wtpt: [i32; 3],
-    trc_k: Option<KnownIccTrc>,
-    trc_rgb: Option<[KnownIccTrc; 3]>,
+    chad: [f32; 9],
+    wtpt: [f32; 3],
+    trc_k: Option<IccCurve>,
+    trc_rgb: Option<[IccCurve; 3]>,
     xyz_rgb: Option<[[i32; 3]; 3]>,
 }
 
@@ -35,33 +85,36 @@ impl IccProfileInfo {
         self.rendering_intent
     }
 
-    #[inline]
-    pub fn trc_color(&self) -> Option<TransferFunction> {
-        let [r, g, b] = self.trc_rgb?;
-        if r == g && g == b {
-            Some(r.into())
-        } else {
-            None
+    /// Returns the transfer function of RGB channels and its maximum error.
+    pub fn trc_color(&self, tolerance: f32) -> Option<(TransferFunction, f32)> {
+        let [r, g, b] = self.trc_rgb.as_ref()?;
+        let (tf, r_err) = match_trc(r, tolerance)?;
+        let mut max_err = r_err;
+        for curve in [g, b] {
+            let err = trc_error(curve, tf)?;
+            if err > tolerance {
+                return None;
+            }
+            max_err = max_err.max(err);
         }
+        Some((tf, max_err))
     }
 
-    #[inline]
-    pub fn trc_gray(&self) -> Option<TransferFunction> {
-        self.trc_k.map(From::from)
-    }
-
-    #[inline]
-    fn chad_f32(&self) -> [f32; 9] {
-        self.chad.map(|x| x as f32 / 65536f32)
+    /// Returns the transfer function of the gray channel and its maximum error.
+    pub fn trc_gray(&self, tolerance: f32) -> Option<(TransferFunction, f32)> {
+        match_trc(self.trc_k.as_ref()?, tolerance)
     }
 
     #[inline]
     fn chad_inv(&self) -> [f32; 9] {
-        let chad_mat = self.chad_f32();
-        crate::ciexyz::matinv(&chad_mat)
+        crate::ciexyz::matinv(&self.chad)
     }
 
-    pub fn primaries(&self) -> Option<Primaries> {
+    /// Returns the primaries and its chromaticity error.
+    ///
+    /// If no known primaries are within the tolerance, custom primaries are returned with the
+    /// error from quantizing the coordinates.
+    pub fn primaries(&self, tolerance: f32) -> Option<(Primaries, f32)> {
         const PRIMARIES_TO_ENUM: [([[f32; 2]; 3], Primaries); 3] = [
             (crate::consts::PRIMARIES_SRGB, Primaries::Srgb),
             (crate::consts::PRIMARIES_P3, Primaries::P3),
@@ -97,40 +150,37 @@ impl IccProfileInfo {
             ],
         ];
 
-        'outer: for (known_primaries, ret) in PRIMARIES_TO_ENUM {
-            for y in 0..3 {
-                for x in 0..2 {
-                    let diff = (primaries[y][x] - known_primaries[y][x]).abs();
-                    if diff >= 1e-4 {
-                        continue 'outer;
-                    }
-                }
+        let best = PRIMARIES_TO_ENUM
+            .into_iter()
+            .map(|(known_primaries, ret)| {
+                let diff = (0..6)
+                    .map(|idx| {
+                        (primaries[idx / 2][idx % 2] - known_primaries[idx / 2][idx % 2]).abs()
+                    })
+                    .fold(0f32, f32::max);
+                (ret, diff)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((ret, diff)) = best {
+            if diff < tolerance {
+                return Some((ret, diff));
             }
-            return Some(ret);
         }
 
-        Some(Primaries::Custom {
-            red: Customxy {
-                x: (primaries[0][0] * 1e6 + 0.5) as i32,
-                y: (primaries[0][1] * 1e6 + 0.5) as i32,
-            },
-            green: Customxy {
-                x: (primaries[1][0] * 1e6 + 0.5) as i32,
-                y: (primaries[1][1] * 1e6 + 0.5) as i32,
-            },
-            blue: Customxy {
-                x: (primaries[2][0] * 1e6 + 0.5) as i32,
-                y: (primaries[2][1] * 1e6 + 0.5) as i32,
-            },
-        })
-    }
-
-    #[inline]
-    fn media_white(&self) -> [f32; 3] {
-        self.wtpt.map(|v| v as f32 / 65536f32)
+        let (red, red_err) = quantize_xy(primaries[0]);
+        let (green, green_err) = quantize_xy(primaries[1]);
+        let (blue, blue_err) = quantize_xy(primaries[2]);
+        Some((
+            Primaries::Custom { red, green, blue },
+            red_err.max(green_err).max(blue_err),
+        ))
     }
 
-    pub fn white_point(&self) -> WhitePoint {
+    /// Returns the white point and its chromaticity error.
+    ///
+    /// If no known white point is within the tolerance, custom white point is returned with the
+    /// error from quantizing the coordinates.
+    pub fn white_point(&self, tolerance: f32) -> (WhitePoint, f32) {
         const WP_TO_ENUM: [([f32; 2], WhitePoint); 3] = [
             (crate::consts::ILLUMINANT_D65, WhitePoint::D65),
             (crate::consts::ILLUMINANT_DCI, WhitePoint::Dci),
@@ -138,61 +188,215 @@ impl IccProfileInfo {
         ];
 
         let chad_inv = self.chad_inv();
-        let ill_xyz = crate::ciexyz::matmul3vec(&chad_inv, &self.media_white());
+        let ill_xyz = crate::ciexyz::matmul3vec(&chad_inv, &self.wtpt);
         let xyz_sum = ill_xyz[0] + ill_xyz[1] + ill_xyz[2];
         let illuminant = [ill_xyz[0] / xyz_sum, ill_xyz[1] / xyz_sum];
 
-        'outer: for (known_wp, ret) in WP_TO_ENUM {
-            for x in 0..2 {
-                let diff = (illuminant[x] - known_wp[x]).abs();
-                if diff >= 1e-4 {
-                    continue 'outer;
-                }
+        let best = WP_TO_ENUM
+            .into_iter()
+            .map(|(known_wp, ret)| {
+                let diff = (illuminant[0] - known_wp[0])
+                    .abs()
+                    .max((illuminant[1] - known_wp[1]).abs());
+                (ret, diff)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((ret, diff)) = best {
+            if diff < tolerance {
+                return (ret, diff);
             }
-            return ret;
         }
 
-        WhitePoint::Custom(Customxy {
-            x: (illuminant[0] * 1e6 + 0.5) as i32,
-            y: (illuminant[1] * 1e6 + 0.5) as i32,
-        })
+        let (wp, err) = quantize_xy(illuminant);
+        (WhitePoint::Custom(wp), err)
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-enum KnownIccTrc {
-    ParametricGamma(u32),
-    Linear,
-    Srgb,
-    Bt709,
+/// Converts chromaticity coordinate to [`Customxy`], returning the quantization error.
+fn quantize_xy([x, y]: [f32; 2]) -> (Customxy, f32) {
+    let xy = Customxy {
+        x: (x * 1e6 + 0.5) as i32,
+        y: (y * 1e6 + 0.5) as i32,
+    };
+    let [qx, qy] = xy.as_float();
+    (xy, (x - qx).abs().max((y - qy).abs()))
 }
 
-impl From<KnownIccTrc> for TransferFunction {
-    fn from(value: KnownIccTrc) -> Self {
-        match value {
-            KnownIccTrc::ParametricGamma(g) => {
+/// Matches tone curve exactly to known transfer functions, by looking at curve parameters.
+fn match_trc_exact(curve: &IccCurve) -> Option<TransferFunction> {
+    fn gamma_tf(g_s15fixed16: i32) -> Option<TransferFunction> {
+        match g_s15fixed16 {
+            ..=65535 => None,
+            65536 => Some(TransferFunction::Linear),
+            g => {
                 let g = g as u64;
                 let g_1e7 = (g * 10000000 + 32768) / 65536;
-                TransferFunction::Gamma {
+                Some(TransferFunction::Gamma {
                     g: g_1e7 as u32,
                     inverted: false,
-                }
+                })
             }
-            KnownIccTrc::Linear => TransferFunction::Linear,
-            KnownIccTrc::Srgb => TransferFunction::Srgb,
-            KnownIccTrc::Bt709 => TransferFunction::Bt709,
         }
     }
+
+    match curve {
+        IccCurve::Identity => Some(TransferFunction::Linear),
+        IccCurve::Gamma(g) => gamma_tf((g * 65536.0) as i32),
+        IccCurve::Parametric {
+            function_type: 0,
+            params,
+        } => gamma_tf((params[0] * 65536.0) as i32),
+        IccCurve::Parametric {
+            function_type: 3,
+            params,
+        } => {
+            let params: [i32; 5] = std::array::from_fn(|idx| (params[idx] * 65536.0) as i32);
+            if params
+                == [
+                    (65536 * 20 + 4) / 9,
+                    (65536 * 1000 + 549) / 1099,
+                    (65536 * 99 + 549) / 1099,
+                    (65536 * 10 + 22) / 45,
+                    (65536 * 81 + 500) / 1000,
+                ]
+            {
+                Some(TransferFunction::Bt709)
+            } else if params
+                == [
+                    (65536 * 24 + 5) / 10,
+                    (65536 * 1000 + 527) / 1055,
+                    (65536 * 55 + 527) / 1055,
+                    (65536 * 100 + 646) / 1292,
+                    (65536 * 4045 + 50000) / 100000,
+                ]
+            {
+                Some(TransferFunction::Srgb)
+            } else if let [gamma, 65536, 0, 65536, 0] = params {
+                gamma_tf(gamma)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
 }
 
-impl KnownIccTrc {
-    fn from_gamma(g_s15fixed16: i32) -> Option<Self> {
-        match g_s15fixed16 {
-            ..=65535 => None,
-            65536 => Some(Self::Linear),
-            g => Some(Self::ParametricGamma(g as u32)),
+/// Samples the tone curve, at the table entries if the curve is a table.
+fn sample_curve(curve: &IccCurve) -> (Vec<f32>, Vec<f32>) {
+    match curve {
+        IccCurve::Table(table) if table.len() >= 2 => {
+            let last = (table.len() - 1) as f32;
+            (
+                (0..table.len()).map(|idx| idx as f32 / last).collect(),
+                table.iter().map(|&v| v as f32 / 65535.0).collect(),
+            )
+        }
+        _ => {
+            let xs = (0..1024).map(|idx| idx as f32 / 1023.0).collect::<Vec<_>>();
+            let ys = xs.iter().map(|&x| curve.eval(x)).collect();
+            (xs, ys)
+        }
+    }
+}
+
+/// Evaluates the decoding function of the transfer function, as stored in ICC tone curves.
+fn eval_tf(tf: TransferFunction, xs: &[f32]) -> Option<Vec<f32>> {
+    let mut out = xs.to_vec();
+    match tf {
+        TransferFunction::Linear => {}
+        TransferFunction::Gamma { g, inverted: false } => {
+            let g = g as f32 / 1e7;
+            for v in &mut out {
+                *v = v.powf(g);
+            }
+        }
+        TransferFunction::Srgb => crate::tf::srgb_to_linear(&mut out),
+        TransferFunction::Bt709 => crate::tf::bt709_to_linear(&mut out),
+        TransferFunction::Pq => {
+            for v in &mut out {
+                *v = crate::tf::pq::pq_to_linear_f64(*v as f64) as f32;
+            }
+        }
+        TransferFunction::Hlg => {
+            for v in &mut out {
+                *v = crate::tf::hlg_to_linear_f64(*v as f64) as f32;
+            }
+        }
+        _ => return None,
+    }
+    Some(out)
+}
+
+/// Computes maximum absolute error between the tone curve and the transfer function.
+fn trc_error(curve: &IccCurve, tf: TransferFunction) -> Option<f32> {
+    if match_trc_exact(curve) == Some(tf) {
+        return Some(0.0);
+    }
+
+    let (xs, ys) = sample_curve(curve);
+    let expected = eval_tf(tf, &xs)?;
+    let err = ys
+        .iter()
+        .zip(expected)
+        .map(|(&y, expected)| (y - expected).abs())
+        .fold(0f32, f32::max);
+    err.is_finite().then_some(err)
+}
+
+/// Finds the transfer function that fits the tone curve the best, within the tolerance.
+fn match_trc(curve: &IccCurve, tolerance: f32) -> Option<(TransferFunction, f32)> {
+    if let Some(tf) = match_trc_exact(curve) {
+        return Some((tf, 0.0));
+    }
+    if tolerance <= 0.0 {
+        return None;
+    }
+
+    let (xs, ys) = sample_curve(curve);
+
+    // Estimate gamma using least squares in log domain.
+    let (num, denom) = xs
+        .iter()
+        .zip(&ys)
+        .filter(|&(&x, &y)| (0.05..=0.95).contains(&x) && y > 1e-6)
+        .fold((0f64, 0f64), |(num, denom), (&x, &y)| {
+            let lx = (x as f64).ln();
+            (num + lx * (y as f64).ln(), denom + lx * lx)
+        });
+    let gamma = num / denom;
+    let mut candidates = vec![
+        TransferFunction::Linear,
+        TransferFunction::Srgb,
+        TransferFunction::Bt709,
+        TransferFunction::Pq,
+        TransferFunction::Hlg,
+    ];
+    if gamma.is_finite() && gamma > 0.0 {
+        // Try rounded gamma first, so that gamma of 2.2 is preferred over 2.2001.
+        for gamma in [(gamma * 100.0).round() / 100.0, gamma] {
+            candidates.push(TransferFunction::Gamma {
+                g: (gamma * 1e7).round() as u32,
+                inverted: false,
+            });
         }
     }
+
+    let mut best: Option<(TransferFunction, f32)> = None;
+    for tf in candidates {
+        let Some(expected) = eval_tf(tf, &xs) else {
+            continue;
+        };
+        let err = ys
+            .iter()
+            .zip(expected)
+            .map(|(&y, expected)| (y - expected).abs())
+            .fold(0f32, f32::max);
+        if err.is_finite() && best.map(|(_, best_err)| err < best_err).unwrap_or(true) {
+            best = Some((tf, err));
+        }
+    }
+
+    best.filter(|&(_, err)| err <= tolerance)
 }
 
 pub(crate) struct IccProfile<'a> {
@@ -218,17 +422,26 @@ struct RawTag<'a> {
     data: &'a [u8],
 }
 
-pub(crate) fn parse_icc_raw(profile: &[u8]) -> Result<IccProfile> {
+pub(super) fn parse_icc_header(profile: &[u8]) -> Result<super::IccHeader> {
     if profile.len() < 128 {
         return Err(Error::IccParseFailure("profile is too short"));
     }
 
-    let size = u32::from_be_bytes([profile[0], profile[1], profile[2], profile[3]]);
+    let read_u32 = |offset: usize| {
+        u32::from_be_bytes([
+            profile[offset],
+            profile[offset + 1],
+            profile[offset + 2],
+            profile[offset + 3],
+        ])
+    };
+    let read_sig = |offset: usize| -> [u8; 4] { read_u32(offset).to_be_bytes() };
+
+    let size = read_u32(0);
     if profile.len() != size as usize {
         return Err(Error::IccParseFailure("profile size mismatch"));
     }
 
-    let color_space = [profile[0x10], profile[0x11], profile[0x12], profile[0x13]];
     let rendering_intent_raw = profile[0x43];
     let rendering_intent = match rendering_intent_raw {
         0 => RenderingIntent::Perceptual,
@@ -238,10 +451,37 @@ pub(crate) fn parse_icc_raw(profile: &[u8]) -> Result<IccProfile> {
         _ => return Err(Error::IccParseFailure("invalid rendering intent")),
     };
 
-    let header = super::IccHeader {
-        color_space,
+    let creation_date = std::array::from_fn(|idx| {
+        u16::from_be_bytes([profile[0x18 + idx * 2], profile[0x19 + idx * 2]])
+    });
+    let illuminant = std::array::from_fn(|idx| read_u32(0x44 + idx * 4) as i32 as f32 / 65536.0);
+    let mut profile_id = [0u8; 16];
+    profile_id.copy_from_slice(&profile[0x54..0x64]);
+
+    Ok(super::IccHeader {
+        size,
+        cmm_type: read_sig(0x04),
+        version: (profile[0x08], profile[0x09] >> 4, profile[0x09] & 0xf),
+        device_class: read_sig(0x0c),
+        color_space: read_sig(0x10),
+        pcs: read_sig(0x14),
+        creation_date,
+        signature: read_sig(0x24),
+        platform: read_sig(0x28),
+        flags: read_u32(0x2c),
+        manufacturer: read_sig(0x30),
+        model: read_sig(0x34),
+        attributes: ((read_u32(0x38) as u64) << 32) | read_u32(0x3c) as u64,
         rendering_intent,
-    };
+        illuminant,
+        creator: read_sig(0x50),
+        profile_id,
+    })
+}
+
+pub(crate) fn parse_icc_raw(profile: &[u8]) -> Result<IccProfile<'_>> {
+    let header = parse_icc_header(profile)?;
+    let size = header.size;
 
     if size < 0x84 {
         return Ok(IccProfile {
@@ -280,7 +520,10 @@ pub(crate) fn parse_icc_raw(profile: &[u8]) -> Result<IccProfile> {
     Ok(IccProfile { header, tags })
 }
 
-pub fn detect_profile_info(profile: &[u8]) -> Result<IccProfileInfo> {
+pub fn detect_profile_info(
+    profile: &[u8],
+    ignore_informational_tags: bool,
+) -> Result<IccProfileInfo> {
     let profile = parse_icc_raw(profile)?;
 
     let color_space = profile.header.color_space;
@@ -288,7 +531,7 @@ pub fn detect_profile_info(profile: &[u8]) -> Result<IccProfileInfo> {
 
     let mut wtpt = [0xf6d6, 0x10000, 0xd32d]; // D50
     let mut chad: Option<[i32; 9]> = None;
-    let mut trcs: [Option<KnownIccTrc>; 4] = [None; 4];
+    let mut trcs: [Option<IccCurve>; 4] = [None, None, None, None];
     let mut xyzs: [Option<[i32; 3]>; 3] = [None; 3];
     for tag in profile.tags {
         let data = tag.data;
@@ -307,86 +550,18 @@ pub fn detect_profile_info(profile: &[u8]) -> Result<IccProfileInfo> {
                     _ => continue,
                 };
 
-                let tf = match data {
-                    [b'p', b'a', b'r', b'a', ..] => {
-                        if data.len() < 12 {
-                            continue;
-                        }
-                        let curve_type = u16::from_be_bytes([data[8], data[9]]);
-                        let parameters = (data.len() - 12) / 4;
-                        match curve_type {
-                            0 => {
-                                if parameters != 1 {
-                                    return Err(Error::IccParseFailure(
-                                        "invalid parametricCurveType",
-                                    ));
-                                }
-
-                                let [gamma]: [i32; 1] = std::array::from_fn(|idx| {
-                                    let mut bytes = [0u8; 4];
-                                    bytes.copy_from_slice(&data[12 + 4 * idx..][..4]);
-                                    i32::from_be_bytes(bytes)
-                                });
-                                if let Some(trc) = KnownIccTrc::from_gamma(gamma) {
-                                    trc
-                                } else {
-                                    continue;
-                                }
-                            }
-                            3 => {
-                                if parameters != 5 {
-                                    return Err(Error::IccParseFailure(
-                                        "invalid parametricCurveType",
-                                    ));
-                                }
-
-                                let params: [i32; 5] = std::array::from_fn(|idx| {
-                                    let mut bytes = [0u8; 4];
-                                    bytes.copy_from_slice(&data[12 + 4 * idx..][..4]);
-                                    i32::from_be_bytes(bytes)
-                                });
-
-                                if params
-                                    == [
-                                        (65536 * 20 + 4) / 9,
-                                        (65536 * 1000 + 549) / 1099,
-                                        (65536 * 99 + 549) / 1099,
-                                        (65536 * 10 + 22) / 45,
-                                        (65536 * 81 + 500) / 1000,
-                                    ]
-                                {
-                                    KnownIccTrc::Bt709
-                                } else if params
-                                    == [
-                                        (65536 * 24 + 5) / 10,
-                                        (65536 * 1000 + 527) / 1055,
-                                        (65536 * 55 + 527) / 1055,
-                                        (65536 * 100 + 646) / 1292,
-                                        (65536 * 4045 + 50000) / 100000,
-                                    ]
-                                {
-                                    KnownIccTrc::Srgb
-                                } else if let [gamma, 65536, 0, 65536, 0] = params {
-                                    if let Some(trc) = KnownIccTrc::from_gamma(gamma) {
-                                        trc
-                                    } else {
-                                        continue;
-                                    }
-                                } else {
-                                    continue;
-                                }
-                            }
-                            _ => continue,
-                        }
-                    }
-                    [b'c', b'u', b'r', b'v', 0, 0, 0, 0, 0, 0, 0, 0] => KnownIccTrc::Linear,
-                    [b'c', b'u', b'r', b'v', 0, 0, 0, 0, 0, 0, 0, 1, a, b] => {
-                        KnownIccTrc::ParametricGamma(u32::from_be_bytes([0, *a, *b, 0]))
+                let curve = match super::inspect::decode_curve(data) {
+                    Ok((curve, _)) => curve,
+                    // Unknown function types are skipped, like unknown curve types.
+                    Err(e)
+                        if data.starts_with(b"para")
+                            && matches!(data.get(8..10), Some([0, 0..=4])) =>
+                    {
+                        return Err(e)
                     }
-                    _ => continue,
+                    Err(_) => continue,
                 };
-
-                trcs[index] = Some(tf);
+                trcs[index] = Some(curve);
             }
             [color, b'X', b'Y', b'Z'] => {
                 let index = match color {
@@ -438,6 +613,8 @@ pub fn detect_profile_info(profile: &[u8]) -> Result<IccProfileInfo> {
             | [b'p', b'r', b'e', b'0'..=b'2'] => {
                 return Err(Error::UnsupportedIccProfile);
             }
+            ref x
+                if ignore_informational_tags && (x == b"lumi" || x == b"meas" || x == b"view") => {}
             ref x
                 if x == b"chrm"
                     || x == b"clro"
@@ -456,22 +633,41 @@ pub fn detect_profile_info(profile: &[u8]) -> Result<IccProfileInfo> {
         }
     }
 
-    let trc_rgb = if let [Some(r), Some(g), Some(b), _] = trcs {
-        Some([r, g, b])
+    let trc_rgb = if let [Some(r), Some(g), Some(b), _] = &trcs {
+        Some([r.clone(), g.clone(), b.clone()])
     } else {
         None
     };
-    let trc_k = trcs[3];
+    let [_, _, _, trc_k] = trcs;
     let xyz_rgb = if let [Some(r), Some(g), Some(b)] = xyzs {
         Some([r, g, b])
     } else {
         None
     };
 
+    let mut wtpt = wtpt.map(|v| v as f32 / 65536f32);
+    let chad = if let Some(chad) = chad {
+        chad.map(|x| x as f32 / 65536f32)
+    } else {
+        // ICCv2 profiles may not have `chad` tag, with `wtpt` being the actual media white point
+        // instead of PCS illuminant. Colorants are adapted to D50 using Bradford transform in
+        // that case.
+        let sum = wtpt[0] + wtpt[1] + wtpt[2];
+        let wtpt_xy = [wtpt[0] / sum, wtpt[1] / sum];
+        let d50 = crate::consts::ILLUMINANT_D50;
+        if (wtpt_xy[0] - d50[0]).abs() < 1e-3 && (wtpt_xy[1] - d50[1]).abs() < 1e-3 {
+            [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+        } else {
+            let chad = crate::ciexyz::adapt_mat(wtpt_xy, d50);
+            wtpt = crate::ciexyz::matmul3vec(&chad, &wtpt);
+            chad
+        }
+    };
+
     Ok(IccProfileInfo {
         color_space,
         rendering_intent,
-        chad: chad.unwrap_or([65536, 0, 0, 0, 65536, 0, 0, 0, 65536]),
+        chad,
         wtpt,
         trc_k,
         trc_rgb,
@@ -501,35 +697,54 @@ fn validate_chad(chad_s15fixed16: [i32; 9]) -> Result<()> {
     Ok(())
 }
 
-pub(crate) fn parse_icc(profile: &[u8]) -> Result<EnumColourEncoding> {
-    let info = detect_profile_info(profile)?;
+/// Matches the ICC profile to an enum color encoding, within the given tolerance.
+///
+/// # Errors
+/// Returns [`Error::UnsupportedIccProfile`] if the profile cannot be represented as an enum color
+/// encoding within the tolerance.
+pub fn match_enum_encoding(
+    profile: &[u8],
+    tolerance: IccMatchTolerance,
+) -> Result<IccEncodingMatch> {
+    let info = detect_profile_info(profile, tolerance.ignore_informational_tags)?;
     let rendering_intent = info.rendering_intent();
 
     if info.is_cmyk() {
         Err(Error::UnsupportedIccProfile)
     } else if info.is_grayscale() {
-        let Some(tf) = info.trc_gray() else {
+        let Some((tf, trc_error)) = info.trc_gray(tolerance.trc) else {
             return Err(Error::UnsupportedIccProfile);
         };
-        let wp = info.white_point();
-        Ok(EnumColourEncoding {
-            colour_space: crate::ColourSpace::Grey,
-            white_point: wp,
-            primaries: Primaries::Srgb,
-            tf,
-            rendering_intent,
+        let (wp, chromaticity_error) = info.white_point(tolerance.chromaticity);
+        Ok(IccEncodingMatch {
+            encoding: EnumColourEncoding {
+                colour_space: crate::ColourSpace::Grey,
+                white_point: wp,
+                primaries: Primaries::Srgb,
+                tf,
+                rendering_intent,
+            },
+            trc_error,
+            chromaticity_error,
         })
     } else if info.is_rgb() {
-        let (Some(tf), Some(primaries)) = (info.trc_color(), info.primaries()) else {
+        let (Some((tf, trc_error)), Some((primaries, primaries_error))) = (
+            info.trc_color(tolerance.trc),
+            info.primaries(tolerance.chromaticity),
+        ) else {
             return Err(Error::UnsupportedIccProfile);
         };
-        let wp = info.white_point();
-        Ok(EnumColourEncoding {
-            colour_space: crate::ColourSpace::Rgb,
-            white_point: wp,
-            primaries,
-            tf,
-            rendering_intent,
+        let (wp, wp_error) = info.white_point(tolerance.chromaticity);
+        Ok(IccEncodingMatch {
+            encoding: EnumColourEncoding {
+                colour_space: crate::ColourSpace::Rgb,
+                white_point: wp,
+                primaries,
+                tf,
+                rendering_intent,
+            },
+            trc_error,
+            chromaticity_error: primaries_error.max(wp_error),
         })
     } else {
         Err(Error::UnsupportedIccProfile)
@@ -538,9 +753,13 @@ pub(crate) fn parse_icc(profile: &[u8]) -> Result<EnumColourEncoding> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_icc;
+    use super::*;
     use crate::*;
 
+    fn parse_icc(profile: &[u8]) -> Result<EnumColourEncoding> {
+        match_enum_encoding(profile, IccMatchTolerance::EXACT).map(|m| m.encoding)
+    }
+
     #[test]
     fn srgb_rel() {
         let profile = parse_icc(include_bytes!("./test-profiles/srgb-rel.icc")).unwrap();
@@ -640,4 +859,283 @@ mod tests {
             },
         ));
     }
+
+    /// Rebuilds `base` profile with some tags replaced or removed.
+    fn rebuild_profile(base: &[u8], replace: &[([u8; 4], Option<Vec<u8>>)]) -> Vec<u8> {
+        let inspection = icc::inspect_icc(base).unwrap();
+        let mut tags = inspection
+            .tags
+            .iter()
+            .map(|tag| {
+                let data = base[tag.offset as usize..][..tag.size as usize].to_vec();
+                (tag.signature, data)
+            })
+            .collect::<Vec<_>>();
+        for (signature, data) in replace {
+            tags.retain(|(sig, _)| sig != signature);
+            if let Some(data) = data {
+                tags.push((*signature, data.clone()));
+            }
+        }
+
+        let mut out = base[..128].to_vec();
+        out.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+        let mut data_out = Vec::new();
+        let data_offset = 132 + tags.len() * 12;
+        for (signature, data) in &tags {
+            out.extend_from_slice(signature);
+            out.extend_from_slice(&((data_offset + data_out.len()) as u32).to_be_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            data_out.extend_from_slice(data);
+            data_out.resize((data_out.len() + 3) & !3, 0);
+        }
+        out.extend(data_out);
+        let len = out.len() as u32;
+        out[..4].copy_from_slice(&len.to_be_bytes());
+        out
+    }
+
+    fn curv_table(n: usize, f: impl Fn(f64) -> f64) -> Vec<u8> {
+        let mut out = b"curv\0\0\0\0".to_vec();
+        out.extend_from_slice(&(n as u32).to_be_bytes());
+        for idx in 0..n {
+            let v = f(idx as f64 / (n - 1) as f64);
+            out.extend_from_slice(&((v * 65535.0 + 0.5) as u16).to_be_bytes());
+        }
+        out
+    }
+
+    fn srgb_eotf(x: f64) -> f64 {
+        if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn with_trc(base: &[u8], trc: Vec<u8>) -> Vec<u8> {
+        rebuild_profile(
+            base,
+            &[
+                (*b"rTRC", Some(trc.clone())),
+                (*b"gTRC", Some(trc.clone())),
+                (*b"bTRC", Some(trc)),
+            ],
+        )
+    }
+
+    #[test]
+    fn sampled_srgb() {
+        let base =
+            icc::colour_encoding_to_icc(&EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let profile = with_trc(&base, curv_table(1024, srgb_eotf));
+
+        assert!(matches!(
+            match_enum_encoding(&profile, IccMatchTolerance::EXACT),
+            Err(Error::UnsupportedIccProfile)
+        ));
+
+        let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        dbg!(&matched);
+        assert!(matches!(
+            matched.encoding,
+            EnumColourEncoding {
+                colour_space: ColourSpace::Rgb,
+                white_point: WhitePoint::D65,
+                primaries: Primaries::Srgb,
+                tf: TransferFunction::Srgb,
+                ..
+            }
+        ));
+        assert!(matched.trc_error > 0.0 && matched.trc_error < 1e-4);
+    }
+
+    #[test]
+    fn sampled_gamma_is_not_srgb() {
+        let base =
+            icc::colour_encoding_to_icc(&EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let profile = with_trc(&base, curv_table(256, |x| x.powf(2.2)));
+
+        let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        dbg!(&matched);
+        assert!(matches!(
+            matched.encoding.tf,
+            TransferFunction::Gamma {
+                g: 22000000,
+                inverted: false
+            }
+        ));
+    }
+
+    #[test]
+    fn sampled_pq_hlg() {
+        let base =
+            icc::colour_encoding_to_icc(&EnumColourEncoding::bt2100_pq(RenderingIntent::Relative));
+        let profile = rebuild_profile(&base, &[(*b"cicp", None)]);
+        let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        assert_eq!(matched.encoding.tf, TransferFunction::Pq);
+        assert_eq!(matched.encoding.primaries, Primaries::Bt2100);
+
+        let base =
+            icc::colour_encoding_to_icc(&EnumColourEncoding::bt2100_hlg(RenderingIntent::Relative));
+        let profile = rebuild_profile(&base, &[(*b"cicp", None)]);
+        let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        assert_eq!(matched.encoding.tf, TransferFunction::Hlg);
+    }
+
+    #[test]
+    fn vendor_display_p3() {
+        // Breakpoint of the tone curve is from older version of sRGB specification.
+        let mut para = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        for v in [2.4f32, 0.94786, 0.05214, 0.07739, 0.03928] {
+            para.extend_from_slice(&((v * 65536.0).round() as i32).to_be_bytes());
+        }
+        let base = icc::colour_encoding_to_icc(&EnumColourEncoding::display_p3(
+            RenderingIntent::Perceptual,
+        ));
+        let profile = with_trc(&base, para);
+
+        let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        dbg!(&matched);
+        assert!(matches!(
+            matched.encoding,
+            EnumColourEncoding {
+                colour_space: ColourSpace::Rgb,
+                white_point: WhitePoint::D65,
+                primaries: Primaries::P3,
+                tf: TransferFunction::Srgb,
+                rendering_intent: RenderingIntent::Perceptual,
+            }
+        ));
+        assert!(matched.trc_error > 0.0);
+    }
+
+    #[test]
+    fn v2_without_chad() {
+        // ICCv2 style sRGB profile: media white point is D65, and there's no `chad` tag.
+        let base =
+            icc::colour_encoding_to_icc(&EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let mut wtpt = b"XYZ \0\0\0\0".to_vec();
+        for v in [0.9505f32, 1.0, 1.089] {
+            wtpt.extend_from_slice(&((v * 65536.0).round() as i32).to_be_bytes());
+        }
+        let profile = rebuild_profile(&base, &[(*b"chad", None), (*b"wtpt", Some(wtpt))]);
+
+        assert!(matches!(
+            match_enum_encoding(&profile, IccMatchTolerance::EXACT),
+            Ok(IccEncodingMatch {
+                encoding: EnumColourEncoding {
+                    white_point: WhitePoint::D65,
+                    primaries: Primaries::Srgb,
+                    tf: TransferFunction::Srgb,
+                    ..
+                },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn custom_primaries_error() {
+        let encoding = EnumColourEncoding {
+            primaries: Primaries::Custom {
+                red: Customxy {
+                    x: 700000,
+                    y: 300000,
+                },
+                green: Customxy {
+                    x: 200000,
+                    y: 700000,
+                },
+                blue: Customxy {
+                    x: 150000,
+                    y: 50000,
+                },
+            },
+            ..EnumColourEncoding::srgb(RenderingIntent::Relative)
+        };
+        let profile = icc::colour_encoding_to_icc(&encoding);
+
+        let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        dbg!(&matched);
+        let Primaries::Custom { red, green, blue } = matched.encoding.primaries else {
+            panic!("primaries should be custom");
+        };
+        for (actual, expected) in [red, green, blue].into_iter().zip([
+            [700000, 300000],
+            [200000, 700000],
+            [150000, 50000],
+        ]) {
+            assert!((actual.x - expected[0]).abs() < 100);
+            assert!((actual.y - expected[1]).abs() < 100);
+        }
+
+        // Error of custom primaries is from quantization.
+        let info = detect_profile_info(&profile, false).unwrap();
+        let (_, primaries_error) = info
+            .primaries(IccMatchTolerance::APPROXIMATE.chromaticity)
+            .unwrap();
+        assert!(primaries_error < 1e-6);
+        assert!(matched.chromaticity_error >= primaries_error);
+    }
+
+    #[test]
+    fn informational_tags() {
+        let base =
+            icc::colour_encoding_to_icc(&EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let lumi = b"XYZ \0\0\0\0\0\x50\0\0\0\x50\0\0\0\x50\0\0".to_vec();
+        let profile = rebuild_profile(&base, &[(*b"lumi", Some(lumi))]);
+
+        assert!(matches!(
+            match_enum_encoding(&profile, IccMatchTolerance::EXACT),
+            Err(Error::UnsupportedIccProfile)
+        ));
+        let matched = match_enum_encoding(&profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        assert_eq!(matched.encoding.tf, TransferFunction::Srgb);
+    }
+
+    #[test]
+    fn unknown_para_function_type() {
+        let base =
+            icc::colour_encoding_to_icc(&EnumColourEncoding::srgb(RenderingIntent::Relative));
+        let profile = with_trc(&base, b"para\0\0\0\0\0\x05\0\0".to_vec());
+        let info = detect_profile_info(&profile, false).unwrap();
+        assert!(info.trc_color(0.0).is_none());
+
+        let profile = with_trc(&base, b"para\0\0\0\0\0\x03\0\0".to_vec());
+        assert!(detect_profile_info(&profile, false).is_err());
+    }
+
+    #[test]
+    fn hp_srgb() {
+        // sRGB profile by Hewlett-Packard, with sampled tone curves, no `chad` tag and
+        // informational tags such as `lumi`, `meas` and `view`.
+        let profile = include_bytes!("./test-profiles/hp-srgb-iec61966-2.1.icc");
+        assert!(matches!(
+            match_enum_encoding(profile, IccMatchTolerance::EXACT),
+            Err(Error::UnsupportedIccProfile)
+        ));
+
+        let strict_tags = IccMatchTolerance {
+            ignore_informational_tags: false,
+            ..IccMatchTolerance::APPROXIMATE
+        };
+        assert!(matches!(
+            match_enum_encoding(profile, strict_tags),
+            Err(Error::UnsupportedIccProfile)
+        ));
+
+        let matched = match_enum_encoding(profile, IccMatchTolerance::APPROXIMATE).unwrap();
+        dbg!(&matched);
+        assert!(matches!(
+            matched.encoding,
+            EnumColourEncoding {
+                colour_space: ColourSpace::Rgb,
+                white_point: WhitePoint::D65,
+                primaries: Primaries::Srgb,
+                tf: TransferFunction::Srgb,
+                rendering_intent: RenderingIntent::Perceptual,
+            }
+        ));
+    }
 }
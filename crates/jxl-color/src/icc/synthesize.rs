@@ -1,10 +1,47 @@
+use jxl_bitstream::BundleDefault;
+
 use crate::{
-    ciexyz::*, consts::*, tf, ColourSpace, EnumColourEncoding, Primaries, RenderingIntent,
+    ciexyz::*, consts::*, tf, ColorEncodingWithProfile, ColorTransform, ColourSpace,
+    EnumColourEncoding, OpsinInverseMatrix, Primaries, RenderingIntent, ToneMapping,
     TransferFunction, WhitePoint,
 };
 
 use super::IccTag;
 
+/// Version of synthesized ICC profiles.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum IccVersion {
+    /// ICCv2 (version 2.4), for legacy software which rejects ICCv4 profiles.
+    ///
+    /// Tone curves are written as `curveType`, and `wtpt` is the actual white point of the
+    /// encoding without `chad` tag. `cicp` tag and HDR LUTs are not written.
+    V2,
+    /// ICCv4 (version 4.4).
+    #[default]
+    V4,
+}
+
+/// Options for [`colour_encoding_to_icc_with_options`].
+///
+/// Start from [`IccSynthesisOptions::default()`] and set the fields needed; more options may be
+/// added in the future.
+#[derive(Debug, Copy, Clone, Default)]
+#[non_exhaustive]
+pub struct IccSynthesisOptions {
+    /// Version of the profile.
+    pub version: IccVersion,
+    /// Whether to write `A2B0` tag with tone mapped LUT for PQ and HLG encodings.
+    ///
+    /// Color management systems without `cicp` tag support will use the LUT, which maps HDR
+    /// signals into SDR range, instead of the tone curves clipping at SDR white. Ignored for
+    /// ICCv2 and grayscale profiles.
+    pub hdr_lut: bool,
+    /// Peak luminance of HDR signals in nits, used to tone map the LUT.
+    ///
+    /// Defaults to 10000 nits for PQ and 1000 nits for HLG.
+    pub hdr_peak_luminance: Option<f32>,
+}
+
 fn append_tag_with_data(
     tags_out: &mut Vec<IccTag>,
     data_out: &mut Vec<u8>,
@@ -44,7 +81,7 @@ fn create_mluc(locale: [u8; 4], strings: &[&str]) -> Vec<u8> {
         data.extend(s.encode_utf16());
         out.extend_from_slice(&locale);
         out.extend_from_slice(&((data.len() as u32 - offset) * 2).to_be_bytes());
-        out.extend_from_slice(&(0x14 + strings.len() as u32 * 12 + offset * 2).to_be_bytes());
+        out.extend_from_slice(&(0x10 + strings.len() as u32 * 12 + offset * 2).to_be_bytes());
     }
     for c in data {
         let b = c.to_be_bytes();
@@ -54,6 +91,23 @@ fn create_mluc(locale: [u8; 4], strings: &[&str]) -> Vec<u8> {
     out
 }
 
+fn create_desc(text: &str) -> Vec<u8> {
+    let mut out = vec![b'd', b'e', b's', b'c', 0, 0, 0, 0];
+    out.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+    // Empty Unicode and ScriptCode descriptions
+    out.resize(out.len() + 4 + 4 + 2 + 1 + 67, 0);
+    out
+}
+
+fn create_text(text: &str) -> Vec<u8> {
+    let mut out = vec![b't', b'e', b'x', b't', 0, 0, 0, 0];
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+    out
+}
+
 fn create_xyz([x, y, z]: [i32; 3]) -> [u8; 20] {
     let mut out = [0u8; 20];
     out[..4].copy_from_slice(b"XYZ ");
@@ -75,6 +129,26 @@ fn create_curv_lut(lut: &[u16]) -> Vec<u8> {
     trc
 }
 
+fn create_curv_gamma(gamma: f64) -> Vec<u8> {
+    let gamma_q = (gamma * 256.0).round();
+    if (gamma_q / 256.0 - gamma).abs() < 1e-6 && gamma_q < 65536.0 {
+        let mut trc = vec![b'c', b'u', b'r', b'v', 0, 0, 0, 0, 0, 0, 0, 1];
+        trc.extend_from_slice(&(gamma_q as u16).to_be_bytes());
+        trc
+    } else {
+        create_curv_lut(&sample_table(4096, |x| x.powf(gamma)))
+    }
+}
+
+fn sample_table(n: usize, f: impl Fn(f64) -> f64) -> Vec<u16> {
+    (0..n)
+        .map(|idx| {
+            let x = idx as f64 / (n - 1) as f64;
+            (f(x).clamp(0.0, 1.0) * 65535.0 + 0.5) as u16
+        })
+        .collect()
+}
+
 fn create_para(ty: u16, params: &[u32]) -> Vec<u8> {
     let mut out = vec![b'p', b'a', b'r', b'a', 0, 0, 0, 0, 0, 0, 0, 0];
     out[8..10].copy_from_slice(&ty.to_be_bytes());
@@ -84,8 +158,119 @@ fn create_para(ty: u16, params: &[u32]) -> Vec<u8> {
     out
 }
 
+/// Number of grid points in each dimension of HDR LUTs.
+const HDR_LUT_GRID_POINTS: usize = 17;
+/// Gamma of M curves of HDR LUTs, in s15Fixed16.
+const HDR_LUT_GAMMA: u32 = (65536 * 22 + 5) / 10;
+
+/// Creates `lutAToBType` data which tone maps PQ or HLG signals into SDR range.
+///
+/// The LUT consists of identity A curves, CLUT which tone maps and gamma-encodes samples,
+/// M curves which decode the gamma, and a matrix which converts samples to PCS XYZ.
+fn create_hdr_lut(
+    colour_encoding: &EnumColourEncoding,
+    peak_luminance: f32,
+    p_pcs: &[f32; 9],
+) -> Option<Vec<u8>> {
+    let from = ColorEncodingWithProfile::new(colour_encoding.clone());
+    let mut linear = colour_encoding.clone();
+    linear.tf = TransferFunction::Linear;
+    let to = ColorEncodingWithProfile::new(linear);
+    let oim = OpsinInverseMatrix::default_with_context(());
+    let mut tone_mapping = ToneMapping::default_with_context(());
+    tone_mapping.intensity_target = peak_luminance;
+    let transform = ColorTransform::new(&from, &to, &oim, &tone_mapping).ok()?;
+
+    const N: usize = HDR_LUT_GRID_POINTS;
+    let grid = |idx: usize| idx as f32 / (N - 1) as f32;
+    let mut r = Vec::with_capacity(N * N * N);
+    let mut g = Vec::with_capacity(N * N * N);
+    let mut b = Vec::with_capacity(N * N * N);
+    // First input channel varies least rapidly.
+    for ri in 0..N {
+        for gi in 0..N {
+            for bi in 0..N {
+                r.push(grid(ri));
+                g.push(grid(gi));
+                b.push(grid(bi));
+            }
+        }
+    }
+    transform
+        .run(&mut [&mut r, &mut g, &mut b], &crate::NullCms)
+        .ok()?;
+
+    let exp = 65536.0 / HDR_LUT_GAMMA as f32;
+    let mut clut = Vec::with_capacity(N * N * N * 3);
+    for ((r, g), b) in r.into_iter().zip(g).zip(b) {
+        for v in [r, g, b] {
+            let v = if v.is_finite() {
+                v.clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            clut.push((v.powf(exp) * 65535.0 + 0.5) as u16);
+        }
+    }
+
+    // PCS XYZ values are encoded so that 1.0 represents 1 + 32767/32768.
+    let scale = 32768.0 / 65535.0;
+    let mut matrix = [0f32; 12];
+    for (out, v) in matrix.iter_mut().zip(p_pcs) {
+        *out = v * scale;
+    }
+
+    let identity = create_para(0, &[0x10000]);
+    let m_curve = create_para(0, &[HDR_LUT_GAMMA]);
+    let mut out = vec![b'm', b'A', b'B', b' ', 0, 0, 0, 0, 3, 3, 0, 0];
+    out.resize(32, 0);
+
+    let b_offset = out.len() as u32;
+    for _ in 0..3 {
+        out.extend_from_slice(&identity);
+    }
+    let matrix_offset = out.len() as u32;
+    for v in matrix {
+        out.extend_from_slice(&((v * 65536.0).round() as i32).to_be_bytes());
+    }
+    let m_offset = out.len() as u32;
+    for _ in 0..3 {
+        out.extend_from_slice(&m_curve);
+    }
+    let clut_offset = out.len() as u32;
+    let mut grid_points = [0u8; 16];
+    grid_points[..3].fill(N as u8);
+    out.extend_from_slice(&grid_points);
+    // 16-bit precision, followed by padding
+    out.extend_from_slice(&[2, 0, 0, 0]);
+    for v in clut {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+    out.resize((out.len() + 3) & (!3), 0);
+    let a_offset = out.len() as u32;
+    for _ in 0..3 {
+        out.extend_from_slice(&identity);
+    }
+
+    for (idx, offset) in [b_offset, matrix_offset, m_offset, clut_offset, a_offset]
+        .into_iter()
+        .enumerate()
+    {
+        out[12 + idx * 4..][..4].copy_from_slice(&offset.to_be_bytes());
+    }
+    Some(out)
+}
+
 /// Creates an ICCv4 profile from the given [`EnumColourEncoding`].
 pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
+    colour_encoding_to_icc_with_options(colour_encoding, IccSynthesisOptions::default())
+}
+
+/// Creates an ICC profile from the given [`EnumColourEncoding`], with options.
+pub fn colour_encoding_to_icc_with_options(
+    colour_encoding: &EnumColourEncoding,
+    options: IccSynthesisOptions,
+) -> Vec<u8> {
     let &EnumColourEncoding {
         colour_space,
         white_point,
@@ -124,6 +309,11 @@ pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
     ];
     header.resize(128, 0);
 
+    let is_v2 = options.version == IccVersion::V2;
+    if is_v2 {
+        header[8..10].copy_from_slice(&[2, 0x40]);
+    }
+
     header[16..20].copy_from_slice(match colour_space {
         ColourSpace::Rgb => b"RGB ",
         ColourSpace::Grey => b"GRAY",
@@ -143,18 +333,24 @@ pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
         "{:?}_{:?}_{:?}_{:?}_{:?}",
         colour_space, rendering_intent, white_point, primaries, tf,
     );
-    append_tag_with_data(
-        &mut tags,
-        &mut data,
-        *b"desc",
-        &create_mluc(*b"enUS", &[&desc]),
-    );
-    append_tag_with_data(
-        &mut tags,
-        &mut data,
-        *b"cprt",
-        &create_mluc(*b"enUS", &["CC0, generated by jxl-oxide"]),
-    );
+    let cprt = "CC0, generated by jxl-oxide";
+    if is_v2 {
+        append_tag_with_data(&mut tags, &mut data, *b"desc", &create_desc(&desc));
+        append_tag_with_data(&mut tags, &mut data, *b"cprt", &create_text(cprt));
+    } else {
+        append_tag_with_data(
+            &mut tags,
+            &mut data,
+            *b"desc",
+            &create_mluc(*b"enUS", &[&desc]),
+        );
+        append_tag_with_data(
+            &mut tags,
+            &mut data,
+            *b"cprt",
+            &create_mluc(*b"enUS", &[cprt]),
+        );
+    }
 
     let mut chad = [1f32, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
     let from_illuminant = match white_point {
@@ -164,7 +360,17 @@ pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
         WhitePoint::Dci => ILLUMINANT_DCI,
     };
 
-    if colour_space == ColourSpace::Rgb {
+    if colour_space == ColourSpace::Rgb && is_v2 {
+        // ICCv2 profiles store the actual white point; colorants are still adapted to D50.
+        let xyz = illuminant_to_xyz(from_illuminant);
+        append_tag_with_data(
+            &mut tags,
+            &mut data,
+            *b"wtpt",
+            &create_xyz(xyz.map(|v| (v * 65536.0 + 0.5) as i32)),
+        );
+        chad = adapt_mat(from_illuminant, ILLUMINANT_D50);
+    } else if colour_space == ColourSpace::Rgb {
         append_tag_with_data(
             &mut tags,
             &mut data,
@@ -188,43 +394,70 @@ pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
         );
     }
 
-    let trc = match tf {
-        TransferFunction::Gamma { g, inverted: false } => {
-            let g = g as u64;
-            let gamma = ((g * 65536 + 5000000) / 10000000) as u32;
-            create_para(0, &[gamma])
+    let trc = if is_v2 {
+        // `parametricCurveType` is not available in ICCv2.
+        match tf {
+            TransferFunction::Gamma { g, inverted: false } => create_curv_gamma(g as f64 / 1e7),
+            TransferFunction::Gamma { g, inverted: true } => create_curv_gamma(1e7 / g as f64),
+            TransferFunction::Bt709 => create_curv_lut(&sample_table(4096, |x| {
+                if x < 0.081 {
+                    x / 4.5
+                } else {
+                    ((x + 0.099) / 1.099).powf(1.0 / 0.45)
+                }
+            })),
+            TransferFunction::Unknown => panic!(),
+            TransferFunction::Linear => vec![b'c', b'u', b'r', b'v', 0, 0, 0, 0, 0, 0, 0, 0],
+            TransferFunction::Srgb => create_curv_lut(&sample_table(4096, |x| {
+                if x <= 0.04045 {
+                    x / 12.92
+                } else {
+                    ((x + 0.055) / 1.055).powf(2.4)
+                }
+            })),
+            TransferFunction::Pq => create_curv_lut(&tf::pq_table(4096)),
+            TransferFunction::Dci => create_curv_gamma(2.6),
+            TransferFunction::Hlg => create_curv_lut(&tf::hlg_table(4096)),
         }
-        TransferFunction::Gamma { g, inverted: true } => {
-            let g = g as u64;
-            let adj = g / 2;
-            let gamma = ((65536u64 * 10000000u64 + adj) / g) as u32;
-            create_para(0, &[gamma])
+    } else {
+        match tf {
+            TransferFunction::Gamma { g, inverted: false } => {
+                let g = g as u64;
+                let gamma = ((g * 65536 + 5000000) / 10000000) as u32;
+                create_para(0, &[gamma])
+            }
+            TransferFunction::Gamma { g, inverted: true } => {
+                let g = g as u64;
+                let adj = g / 2;
+                let gamma = ((65536u64 * 10000000u64 + adj) / g) as u32;
+                create_para(0, &[gamma])
+            }
+            TransferFunction::Bt709 => create_para(
+                3,
+                &[
+                    (65536 * 20 + 4) / 9,
+                    (65536 * 1000 + 549) / 1099,
+                    (65536 * 99 + 549) / 1099,
+                    (65536 * 10 + 22) / 45,
+                    (65536 * 81 + 500) / 1000,
+                ],
+            ),
+            TransferFunction::Unknown => panic!(),
+            TransferFunction::Linear => vec![b'c', b'u', b'r', b'v', 0, 0, 0, 0, 0, 0, 0, 0],
+            TransferFunction::Srgb => create_para(
+                3,
+                &[
+                    (65536 * 24 + 5) / 10,
+                    (65536 * 1000 + 527) / 1055,
+                    (65536 * 55 + 527) / 1055,
+                    (65536 * 100 + 646) / 1292,
+                    (65536 * 4045 + 50000) / 100000,
+                ],
+            ),
+            TransferFunction::Pq => create_curv_lut(&tf::pq_table(4096)),
+            TransferFunction::Dci => create_para(0, &[(65536 * 26 + 5) / 10]),
+            TransferFunction::Hlg => create_curv_lut(&tf::hlg_table(4096)),
         }
-        TransferFunction::Bt709 => create_para(
-            3,
-            &[
-                (65536 * 20 + 4) / 9,
-                (65536 * 1000 + 549) / 1099,
-                (65536 * 99 + 549) / 1099,
-                (65536 * 10 + 22) / 45,
-                (65536 * 81 + 500) / 1000,
-            ],
-        ),
-        TransferFunction::Unknown => panic!(),
-        TransferFunction::Linear => vec![b'c', b'u', b'r', b'v', 0, 0, 0, 0, 0, 0, 0, 0],
-        TransferFunction::Srgb => create_para(
-            3,
-            &[
-                (65536 * 24 + 5) / 10,
-                (65536 * 1000 + 527) / 1055,
-                (65536 * 55 + 527) / 1055,
-                (65536 * 100 + 646) / 1292,
-                (65536 * 4045 + 50000) / 100000,
-            ],
-        ),
-        TransferFunction::Pq => create_curv_lut(&tf::pq_table(4096)),
-        TransferFunction::Dci => create_para(0, &[(65536 * 26 + 5) / 10]),
-        TransferFunction::Hlg => create_curv_lut(&tf::hlg_table(4096)),
     };
 
     let primaries = match primaries {
@@ -238,9 +471,12 @@ pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
         Primaries::P3 => PRIMARIES_P3,
     };
 
-    if matches!(tf, TransferFunction::Pq | TransferFunction::Hlg) {
+    let is_hdr = matches!(tf, TransferFunction::Pq | TransferFunction::Hlg);
+    if is_hdr && !is_v2 {
         if let Some(cicp) = colour_encoding.cicp() {
-            append_tag_with_data(&mut tags, &mut data, *b"cicp", &cicp);
+            let mut cicp_data = vec![b'c', b'i', b'c', b'p', 0, 0, 0, 0];
+            cicp_data.extend_from_slice(&cicp);
+            append_tag_with_data(&mut tags, &mut data, *b"cicp", &cicp_data);
         }
     }
 
@@ -263,6 +499,21 @@ pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
             append_tag_with_data(&mut tags, &mut data, *b"rXYZ", &create_xyz(p_data[0]));
             append_tag_with_data(&mut tags, &mut data, *b"gXYZ", &create_xyz(p_data[1]));
             append_tag_with_data(&mut tags, &mut data, *b"bXYZ", &create_xyz(p_data[2]));
+
+            if is_hdr && !is_v2 && options.hdr_lut {
+                let default_peak = if tf == TransferFunction::Pq {
+                    10000.0
+                } else {
+                    1000.0
+                };
+                let peak_luminance = options
+                    .hdr_peak_luminance
+                    .filter(|v| v.is_finite() && *v > 0.0)
+                    .unwrap_or(default_peak);
+                if let Some(lut) = create_hdr_lut(colour_encoding, peak_luminance, &p_pcs) {
+                    append_tag_with_data(&mut tags, &mut data, *b"A2B0", &lut);
+                }
+            }
         }
         ColourSpace::Grey => {
             append_tag_with_data(&mut tags, &mut data, *b"kTRC", &trc);
@@ -287,3 +538,53 @@ pub fn colour_encoding_to_icc(colour_encoding: &EnumColourEncoding) -> Vec<u8> {
     out[..4].copy_from_slice(&total_len.to_be_bytes());
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_tag<'a>(profile: &'a [u8], sig: &[u8; 4]) -> Option<&'a [u8]> {
+        let tag_count = u32::from_be_bytes(profile[128..132].try_into().unwrap()) as usize;
+        profile[132..][..tag_count * 12]
+            .chunks_exact(12)
+            .find(|entry| &entry[..4] == sig)
+            .map(|entry| {
+                let offset = u32::from_be_bytes(entry[4..8].try_into().unwrap()) as usize;
+                let len = u32::from_be_bytes(entry[8..12].try_into().unwrap()) as usize;
+                &profile[offset..][..len]
+            })
+    }
+
+    #[test]
+    fn mluc_record_offsets() {
+        let strings = ["RGB_D65_SRG_Rel_SRG", "CC0"];
+        let mluc = create_mluc(*b"enUS", &strings);
+        assert_eq!(&mluc[..4], b"mluc");
+        assert_eq!(u32::from_be_bytes(mluc[8..12].try_into().unwrap()), 2);
+
+        for (record, expected) in mluc[16..].chunks_exact(12).zip(strings) {
+            let len = u32::from_be_bytes(record[4..8].try_into().unwrap()) as usize;
+            let offset = u32::from_be_bytes(record[8..12].try_into().unwrap()) as usize;
+            let units = mluc[offset..][..len]
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect::<Vec<_>>();
+            assert_eq!(String::from_utf16(&units).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn cicp_tag_type() {
+        let profile =
+            colour_encoding_to_icc(&EnumColourEncoding::bt2100_pq(RenderingIntent::Relative));
+        let cicp = find_tag(&profile, b"cicp").unwrap();
+        assert_eq!(cicp.len(), 12);
+        assert_eq!(&cicp[..8], b"cicp\0\0\0\0");
+        assert_eq!(cicp[8..], [9, 16, 0, 1]);
+
+        let desc = find_tag(&profile, b"desc").unwrap();
+        assert_eq!(&desc[..4], b"mluc");
+        let offset = u32::from_be_bytes(desc[24..28].try_into().unwrap()) as usize;
+        assert_eq!(offset, 28);
+    }
+}
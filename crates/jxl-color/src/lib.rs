@@ -27,4 +27,5 @@ pub use cms::*;
 pub use convert::*;
 pub use error::*;
 pub use header::*;
+pub use xyb::{convert_to_xyb, linear_srgb_to_xyb};
 pub use ycbcr::ycbcr_to_rgb;
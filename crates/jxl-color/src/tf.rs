@@ -98,12 +98,15 @@ unsafe fn linear_to_gamma_x86_64_avx2(samples: &mut [f32], gamma: f32) -> &mut [
 
 /// Converts scene luminance values to display luminance values using the hybrid log-gamma
 /// transfer function (HLG OOTF).
+///
+/// System gamma is derived from `display_luminance`, nominal peak luminance of the display in
+/// nits, as described in Rec. ITU-R BT.2100.
 pub fn hlg_oo(
     [samples_r, samples_g, samples_b]: [&mut [f32]; 3],
     [lr, lg, lb]: [f32; 3],
-    intensity_target: f32,
+    display_luminance: f32,
 ) {
-    let gamma = 1.2f32 * 1.111f32.powf((intensity_target / 1e3).log2());
+    let gamma = 1.2f32 * 1.111f32.powf((display_luminance / 1e3).log2());
     // 1/g - 1
     let exp = gamma - 1.0;
 
@@ -118,12 +121,15 @@ pub fn hlg_oo(
 
 /// Converts the display-referred samples to scene-referred signals using the hybrid log-gamma
 /// transfer function (HLG inverse OOTF).
+///
+/// System gamma is derived from `display_luminance`, nominal peak luminance of the display in
+/// nits, as described in Rec. ITU-R BT.2100.
 pub fn hlg_inverse_oo(
     [samples_r, samples_g, samples_b]: [&mut [f32]; 3],
     [lr, lg, lb]: [f32; 3],
-    intensity_target: f32,
+    display_luminance: f32,
 ) {
-    let gamma = 1.2f32 * 1.111f32.powf((intensity_target / 1e3).log2());
+    let gamma = 1.2f32 * 1.111f32.powf((display_luminance / 1e3).log2());
     // 1/g - 1
     let exp = (1.0 - gamma) / gamma;
 
@@ -166,10 +172,23 @@ pub fn hlg_to_linear(samples: &mut [f32]) {
     }
 }
 
+const HLG_A_F64: f64 = 0.17883277;
+const HLG_B_F64: f64 = 0.28466892;
+const HLG_C_F64: f64 = 0.5599107;
+
+/// HLG inverse OETF in `f64`.
+pub(crate) fn hlg_to_linear_f64(e: f64) -> f64 {
+    if e <= 0.5 {
+        e * e / 3.0
+    } else {
+        (((e - HLG_C_F64) / HLG_A_F64).exp() + HLG_B_F64) / 12.0
+    }
+}
+
 pub(crate) fn hlg_table(n: usize) -> Vec<u16> {
-    const A: f64 = 0.17883277;
-    const B: f64 = 0.28466892;
-    const C: f64 = 0.5599107;
+    const A: f64 = HLG_A_F64;
+    const B: f64 = HLG_B_F64;
+    const C: f64 = HLG_C_F64;
 
     let mut out = vec![0u16; n];
     for (idx, out) in out[..=(n - 1) / 2].iter_mut().enumerate() {
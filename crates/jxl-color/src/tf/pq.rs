@@ -429,21 +429,25 @@ pub(crate) unsafe fn pq_to_linear_aarch64_neon(
     vreinterpretq_f32_u32(vorrq_u32(vreinterpretq_u32_f32(v), sign))
 }
 
-pub(crate) fn pq_table(n: usize) -> Vec<u16> {
+/// PQ EOTF in `f64`, where output of 1.0 represents 10000 nits.
+pub(crate) fn pq_to_linear_f64(e: f64) -> f64 {
     const M1_RECIP_F64: f64 = 8192.0 / 1305.0;
     const M2_RECIP_F64: f64 = 32.0 / 2523.0;
     const C1_F64: f64 = 107.0 / 128.0;
     const C2_F64: f64 = 2413.0 / 128.0;
     const C3_F64: f64 = 2392.0 / 128.0;
 
+    let e_pow = e.powf(M2_RECIP_F64);
+    let numerator = (e_pow - C1_F64).max(0.0);
+    let denominator = e_pow.mul_add(-C3_F64, C2_F64);
+    (numerator / denominator).powf(M1_RECIP_F64)
+}
+
+pub(crate) fn pq_table(n: usize) -> Vec<u16> {
     let mut out = vec![0u16; n];
     for (idx, out) in out.iter_mut().enumerate() {
         let e = idx as f64 / (n - 1) as f64;
-
-        let e_pow = e.powf(M2_RECIP_F64);
-        let numerator = (e_pow - C1_F64).max(0.0);
-        let denominator = e_pow.mul_add(-C3_F64, C2_F64);
-        let d = (numerator / denominator).powf(M1_RECIP_F64);
+        let d = pq_to_linear_f64(e);
         *out = (d * 65535.0) as u16; // clamped
     }
     out
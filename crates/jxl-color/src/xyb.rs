@@ -1,3 +1,24 @@
+use crate::{
+    convert_color, ColorEncodingWithProfile, ColourSpace, EnumColourEncoding, NullCms,
+    RenderingIntent, Result,
+};
+
+/// Opsin absorbance matrix, the inverse of the default opsin inverse matrix.
+#[allow(clippy::excessive_precision)]
+const OPSIN_ABSORBANCE_MATRIX: [[f32; 3]; 3] = [
+    [0.30, 0.622, 0.078],
+    [0.23, 0.692, 0.078],
+    [
+        0.24342268924547819,
+        0.20476744424496821,
+        0.55180986650955360,
+    ],
+];
+
+/// Default opsin bias, as in the default opsin inverse matrix.
+#[allow(clippy::excessive_precision)]
+const OPSIN_BIAS: f32 = -0.0037930732552754493;
+
 pub(crate) fn run(xyb: [&mut [f32]; 3], ob: [f32; 3], intensity_target: f32) {
     #[cfg(target_arch = "x86_64")]
     {
@@ -58,3 +79,94 @@ fn run_generic(xyb: [&mut [f32]; 3], ob: [f32; 3], intensity_target: f32) {
         *b = (g_s * g_s).mul_add(g_s, ob[2]) * itscale;
     }
 }
+
+/// Converts linear sRGB samples to XYB in place, using the default opsin absorbance matrix and
+/// bias.
+///
+/// Sample value of 1.0 represents `intensity_target` nits. This is the inverse of the XYB
+/// conversion done while rendering images with default opsin inverse matrix.
+///
+/// # Panics
+/// Panics if the channels have different lengths.
+pub fn linear_srgb_to_xyb(rgb: [&mut [f32]; 3], intensity_target: f32) {
+    let scale = intensity_target / 255.0;
+
+    let [r, g, b] = rgb;
+    if r.len() != g.len() || g.len() != b.len() {
+        panic!("Grid size mismatch");
+    }
+    let cbrt_bias = OPSIN_BIAS.cbrt();
+
+    for ((r, g), b) in r.iter_mut().zip(&mut *g).zip(&mut *b) {
+        let rgb = [*r * scale, *g * scale, *b * scale];
+        let [l, m, s] = OPSIN_ABSORBANCE_MATRIX.map(|row| {
+            let v = row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2];
+            // Clamp negative values, which can't be represented after cube root.
+            (v - OPSIN_BIAS).max(0.0).cbrt() + cbrt_bias
+        });
+
+        *r = (l - m) * 0.5;
+        *g = (l + m) * 0.5;
+        *b = s;
+    }
+}
+
+/// Converts samples in the given color encoding to XYB in place.
+///
+/// Samples are converted to linear sRGB first, with tone mapping and gamut mapping as done while
+/// rendering, and then to XYB using [`linear_srgb_to_xyb`]. If the color encoding is grayscale,
+/// only the first channel is read as input.
+///
+/// # Errors
+/// Returns an error if the color encoding is not supported.
+pub fn convert_to_xyb(
+    encoding: &EnumColourEncoding,
+    channels: [&mut [f32]; 3],
+    intensity_target: f32,
+) -> Result<()> {
+    if encoding.colour_space == ColourSpace::Xyb {
+        return Ok(());
+    }
+
+    let [r, g, b] = channels;
+    convert_color(
+        &ColorEncodingWithProfile::new(encoding.clone()),
+        &ColorEncodingWithProfile::new(EnumColourEncoding::srgb_linear(RenderingIntent::Relative)),
+        [&mut *r, &mut *g, &mut *b],
+        intensity_target,
+        &NullCms,
+    )?;
+    linear_srgb_to_xyb([r, g, b], intensity_target);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jxl_bitstream::BundleDefault;
+
+    use super::*;
+    use crate::OpsinInverseMatrix;
+
+    #[test]
+    fn xyb_roundtrip() {
+        let oim = OpsinInverseMatrix::default_with_context(());
+        let mut r = [0.0f32, 1.0, 0.25, 0.8];
+        let mut g = [0.0f32, 1.0, 0.5, 0.1];
+        let mut b = [0.0f32, 1.0, 0.75, 0.3];
+        let expected = [r, g, b];
+
+        linear_srgb_to_xyb([&mut r, &mut g, &mut b], 255.0);
+        // Gray has zero X.
+        assert!(r[0].abs() < 1e-6);
+        assert!(r[1].abs() < 1e-6);
+
+        run([&mut r, &mut g, &mut b], oim.opsin_bias, 255.0);
+        for idx in 0..r.len() {
+            let lms = [r[idx], g[idx], b[idx]];
+            for (row, expected) in oim.inv_mat.iter().zip(expected) {
+                let v = row[0] * lms[0] + row[1] * lms[1] + row[2] * lms[2];
+                assert!((v - expected[idx]).abs() < 1e-3);
+            }
+        }
+    }
+}
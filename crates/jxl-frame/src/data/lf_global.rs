@@ -51,6 +51,7 @@ impl<S: Sample> Bundle<LfGlobalParams<'_, '_>> for LfGlobal<S> {
         let LfGlobalParams {
             image_header,
             frame_header: header,
+            tracker,
             ..
         } = params;
         let image_size = (header.width * header.height) as u64;
@@ -62,7 +63,7 @@ impl<S: Sample> Bundle<LfGlobalParams<'_, '_>> for LfGlobal<S> {
                 let span = tracing::span!(tracing::Level::TRACE, "Decode Patches");
                 let _guard = span.enter();
 
-                let patches = Patches::parse(bitstream, (image_header, header))?;
+                let patches = Patches::parse(bitstream, (image_header, header, tracker))?;
                 let it = patches
                     .patches
                     .iter()
@@ -89,7 +90,7 @@ impl<S: Sample> Bundle<LfGlobalParams<'_, '_>> for LfGlobal<S> {
                 let span = tracing::span!(tracing::Level::TRACE, "Decode Splines");
                 let _guard = span.enter();
 
-                Splines::parse(bitstream, header)
+                Splines::parse(bitstream, (header, tracker))
             })
             .transpose()?;
         let noise = header
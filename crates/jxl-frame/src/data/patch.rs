@@ -1,4 +1,5 @@
 use jxl_bitstream::{unpack_signed, Bitstream, Bundle};
+use jxl_grid::{AllocHandle, AllocTracker};
 use jxl_image::ImageHeader;
 
 use crate::{FrameHeader, Result};
@@ -6,6 +7,7 @@ use crate::{FrameHeader, Result};
 #[derive(Debug)]
 pub struct Patches {
     pub patches: Vec<PatchRef>,
+    _alloc_handle: Option<AllocHandle>,
 }
 
 #[derive(Debug)]
@@ -80,12 +82,12 @@ impl PatchBlendMode {
     }
 }
 
-impl Bundle<(&ImageHeader, &FrameHeader)> for Patches {
+impl Bundle<(&ImageHeader, &FrameHeader, Option<&AllocTracker>)> for Patches {
     type Error = crate::Error;
 
     fn parse(
         bitstream: &mut Bitstream,
-        (image_header, frame_header): (&ImageHeader, &FrameHeader),
+        (image_header, frame_header, tracker): (&ImageHeader, &FrameHeader, Option<&AllocTracker>),
     ) -> Result<Self> {
         let num_extra = image_header.metadata.ec_info.len();
         let alpha_channel_indices = image_header
@@ -112,6 +114,9 @@ impl Bundle<(&ImageHeader, &FrameHeader)> for Patches {
             return Err(jxl_bitstream::Error::ProfileConformance("too many patches").into());
         }
 
+        let mut alloc_handle = tracker
+            .map(|tracker| tracker.alloc::<PatchRef>(num_patch_refs as usize))
+            .transpose()?;
         let mut total_patches = 0u32;
         let patches = std::iter::repeat_with(|| -> Result<_> {
             let ref_idx = decoder.read_varint(bitstream, 1)?;
@@ -133,6 +138,10 @@ impl Bundle<(&ImageHeader, &FrameHeader)> for Patches {
                 tracing::error!(total_patches, max_num_patches, "Too many patches");
                 return Err(jxl_bitstream::Error::ProfileConformance("too many patches").into());
             }
+            if let Some(handle) = &mut alloc_handle {
+                handle.grow::<PatchTarget>(count as usize)?;
+                handle.grow::<BlendingModeInformation>(count as usize * (num_extra + 1))?;
+            }
 
             let mut prev_xy = None;
             let patch_targets = std::iter::repeat_with(|| -> Result<_> {
@@ -199,6 +208,9 @@ impl Bundle<(&ImageHeader, &FrameHeader)> for Patches {
         .collect::<Result<Vec<_>>>()?;
 
         decoder.finalize()?;
-        Ok(Self { patches })
+        Ok(Self {
+            patches,
+            _alloc_handle: alloc_handle,
+        })
     }
 }
@@ -1,5 +1,6 @@
 use jxl_bitstream::{unpack_signed, Bitstream, Bundle};
 use jxl_coding::Decoder;
+use jxl_grid::{AllocHandle, AllocTracker};
 
 use crate::{FrameHeader, Result};
 
@@ -11,12 +12,16 @@ const MAX_NUM_CONTROL_POINTS: usize = 1 << 20;
 pub struct Splines {
     pub quant_splines: Vec<QuantSpline>,
     pub quant_adjust: i32,
+    _alloc_handle: Option<AllocHandle>,
 }
 
-impl Bundle<&FrameHeader> for Splines {
+impl Bundle<(&FrameHeader, Option<&AllocTracker>)> for Splines {
     type Error = crate::Error;
 
-    fn parse(bitstream: &mut Bitstream, header: &FrameHeader) -> Result<Self> {
+    fn parse(
+        bitstream: &mut Bitstream,
+        (header, tracker): (&FrameHeader, Option<&AllocTracker>),
+    ) -> Result<Self> {
         let mut decoder = jxl_coding::Decoder::parse(bitstream, 6)?;
         decoder.begin(bitstream)?;
 
@@ -29,6 +34,13 @@ impl Bundle<&FrameHeader> for Splines {
         }
         let num_splines = num_splines + 1;
 
+        let mut alloc_handle = tracker
+            .map(|tracker| tracker.alloc::<(i64, i64)>(num_splines))
+            .transpose()?;
+        if let Some(handle) = &mut alloc_handle {
+            handle.grow::<QuantSpline>(num_splines)?;
+        }
+
         let mut start_points = vec![(0i64, 0i64); num_splines];
         let mut prev_point = (
             decoder.read_varint(bitstream, 1)? as i64,
@@ -54,6 +66,9 @@ impl Bundle<&FrameHeader> for Splines {
             )?;
 
             acc_control_points += spline.quant_points.len();
+            if let Some(handle) = &mut alloc_handle {
+                handle.grow::<(i64, i64)>(spline.quant_points.len())?;
+            }
             splines.push(spline);
         }
 
@@ -62,6 +77,7 @@ impl Bundle<&FrameHeader> for Splines {
         Ok(Self {
             quant_adjust,
             quant_splines: splines,
+            _alloc_handle: alloc_handle,
         })
     }
 }
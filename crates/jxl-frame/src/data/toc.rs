@@ -5,6 +5,7 @@ use jxl_bitstream::{read_bits, Bitstream, Bundle};
 ///
 /// Frame data are organized in groups. TOC specified the size and order of each group, and it is
 /// decoded after the frame header.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Toc {
     num_lf_groups: usize,
     num_groups: usize,
@@ -49,6 +50,7 @@ impl std::fmt::Debug for Toc {
 
 /// Information about a group in TOC.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TocGroup {
     /// Kind of the group.
     pub kind: TocGroupKind,
@@ -59,6 +61,7 @@ pub struct TocGroup {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TocGroupKind {
     All,
     LfGlobal,
@@ -2,6 +2,7 @@ use crate::{header::Encoding, Result};
 use jxl_bitstream::{Bitstream, Bundle};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Gabor {
     Disabled,
     Enabled([[f32; 2]; 3]),
@@ -50,12 +51,14 @@ impl Gabor {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EdgePreservingFilter {
     Disabled,
     Enabled(EpfParams),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EpfParams {
     pub iters: u32,
     pub sharp_lut: [f32; 8],
@@ -164,6 +167,7 @@ impl Bundle<Encoding> for EdgePreservingFilter {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EpfSigma {
     pub quant_mul: f32,
     pub pass0_sigma_scale: f32,
@@ -5,6 +5,7 @@ use jxl_image::{BitDepth, Extensions, ImageHeader, SizeHeader};
 define_bundle! {
     /// Frame header.
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct FrameHeader ctx(headers: &ImageHeader) error(crate::Error) {
         all_default: ty(Bool) default(true),
         pub frame_type: ty(Bundle(FrameType)) cond(!all_default) default(FrameType::RegularFrame),
@@ -133,6 +134,7 @@ define_bundle! {
     }
 
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Passes error(crate::Error) {
         pub num_passes: ty(U32(1, 2, 3, 4 + u(3))) default(1),
         pub num_ds: ty(U32(0, 1, 2, 3 + u(1))) cond(num_passes != 1) default(0),
@@ -142,6 +144,7 @@ define_bundle! {
     }
 
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct BlendingInfo ctx(context: (bool, Option<BlendMode>, CanvasSizeParams<'_>)) error(crate::Error) {
         pub mode: ty(Bundle(BlendMode)),
         pub alpha_channel:
@@ -159,6 +162,7 @@ define_bundle! {
     }
 
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct RestorationFilter ctx(encoding: Encoding) error(crate::Error) {
         all_default: ty(Bool) default(true),
         pub gab: ty(Bundle(crate::filter::Gabor)) cond(!all_default),
@@ -376,6 +380,7 @@ impl FrameHeader {
 
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FrameType {
     #[default]
     RegularFrame = 0,
@@ -410,6 +415,7 @@ impl<Ctx> Bundle<Ctx> for FrameType {
 
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Encoding {
     #[default]
     VarDct = 0,
@@ -429,6 +435,7 @@ impl<Ctx> Bundle<Ctx> for Encoding {
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FrameFlags(u64);
 
 impl FrameFlags {
@@ -469,6 +476,7 @@ impl<Ctx> Bundle<Ctx> for FrameFlags {
 
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlendMode {
     #[default]
     Replace = 0,
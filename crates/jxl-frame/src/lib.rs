@@ -21,7 +21,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use jxl_bitstream::{read_bits, Bitstream, Bundle, Lz77Mode};
-use jxl_grid::AllocTracker;
+use jxl_grid::{AllocCategory, AllocHandle, AllocTracker};
 use jxl_image::ImageHeader;
 
 pub mod data;
@@ -66,18 +66,42 @@ struct AllGroupOffsets {
 struct GroupData {
     toc_group: TocGroup,
     bytes: Vec<u8>,
+    alloc_handle: Option<AllocHandle>,
 }
 
 impl From<TocGroup> for GroupData {
     fn from(value: TocGroup) -> Self {
-        let cap = value.size as usize;
+        // Sizes in the TOC are not trusted; the buffer grows as data arrives.
         Self {
             toc_group: value,
-            bytes: Vec::with_capacity(cap),
+            bytes: Vec::new(),
+            alloc_handle: None,
         }
     }
 }
 
+impl GroupData {
+    fn extend_from_slice(&mut self, buf: &[u8], tracker: Option<&AllocTracker>) -> Result<()> {
+        let len = self.bytes.len() + buf.len();
+        if len > self.bytes.capacity() {
+            // Grow geometrically, but not past the size of the group.
+            let new_cap = len
+                .max(self.bytes.capacity() * 2)
+                .min(self.toc_group.size as usize);
+            let additional = new_cap - self.bytes.capacity();
+            if let Some(tracker) = tracker {
+                match &mut self.alloc_handle {
+                    Some(handle) => handle.grow::<u8>(additional)?,
+                    None => self.alloc_handle = Some(tracker.alloc::<u8>(additional)?),
+                }
+            }
+            self.bytes.reserve_exact(new_cap - self.bytes.len());
+        }
+        self.bytes.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FrameContext<'a> {
     pub image_header: Arc<ImageHeader>,
@@ -255,25 +279,38 @@ impl Frame {
 }
 
 impl Frame {
-    pub fn feed_bytes<'buf>(&mut self, mut buf: &'buf [u8]) -> &'buf [u8] {
+    /// Feeds frame data, and returns the remaining bytes which don't belong to the frame.
+    ///
+    /// Returns an error if buffering the data exceeds the allocation limit.
+    pub fn feed_bytes<'buf>(&mut self, mut buf: &'buf [u8]) -> Result<&'buf [u8]> {
+        let tracker = self
+            .tracker
+            .as_ref()
+            .map(|tracker| tracker.with_category(AllocCategory::FrameData));
         while let Some(group_data) = self.data.get_mut(self.reading_data_index) {
             let bytes_left = group_data.toc_group.size as usize - group_data.bytes.len();
             if buf.len() < bytes_left {
-                group_data.bytes.extend_from_slice(buf);
-                return &[];
+                group_data.extend_from_slice(buf, tracker.as_ref())?;
+                return Ok(&[]);
             }
             let (l, r) = buf.split_at(bytes_left);
-            group_data.bytes.extend_from_slice(l);
+            group_data.extend_from_slice(l, tracker.as_ref())?;
             buf = r;
             self.reading_data_index += 1;
         }
-        buf
+        Ok(buf)
     }
 
     #[inline]
     pub fn is_loading_done(&self) -> bool {
         self.reading_data_index >= self.data.len()
     }
+
+    /// Returns the number of groups fully loaded, in bitstream order.
+    #[inline]
+    pub fn num_loaded_groups(&self) -> usize {
+        self.reading_data_index
+    }
 }
 
 impl Frame {
@@ -1,17 +1,181 @@
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
-    Arc,
+    Arc, OnceLock,
 };
 
+use crate::pool::{BufferPool, IdleBuffer};
+
 /// Allocation tracker with total memory limit.
+///
+/// Allocations are tagged with the [category][AllocCategory] of the tracker. Use
+/// [`with_category`][Self::with_category] to create a tracker sharing the same limit, but with a
+/// different category.
+///
+/// Grid buffers can be recycled by enabling the buffer pool with
+/// [`with_buffer_pool`][Self::with_buffer_pool].
 #[derive(Debug, Clone)]
 pub struct AllocTracker {
     inner: Arc<AllocTrackerInner>,
+    category: AllocCategory,
+}
+
+/// Category of tracked allocations.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AllocCategory {
+    /// Uncategorized allocations.
+    #[default]
+    Other,
+    /// Bitstream data of frames.
+    FrameData,
+    /// Modular channel buffers.
+    ModularChannel,
+    /// VarDCT coefficients and block metadata.
+    VarDctCoeff,
+    /// Scratch buffers used by restoration filters and image features.
+    FilterScratch,
+    /// Blended frames, which may be kept as reference frames.
+    ReferenceFrame,
+    /// Color channel buffers of decoded frames.
+    ColorBuffer,
+}
+
+impl AllocCategory {
+    const COUNT: usize = 7;
+
+    /// All categories.
+    pub const ALL: [AllocCategory; Self::COUNT] = [
+        Self::Other,
+        Self::FrameData,
+        Self::ModularChannel,
+        Self::VarDctCoeff,
+        Self::FilterScratch,
+        Self::ReferenceFrame,
+        Self::ColorBuffer,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    current_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    num_allocs: AtomicUsize,
+    live_allocs: AtomicUsize,
+}
+
+impl Counters {
+    fn record_alloc(&self, bytes: usize) {
+        let current = self.current_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak_bytes.fetch_max(current, Ordering::Relaxed);
+        self.num_allocs.fetch_add(1, Ordering::Relaxed);
+        self.live_allocs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_grow(&self, bytes: usize) {
+        let current = self.current_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak_bytes.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn record_free(&self, bytes: usize) {
+        self.current_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        self.live_allocs.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> AllocCategoryStats {
+        AllocCategoryStats {
+            current_bytes: self.current_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            num_allocs: self.num_allocs.load(Ordering::Relaxed),
+            live_allocs: self.live_allocs.load(Ordering::Relaxed),
+        }
+    }
 }
 
 #[derive(Debug)]
 struct AllocTrackerInner {
     bytes_left: AtomicUsize,
+    failed_allocs: AtomicUsize,
+    total: Counters,
+    categories: [Counters; AllocCategory::COUNT],
+    pool: OnceLock<BufferPool>,
+}
+
+impl AllocTrackerInner {
+    fn release(&self, bytes: usize, category: AllocCategory) {
+        self.total.record_free(bytes);
+        self.categories[category.index()].record_free(bytes);
+        let prev = self.bytes_left.fetch_add(bytes, Ordering::Relaxed);
+        tracing::trace!(bytes, left = prev + bytes, "Released allocation handle");
+    }
+
+    /// Releases idle buffers in the pool, and returns whether any memory is released.
+    fn evict_idle_buffers(&self) -> bool {
+        let Some(pool) = self.pool.get() else {
+            return false;
+        };
+        let evicted = pool.evict_all();
+        for idle in &evicted {
+            self.release(idle.bytes, idle.category);
+        }
+        if !evicted.is_empty() {
+            tracing::debug!(
+                num_buffers = evicted.len(),
+                "Evicted idle buffers from pool"
+            );
+        }
+        !evicted.is_empty()
+    }
+}
+
+/// Snapshot of allocation statistics, returned by [`AllocTracker::stats`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct AllocStats {
+    /// Number of bytes that can be allocated before hitting the limit.
+    pub bytes_left: usize,
+    /// Number of allocations rejected because of the limit.
+    pub failed_allocs: usize,
+    /// Statistics of all allocations.
+    pub total: AllocCategoryStats,
+    /// Number of bytes held by idle buffers in the buffer pool.
+    ///
+    /// Idle buffers are included in the statistics of allocations.
+    pub pooled_bytes: usize,
+    /// Number of idle buffers in the buffer pool.
+    pub pooled_buffers: usize,
+    categories: [AllocCategoryStats; AllocCategory::COUNT],
+}
+
+impl AllocStats {
+    /// Returns the statistics of allocations of the given category.
+    pub fn category(&self, category: AllocCategory) -> AllocCategoryStats {
+        self.categories[category.index()]
+    }
+
+    /// Returns an iterator over statistics of each category.
+    pub fn categories(&self) -> impl Iterator<Item = (AllocCategory, AllocCategoryStats)> + '_ {
+        AllocCategory::ALL
+            .into_iter()
+            .zip(self.categories.iter().copied())
+    }
+}
+
+/// Allocation statistics of a category.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AllocCategoryStats {
+    /// Number of bytes currently allocated.
+    pub current_bytes: usize,
+    /// Maximum number of bytes allocated at the same time.
+    pub peak_bytes: usize,
+    /// Total number of allocations made.
+    pub num_allocs: usize,
+    /// Number of allocations not released yet.
+    pub live_allocs: usize,
 }
 
 impl AllocTracker {
@@ -20,10 +184,50 @@ impl AllocTracker {
         Self {
             inner: Arc::new(AllocTrackerInner {
                 bytes_left: AtomicUsize::new(bytes_left),
+                failed_allocs: AtomicUsize::new(0),
+                total: Counters::default(),
+                categories: Default::default(),
+                pool: OnceLock::new(),
             }),
+            category: AllocCategory::Other,
         }
     }
 
+    /// Enables the buffer pool, which keeps up to `max_idle_bytes` bytes of released grid buffers
+    /// for reuse.
+    ///
+    /// Idle buffers in the pool are still recorded in the tracker, and they're released when an
+    /// allocation would exceed the limit. The pool is shared by all trackers created from the same
+    /// [`with_limit`][Self::with_limit] call. This method has no effect if the pool is already
+    /// enabled.
+    pub fn with_buffer_pool(self, max_idle_bytes: usize) -> Self {
+        self.inner
+            .pool
+            .get_or_init(|| BufferPool::new(max_idle_bytes));
+        self
+    }
+
+    /// Returns whether the buffer pool is enabled.
+    #[inline]
+    pub fn has_buffer_pool(&self) -> bool {
+        self.inner.pool.get().is_some()
+    }
+
+    /// Returns a tracker sharing the limit and statistics with `self`, which tags allocations with
+    /// the given category.
+    pub fn with_category(&self, category: AllocCategory) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            category,
+        }
+    }
+
+    /// Returns the category of allocations recorded by this tracker.
+    #[inline]
+    pub fn category(&self) -> AllocCategory {
+        self.category
+    }
+
     /// Records an allocation of `count` number of `T`, and returns handle of the record.
     ///
     /// Returns an error if the allocation exceeds the current limit.
@@ -37,19 +241,62 @@ impl AllocTracker {
 
         match result {
             Ok(prev) => {
-                tracing::trace!(bytes, left = prev - bytes, "Created allocation handle");
+                tracing::trace!(bytes, left = prev - bytes, category = ?self.category, "Created allocation handle");
+                self.inner.total.record_alloc(bytes);
+                self.inner.categories[self.category.index()].record_alloc(bytes);
                 Ok(AllocHandle {
                     bytes,
                     inner: Arc::clone(&self.inner),
+                    category: self.category,
+                    recycled: false,
                 })
             }
+            Err(_) if self.inner.evict_idle_buffers() => self.alloc::<T>(count),
             Err(left) => {
-                tracing::trace!(bytes, left, "Allocation failed");
+                tracing::trace!(bytes, left, category = ?self.category, "Allocation failed");
+                self.inner.failed_allocs.fetch_add(1, Ordering::Relaxed);
                 Err(crate::Error::OutOfMemory(bytes))
             }
         }
     }
 
+    /// Takes an idle buffer of the given size class from the buffer pool, with the handle
+    /// recording the buffer.
+    pub(crate) fn take_pooled<T: Send + 'static>(
+        &self,
+        class_len: usize,
+    ) -> Option<(Vec<T>, AllocHandle)> {
+        let pool = self.inner.pool.get()?;
+        let (
+            buf,
+            IdleBuffer {
+                bytes, category, ..
+            },
+        ) = pool.take::<T>(class_len)?;
+
+        if category != self.category {
+            let inner = &*self.inner;
+            inner.categories[category.index()].record_free(bytes);
+            inner.categories[self.category.index()].record_alloc(bytes);
+            inner.categories[self.category.index()]
+                .num_allocs
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+        let handle = AllocHandle {
+            bytes,
+            inner: Arc::clone(&self.inner),
+            category: self.category,
+            recycled: false,
+        };
+        Some((buf, handle))
+    }
+
+    /// Returns whether the tracker is associated with a buffer pool.
+    #[inline]
+    pub(crate) fn pool(&self) -> Option<&BufferPool> {
+        self.inner.pool.get()
+    }
+
     /// Expands the current limit by `by_bytes` bytes.
     pub fn expand_limit(&self, by_bytes: usize) {
         self.inner.bytes_left.fetch_add(by_bytes, Ordering::Relaxed);
@@ -72,6 +319,27 @@ impl AllocTracker {
             Err(crate::Error::OutOfMemory(by_bytes))
         }
     }
+
+    /// Returns a snapshot of allocation statistics.
+    ///
+    /// Statistics are shared by all trackers created from the same
+    /// [`with_limit`][Self::with_limit] call.
+    pub fn stats(&self) -> AllocStats {
+        let inner = &*self.inner;
+        let (pooled_bytes, pooled_buffers) = inner
+            .pool
+            .get()
+            .map(|pool| pool.idle_stats())
+            .unwrap_or_default();
+        AllocStats {
+            bytes_left: inner.bytes_left.load(Ordering::Relaxed),
+            failed_allocs: inner.failed_allocs.load(Ordering::Relaxed),
+            total: inner.total.snapshot(),
+            pooled_bytes,
+            pooled_buffers,
+            categories: std::array::from_fn(|idx| inner.categories[idx].snapshot()),
+        }
+    }
 }
 
 /// Allocation handle.
@@ -79,22 +347,173 @@ impl AllocTracker {
 pub struct AllocHandle {
     bytes: usize,
     inner: Arc<AllocTrackerInner>,
+    category: AllocCategory,
+    /// Whether the allocation record is owned by the buffer pool.
+    recycled: bool,
 }
 
 impl Drop for AllocHandle {
     fn drop(&mut self) {
-        let bytes = self.bytes;
-        let prev = self.inner.bytes_left.fetch_add(bytes, Ordering::Relaxed);
-        tracing::trace!(bytes, left = prev + bytes, "Released allocation handle");
+        if self.recycled {
+            return;
+        }
+        self.inner.release(self.bytes, self.category);
         self.bytes = 0;
     }
 }
 
 impl AllocHandle {
-    /// Returns the tracker the handle belongs to.
+    /// Records an additional allocation of `count` number of `T` to the handle.
+    ///
+    /// Returns an error if the allocation exceeds the current limit, leaving the handle unchanged.
+    pub fn grow<T>(&mut self, count: usize) -> Result<(), crate::Error> {
+        let bytes = count * std::mem::size_of::<T>();
+        let result = self.inner.bytes_left.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |bytes_left| bytes_left.checked_sub(bytes),
+        );
+
+        match result {
+            Ok(prev) => {
+                tracing::trace!(bytes, left = prev - bytes, category = ?self.category, "Grew allocation handle");
+                self.inner.total.record_grow(bytes);
+                self.inner.categories[self.category.index()].record_grow(bytes);
+                self.bytes += bytes;
+                Ok(())
+            }
+            Err(_) if self.inner.evict_idle_buffers() => self.grow::<T>(count),
+            Err(left) => {
+                tracing::trace!(bytes, left, category = ?self.category, "Allocation failed");
+                self.inner.failed_allocs.fetch_add(1, Ordering::Relaxed);
+                Err(crate::Error::OutOfMemory(bytes))
+            }
+        }
+    }
+
+    /// Puts the buffer recorded by the handle into the buffer pool, keeping the allocation
+    /// recorded.
+    ///
+    /// The buffer is released if the pool is full.
+    pub(crate) fn recycle<T: Send + 'static>(mut self, buf: Vec<T>) {
+        let Some(pool) = self.inner.pool.get() else {
+            return;
+        };
+        // The pool now owns the allocation record.
+        self.recycled = pool.put(buf, self.bytes, self.category).is_ok();
+    }
+
+    /// Returns the number of bytes recorded by the handle.
+    #[inline]
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the tracker the handle belongs to, with the category of the handle.
     pub fn tracker(&self) -> AllocTracker {
         AllocTracker {
             inner: Arc::clone(&self.inner),
+            category: self.category,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let tracker = AllocTracker::with_limit(1000);
+        let modular = tracker.with_category(AllocCategory::ModularChannel);
+
+        let a = tracker.alloc::<u8>(100).unwrap();
+        let b = modular.alloc::<u32>(50).unwrap();
+        assert!(modular.alloc::<u8>(1000).is_err());
+        drop(a);
+        let c = b.tracker().alloc::<u16>(100).unwrap();
+        assert_eq!(c.tracker().category(), AllocCategory::ModularChannel);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.bytes_left, 600);
+        assert_eq!(stats.failed_allocs, 1);
+        assert_eq!(stats.total.current_bytes, 400);
+        assert_eq!(stats.total.peak_bytes, 400);
+        assert_eq!(stats.total.num_allocs, 3);
+        assert_eq!(stats.total.live_allocs, 2);
+
+        let other = stats.category(AllocCategory::Other);
+        assert_eq!(other.current_bytes, 0);
+        assert_eq!(other.peak_bytes, 100);
+        let modular_stats = stats.category(AllocCategory::ModularChannel);
+        assert_eq!(modular_stats.current_bytes, 400);
+        assert_eq!(modular_stats.num_allocs, 2);
+
+        drop(b);
+        drop(c);
+        let stats = modular.stats();
+        assert_eq!(stats.bytes_left, 1000);
+        assert_eq!(stats.total.current_bytes, 0);
+        assert_eq!(stats.total.peak_bytes, 400);
+        assert_eq!(stats.categories().count(), AllocCategory::ALL.len());
+    }
+
+    #[test]
+    fn grow_handle() {
+        let tracker = AllocTracker::with_limit(1000);
+        let mut handle = tracker
+            .with_category(AllocCategory::FrameData)
+            .alloc::<u8>(100)
+            .unwrap();
+        handle.grow::<u8>(400).unwrap();
+        assert!(handle.grow::<u8>(600).is_err());
+        assert_eq!(handle.bytes(), 500);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.bytes_left, 500);
+        assert_eq!(stats.failed_allocs, 1);
+        let frame_data = stats.category(AllocCategory::FrameData);
+        assert_eq!(frame_data.current_bytes, 500);
+        assert_eq!(frame_data.num_allocs, 1);
+
+        drop(handle);
+        assert_eq!(tracker.stats().bytes_left, 1000);
+    }
+
+    #[test]
+    fn buffer_pool() {
+        let tracker = AllocTracker::with_limit(1 << 20).with_buffer_pool(1 << 20);
+        let grid = crate::AlignedGrid::<f32>::with_alloc_tracker(64, 64, Some(&tracker)).unwrap();
+        let ptr = grid.buf().as_ptr();
+        let bytes_left = tracker.stats().bytes_left;
+        drop(grid);
+
+        // Idle buffers are still recorded.
+        let stats = tracker.stats();
+        assert_eq!(stats.pooled_buffers, 1);
+        assert_eq!(stats.bytes_left, bytes_left);
+        assert_eq!(stats.total.current_bytes, stats.pooled_bytes);
+
+        let modular = tracker.with_category(AllocCategory::ModularChannel);
+        let grid = crate::AlignedGrid::<f32>::with_alloc_tracker(63, 65, Some(&modular)).unwrap();
+        assert_eq!(grid.buf().as_ptr(), ptr);
+        assert!(grid.buf().iter().all(|&v| v == 0.0));
+        let stats = tracker.stats();
+        assert_eq!(stats.pooled_buffers, 0);
+        assert_eq!(stats.total.num_allocs, 1);
+        assert_eq!(stats.category(AllocCategory::Other).current_bytes, 0);
+        assert_eq!(
+            stats.category(AllocCategory::ModularChannel).current_bytes,
+            stats.total.current_bytes,
+        );
+        drop(grid);
+
+        // Idle buffers are evicted if an allocation would exceed the limit.
+        let handle = tracker.alloc::<u8>(bytes_left + 1).unwrap();
+        let stats = tracker.stats();
+        assert_eq!(stats.pooled_buffers, 0);
+        assert_eq!(stats.failed_allocs, 0);
+        drop(handle);
+        assert_eq!(tracker.stats().bytes_left, 1 << 20);
+    }
+}
@@ -2,6 +2,7 @@
 //! images.
 mod alloc_tracker;
 mod mutable_subgrid;
+mod pool;
 mod shared_subgrid;
 mod simd;
 pub use alloc_tracker::*;
@@ -33,6 +34,15 @@ pub struct AlignedGrid<S> {
     offset: usize,
     buf: Vec<S>,
     handle: Option<AllocHandle>,
+    recycle: Option<fn(Vec<S>, AllocHandle)>,
+}
+
+impl<S> Drop for AlignedGrid<S> {
+    fn drop(&mut self) {
+        if let (Some(recycle), Some(handle)) = (self.recycle, self.handle.take()) {
+            recycle(std::mem::take(&mut self.buf), handle);
+        }
+    }
 }
 
 impl<S> std::fmt::Debug for AlignedGrid<S> {
@@ -54,14 +64,17 @@ impl<S> AlignedGrid<S> {
             offset: 0,
             buf: Vec::new(),
             handle: None,
+            recycle: None,
         }
     }
 }
 
-impl<S: Default + Clone> AlignedGrid<S> {
+impl<S: Default + Clone + Send + 'static> AlignedGrid<S> {
     const ALIGN: usize = 32;
 
     /// Create a new buffer, recording the allocation if a tracker is given.
+    ///
+    /// The buffer is taken from the buffer pool if the tracker has one.
     #[inline]
     pub fn with_alloc_tracker(
         width: usize,
@@ -70,6 +83,11 @@ impl<S: Default + Clone> AlignedGrid<S> {
     ) -> Result<Self, Error> {
         let len = width * height;
         let buf_len = len + (Self::ALIGN - 1) / std::mem::size_of::<S>();
+        if let Some(mut out) = Self::from_pool(width, height, buf_len, tracker)? {
+            out.buf.resize(len + out.offset, S::default());
+            return Ok(out);
+        }
+
         let handle = tracker
             .map(|tracker| tracker.alloc::<S>(buf_len))
             .transpose()?;
@@ -85,9 +103,56 @@ impl<S: Default + Clone> AlignedGrid<S> {
             offset,
             buf,
             handle,
+            recycle: None,
         })
     }
 
+    /// Creates an empty buffer from the buffer pool of the tracker, reusing an idle buffer if
+    /// there is one.
+    ///
+    /// Returns `None` if the tracker doesn't have a buffer pool, or the buffer is too small to be
+    /// pooled.
+    fn from_pool(
+        width: usize,
+        height: usize,
+        buf_len: usize,
+        tracker: Option<&AllocTracker>,
+    ) -> Result<Option<Self>, Error> {
+        let Some(tracker) = tracker.filter(|tracker| tracker.pool().is_some()) else {
+            return Ok(None);
+        };
+        let Some(class_len) = pool::BufferPool::size_class::<S>(buf_len) else {
+            return Ok(None);
+        };
+
+        let (mut buf, handle) = match tracker.take_pooled::<S>(class_len) {
+            Some(x) => x,
+            None => {
+                let handle = tracker.alloc::<S>(class_len)?;
+                (Vec::with_capacity(class_len), handle)
+            }
+        };
+
+        let extra = buf.as_ptr() as usize & (Self::ALIGN - 1);
+        let offset = ((Self::ALIGN - extra) % Self::ALIGN) / std::mem::size_of::<S>();
+        buf.resize_with(offset, S::default);
+
+        Ok(Some(Self {
+            width,
+            height,
+            offset,
+            buf,
+            handle: Some(handle),
+            recycle: Some(Self::recycle_buf),
+        }))
+    }
+
+    fn recycle_buf(buf: Vec<S>, handle: AllocHandle) {
+        if pool::BufferPool::size_class::<S>(buf.capacity()) == Some(buf.capacity()) {
+            handle.recycle(buf);
+        }
+    }
+
     #[inline]
     fn empty_aligned(
         width: usize,
@@ -96,6 +161,10 @@ impl<S: Default + Clone> AlignedGrid<S> {
     ) -> Result<Self, Error> {
         let len = width * height;
         let buf_len = len + (Self::ALIGN - 1) / std::mem::size_of::<S>();
+        if let Some(out) = Self::from_pool(width, height, buf_len, tracker)? {
+            return Ok(out);
+        }
+
         let handle = tracker
             .map(|tracker| tracker.alloc::<S>(buf_len))
             .transpose()?;
@@ -111,6 +180,7 @@ impl<S: Default + Clone> AlignedGrid<S> {
             offset,
             buf,
             handle,
+            recycle: None,
         })
     }
 
@@ -194,7 +264,7 @@ pub struct PaddedGrid<S: Clone> {
     padding: usize,
 }
 
-impl<S: Default + Clone> PaddedGrid<S> {
+impl<S: Default + Clone + Send + 'static> PaddedGrid<S> {
     /// Create a new buffer.
     pub fn with_alloc_tracker(
         width: usize,
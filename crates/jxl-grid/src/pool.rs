@@ -0,0 +1,111 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::AllocCategory;
+
+/// Buffers smaller than this are not pooled.
+const MIN_POOLED_BYTES: usize = 4096;
+
+/// Pool of idle grid buffers, grouped by element type and size class.
+///
+/// Idle buffers stay recorded in the tracker they were allocated from, until they're reused or
+/// evicted.
+#[derive(Debug)]
+pub(crate) struct BufferPool {
+    max_idle_bytes: usize,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    idle_bytes: usize,
+    num_buffers: usize,
+    buffers: HashMap<(TypeId, usize), Vec<IdleBuffer>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct IdleBuffer {
+    buf: Box<dyn Any + Send>,
+    pub(crate) bytes: usize,
+    pub(crate) category: AllocCategory,
+}
+
+impl BufferPool {
+    pub(crate) fn new(max_idle_bytes: usize) -> Self {
+        Self {
+            max_idle_bytes,
+            state: Mutex::new(PoolState::default()),
+        }
+    }
+
+    /// Returns the size class of a buffer of `len` elements of `T`, or `None` if the buffer should
+    /// not be pooled.
+    ///
+    /// Size classes are spaced so that at most 25% of a buffer is wasted.
+    pub(crate) fn size_class<T>(len: usize) -> Option<usize> {
+        if len.saturating_mul(std::mem::size_of::<T>()) < MIN_POOLED_BYTES {
+            return None;
+        }
+
+        let step = 1usize << (len.ilog2().saturating_sub(2));
+        len.checked_next_multiple_of(step)
+    }
+
+    /// Takes an idle buffer with the given size class.
+    pub(crate) fn take<T: Send + 'static>(&self, class_len: usize) -> Option<(Vec<T>, IdleBuffer)> {
+        let mut state = self.state.lock().unwrap();
+        let buffers = state.buffers.get_mut(&(TypeId::of::<T>(), class_len))?;
+        let mut idle = buffers.pop()?;
+        state.idle_bytes -= idle.bytes;
+        state.num_buffers -= 1;
+        drop(state);
+
+        let buf = std::mem::replace(&mut idle.buf, Box::new(()));
+        let mut buf = *buf.downcast::<Vec<T>>().unwrap();
+        buf.clear();
+        Some((buf, idle))
+    }
+
+    /// Puts a buffer into the pool.
+    ///
+    /// Returns the buffer back if the pool is full.
+    pub(crate) fn put<T: Send + 'static>(
+        &self,
+        buf: Vec<T>,
+        bytes: usize,
+        category: AllocCategory,
+    ) -> Result<(), Vec<T>> {
+        let mut state = self.state.lock().unwrap();
+        if state.idle_bytes + bytes > self.max_idle_bytes {
+            return Err(buf);
+        }
+
+        state.idle_bytes += bytes;
+        state.num_buffers += 1;
+        state
+            .buffers
+            .entry((TypeId::of::<T>(), buf.capacity()))
+            .or_default()
+            .push(IdleBuffer {
+                buf: Box::new(buf),
+                bytes,
+                category,
+            });
+        Ok(())
+    }
+
+    /// Removes every idle buffer from the pool.
+    pub(crate) fn evict_all(&self) -> Vec<IdleBuffer> {
+        let mut state = self.state.lock().unwrap();
+        state.idle_bytes = 0;
+        state.num_buffers = 0;
+        state.buffers.drain().flat_map(|(_, v)| v).collect()
+    }
+
+    /// Returns the number of idle bytes and buffers in the pool.
+    pub(crate) fn idle_stats(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.idle_bytes, state.num_buffers)
+    }
+}
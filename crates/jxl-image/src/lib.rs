@@ -12,6 +12,7 @@ use jxl_color::header::*;
 ///
 /// Use [`Bundle::parse`] to parse the header.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImageHeader {
     /// Image size information.
     pub size: SizeHeader,
@@ -84,6 +85,7 @@ impl ImageHeader {
 define_bundle! {
     /// Image size information.
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct SizeHeader {
         div8: ty(Bool) default(false),
         h_div8: ty(1 + u(5)) cond(div8) default(0),
@@ -121,6 +123,7 @@ impl SizeHeader {
 define_bundle! {
     /// Image metadata.
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct ImageMetadata {
         all_default: ty(Bool) default(true),
         extra_fields: ty(Bool) cond(!all_default) default(false),
@@ -160,12 +163,15 @@ define_bundle! {
         /// 2x upsampling weights.
         pub up2_weight: ty(Array[F16]; 15) cond(cw_mask & 1 != 0) default(Self::D_UP2),
         /// 4x upsampling weights.
+        #[cfg_attr(feature = "serde", serde(with = "jxl_bitstream::serde_array"))]
         pub up4_weight: ty(Array[F16]; 55) cond(cw_mask & 2 != 0) default(Self::D_UP4),
         /// 8x upsampling weights.
+        #[cfg_attr(feature = "serde", serde(with = "jxl_bitstream::serde_array"))]
         pub up8_weight: ty(Array[F16]; 210) cond(cw_mask & 4 != 0) default(Self::D_UP8),
     }
 
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct PreviewHeader {
         div8: ty(Bool),
         h_div8: ty(U32(16, 32, 1 + u(5), 33 + u(9))) cond(div8) default(1),
@@ -174,10 +180,10 @@ define_bundle! {
             ty(U32(1 + u(6), 65 + u(8), 321 + u(10), 1345 + u(12))) cond(!div8)
             default(8 * h_div8),
         ratio: ty(u(3)),
-        w_div8: ty(U32(16, 32, 1 + u(5), 33 + u(9))) cond(div8) default(1),
+        w_div8: ty(U32(16, 32, 1 + u(5), 33 + u(9))) cond(div8 && ratio == 0) default(1),
         /// Width of the preview image.
         pub width:
-            ty(U32(1 + u(6), 65 + u(8), 321 + u(10), 1345 + u(12))) cond(!div8)
+            ty(U32(1 + u(6), 65 + u(8), 321 + u(10), 1345 + u(12))) cond(!div8 && ratio == 0)
             default(SizeHeader::compute_default_width(ratio, w_div8, height)),
     }
 
@@ -186,6 +192,7 @@ define_bundle! {
     /// TPS (ticks per second) is computed as `tps_numerator / tps_denominator`, which means
     /// `tps_denominator / tps_numerator` seconds per tick.
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct AnimationHeader {
         /// TPS numerator.
         pub tps_numerator: ty(U32(100, 1000, 1 + u(10), 1 + u(30))) default(0),
@@ -200,6 +207,7 @@ define_bundle! {
 
 #[derive(Debug, Default)]
 #[allow(unused)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Extensions {
     extension_bits: u64,
 }
@@ -275,6 +283,7 @@ impl ImageMetadata {
 
 /// Information about an extra channel.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExtraChannelInfo {
     /// Type and associated parameters of the channel.
     pub ty: ExtraChannelType,
@@ -357,6 +366,7 @@ impl ExtraChannelInfo {
 /// Type of an extra channel.
 #[derive(Debug, PartialEq, Copy, Clone)]
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExtraChannelType {
     Alpha {
         alpha_associated: bool,
@@ -421,6 +431,7 @@ impl TryFrom<u32> for ExtraChannelTypeRaw {
 
 /// Bit depth information.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BitDepth {
     /// Modular image samples represent integer values, where the range
     /// `0..=(1 << bits_per_sample) - 1` corresponds to \[0.0, 1.0\], scaled linearly.
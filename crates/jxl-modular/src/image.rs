@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use jxl_bitstream::Bitstream;
 use jxl_coding::{Decoder, DecoderRleMode, RleToken};
-use jxl_grid::{AlignedGrid, AllocTracker, MutableSubgrid};
+use jxl_grid::{AlignedGrid, AllocCategory, AllocTracker, MutableSubgrid};
 
 use crate::{
     ma::{FlatMaTree, MaTreeLeafClustered, SimpleMaTable},
@@ -113,6 +113,8 @@ impl<S: Sample> ModularImageDestination<S> {
         channels: ModularChannels,
         tracker: Option<&AllocTracker>,
     ) -> Result<Self> {
+        let tracker = channel_tracker(tracker);
+        let tracker = tracker.as_ref();
         let mut meta_channels = Vec::new();
         for tr in &header.transform {
             tr.prepare_meta_channels(&mut meta_channels, tracker)?;
@@ -389,6 +391,19 @@ pub struct TransformedModularSubimage<'dest, S: Sample> {
     partial: bool,
 }
 
+/// Tags allocations of channel buffers as modular channels.
+///
+/// Categories set by other subsystems, such as LF coefficients of VarDCT, are kept.
+fn channel_tracker(tracker: Option<&AllocTracker>) -> Option<AllocTracker> {
+    tracker.map(|tracker| {
+        if tracker.category() == AllocCategory::Other {
+            tracker.with_category(AllocCategory::ModularChannel)
+        } else {
+            tracker.clone()
+        }
+    })
+}
+
 impl<'dest, S: Sample> TransformedModularSubimage<'dest, S> {
     fn empty(header: &ModularHeader, ma_ctx: &MaConfig, bit_depth: u32) -> Self {
         Self {
@@ -434,8 +449,9 @@ impl<'dest, S: Sample> TransformedModularSubimage<'dest, S> {
             meta_channels: Vec::new(),
             image_channels: self.grid,
         };
+        let tracker = channel_tracker(tracker);
         for tr in &image.header.transform {
-            tr.prepare_meta_channels(&mut image.meta_channels, tracker)?;
+            tr.prepare_meta_channels(&mut image.meta_channels, tracker.as_ref())?;
         }
         Ok(image)
     }
@@ -166,7 +166,7 @@ impl Bundle<MaConfigParams<'_>> for MaConfig {
         let cluster_map = decoder.cluster_map();
 
         let tree_alloc_handle = tracker
-            .map(|tracker| tracker.alloc::<FoldingTree>(nodes.len()))
+            .map(|tracker| tracker.alloc::<MaTreeNode>(nodes.len()))
             .transpose()?;
         let mut tmp = VecDeque::<(_, usize)>::with_capacity(max_depth);
         for node in nodes.into_iter().rev() {
@@ -1,5 +1,7 @@
 pub mod color_encoding;
+pub mod compare;
 pub mod decode;
+pub mod extract;
 #[cfg(feature = "__devtools")]
 pub mod generate_fixture;
 pub mod info;
@@ -7,14 +9,18 @@ pub mod info;
 pub mod progressive;
 #[cfg(test)]
 pub mod tests;
+pub mod tiles;
 
 pub use color_encoding::parse_color_encoding;
+pub use compare::CompareArgs;
 pub use decode::DecodeArgs;
+pub use extract::ExtractArgs;
 #[cfg(feature = "__devtools")]
 pub use generate_fixture::GenerateFixtureArgs;
 pub use info::InfoArgs;
 #[cfg(feature = "__devtools")]
 pub use progressive::ProgressiveArgs;
+pub use tiles::TilesArgs;
 
 #[derive(Debug, clap::Parser)]
 #[command(version)]
@@ -47,6 +53,12 @@ pub enum Subcommands {
     /// Print information about JPEG XL image.
     #[command(short_flag = 'I')]
     Info(InfoArgs),
+    /// Export image pyramid tiles for Deep Zoom or IIIF viewers.
+    Tiles(TilesArgs),
+    /// Compare two images and compute distortion metrics.
+    Compare(CompareArgs),
+    /// Extract metadata boxes, ICC profiles, codestream and preview from JPEG XL image.
+    Extract(ExtractArgs),
     /// (devtools) Generate frames for progressive decoding animation.
     #[cfg(feature = "__devtools")]
     Progressive(ProgressiveArgs),
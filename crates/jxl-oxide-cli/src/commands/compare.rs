@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use jxl_oxide::EnumColourEncoding;
+
+/// Compare two images and compute distortion metrics.
+#[derive(Debug, Parser)]
+#[non_exhaustive]
+pub struct CompareArgs {
+    /// Reference image, either JPEG XL, PNG or Numpy
+    pub reference: PathBuf,
+    /// Distorted image, either JPEG XL, PNG or Numpy
+    pub distorted: PathBuf,
+    /// Color space to compute per-channel metrics in, with the same syntax as `--target-colorspace`
+    /// of decode
+    ///
+    /// JPEG XL images are rendered in this color space, and PNG images are converted to this color
+    /// space using their `cICP`, `iCCP`, `sRGB`, `gAMA` and `cHRM` chunks. PNG images without color
+    /// space information and Numpy images are assumed to be already in this color space. Defaults
+    /// to sRGB.
+    #[arg(long, value_parser = super::parse_color_encoding)]
+    pub colorspace: Option<EnumColourEncoding>,
+    /// Index of the keyframe to compare
+    #[arg(long, default_value_t = 0)]
+    pub frame: usize,
+    /// Write heatmap of XYB perceptual distance as 8-bit PNG
+    #[arg(long)]
+    pub heatmap: Option<PathBuf>,
+    /// Output format
+    #[arg(value_enum, long, default_value_t = CompareFormat::Text)]
+    pub format: CompareFormat,
+    /// Number of parallelism to use
+    #[arg(short = 'j', long)]
+    pub num_threads: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CompareFormat {
+    /// Human-readable text.
+    Text,
+    /// JSON object, for consumption by other programs.
+    Json,
+}
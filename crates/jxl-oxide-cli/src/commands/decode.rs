@@ -1,28 +1,80 @@
-use std::path::PathBuf;
+use std::{ops::Range, path::PathBuf};
 
 use clap::Parser;
-use jxl_oxide::{CropInfo, EnumColourEncoding, Lz77Mode};
+use jxl_oxide::{icc::IccVersion, CropInfo, EnumColourEncoding, Lz77Mode};
 
 #[derive(Debug, Parser)]
 #[non_exhaustive]
 pub struct DecodeArgs {
-    /// Output file
+    /// Output file, or `-` to write to stdout
     #[arg(short, long)]
     pub output: Option<PathBuf>,
     /// Output ICC file
     #[arg(long)]
     pub icc_output: Option<PathBuf>,
-    /// Input file
-    pub input: PathBuf,
+    /// Input files, or directories containing JPEG XL images; `-` reads from stdin
+    ///
+    /// Images are decoded in batch if multiple inputs or a directory is given.
+    #[arg(required = true)]
+    pub input: Vec<PathBuf>,
+    /// Output directory of batch decoding
+    ///
+    /// Files are decoded in batch if this option is set, even with a single input.
+    #[arg(long, conflicts_with_all = ["output", "icc_output"])]
+    pub output_dir: Option<PathBuf>,
+    /// File name pattern of batch decoding output
+    ///
+    /// `{stem}` is replaced with the file stem of the input, and `{ext}` with the extension of the
+    /// output format.
+    #[arg(long, default_value = "{stem}.{ext}")]
+    pub name_pattern: String,
+    /// Search subdirectories of input directories, mirroring the directory structure in the output
+    /// directory
+    #[arg(short, long)]
+    pub recursive: bool,
     /// (unstable) Region to render, in format of 'width height left top'
     #[arg(long, value_parser = parse_crop_info)]
     pub crop: Option<CropInfo>,
     /// (unstable) Approximate memory limit, in bytes
     #[arg(long, default_value_t = 0)]
     pub approx_memory_limit: usize,
+    /// Whether to write keyframes into a single file, or each keyframe into a separate file
+    ///
+    /// With `each`, `{index}` in the output path is replaced with the keyframe index (`-{index}`
+    /// is appended to the file stem if absent), and the name, duration and blending information
+    /// of frames are written to JSON, named after the output path with `{index}` replaced by
+    /// `frames`. Each file contains the keyframe composited onto the full canvas. Cannot be used
+    /// when writing to stdout.
+    #[arg(value_enum, long, default_value_t = FramesMode::All)]
+    pub frames: FramesMode,
+    /// Write an updated image each time a new progressive stage or keyframe is loaded
+    ///
+    /// The output file is overwritten with each update. Cannot be used when writing to stdout.
+    #[arg(long, requires = "output")]
+    pub progressive: bool,
+    /// Index of the keyframe to decode
+    #[arg(long, conflicts_with = "frame_range")]
+    pub frame: Option<usize>,
+    /// Range of keyframes to decode, in format of 'start-end', inclusive
+    ///
+    /// Either end can be omitted, e.g. '2-' decodes keyframes from index 2 to the last one.
+    #[arg(long, value_parser = parse_frame_range)]
+    pub frame_range: Option<Range<usize>>,
     /// Format to output
     #[arg(value_enum, short = 'f', long, default_value_t = OutputFormat::Png)]
     pub output_format: OutputFormat,
+    /// Sample layout of raw output
+    #[arg(value_enum, long, default_value_t = RawLayout::Interleaved)]
+    pub raw_layout: RawLayout,
+    /// Luminance of sample value 1.0 in OpenEXR output, in nits
+    ///
+    /// Defaults to 203 nits for HDR images (PQ, HLG, or intensity target above 255 nits), and
+    /// the intensity target of the image otherwise.
+    #[arg(long, value_parser = parse_display_luminance)]
+    pub exr_white_luminance: Option<f32>,
+    /// Write extra channels other than alpha to OpenEXR output, named after the channel name
+    #[arg(long)]
+    pub exr_extra_channels: bool,
     /// (unstable) Target colorspace specification
     ///
     /// Specification string consists of (optional) preset and a sequence of parameters delimited by commas.
@@ -66,8 +118,26 @@ pub struct DecodeArgs {
     /// (unstable) Path to target ICC profile
     #[arg(long)]
     pub target_icc: Option<PathBuf>,
+    /// (unstable) Nominal peak luminance of the display used by HLG OOTF, in nits
+    ///
+    /// Defaults to the intensity target of the image.
+    #[arg(long, value_parser = parse_display_luminance)]
+    pub hlg_display_luminance: Option<f32>,
+    /// (unstable) Treat HLG signals as scene-referred, without applying HLG OOTF
+    #[arg(long, conflicts_with = "hlg_display_luminance")]
+    pub hlg_scene_referred: bool,
+    /// Version of ICC profile embedded in PNG output
+    ///
+    /// ICCv2 profiles can be used with legacy software which rejects ICCv4 profiles.
+    #[arg(value_enum, long, default_value_t = IccVersionArg::V4)]
+    pub icc_version: IccVersionArg,
+    /// Embed tone mapped LUT in ICC profile of PQ or HLG PNG output
+    ///
+    /// Color managed applications without CICP support will use the LUT to display HDR images.
+    /// Has no effect with ICCv2 profiles.
+    #[arg(long)]
+    pub hdr_icc_lut: bool,
     /// Number of parallelism to use
-    #[cfg(feature = "rayon")]
     #[arg(short = 'j', long)]
     pub num_threads: Option<usize>,
     /// Number of repeated decoding, used for benchmarking
@@ -88,6 +158,66 @@ pub enum OutputFormat {
     Png16,
     /// Numpy, used for conformance test.
     Npy,
+    /// PPM, or PGM if grayscale. 8-bit or 16-bit depending on bit depth, without alpha.
+    Ppm,
+    /// PAM, 8-bit or 16-bit depending on bit depth, with alpha and extra channels.
+    Pam,
+    /// PFM, 32-bit float without clamping, without alpha. Holds a single keyframe.
+    Pfm,
+    /// 32-bit float samples in little endian, with JSON description written to `<output>.json`.
+    Raw,
+    /// TIFF, respects bit depth information, with alpha and extra channels.
+    ///
+    /// CMYK images are written as separated CMYK with the embedded ICC profile.
+    Tiff,
+    /// TIFF, always 8-bit.
+    Tiff8,
+    /// TIFF, always 16-bit.
+    Tiff16,
+    /// TIFF, always 32-bit integer.
+    Tiff32,
+    /// TIFF, always 32-bit float.
+    TiffFloat,
+    /// OpenEXR, scene-linear 16-bit half float.
+    ///
+    /// Alpha is written as `A`; other extra channels are written with `--exr-extra-channels`.
+    /// Multiple keyframes are written as a multi-part file.
+    Exr,
+    /// OpenEXR, scene-linear 32-bit float.
+    ExrFloat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FramesMode {
+    /// Write all keyframes into a single file, as an animation or a stack if supported.
+    All,
+    /// Write each keyframe into a separate file.
+    Each,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RawLayout {
+    /// Channels are interleaved for each pixel.
+    Interleaved,
+    /// Each channel is written as a separate plane.
+    Planar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum IccVersionArg {
+    /// ICCv2, version 2.4.
+    V2,
+    /// ICCv4, version 4.4.
+    V4,
+}
+
+impl From<IccVersionArg> for IccVersion {
+    fn from(value: IccVersionArg) -> Self {
+        match value {
+            IccVersionArg::V2 => IccVersion::V2,
+            IccVersionArg::V4 => IccVersion::V4,
+        }
+    }
 }
 
 fn parse_crop_info(s: &str) -> Result<CropInfo, std::num::ParseIntError> {
@@ -133,6 +263,38 @@ fn parse_crop_info(s: &str) -> Result<CropInfo, std::num::ParseIntError> {
     })
 }
 
+fn parse_frame_range(s: &str) -> Result<Range<usize>, String> {
+    let Some((start, end)) = s.trim().split_once('-') else {
+        return Err(String::from("range should be in format of 'start-end'"));
+    };
+    let start = start.trim();
+    let end = end.trim();
+    let start = if start.is_empty() {
+        0
+    } else {
+        start.parse::<usize>().map_err(|e| e.to_string())?
+    };
+    let end = if end.is_empty() {
+        usize::MAX
+    } else {
+        let end = end.parse::<usize>().map_err(|e| e.to_string())?;
+        if end < start {
+            return Err(String::from("end of range should not be less than start"));
+        }
+        end.saturating_add(1)
+    };
+    Ok(start..end)
+}
+
+fn parse_display_luminance(s: &str) -> Result<f32, String> {
+    let luminance = s.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    if luminance.is_finite() && luminance > 0.0 {
+        Ok(luminance)
+    } else {
+        Err(String::from("luminance should be a positive number"))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 #[doc(hidden)]
 pub enum Lz77ModeArg {
@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+/// Extract metadata boxes, ICC profiles, codestream and preview from JPEG XL image.
+#[derive(Debug, Parser)]
+#[non_exhaustive]
+pub struct ExtractArgs {
+    /// Input file
+    pub input: PathBuf,
+    /// Output directory
+    ///
+    /// Extracted files are named after the file stem of the input, e.g. `image.exif`.
+    #[arg(short, long, default_value = ".")]
+    pub output: PathBuf,
+    /// Parts to extract, separated by commas
+    ///
+    /// Every part found in the image is extracted if not specified.
+    #[arg(value_enum, long, value_delimiter = ',')]
+    pub parts: Vec<ExtractPart>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExtractPart {
+    /// Exif metadata, starting with TIFF header (`.exif`).
+    Exif,
+    /// XMP metadata (`.xmp`).
+    Xmp,
+    /// JUMBF superbox (`.jumbf`).
+    Jumbf,
+    /// Embedded ICC profile (`.original.icc`) and ICC profile of rendered images
+    /// (`.rendered.icc`).
+    Icc,
+    /// Bare codestream, reassembled from codestream boxes (`.codestream.jxl`).
+    Codestream,
+    /// Preview frame as PNG (`.preview.png`).
+    Preview,
+    /// Other boxes, with the box type in the file name (`.box-{type}.bin`).
+    Boxes,
+}
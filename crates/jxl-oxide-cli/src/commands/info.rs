@@ -12,6 +12,19 @@ pub struct InfoArgs {
     #[arg(long)]
     pub all_frames: bool,
     /// Output group sizes and offsets
+    ///
+    /// Group sizes and offsets are always included in JSON output.
     #[arg(long)]
     pub with_offset: bool,
+    /// Output format
+    #[arg(value_enum, long, default_value_t = InfoFormat::Text)]
+    pub format: InfoFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum InfoFormat {
+    /// Human-readable text.
+    Text,
+    /// JSON object, for consumption by other programs.
+    Json,
 }
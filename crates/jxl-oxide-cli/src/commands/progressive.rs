@@ -14,7 +14,6 @@ pub struct ProgressiveArgs {
     #[arg(short, long)]
     pub output: Option<PathBuf>,
     /// Number of parallelism to use
-    #[cfg(feature = "rayon")]
     #[arg(short = 'j', long)]
     pub num_threads: Option<usize>,
 }
@@ -3,7 +3,14 @@ mod clap {
 
     use clap::Parser;
 
-    use super::super::{Args, Subcommands};
+    use super::super::{
+        compare::CompareFormat,
+        decode::{FramesMode, IccVersionArg, OutputFormat, RawLayout},
+        extract::ExtractPart,
+        info::InfoFormat,
+        tiles::{TileFormat, TileLayout},
+        Args, Subcommands,
+    };
 
     #[test]
     fn basic_decode() {
@@ -14,10 +21,241 @@ mod clap {
         };
         assert!(args.decode.is_none());
         assert_eq!(args.globals.verbose, 0);
-        assert_eq!(decode_args.input, Path::new("input.jxl"));
+        assert_eq!(decode_args.input, [Path::new("input.jxl")]);
         assert_eq!(decode_args.output.as_deref(), Some(Path::new("output.png")));
     }
 
+    #[test]
+    fn decode_batch() {
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "a.jxl",
+            "b.jxl",
+            "images",
+            "--output-dir",
+            "out",
+            "--name-pattern",
+            "{stem}-decoded.{ext}",
+            "-r",
+        ])
+        .unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(
+            decode_args.input,
+            [Path::new("a.jxl"), Path::new("b.jxl"), Path::new("images")]
+        );
+        assert_eq!(decode_args.output_dir.as_deref(), Some(Path::new("out")));
+        assert_eq!(decode_args.name_pattern, "{stem}-decoded.{ext}");
+        assert!(decode_args.recursive);
+
+        let args = Args::try_parse_from(["jxl-oxide", "a.jxl", "b.jxl"]).unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.output_dir, None);
+        assert_eq!(decode_args.name_pattern, "{stem}.{ext}");
+        assert!(!decode_args.recursive);
+
+        let args =
+            Args::try_parse_from(["jxl-oxide", "a.jxl", "-o", "a.png", "--output-dir", "out"]);
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn decode_stdio() {
+        let args = Args::try_parse_from(["jxl-oxide", "-", "-o", "-"]).unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.input, [Path::new("-")]);
+        assert_eq!(decode_args.output.as_deref(), Some(Path::new("-")));
+        assert!(!decode_args.progressive);
+
+        let args =
+            Args::try_parse_from(["jxl-oxide", "-", "-o", "a.png", "--progressive"]).unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.output.as_deref(), Some(Path::new("a.png")));
+        assert!(decode_args.progressive);
+
+        let args = Args::try_parse_from(["jxl-oxide", "-", "--progressive"]);
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn decode_raw() {
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "decode",
+            "input.jxl",
+            "-o",
+            "output.raw",
+            "-f",
+            "raw",
+            "--raw-layout",
+            "planar",
+        ])
+        .unwrap();
+        let Some(Subcommands::Decode(decode_args)) = args.subcommand else {
+            panic!();
+        };
+        assert_eq!(decode_args.output_format, OutputFormat::Raw);
+        assert_eq!(decode_args.raw_layout, RawLayout::Planar);
+
+        let args = Args::try_parse_from(["jxl-oxide", "input.jxl", "-f", "pfm"]).unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.output_format, OutputFormat::Pfm);
+        assert_eq!(decode_args.raw_layout, RawLayout::Interleaved);
+    }
+
+    #[test]
+    fn decode_tiff() {
+        for (arg, format) in [
+            ("tiff", OutputFormat::Tiff),
+            ("tiff8", OutputFormat::Tiff8),
+            ("tiff16", OutputFormat::Tiff16),
+            ("tiff32", OutputFormat::Tiff32),
+            ("tiff-float", OutputFormat::TiffFloat),
+        ] {
+            let args = Args::try_parse_from(["jxl-oxide", "input.jxl", "-f", arg]).unwrap();
+            assert_eq!(args.decode.unwrap().output_format, format);
+        }
+    }
+
+    #[test]
+    fn decode_exr() {
+        let args = Args::try_parse_from(["jxl-oxide", "input.jxl", "-f", "exr"]).unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.output_format, OutputFormat::Exr);
+        assert_eq!(decode_args.exr_white_luminance, None);
+        assert!(!decode_args.exr_extra_channels);
+
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "input.jxl",
+            "-f",
+            "exr-float",
+            "--exr-white-luminance",
+            "100",
+            "--exr-extra-channels",
+        ])
+        .unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.output_format, OutputFormat::ExrFloat);
+        assert_eq!(decode_args.exr_white_luminance, Some(100.0));
+        assert!(decode_args.exr_extra_channels);
+
+        assert!(
+            Args::try_parse_from(["jxl-oxide", "input.jxl", "--exr-white-luminance", "-1"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn decode_frames() {
+        let args = Args::try_parse_from(["jxl-oxide", "input.jxl"]).unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.frames, FramesMode::All);
+        assert_eq!(decode_args.frame, None);
+        assert_eq!(decode_args.frame_range, None);
+
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "input.jxl",
+            "--frames",
+            "each",
+            "-o",
+            "out-{index}.png",
+            "--frame",
+            "3",
+        ])
+        .unwrap();
+        let decode_args = args.decode.unwrap();
+        assert_eq!(decode_args.frames, FramesMode::Each);
+        assert_eq!(decode_args.frame, Some(3));
+
+        for (arg, range) in [("2-5", 2..6), ("2-", 2..usize::MAX), ("-1", 0..2)] {
+            let arg = format!("--frame-range={arg}");
+            let args = Args::try_parse_from(["jxl-oxide", "input.jxl", &arg]).unwrap();
+            assert_eq!(args.decode.unwrap().frame_range, Some(range));
+        }
+
+        for arg in ["5-2", "3", "a-b"] {
+            let arg = format!("--frame-range={arg}");
+            assert!(Args::try_parse_from(["jxl-oxide", "input.jxl", &arg]).is_err());
+        }
+        assert!(Args::try_parse_from([
+            "jxl-oxide",
+            "input.jxl",
+            "--frame",
+            "1",
+            "--frame-range",
+            "1-2"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn compare() {
+        let args = Args::try_parse_from(["jxl-oxide", "compare", "a.jxl", "b.png"]).unwrap();
+        let Some(Subcommands::Compare(compare_args)) = args.subcommand else {
+            panic!();
+        };
+        assert_eq!(compare_args.reference, Path::new("a.jxl"));
+        assert_eq!(compare_args.distorted, Path::new("b.png"));
+        assert!(compare_args.colorspace.is_none());
+        assert_eq!(compare_args.frame, 0);
+        assert_eq!(compare_args.format, CompareFormat::Text);
+
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "compare",
+            "a.jxl",
+            "b.npy",
+            "--colorspace",
+            "srgb,tf=linear",
+            "--heatmap",
+            "heatmap.png",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        let Some(Subcommands::Compare(compare_args)) = args.subcommand else {
+            panic!();
+        };
+        assert!(compare_args.colorspace.is_some());
+        assert_eq!(
+            compare_args.heatmap.as_deref(),
+            Some(Path::new("heatmap.png"))
+        );
+        assert_eq!(compare_args.format, CompareFormat::Json);
+    }
+
+    #[test]
+    fn extract() {
+        let args = Args::try_parse_from(["jxl-oxide", "extract", "input.jxl"]).unwrap();
+        let Some(Subcommands::Extract(extract_args)) = args.subcommand else {
+            panic!();
+        };
+        assert_eq!(extract_args.input, Path::new("input.jxl"));
+        assert_eq!(extract_args.output, Path::new("."));
+        assert!(extract_args.parts.is_empty());
+
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "extract",
+            "input.jxl",
+            "-o",
+            "out",
+            "--parts",
+            "exif,icc,preview",
+        ])
+        .unwrap();
+        let Some(Subcommands::Extract(extract_args)) = args.subcommand else {
+            panic!();
+        };
+        assert_eq!(extract_args.output, Path::new("out"));
+        assert_eq!(
+            extract_args.parts,
+            [ExtractPart::Exif, ExtractPart::Icc, ExtractPart::Preview]
+        );
+    }
+
     #[test]
     fn basic_info() {
         let args = Args::try_parse_from(["jxl-oxide", "info", "input.jxl"]).unwrap();
@@ -27,6 +265,64 @@ mod clap {
         assert!(args.decode.is_none());
         assert_eq!(args.globals.verbose, 0);
         assert_eq!(info_args.input, Path::new("input.jxl"));
+        assert_eq!(info_args.format, InfoFormat::Text);
+
+        let args =
+            Args::try_parse_from(["jxl-oxide", "info", "input.jxl", "--format", "json"]).unwrap();
+        let Some(Subcommands::Info(info_args)) = args.subcommand else {
+            panic!();
+        };
+        assert_eq!(info_args.format, InfoFormat::Json);
+    }
+
+    #[test]
+    fn basic_tiles() {
+        let args = Args::try_parse_from(["jxl-oxide", "tiles", "input.jxl", "-o", "out"]).unwrap();
+        let Some(Subcommands::Tiles(tiles_args)) = args.subcommand else {
+            panic!();
+        };
+        assert!(args.decode.is_none());
+        assert_eq!(tiles_args.input, Path::new("input.jxl"));
+        assert_eq!(tiles_args.output, Path::new("out"));
+        assert_eq!(tiles_args.layout, TileLayout::Dzi);
+        assert_eq!(tiles_args.format, TileFormat::Png);
+        assert_eq!(tiles_args.tile_size, 256);
+        assert!(!tiles_args.no_lf);
+
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "tiles",
+            "input.jxl",
+            "-o",
+            "out",
+            "--layout",
+            "iiif",
+            "-f",
+            "raw",
+            "--tile-size",
+            "512",
+            "--no-lf",
+        ])
+        .unwrap();
+        let Some(Subcommands::Tiles(tiles_args)) = args.subcommand else {
+            panic!();
+        };
+        assert_eq!(tiles_args.layout, TileLayout::Iiif);
+        assert_eq!(tiles_args.format, TileFormat::Raw);
+        assert_eq!(tiles_args.tile_size, 512);
+        assert!(tiles_args.no_lf);
+
+        assert!(Args::try_parse_from(["jxl-oxide", "tiles", "input.jxl"]).is_err());
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "tiles",
+            "input.jxl",
+            "-o",
+            "out",
+            "--tile-size",
+            "8",
+        ]);
+        assert!(args.is_err());
     }
 
     #[test]
@@ -37,7 +333,7 @@ mod clap {
         };
         assert!(args.subcommand.is_none());
         assert_eq!(args.globals.verbose, 0);
-        assert_eq!(decode_args.input, Path::new("input.jxl"));
+        assert_eq!(decode_args.input, [Path::new("input.jxl")]);
         assert_eq!(decode_args.output.as_deref(), Some(Path::new("output.png")));
     }
 
@@ -52,6 +348,64 @@ mod clap {
         assert_eq!(info_args.input, Path::new("input.jxl"));
     }
 
+    #[test]
+    fn hlg_options() {
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "input.jxl",
+            "--target-colorspace",
+            "rec2100,tf=hlg",
+            "--hlg-display-luminance",
+            "400",
+        ])
+        .unwrap();
+        let Some(decode_args) = args.decode else {
+            panic!();
+        };
+        assert_eq!(decode_args.hlg_display_luminance, Some(400.0));
+        assert!(!decode_args.hlg_scene_referred);
+
+        let args =
+            Args::try_parse_from(["jxl-oxide", "input.jxl", "--hlg-display-luminance", "-1"]);
+        assert!(args.is_err());
+
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "input.jxl",
+            "--hlg-scene-referred",
+            "--hlg-display-luminance",
+            "1000",
+        ]);
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn icc_options() {
+        let args = Args::try_parse_from(["jxl-oxide", "input.jxl"]).unwrap();
+        let Some(decode_args) = args.decode else {
+            panic!();
+        };
+        assert_eq!(decode_args.icc_version, IccVersionArg::V4);
+        assert!(!decode_args.hdr_icc_lut);
+
+        let args = Args::try_parse_from([
+            "jxl-oxide",
+            "input.jxl",
+            "--icc-version",
+            "v2",
+            "--hdr-icc-lut",
+        ])
+        .unwrap();
+        let Some(decode_args) = args.decode else {
+            panic!();
+        };
+        assert_eq!(decode_args.icc_version, IccVersionArg::V2);
+        assert!(decode_args.hdr_icc_lut);
+
+        let args = Args::try_parse_from(["jxl-oxide", "input.jxl", "--icc-version", "v3"]);
+        assert!(args.is_err());
+    }
+
     #[test]
     fn verbose() {
         let args = Args::try_parse_from(["jxl-oxide", "input.jxl", "-v"]).unwrap();
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+/// Export image pyramid tiles for Deep Zoom or IIIF viewers.
+#[derive(Debug, Parser)]
+#[non_exhaustive]
+pub struct TilesArgs {
+    /// Input file
+    pub input: PathBuf,
+    /// Output directory
+    #[arg(short, long)]
+    pub output: PathBuf,
+    /// Layout of the tile pyramid
+    #[arg(value_enum, long, default_value_t = TileLayout::Dzi)]
+    pub layout: TileLayout,
+    /// Format of tiles
+    #[arg(value_enum, short = 'f', long, default_value_t = TileFormat::Png)]
+    pub format: TileFormat,
+    /// Width and height of tiles, in pixels
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u32).range(16..=8192))]
+    pub tile_size: u32,
+    /// Identifier of the image written to IIIF `info.json`, usually the base URL of the tiles
+    ///
+    /// Defaults to the name of output directory.
+    #[arg(long)]
+    pub iiif_id: Option<String>,
+    /// Downsample full resolution image for every pyramid level
+    ///
+    /// By default, levels at 1/8 scale or smaller are rendered from LF (and LF frame) data, without
+    /// decoding HF coefficients.
+    #[arg(long)]
+    pub no_lf: bool,
+    /// Number of parallelism to use
+    #[arg(short = 'j', long)]
+    pub num_threads: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TileLayout {
+    /// Deep Zoom Image, with `.dzi` manifest and `_files` directory.
+    Dzi,
+    /// IIIF Image API 3.0 level 0, with `info.json` manifest.
+    Iiif,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TileFormat {
+    /// 8-bit PNG in sRGB.
+    Png,
+    /// Interleaved 32-bit float samples in little endian, without header.
+    Raw,
+}
@@ -0,0 +1,849 @@
+use std::io::prelude::*;
+use std::path::Path;
+
+use jxl_oxide::{
+    color::{ColourSpace, Customxy, Primaries, RenderingIntent, TransferFunction, WhitePoint},
+    ColorEncodingWithProfile, EnumColourEncoding, JxlImage, JxlThreadPool, Lcms2,
+};
+use serde_json::{json, Value};
+
+use crate::commands::compare::*;
+use crate::{Error, Result};
+
+/// Intensity target used for color conversion of PNG images and XYB conversion.
+const XYB_INTENSITY_TARGET: f32 = 255.0;
+
+/// Weights of X, Y and B differences in XYB distance.
+///
+/// Ratio of the weights follows default DC quantization steps of libjxl, scaled so that distance
+/// of 1.0 corresponds to difference of 0.01 in Y.
+const XYB_WEIGHTS: [f32; 3] = [800.0, 100.0, 50.0];
+
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+/// Image loaded for comparison, with planar samples.
+struct CompareImage {
+    width: usize,
+    height: usize,
+    /// One (grayscale) or three color channels.
+    color: Vec<Vec<f32>>,
+    alpha: Option<Vec<f32>>,
+}
+
+impl CompareImage {
+    fn from_interleaved(
+        width: usize,
+        height: usize,
+        buf: &[f32],
+        channels: usize,
+        color_channels: usize,
+        alpha_idx: Option<usize>,
+    ) -> Self {
+        let plane = |idx: usize| {
+            buf.chunks_exact(channels)
+                .map(|pixel| pixel[idx])
+                .collect::<Vec<_>>()
+        };
+        Self {
+            width,
+            height,
+            color: (0..color_channels).map(plane).collect(),
+            alpha: alpha_idx.map(plane),
+        }
+    }
+
+    /// Converts color channels from `from` to `to` in place.
+    ///
+    /// Grayscale images stay grayscale if `to` is an RGB color space.
+    fn convert_color(
+        &mut self,
+        from: &ColorEncodingWithProfile,
+        to: &EnumColourEncoding,
+    ) -> Result<()> {
+        let mut to = to.clone();
+        if self.color.len() == 1 && to.colour_space == ColourSpace::Rgb {
+            to.colour_space = ColourSpace::Grey;
+        }
+
+        let len = self.width * self.height;
+        let mut planes = std::mem::take(&mut self.color);
+        planes.resize_with(3, || vec![0f32; len]);
+        let [a, b, c] = &mut planes[..] else {
+            unreachable!()
+        };
+        jxl_oxide::convert_color(
+            from,
+            &ColorEncodingWithProfile::new(to.clone()),
+            [a, b, c],
+            XYB_INTENSITY_TARGET,
+            &Lcms2,
+        )
+        .map_err(|e| Error::Compare(format!("cannot convert color: {e}")))?;
+
+        if to.colour_space == ColourSpace::Grey {
+            planes.truncate(1);
+        }
+        self.color = planes;
+        Ok(())
+    }
+}
+
+struct ChannelMetrics {
+    name: &'static str,
+    max_error: f64,
+    mse: f64,
+    psnr: f64,
+    ssim: f64,
+}
+
+pub fn handle_compare(args: CompareArgs) -> Result<()> {
+    let _guard = tracing::trace_span!("Handle compare subcommand").entered();
+
+    #[cfg(feature = "rayon")]
+    let pool = JxlThreadPool::rayon(args.num_threads);
+    #[cfg(not(feature = "rayon"))]
+    let pool = JxlThreadPool::std_threads(args.num_threads);
+
+    let encoding = args
+        .colorspace
+        .clone()
+        .unwrap_or_else(|| EnumColourEncoding::srgb(RenderingIntent::Relative));
+    tracing::debug!(?encoding, "Comparing images");
+
+    let reference = load_image(&args.reference, &encoding, args.frame, &pool)?;
+    let distorted = load_image(&args.distorted, &encoding, args.frame, &pool)?;
+    if (reference.width, reference.height) != (distorted.width, distorted.height) {
+        return Err(Error::Compare(format!(
+            "dimension mismatch: {}x{} and {}x{}",
+            reference.width, reference.height, distorted.width, distorted.height,
+        )));
+    }
+    if reference.color.len() != distorted.color.len() {
+        return Err(Error::Compare(format!(
+            "number of color channels mismatch: {} and {}",
+            reference.color.len(),
+            distorted.color.len(),
+        )));
+    }
+
+    let width = reference.width;
+    let height = reference.height;
+    let color_names: &[&str] = if reference.color.len() == 1 {
+        &["Y"]
+    } else {
+        &["R", "G", "B"]
+    };
+    let mut channels = color_names
+        .iter()
+        .zip(reference.color.iter().zip(&distorted.color))
+        .map(|(&name, (a, b))| (name, &**a, &**b))
+        .collect::<Vec<_>>();
+    match (&reference.alpha, &distorted.alpha) {
+        (Some(a), Some(b)) => channels.push(("A", a, b)),
+        (None, None) => {}
+        _ => tracing::warn!("Only one of the images has alpha; skipping alpha channel"),
+    }
+
+    let metrics = channels
+        .into_iter()
+        .map(|(name, a, b)| channel_metrics(name, a, b, width, height))
+        .collect::<Vec<_>>();
+
+    let distance = xyb_distance(&reference, &distorted, &encoding)?;
+    let max_distance = distance.iter().fold(0f32, |acc, &d| acc.max(d)) as f64;
+    let p3_norm =
+        (distance.iter().map(|&d| (d as f64).powi(3)).sum::<f64>() / distance.len() as f64).cbrt();
+
+    if let Some(heatmap) = &args.heatmap {
+        tracing::debug!(path = ?heatmap, "Writing heatmap");
+        let output = std::fs::File::create(heatmap).map_err(Error::WriteImage)?;
+        write_heatmap(output, &distance, width, height).map_err(Error::WriteImage)?;
+    }
+
+    match args.format {
+        CompareFormat::Text => {
+            println!(
+                "{:<8}{:>12}{:>12}{:>12}",
+                "Channel", "Max error", "PSNR (dB)", "SSIM"
+            );
+            for m in &metrics {
+                println!(
+                    "{:<8}{:>12.6}{:>12.3}{:>12.6}",
+                    m.name, m.max_error, m.psnr, m.ssim
+                );
+            }
+            println!("XYB distance: max {max_distance:.4}, 3-norm {p3_norm:.4}");
+        }
+        CompareFormat::Json => {
+            let channels = metrics
+                .iter()
+                .map(|m| {
+                    json!({
+                        "name": m.name,
+                        "max_error": m.max_error,
+                        "mse": m.mse,
+                        "psnr": m.psnr,
+                        "ssim": m.ssim,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let result: Value = json!({
+                "width": width,
+                "height": height,
+                "channels": channels,
+                "xyb_distance": {
+                    "max": max_distance,
+                    "p3_norm": p3_norm,
+                },
+            });
+            println!("{result:#}");
+        }
+    }
+
+    Ok(())
+}
+
+fn load_image(
+    path: &Path,
+    encoding: &EnumColourEncoding,
+    frame: usize,
+    pool: &JxlThreadPool,
+) -> Result<CompareImage> {
+    let mut signature = Vec::with_capacity(8);
+    std::fs::File::open(path)
+        .and_then(|file| file.take(8).read_to_end(&mut signature))
+        .map_err(|e| Error::ReadImage(e.into()))?;
+
+    if signature.starts_with(b"\x89PNG\r\n\x1a\n") {
+        if frame != 0 {
+            tracing::warn!(?path, "Comparing the first frame of PNG image");
+        }
+        let data = std::fs::read(path).map_err(|e| Error::ReadImage(e.into()))?;
+        load_png(&data, encoding)
+    } else if signature.starts_with(b"\x93NUMPY") {
+        let data = std::fs::read(path).map_err(|e| Error::ReadImage(e.into()))?;
+        load_npy(&data, frame).map_err(|e| Error::ReadImage(e.into()))
+    } else {
+        load_jxl(path, encoding, frame, pool)
+    }
+}
+
+fn load_jxl(
+    path: &Path,
+    encoding: &EnumColourEncoding,
+    frame: usize,
+    pool: &JxlThreadPool,
+) -> Result<CompareImage> {
+    let mut image = JxlImage::builder()
+        .pool(pool.clone())
+        .open(path)
+        .map_err(Error::ReadJxl)?;
+    if !image.is_loading_done() {
+        tracing::warn!(?path, "Partial image");
+    }
+    if frame >= image.num_loaded_keyframes() {
+        return Err(Error::Render(
+            format!("keyframe {frame} is not available in {}", path.display()).into(),
+        ));
+    }
+
+    image.request_color_encoding(encoding.clone());
+    let render = image.render_frame(frame).map_err(Error::Render)?;
+    let fb = render.image_all_channels();
+    let color_channels = render.color_channels().len();
+    let alpha_idx = render
+        .extra_channels()
+        .0
+        .iter()
+        .position(|ec| ec.is_alpha())
+        .map(|idx| color_channels + idx);
+    Ok(CompareImage::from_interleaved(
+        fb.width(),
+        fb.height(),
+        fb.buf(),
+        fb.channels(),
+        color_channels,
+        alpha_idx,
+    ))
+}
+
+/// Loads the first frame of PNG image, converting color channels to `encoding`.
+///
+/// Color space of the image is read from `cICP`, `iCCP`, `sRGB`, and `gAMA` and `cHRM` chunks, in
+/// the order of precedence. Images without color space information are assumed to be already in
+/// `encoding`.
+fn load_png(data: &[u8], encoding: &EnumColourEncoding) -> Result<CompareImage> {
+    let decoding_error = |e: png::DecodingError| Error::ReadImage(e.into());
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(decoding_error)?;
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(decoding_error)?;
+    buf.truncate(info.buffer_size());
+
+    let samples = if info.bit_depth == png::BitDepth::Sixteen {
+        buf.chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.0)
+            .collect::<Vec<_>>()
+    } else {
+        buf.iter().map(|&b| b as f32 / 255.0).collect::<Vec<_>>()
+    };
+
+    let channels = info.color_type.samples();
+    let color_channels = if channels < 3 { 1 } else { 3 };
+    let alpha_idx = (channels > color_channels).then_some(color_channels);
+    let mut image = CompareImage::from_interleaved(
+        info.width as usize,
+        info.height as usize,
+        &samples,
+        channels,
+        color_channels,
+        alpha_idx,
+    );
+
+    if let Some(source) = png_color_encoding(data, reader.info(), color_channels == 1)? {
+        tracing::debug!(?source, "Converting PNG image");
+        image.convert_color(&source, encoding)?;
+    }
+    Ok(image)
+}
+
+/// Reads color space information of PNG image.
+fn png_color_encoding(
+    data: &[u8],
+    info: &png::Info,
+    grayscale: bool,
+) -> Result<Option<ColorEncodingWithProfile>> {
+    let colour_space = if grayscale {
+        ColourSpace::Grey
+    } else {
+        ColourSpace::Rgb
+    };
+
+    if let Some(cicp) = find_png_chunk(data, *b"cICP") {
+        match cicp_encoding(cicp, colour_space) {
+            Some(encoding) => return Ok(Some(ColorEncodingWithProfile::new(encoding))),
+            None => tracing::warn!(?cicp, "Unsupported cICP chunk, ignoring"),
+        }
+    }
+
+    if let Some(icc) = &info.icc_profile {
+        let encoding =
+            ColorEncodingWithProfile::with_icc(icc).map_err(|e| Error::ReadImage(e.into()))?;
+        return Ok(Some(encoding));
+    }
+
+    if let Some(intent) = info.srgb {
+        let rendering_intent = match intent {
+            png::SrgbRenderingIntent::Perceptual => RenderingIntent::Perceptual,
+            png::SrgbRenderingIntent::RelativeColorimetric => RenderingIntent::Relative,
+            png::SrgbRenderingIntent::Saturation => RenderingIntent::Saturation,
+            png::SrgbRenderingIntent::AbsoluteColorimetric => RenderingIntent::Absolute,
+        };
+        let mut encoding = EnumColourEncoding::srgb(rendering_intent);
+        encoding.colour_space = colour_space;
+        return Ok(Some(ColorEncodingWithProfile::new(encoding)));
+    }
+
+    if info.source_gamma.is_none() && info.source_chromaticities.is_none() {
+        return Ok(None);
+    }
+
+    // Missing gAMA or cHRM defaults to sRGB.
+    let tf = match info.source_gamma {
+        // gAMA stores encoding exponent, same as the bitstream.
+        Some(gamma) => TransferFunction::Gamma {
+            g: gamma.into_scaled() * 100,
+            inverted: true,
+        },
+        None => TransferFunction::Srgb,
+    };
+    let (white_point, primaries) = match info.source_chromaticities {
+        Some(chrm) => {
+            let xy = |(x, y): (png::ScaledFloat, png::ScaledFloat)| Customxy {
+                x: x.into_scaled() as i32 * 10,
+                y: y.into_scaled() as i32 * 10,
+            };
+            (
+                WhitePoint::Custom(xy(chrm.white)),
+                Primaries::Custom {
+                    red: xy(chrm.red),
+                    green: xy(chrm.green),
+                    blue: xy(chrm.blue),
+                },
+            )
+        }
+        None => (WhitePoint::D65, Primaries::Srgb),
+    };
+    Ok(Some(ColorEncodingWithProfile::new(EnumColourEncoding {
+        colour_space,
+        white_point,
+        primaries,
+        tf,
+        rendering_intent: RenderingIntent::Relative,
+    })))
+}
+
+/// Finds the data of the first chunk of the given type before image data.
+fn find_png_chunk(data: &[u8], ty: [u8; 4]) -> Option<&[u8]> {
+    let mut pos = 8;
+    while let Some(header) = data.get(pos..pos + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_ty = &header[4..];
+        if chunk_ty == b"IDAT" {
+            return None;
+        }
+        let chunk = data.get(pos + 8..)?.get(..len)?;
+        if chunk_ty == ty {
+            return Some(chunk);
+        }
+        pos += 12 + len;
+    }
+    None
+}
+
+/// Maps coding-independent code points of full range RGB to enum color encoding.
+fn cicp_encoding(cicp: &[u8], colour_space: ColourSpace) -> Option<EnumColourEncoding> {
+    let &[primaries, tf, 0, 1] = cicp else {
+        return None;
+    };
+    let (white_point, primaries) = match primaries {
+        1 => (WhitePoint::D65, Primaries::Srgb),
+        9 => (WhitePoint::D65, Primaries::Bt2100),
+        11 => (WhitePoint::Dci, Primaries::P3),
+        12 => (WhitePoint::D65, Primaries::P3),
+        _ => return None,
+    };
+    let tf = match tf {
+        1 | 6 | 14 | 15 => TransferFunction::Bt709,
+        8 => TransferFunction::Linear,
+        13 => TransferFunction::Srgb,
+        16 => TransferFunction::Pq,
+        17 => TransferFunction::Dci,
+        18 => TransferFunction::Hlg,
+        _ => return None,
+    };
+    Some(EnumColourEncoding {
+        colour_space,
+        white_point,
+        primaries,
+        tf,
+        rendering_intent: RenderingIntent::Relative,
+    })
+}
+
+/// Loads Numpy array of 32-bit float samples, in the shape of `(frames, height, width, channels)`
+/// or `(height, width, channels)`.
+///
+/// If there are more channels than color channels, the first of them is treated as alpha. Numpy
+/// arrays don't have color space information, so samples are assumed to be already in the color
+/// space used for comparison.
+fn load_npy(data: &[u8], frame: usize) -> std::result::Result<CompareImage, String> {
+    let malformed = || String::from("malformed Numpy header");
+    if !data.starts_with(b"\x93NUMPY") {
+        return Err(malformed());
+    }
+    let header_len_bytes = |len: usize| data.get(8..8 + len).ok_or_else(malformed);
+    let (header_len, header_start) = match data.get(6) {
+        Some(1) => {
+            let b = header_len_bytes(2)?;
+            (u16::from_le_bytes([b[0], b[1]]) as usize, 10)
+        }
+        Some(2 | 3) => {
+            let b = header_len_bytes(4)?;
+            (u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize, 12)
+        }
+        _ => return Err(malformed()),
+    };
+    let header = data
+        .get(header_start..header_start + header_len)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or_else(malformed)?;
+    if !header.contains("'descr': '<f4'") || !header.contains("'fortran_order': False") {
+        return Err(String::from(
+            "only little endian float32 arrays in C order are supported",
+        ));
+    }
+
+    let shape = header
+        .split_once("'shape': (")
+        .and_then(|(_, shape)| shape.split_once(')'))
+        .ok_or_else(malformed)?
+        .0;
+    let shape = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| malformed())?;
+    let (frames, height, width, channels) = match *shape {
+        [frames, height, width, channels] => (frames, height, width, channels),
+        [height, width, channels] => (1, height, width, channels),
+        _ => return Err(format!("unsupported Numpy array shape {shape:?}")),
+    };
+    if channels == 0 {
+        return Err(String::from("Numpy array has no channels"));
+    }
+    if frame >= frames {
+        return Err(format!("keyframe {frame} is not available"));
+    }
+
+    let truncated = || String::from("Numpy array is truncated");
+    let frame_len = height
+        .checked_mul(width)
+        .and_then(|len| len.checked_mul(channels))
+        .and_then(|len| len.checked_mul(4))
+        .ok_or_else(truncated)?;
+    let body = &data[header_start + header_len..];
+    let frame_data = frame
+        .checked_mul(frame_len)
+        .and_then(|start| body.get(start..)?.get(..frame_len))
+        .ok_or_else(truncated)?;
+    let samples = frame_data
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<_>>();
+
+    let color_channels = if channels < 3 { 1 } else { 3 };
+    let alpha_idx = (channels > color_channels).then_some(color_channels);
+    Ok(CompareImage::from_interleaved(
+        width,
+        height,
+        &samples,
+        channels,
+        color_channels,
+        alpha_idx,
+    ))
+}
+
+fn channel_metrics(
+    name: &'static str,
+    a: &[f32],
+    b: &[f32],
+    width: usize,
+    height: usize,
+) -> ChannelMetrics {
+    let mut max_error = 0f64;
+    let mut sum_sq = 0f64;
+    for (&a, &b) in a.iter().zip(b) {
+        let diff = (a as f64 - b as f64).abs();
+        max_error = max_error.max(diff);
+        sum_sq += diff * diff;
+    }
+    let mse = sum_sq / a.len() as f64;
+    let psnr = if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        -10.0 * mse.log10()
+    };
+
+    ChannelMetrics {
+        name,
+        max_error,
+        mse,
+        psnr,
+        ssim: ssim(a, b, width, height),
+    }
+}
+
+/// Computes mean SSIM with 11x11 Gaussian window of standard deviation 1.5, assuming dynamic range
+/// of 1.0.
+fn ssim(a: &[f32], b: &[f32], width: usize, height: usize) -> f64 {
+    let product = |x: &[f32], y: &[f32]| x.iter().zip(y).map(|(x, y)| x * y).collect::<Vec<_>>();
+    let mu_a = gaussian_blur(a, width, height);
+    let mu_b = gaussian_blur(b, width, height);
+    let aa = gaussian_blur(&product(a, a), width, height);
+    let bb = gaussian_blur(&product(b, b), width, height);
+    let ab = gaussian_blur(&product(a, b), width, height);
+
+    let mut sum = 0f64;
+    for idx in 0..a.len() {
+        let mu_a = mu_a[idx] as f64;
+        let mu_b = mu_b[idx] as f64;
+        let var_a = aa[idx] as f64 - mu_a * mu_a;
+        let var_b = bb[idx] as f64 - mu_b * mu_b;
+        let cov = ab[idx] as f64 - mu_a * mu_b;
+        let numer = (2.0 * mu_a * mu_b + SSIM_C1) * (2.0 * cov + SSIM_C2);
+        let denom = (mu_a * mu_a + mu_b * mu_b + SSIM_C1) * (var_a + var_b + SSIM_C2);
+        sum += numer / denom;
+    }
+    sum / a.len() as f64
+}
+
+/// Applies separable 11-tap Gaussian blur, clamping coordinates at the edges.
+fn gaussian_blur(plane: &[f32], width: usize, height: usize) -> Vec<f32> {
+    const RADIUS: isize = 5;
+    let mut kernel = [0f32; 2 * RADIUS as usize + 1];
+    for (k, v) in kernel.iter_mut().enumerate() {
+        let x = k as f32 - RADIUS as f32;
+        *v = (-x * x / (2.0 * 1.5 * 1.5)).exp();
+    }
+    let kernel_sum = kernel.iter().sum::<f32>();
+    for v in &mut kernel {
+        *v /= kernel_sum;
+    }
+
+    let clamp = |v: isize, len: usize| v.clamp(0, len as isize - 1) as usize;
+    let mut horizontal = vec![0f32; plane.len()];
+    for y in 0..height {
+        let row = &plane[y * width..][..width];
+        for x in 0..width {
+            horizontal[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * row[clamp(x as isize + k as isize - RADIUS, width)])
+                .sum();
+        }
+    }
+
+    let mut out = vec![0f32; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            out[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| {
+                    let y = clamp(y as isize + k as isize - RADIUS, height);
+                    w * horizontal[y * width + x]
+                })
+                .sum();
+        }
+    }
+    out
+}
+
+/// Computes per-pixel weighted Euclidean distance in XYB.
+fn xyb_distance(
+    reference: &CompareImage,
+    distorted: &CompareImage,
+    encoding: &EnumColourEncoding,
+) -> Result<Vec<f32>> {
+    let to_xyb = |image: &CompareImage| -> Result<[Vec<f32>; 3]> {
+        let mut planes = if image.color.len() == 1 {
+            [
+                image.color[0].clone(),
+                image.color[0].clone(),
+                image.color[0].clone(),
+            ]
+        } else {
+            [
+                image.color[0].clone(),
+                image.color[1].clone(),
+                image.color[2].clone(),
+            ]
+        };
+        let [x, y, b] = &mut planes;
+        let mut encoding = encoding.clone();
+        if image.color.len() == 1 && encoding.colour_space == ColourSpace::Rgb {
+            encoding.colour_space = ColourSpace::Grey;
+        }
+        jxl_oxide::convert_to_xyb(&encoding, [x, y, b], XYB_INTENSITY_TARGET)
+            .map_err(|e| Error::Compare(format!("cannot convert to XYB: {e}")))?;
+        Ok(planes)
+    };
+
+    let reference = to_xyb(reference)?;
+    let distorted = to_xyb(distorted)?;
+    let len = reference[0].len();
+    let distance = (0..len)
+        .map(|idx| {
+            let sum_sq = (0..3)
+                .map(|c| {
+                    let diff = (reference[c][idx] - distorted[c][idx]) * XYB_WEIGHTS[c];
+                    diff * diff
+                })
+                .sum::<f32>();
+            sum_sq.sqrt()
+        })
+        .collect();
+    Ok(distance)
+}
+
+/// Maps distance to color; black at 0, blue, green and yellow in between, and red at 2 or above.
+fn heatmap_color(distance: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 255.0],
+        [0.0, 255.0, 0.0],
+        [255.0, 255.0, 0.0],
+        [255.0, 0.0, 0.0],
+    ];
+    let t = (distance / 2.0).clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let idx = (t as usize).min(STOPS.len() - 2);
+    let frac = t - idx as f32;
+    let [from, to] = [STOPS[idx], STOPS[idx + 1]];
+    std::array::from_fn(|c| (from[c] + (to[c] - from[c]) * frac + 0.5) as u8)
+}
+
+fn write_heatmap<W: Write>(
+    output: W,
+    distance: &[f32],
+    width: usize,
+    height: usize,
+) -> std::io::Result<()> {
+    let mut encoder = png::Encoder::new(output, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_srgb(png::SrgbRenderingIntent::RelativeColorimetric);
+    let mut writer = encoder.write_header()?;
+    let buf = distance
+        .iter()
+        .flat_map(|&d| heatmap_color(d))
+        .collect::<Vec<_>>();
+    writer.write_image_data(&buf)?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn npy(header: &str, samples: &[f32]) -> Vec<u8> {
+        let mut data = b"\x93NUMPY\x01\x00".to_vec();
+        data.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        data.extend_from_slice(header.as_bytes());
+        for sample in samples {
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+        data
+    }
+
+    /// Encodes a row of 8-bit grayscale samples, writing `chunks` before image data.
+    fn png(bytes: &[u8], gamma: Option<u32>, srgb: bool, chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, bytes.len() as u32, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        if let Some(gamma) = gamma {
+            encoder.set_source_gamma(png::ScaledFloat::from_scaled(gamma));
+        }
+        if srgb {
+            encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+        }
+        let mut writer = encoder.write_header().unwrap();
+        for &(&ty, chunk) in chunks {
+            writer
+                .write_chunk(png::chunk::ChunkType(ty), chunk)
+                .unwrap();
+        }
+        writer.write_image_data(bytes).unwrap();
+        writer.finish().unwrap();
+        data
+    }
+
+    /// Compares samples, allowing errors of fast math transfer functions.
+    fn assert_samples(actual: &[f32], expected: impl IntoIterator<Item = f32>) {
+        for (&actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+        }
+    }
+
+    fn srgb_to_linear(v: f32) -> f32 {
+        if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn linear_to_srgb(v: f32) -> f32 {
+        if v <= 0.0031308 {
+            v * 12.92
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    #[test]
+    fn npy_frames() {
+        let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 1, 2, 2), }\n";
+        let data = npy(header, &[0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7]);
+
+        let image = load_npy(&data, 1).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.color, [vec![0.4, 0.6]]);
+        assert_eq!(image.alpha, Some(vec![0.5, 0.7]));
+
+        assert!(load_npy(&data, 2).is_err());
+    }
+
+    #[test]
+    fn npy_malformed() {
+        let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (1, 2, 3), }\n";
+        let data = npy(header, &[0.0; 6]);
+        load_npy(&data, 0).unwrap();
+
+        // Truncated header
+        for len in [0, 6, 7, 8, 9, 12] {
+            assert!(load_npy(&data[..len], 0).is_err(), "length {len}");
+        }
+        assert!(load_npy(b"\x93NUMPY\x02\x00\x10\x00", 0).is_err());
+        // Truncated body
+        assert!(load_npy(&data[..data.len() - 1], 0).is_err());
+        // Wrong magic
+        let mut wrong_magic = data.clone();
+        wrong_magic[1] = b'n';
+        assert!(load_npy(&wrong_magic, 0).is_err());
+
+        let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (1, 2, 0), }\n";
+        assert!(load_npy(&npy(header, &[]), 0).is_err());
+        let header =
+            "{'descr': '<f4', 'fortran_order': False, 'shape': (4294967296, 4294967296, 3), }\n";
+        assert!(load_npy(&npy(header, &[]), 0).is_err());
+    }
+
+    #[test]
+    fn png_color_space() {
+        let linear = EnumColourEncoding::srgb_linear(RenderingIntent::Relative);
+        let srgb = EnumColourEncoding::srgb(RenderingIntent::Relative);
+        let bytes = [0u8, 64, 128, 255];
+        let encoded = bytes.map(|b| b as f32 / 255.0);
+
+        // Untagged images are assumed to be in the target color space.
+        let image = load_png(&png(&bytes, None, false, &[]), &linear).unwrap();
+        assert_eq!(image.color, [encoded.to_vec()]);
+
+        let image = load_png(&png(&bytes, Some(45455), false, &[]), &linear).unwrap();
+        assert_eq!(image.color.len(), 1);
+        assert_samples(&image.color[0], encoded.map(|v| v.powf(1.0 / 0.45455)));
+
+        let image = load_png(&png(&bytes, None, true, &[]), &linear).unwrap();
+        assert_samples(&image.color[0], encoded.map(srgb_to_linear));
+
+        // cICP takes precedence over sRGB.
+        let cicp = png(&bytes, None, true, &[(b"cICP", &[1, 8, 0, 1])]);
+        let image = load_png(&cicp, &linear).unwrap();
+        assert_samples(&image.color[0], encoded);
+        let image = load_png(&cicp, &srgb).unwrap();
+        assert_samples(&image.color[0], encoded.map(linear_to_srgb));
+    }
+
+    #[test]
+    fn metrics_identical() {
+        let plane = (0..64 * 64)
+            .map(|idx| (idx % 64) as f32 / 64.0)
+            .collect::<Vec<_>>();
+        let m = channel_metrics("Y", &plane, &plane, 64, 64);
+        assert_eq!(m.max_error, 0.0);
+        assert_eq!(m.mse, 0.0);
+        assert_eq!(m.psnr, f64::INFINITY);
+        assert!((m.ssim - 1.0).abs() < 1e-6, "{}", m.ssim);
+    }
+
+    #[test]
+    fn metrics_offset() {
+        let a = vec![0.25f32; 32 * 32];
+        let b = vec![0.35f32; 32 * 32];
+        let m = channel_metrics("Y", &a, &b, 32, 32);
+        assert!((m.max_error - 0.1).abs() < 1e-6);
+        assert!((m.mse - 0.01).abs() < 1e-6);
+        assert!((m.psnr - 20.0).abs() < 1e-3, "{}", m.psnr);
+
+        // Flat images only have luminance term.
+        let expected = (2.0 * 0.25 * 0.35 + SSIM_C1) / (0.25 * 0.25 + 0.35 * 0.35 + SSIM_C1);
+        assert!((m.ssim - expected).abs() < 1e-4, "{} != {expected}", m.ssim);
+    }
+}
@@ -1,36 +1,125 @@
-use std::time::Duration;
+use std::{
+    io::prelude::*,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use jxl_oxide::{AllocTracker, CropInfo, EnumColourEncoding, JxlImage, JxlThreadPool, Render};
+use jxl_oxide::{
+    color::{ColourEncoding, ColourSpace, RenderingIntent},
+    icc::IccSynthesisOptions,
+    image::BitDepth,
+    AllocTracker, CropInfo, EnumColourEncoding, InitializeResult, JxlImage, JxlImageBuilder,
+    JxlThreadPool, Render,
+};
 
 use crate::commands::decode::*;
 use crate::{output, Error, Result};
 
+mod batch;
+
+/// Size of chunks read from the input and fed into the decoder.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
 pub fn handle_decode(args: DecodeArgs) -> Result<()> {
     let _guard = tracing::trace_span!("Handle decode subcommand").entered();
 
     #[cfg(feature = "rayon")]
     let pool = JxlThreadPool::rayon(args.num_threads);
     #[cfg(not(feature = "rayon"))]
-    let pool = JxlThreadPool::none();
+    let pool = JxlThreadPool::std_threads(args.num_threads);
+
+    if batch::is_batch(&args) {
+        return batch::handle_batch(&args, &pool);
+    }
+
+    if args.progressive && args.output.as_deref().is_some_and(is_stdio) {
+        return Err(Error::WriteImage(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--progressive cannot be used when writing to stdout",
+        )));
+    }
+    if args.frames == FramesMode::Each && args.output.as_deref().is_some_and(is_stdio) {
+        return Err(Error::WriteImage(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--frames each cannot be used when writing to stdout",
+        )));
+    }
+
+    decode_file(&args, &args.input[0], args.output.as_deref(), &pool)?;
+    Ok(())
+}
 
+/// Decodes an image, writing it to `output` if given, and returns the number of pixels in the
+/// decoded region.
+fn decode_file(
+    args: &DecodeArgs,
+    input: &Path,
+    output: Option<&Path>,
+    pool: &JxlThreadPool,
+) -> Result<u64> {
     let mut image_builder = JxlImage::builder()
         .pool(pool.clone())
         .lz77_mode(args.lz77_mode.into());
-    if args.approx_memory_limit != 0 {
-        let tracker = AllocTracker::with_limit(args.approx_memory_limit);
-        image_builder = image_builder.alloc_tracker(tracker);
-    }
-    let mut image = image_builder.open(&args.input).map_err(Error::ReadJxl)?;
-    if !image.is_loading_done() {
-        tracing::warn!("Partial image");
+    let tracker =
+        (args.approx_memory_limit != 0).then(|| AllocTracker::with_limit(args.approx_memory_limit));
+    if let Some(tracker) = &tracker {
+        image_builder = image_builder.alloc_tracker(tracker.clone());
     }
+    let mut reader = open_input(input)?;
+    let mut buf = vec![0u8; READ_CHUNK_SIZE];
+    let mut image = init_image(image_builder, &mut *reader, &mut buf)?;
 
-    let output_png = args.output.is_some()
+    let output_rgb = output.is_some()
         && matches!(
             args.output_format,
-            OutputFormat::Png | OutputFormat::Png8 | OutputFormat::Png16
+            OutputFormat::Png
+                | OutputFormat::Png8
+                | OutputFormat::Png16
+                | OutputFormat::Ppm
+                | OutputFormat::Pam
+                | OutputFormat::Pfm
         );
-    if let Some(icc_path) = &args.target_icc {
+    let output_exr = matches!(
+        args.output_format,
+        OutputFormat::Exr | OutputFormat::ExrFloat
+    );
+    let mut exr_attributes = None;
+    if output_exr {
+        if args.target_icc.is_some() {
+            tracing::warn!("--target-icc is ignored for OpenEXR output");
+        }
+
+        let image_meta = &image.image_header().metadata;
+        let mut encoding = match (&args.target_colorspace, &image_meta.colour_encoding) {
+            (Some(encoding), _) => encoding.clone(),
+            (None, ColourEncoding::Enum(encoding)) => encoding.clone(),
+            (None, ColourEncoding::IccProfile(_)) => {
+                tracing::info!("Input has ICC profile; writing OpenEXR in linear sRGB");
+                EnumColourEncoding::srgb_linear(RenderingIntent::Relative)
+            }
+        };
+        if !matches!(encoding.colour_space, ColourSpace::Rgb | ColourSpace::Grey) {
+            tracing::warn!("Unsupported color space for OpenEXR output; using linear sRGB");
+            encoding = EnumColourEncoding::srgb_linear(encoding.rendering_intent);
+        }
+
+        let intensity_target = image_meta.tone_mapping.intensity_target;
+        let is_hdr = intensity_target > 255.0
+            || matches!(
+                &image_meta.colour_encoding,
+                ColourEncoding::Enum(encoding) if encoding.is_hdr()
+            );
+        let white_luminance =
+            args.exr_white_luminance
+                .unwrap_or(if is_hdr { 203.0 } else { intensity_target });
+        let [red, green, blue] = encoding.primaries.as_chromaticity();
+        let white = encoding.white_point.as_chromaticity();
+
+        tracing::debug!(?encoding, white_luminance, "Setting extended linear output");
+        image.request_extended_linear(encoding, white_luminance);
+        exr_attributes = Some(([red, green, blue, white], white_luminance));
+    } else if let Some(icc_path) = &args.target_icc {
         tracing::debug!("Setting target ICC profile");
         let icc_profile = std::fs::read(icc_path).map_err(Error::ReadIcc)?;
         match image.request_icc(&icc_profile) {
@@ -42,12 +131,26 @@ pub fn handle_decode(args: DecodeArgs) -> Result<()> {
     } else if let Some(encoding) = &args.target_colorspace {
         tracing::debug!(?encoding, "Setting target color space");
         image.request_color_encoding(encoding.clone());
-    } else if output_png && image.pixel_format().has_black() {
+    } else if output_rgb && image.pixel_format().has_black() {
         tracing::debug!("Input is CMYK; setting target color encoding to sRGB");
-        image.request_color_encoding(EnumColourEncoding::srgb(
-            jxl_oxide::color::RenderingIntent::Relative,
-        ));
+        image.request_color_encoding(EnumColourEncoding::srgb(RenderingIntent::Relative));
+    }
+    let output_cmyk_unsupported = matches!(
+        args.output_format,
+        OutputFormat::Png
+            | OutputFormat::Png8
+            | OutputFormat::Png16
+            | OutputFormat::Ppm
+            | OutputFormat::Pfm
+    );
+    if output.is_some() && output_cmyk_unsupported && image.pixel_format().has_black() {
+        return Err(Error::WriteImage(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "CMYK target profile requires PAM, TIFF or raw output",
+        )));
     }
+    image.set_hlg_display_luminance(args.hlg_display_luminance);
+    image.set_hlg_scene_referred(args.hlg_scene_referred);
 
     let image_meta = &image.image_header().metadata;
     tracing::info!("Image dimension: {}x{}", image.width(), image.height());
@@ -91,13 +194,44 @@ pub fn handle_decode(args: DecodeArgs) -> Result<()> {
         top: 0,
     });
     let CropInfo { width, height, .. } = crop_region;
-    let total_pixels = width * height;
-    let mps = total_pixels as f64 / 1e6;
+    let pixels = width as u64 * height as u64;
+    let mps = pixels as f64 / 1e6;
 
-    if args.output_format == OutputFormat::Npy {
+    if matches!(
+        args.output_format,
+        OutputFormat::Npy
+            | OutputFormat::Raw
+            | OutputFormat::Tiff
+            | OutputFormat::Tiff8
+            | OutputFormat::Tiff16
+            | OutputFormat::Tiff32
+            | OutputFormat::TiffFloat
+    ) || (output_exr && args.exr_extra_channels)
+    {
         image.set_render_spot_color(false);
     }
 
+    image.set_image_region(crop_region);
+    load_image(
+        args,
+        &mut image,
+        &mut *reader,
+        &mut buf,
+        output.filter(|_| args.progressive),
+        exr_attributes,
+        width,
+        height,
+    )?;
+    if !image.is_loading_done() {
+        tracing::warn!("Partial image");
+    }
+
+    let keyframe_range = if let Some(frame) = args.frame {
+        frame..frame.saturating_add(1)
+    } else {
+        args.frame_range.clone().unwrap_or(0..usize::MAX)
+    };
+
     let keyframes = if let Some(num_reps @ 2..) = args.num_reps {
         tracing::info!("Running {num_reps} repetitions");
 
@@ -105,11 +239,11 @@ pub fn handle_decode(args: DecodeArgs) -> Result<()> {
         for _ in 0..num_reps - 1 {
             // Resets internal cache
             image.set_image_region(crop_region);
-            let (_, elapsed) = run_once(&mut image)?;
+            let (_, elapsed) = run_once(&mut image, keyframe_range.clone())?;
             durations.push(elapsed);
         }
         image.set_image_region(crop_region);
-        let (keyframes, elapsed) = run_once(&mut image)?;
+        let (keyframes, elapsed) = run_once(&mut image, keyframe_range.clone())?;
         durations.push(elapsed);
 
         let min = durations.iter().min().unwrap().as_secs_f64();
@@ -135,7 +269,7 @@ pub fn handle_decode(args: DecodeArgs) -> Result<()> {
         keyframes
     } else {
         image.set_image_region(crop_region);
-        let (keyframes, elapsed) = run_once(&mut image)?;
+        let (keyframes, elapsed) = run_once(&mut image, keyframe_range.clone())?;
         let elapsed_seconds = elapsed.as_secs_f64();
         tracing::info!(
             "Took {:.2} ms ({:.2} MP/s)",
@@ -145,78 +279,378 @@ pub fn handle_decode(args: DecodeArgs) -> Result<()> {
         keyframes
     };
 
-    if let Some(output) = &args.output {
+    if keyframes.is_empty() {
+        let num_keyframes = image.num_loaded_keyframes();
+        if let Some(frame) = args.frame {
+            return Err(Error::Render(
+                format!("keyframe {frame} is out of range, image has {num_keyframes} keyframes")
+                    .into(),
+            ));
+        }
+        if let Some(range) = &args.frame_range {
+            return Err(Error::Render(
+                format!(
+                    "keyframe range starts at {}, image has {num_keyframes} keyframes",
+                    range.start
+                )
+                .into(),
+            ));
+        }
+    }
+
+    if let Some(tracker) = &tracker {
+        let stats = tracker.stats();
+        tracing::info!(
+            "Peak tracked memory: {} bytes ({} allocations)",
+            stats.total.peak_bytes,
+            stats.total.num_allocs,
+        );
+        for (category, category_stats) in stats.categories() {
+            if category_stats.num_allocs == 0 {
+                continue;
+            }
+            tracing::debug!(
+                ?category,
+                peak_bytes = category_stats.peak_bytes,
+                current_bytes = category_stats.current_bytes,
+                num_allocs = category_stats.num_allocs,
+            );
+        }
+    }
+
+    if let Some(output) = output {
         if keyframes.is_empty() {
             tracing::warn!("No keyframes are decoded");
-            return Ok(());
+            return Ok(pixels);
         }
 
         tracing::debug!(output_format = format_args!("{:?}", args.output_format));
-        let pixel_format = image.pixel_format();
-        let output = std::fs::File::create(output).map_err(Error::WriteImage)?;
-        match args.output_format {
-            OutputFormat::Png => {
-                let force_bit_depth = if let Some(encoding) = &args.target_colorspace {
-                    if encoding.is_srgb_gamut() {
-                        Some(png::BitDepth::Eight)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-
-                output::write_png(
-                    output,
+        if args.frames == FramesMode::Each {
+            let mut paths = Vec::with_capacity(keyframes.len());
+            for keyframe in &keyframes {
+                let path = frame_output_path(output, &keyframe.keyframe_index().to_string());
+                tracing::debug!(?path, "Writing keyframe");
+                write_output(
+                    args,
                     &image,
-                    &keyframes,
-                    pixel_format,
-                    force_bit_depth,
+                    std::slice::from_ref(keyframe),
+                    &path,
+                    exr_attributes,
                     width,
                     height,
-                )
-                .map_err(Error::WriteImage)?;
+                )?;
+                paths.push(path);
             }
-            OutputFormat::Png8 => {
-                output::write_png(
-                    output,
-                    &image,
-                    &keyframes,
-                    pixel_format,
-                    Some(png::BitDepth::Eight),
-                    width,
-                    height,
-                )
+
+            let description_path = frame_output_path(output, "frames").with_extension("json");
+            let description = output::frames_description(&image, &keyframes, &paths);
+            tracing::debug!(path = ?description_path, "Writing frame description");
+            std::fs::write(description_path, format!("{description:#}\n"))
                 .map_err(Error::WriteImage)?;
+        } else {
+            write_output(
+                args,
+                &image,
+                &keyframes,
+                output,
+                exr_attributes,
+                width,
+                height,
+            )?;
+        }
+    } else {
+        tracing::info!("No output path specified, skipping output encoding");
+    };
+
+    Ok(pixels)
+}
+
+/// Writes keyframes into a single file in the requested output format.
+fn write_output(
+    args: &DecodeArgs,
+    image: &JxlImage,
+    keyframes: &[Render],
+    output_path: &Path,
+    exr_attributes: Option<([[f32; 2]; 4], f32)>,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    let pixel_format = image.pixel_format();
+    let mut icc_options = IccSynthesisOptions::default();
+    icc_options.version = args.icc_version.into();
+    icc_options.hdr_lut = args.hdr_icc_lut;
+    let mut output = create_output(output_path)?;
+    match args.output_format {
+        OutputFormat::Png => {
+            let force_bit_depth = if let Some(encoding) = &args.target_colorspace {
+                if encoding.is_srgb_gamut() {
+                    Some(png::BitDepth::Eight)
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+            output::write_png(
+                &mut output,
+                image,
+                keyframes,
+                pixel_format,
+                force_bit_depth,
+                icc_options,
+                width,
+                height,
+            )
+            .map_err(Error::WriteImage)?;
+        }
+        OutputFormat::Png8 => {
+            output::write_png(
+                &mut output,
+                image,
+                keyframes,
+                pixel_format,
+                Some(png::BitDepth::Eight),
+                icc_options,
+                width,
+                height,
+            )
+            .map_err(Error::WriteImage)?;
+        }
+        OutputFormat::Png16 => {
+            output::write_png(
+                &mut output,
+                image,
+                keyframes,
+                pixel_format,
+                Some(png::BitDepth::Sixteen),
+                icc_options,
+                width,
+                height,
+            )
+            .map_err(Error::WriteImage)?;
+        }
+        OutputFormat::Npy => {
+            if args.icc_output.is_none() {
+                tracing::warn!("--icc-output is not set. Numpy buffer alone cannot be used to display image as its colorspace is unknown.");
             }
-            OutputFormat::Png16 => {
-                output::write_png(
-                    output,
-                    &image,
-                    &keyframes,
-                    pixel_format,
-                    Some(png::BitDepth::Sixteen),
-                    width,
-                    height,
-                )
+
+            output::write_npy(&mut output, keyframes, width, height).map_err(Error::WriteImage)?;
+        }
+        OutputFormat::Ppm | OutputFormat::Pam => {
+            let sixteen_bits = image.image_header().metadata.bit_depth.bits_per_sample() > 8;
+            let pam = args.output_format == OutputFormat::Pam;
+            output::write_pnm(&mut output, keyframes, pixel_format, pam, sixteen_bits)
                 .map_err(Error::WriteImage)?;
+        }
+        OutputFormat::Pfm => {
+            output::write_pfm(&mut output, keyframes, pixel_format).map_err(Error::WriteImage)?;
+        }
+        OutputFormat::Raw => {
+            if args.icc_output.is_none() {
+                tracing::warn!("--icc-output is not set. Raw buffer alone cannot be used to display image as its colorspace is unknown.");
             }
-            OutputFormat::Npy => {
-                if args.icc_output.is_none() {
-                    tracing::warn!("--icc-output is not set. Numpy buffer alone cannot be used to display image as its colorspace is unknown.");
-                }
 
-                output::write_npy(output, &keyframes, width, height).map_err(Error::WriteImage)?;
+            output::write_raw(&mut output, keyframes, args.raw_layout)
+                .map_err(Error::WriteImage)?;
+
+            if is_stdio(output_path) {
+                tracing::info!("Writing to stdout, skipping raw output description");
+                return Ok(());
             }
+            let mut description_path = output_path.as_os_str().to_owned();
+            description_path.push(".json");
+            let description = output::raw_description(image, keyframes, args.raw_layout);
+            tracing::debug!(path = ?description_path, "Writing raw output description");
+            std::fs::write(description_path, format!("{description:#}\n"))
+                .map_err(Error::WriteImage)?;
         }
-    } else {
-        tracing::info!("No output path specified, skipping output encoding");
-    };
+        OutputFormat::Tiff
+        | OutputFormat::Tiff8
+        | OutputFormat::Tiff16
+        | OutputFormat::Tiff32
+        | OutputFormat::TiffFloat => {
+            let sample_format = match args.output_format {
+                OutputFormat::Tiff8 => output::TiffSampleFormat::U8,
+                OutputFormat::Tiff16 => output::TiffSampleFormat::U16,
+                OutputFormat::Tiff32 => output::TiffSampleFormat::U32,
+                OutputFormat::TiffFloat => output::TiffSampleFormat::F32,
+                _ => match image.image_header().metadata.bit_depth {
+                    BitDepth::FloatSample { .. } => output::TiffSampleFormat::F32,
+                    bit_depth if bit_depth.bits_per_sample() > 8 => output::TiffSampleFormat::U16,
+                    _ => output::TiffSampleFormat::U8,
+                },
+            };
+            let icc = image.rendered_icc_with_options(icc_options);
+            output::write_tiff(&mut output, keyframes, sample_format, &icc)
+                .map_err(Error::WriteImage)?;
+        }
+        OutputFormat::Exr | OutputFormat::ExrFloat => {
+            let pixel_type = if args.output_format == OutputFormat::ExrFloat {
+                output::ExrPixelType::Float
+            } else {
+                output::ExrPixelType::Half
+            };
+            let (chromaticities, white_luminance) = exr_attributes.unwrap();
+            output::write_exr(
+                &mut output,
+                keyframes,
+                pixel_type,
+                chromaticities,
+                white_luminance,
+                args.exr_extra_channels,
+            )
+            .map_err(Error::WriteImage)?;
+        }
+    }
+
+    output.flush().map_err(Error::WriteImage)?;
+    Ok(())
+}
+
+/// Returns whether `path` refers to stdin or stdout.
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+fn open_input(input: &Path) -> Result<Box<dyn Read>> {
+    if is_stdio(input) {
+        tracing::debug!("Reading from stdin");
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    let file = std::fs::File::open(input).map_err(|e| Error::ReadJxl(e.into()))?;
+    Ok(Box::new(file))
+}
+
+fn create_output(path: &Path) -> Result<Box<dyn Write>> {
+    if is_stdio(path) {
+        tracing::debug!("Writing to stdout");
+        return Ok(Box::new(std::io::BufWriter::new(std::io::stdout().lock())));
+    }
+    let file = std::fs::File::create(path).map_err(Error::WriteImage)?;
+    Ok(Box::new(std::io::BufWriter::new(file)))
+}
+
+fn read_chunk(reader: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+    loop {
+        match reader.read(buf) {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            result => return result.map_err(|e| Error::ReadJxl(e.into())),
+        }
+    }
+}
+
+/// Feeds the input into the decoder until the image header is parsed.
+fn init_image(builder: JxlImageBuilder, reader: &mut dyn Read, buf: &mut [u8]) -> Result<JxlImage> {
+    let mut uninit_image = builder.build_uninit();
+    loop {
+        let count = read_chunk(reader, buf)?;
+        if count == 0 {
+            return Err(Error::ReadJxl(
+                "input ended before parsing image header".into(),
+            ));
+        }
+
+        uninit_image
+            .feed_bytes(&buf[..count])
+            .map_err(Error::ReadJxl)?;
+        match uninit_image.try_init().map_err(Error::ReadJxl)? {
+            InitializeResult::NeedMoreData(image) => uninit_image = image,
+            InitializeResult::Initialized(image) => return Ok(image),
+        }
+    }
+}
+
+/// Feeds the rest of the input into the decoder.
+///
+/// If `progressive_output` is given, keyframes are rendered and written to it each time a new
+/// progressive stage or keyframe is loaded. The fully loaded image is left to the caller.
+#[allow(clippy::too_many_arguments)]
+fn load_image(
+    args: &DecodeArgs,
+    image: &mut JxlImage,
+    reader: &mut dyn Read,
+    buf: &mut [u8],
+    progressive_output: Option<&Path>,
+    exr_attributes: Option<([[f32; 2]; 4], f32)>,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    let mut last_stage = (image.num_loaded_keyframes(), image.loading_frame_stage());
+    while !image.is_loading_done() {
+        let count = read_chunk(reader, buf)?;
+        if count == 0 {
+            break;
+        }
+        image.feed_bytes(&buf[..count]).map_err(Error::ReadJxl)?;
+
+        let Some(output) = progressive_output else {
+            continue;
+        };
+        let stage = (image.num_loaded_keyframes(), image.loading_frame_stage());
+        if stage == last_stage || image.is_loading_done() {
+            continue;
+        }
+
+        let render = if stage.0 != last_stage.0 {
+            image.render_frame_cropped(stage.0 - 1)
+        } else {
+            image.render_loading_frame_cropped()
+        };
+        last_stage = stage;
+        let render = match render {
+            Ok(render) => render,
+            Err(e) => {
+                tracing::debug!(%e, "Cannot render loaded data yet");
+                continue;
+            }
+        };
 
+        let keyframe_index = render.keyframe_index();
+        tracing::info!(
+            keyframe_index,
+            stage = stage.1,
+            "Writing progressive output"
+        );
+        let path = if args.frames == FramesMode::Each {
+            frame_output_path(output, &keyframe_index.to_string())
+        } else {
+            output.to_owned()
+        };
+        write_output(
+            args,
+            image,
+            std::slice::from_ref(&render),
+            &path,
+            exr_attributes,
+            width,
+            height,
+        )?;
+    }
     Ok(())
 }
 
-fn run_once(image: &mut JxlImage) -> Result<(Vec<Render>, Duration)> {
+/// Returns the output path of the keyframe, replacing `{index}` in `pattern` with `index`.
+///
+/// If `pattern` doesn't contain `{index}`, `-{index}` is appended to the file stem.
+fn frame_output_path(pattern: &Path, index: &str) -> PathBuf {
+    if let Some(pattern) = pattern.to_str().filter(|s| s.contains("{index}")) {
+        return PathBuf::from(pattern.replace("{index}", index));
+    }
+
+    let mut file_name = pattern.file_stem().unwrap_or_default().to_owned();
+    file_name.push("-");
+    file_name.push(index);
+    if let Some(ext) = pattern.extension() {
+        file_name.push(".");
+        file_name.push(ext);
+    }
+    pattern.with_file_name(file_name)
+}
+
+fn run_once(image: &mut JxlImage, frames: Range<usize>) -> Result<(Vec<Render>, Duration)> {
+    let num_keyframes = image.num_loaded_keyframes();
+    let loaded_frames = frames.start.min(num_keyframes)..frames.end.min(num_keyframes);
     let mut keyframes = Vec::new();
     #[allow(unused_mut)]
     let mut rendered = false;
@@ -228,7 +662,8 @@ fn run_once(image: &mut JxlImage) -> Result<(Vec<Render>, Duration)> {
             .install(|| {
                 use rayon::prelude::*;
 
-                (0..image.num_loaded_keyframes())
+                loaded_frames
+                    .clone()
                     .into_par_iter()
                     .map(|idx| image.render_frame_cropped(idx))
                     .collect::<std::result::Result<Vec<_>, _>>()
@@ -238,19 +673,131 @@ fn run_once(image: &mut JxlImage) -> Result<(Vec<Render>, Duration)> {
     }
 
     if !rendered {
-        for idx in 0..image.num_loaded_keyframes() {
-            let frame = image
-                .render_frame_cropped(idx)
-                .expect("rendering frames failed");
+        for idx in loaded_frames {
+            let frame = image.render_frame_cropped(idx).map_err(Error::Render)?;
             keyframes.push(frame);
         }
     }
 
-    if let Ok(frame) = image.render_loading_frame_cropped() {
-        tracing::warn!("Rendered partially loaded frame");
-        keyframes.push(frame);
+    if frames.contains(&num_keyframes) {
+        if let Ok(frame) = image.render_loading_frame_cropped() {
+            tracing::warn!("Rendered partially loaded frame");
+            keyframes.push(frame);
+        }
     }
 
     let elapsed = decode_start.elapsed();
     Ok((keyframes, elapsed))
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::test_util::{temp_dir, SMALL_IMAGE};
+
+    fn decode(dir: &Path, extra_args: &[&str]) -> Result<()> {
+        let input = dir.join("image.jxl");
+        std::fs::write(&input, SMALL_IMAGE).unwrap();
+        let output = dir.join("out.pam");
+
+        let mut args = vec![
+            "decode".as_ref(),
+            input.as_os_str(),
+            "-o".as_ref(),
+            output.as_os_str(),
+            "-f".as_ref(),
+            "pam".as_ref(),
+        ];
+        args.extend(extra_args.iter().map(std::ffi::OsStr::new));
+        handle_decode(DecodeArgs::parse_from(args))
+    }
+
+    #[test]
+    fn frame_in_range() {
+        let dir = temp_dir("decode-frame-in-range");
+        decode(&dir, &["--frame", "0"]).unwrap();
+        assert!(dir.join("out.pam").exists());
+        decode(&dir, &["--frame-range", "0-5"]).unwrap();
+    }
+
+    #[test]
+    fn frame_out_of_range() {
+        let dir = temp_dir("decode-frame-out-of-range");
+        let err = decode(&dir, &["--frame", "1"]).unwrap_err();
+        assert!(matches!(err, Error::Render(_)));
+        assert!(!dir.join("out.pam").exists());
+
+        let err = decode(&dir, &["--frame-range", "1-"]).unwrap_err();
+        assert!(matches!(err, Error::Render(_)));
+        assert!(!dir.join("out.pam").exists());
+    }
+
+    #[test]
+    fn progressive_stdout() {
+        let dir = temp_dir("decode-progressive-stdout");
+        let input = dir.join("image.jxl");
+        std::fs::write(&input, SMALL_IMAGE).unwrap();
+
+        let args = DecodeArgs::parse_from([
+            "decode".as_ref(),
+            input.as_os_str(),
+            "-o".as_ref(),
+            "-".as_ref(),
+            "--progressive".as_ref(),
+        ]);
+        let err = handle_decode(args).unwrap_err();
+        assert!(matches!(err, Error::WriteImage(_)));
+    }
+
+    #[test]
+    fn cmyk_target_icc_png() {
+        let dir = temp_dir("decode-cmyk-target-icc");
+        let input = dir.join("image.jxl");
+        std::fs::write(&input, SMALL_IMAGE).unwrap();
+        // sRGB profile marked as CMYK, enough for the pixel format to report CMYK.
+        let mut icc = include_bytes!("../../jxl-color/src/icc/test-profiles/srgb-rel.icc").to_vec();
+        icc[0x10..0x14].copy_from_slice(b"CMYK");
+        let icc_path = dir.join("cmyk.icc");
+        std::fs::write(&icc_path, icc).unwrap();
+        let output = dir.join("out.png");
+
+        let args = DecodeArgs::parse_from([
+            "decode".as_ref(),
+            input.as_os_str(),
+            "-o".as_ref(),
+            output.as_os_str(),
+            "--target-icc".as_ref(),
+            icc_path.as_os_str(),
+        ]);
+        let err = handle_decode(args).unwrap_err();
+        assert!(matches!(err, Error::WriteImage(_)));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn frames_each_stdout() {
+        let dir = temp_dir("decode-frames-each-stdout");
+        let input = dir.join("image.jxl");
+        std::fs::write(&input, SMALL_IMAGE).unwrap();
+
+        let args = DecodeArgs::parse_from([
+            "decode".as_ref(),
+            input.as_os_str(),
+            "-o".as_ref(),
+            "-".as_ref(),
+            "--frames".as_ref(),
+            "each".as_ref(),
+        ]);
+        let err = handle_decode(args).unwrap_err();
+        assert!(matches!(err, Error::WriteImage(_)));
+    }
+
+    #[test]
+    fn progressive_file() {
+        let dir = temp_dir("decode-progressive-file");
+        decode(&dir, &["--progressive"]).unwrap();
+        assert!(dir.join("out.pam").exists());
+    }
+}
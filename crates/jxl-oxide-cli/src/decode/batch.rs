@@ -0,0 +1,334 @@
+use std::collections::HashSet;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+
+use jxl_oxide::JxlThreadPool;
+
+use crate::commands::decode::*;
+use crate::{Error, Result};
+
+struct BatchJob {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    result: Option<Result<u64>>,
+}
+
+/// Returns whether the arguments request decoding in batch.
+pub(super) fn is_batch(args: &DecodeArgs) -> bool {
+    args.input.len() > 1 || args.output_dir.is_some() || args.input.iter().any(|p| p.is_dir())
+}
+
+/// Decodes every input, scheduling files on the thread pool.
+pub(super) fn handle_batch(args: &DecodeArgs, pool: &JxlThreadPool) -> Result<()> {
+    if args.output.is_some() || args.icc_output.is_some() {
+        return Err(Error::Batch(String::from(
+            "--output and --icc-output cannot be used with multiple inputs; use --output-dir",
+        )));
+    }
+    if args.input.iter().any(|input| super::is_stdio(input)) {
+        return Err(Error::Batch(String::from(
+            "stdin cannot be used with multiple inputs",
+        )));
+    }
+    if args.output_dir.is_none() {
+        tracing::info!("No output directory specified, skipping output encoding");
+    }
+
+    let mut jobs = plan_jobs(args)?;
+
+    let num_files = jobs.len();
+    if num_files == 0 {
+        tracing::warn!("No JPEG XL images found");
+        return Ok(());
+    }
+    tracing::info!("Decoding {num_files} files");
+
+    let decode_start = std::time::Instant::now();
+    pool.for_each_mut_slice(&mut jobs, |job| {
+        let _guard = tracing::trace_span!("Decode file", input = %job.input.display()).entered();
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            if let Some(parent) = job.output.as_deref().and_then(Path::parent) {
+                std::fs::create_dir_all(parent).map_err(Error::WriteImage)?;
+            }
+            super::decode_file(args, &job.input, job.output.as_deref(), pool)
+        }))
+        .unwrap_or_else(|_| Err(Error::Render("decoder panicked".into())));
+
+        if let Err(e) = &result {
+            tracing::error!("{}: {e}", job.input.display());
+        }
+        job.result = Some(result);
+    });
+    let elapsed_seconds = decode_start.elapsed().as_secs_f64();
+
+    let mut pixels = 0u64;
+    let mut failed = Vec::new();
+    for job in &jobs {
+        match job.result.as_ref().unwrap() {
+            Ok(job_pixels) => pixels += job_pixels,
+            Err(e) => failed.push((&job.input, e)),
+        }
+    }
+
+    let mps = pixels as f64 / 1e6;
+    tracing::info!(
+        "Decoded {} of {} files in {:.2} s ({:.2} MP, {:.2} MP/s)",
+        num_files - failed.len(),
+        num_files,
+        elapsed_seconds,
+        mps,
+        mps / elapsed_seconds,
+    );
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    tracing::error!("Failed to decode {} files:", failed.len());
+    for (input, e) in &failed {
+        tracing::error!("  {}: {e}", input.display());
+    }
+    Err(Error::Batch(format!(
+        "{} of {num_files} files failed",
+        failed.len()
+    )))
+}
+
+/// Lists input files with their output paths, mirroring directory structure of the inputs.
+fn plan_jobs(args: &DecodeArgs) -> Result<Vec<BatchJob>> {
+    let mut jobs = Vec::new();
+    let mut output_paths = HashSet::new();
+    for input in &args.input {
+        let files = if input.is_dir() {
+            let mut files = Vec::new();
+            collect_files(input, Path::new(""), args.recursive, &mut files)
+                .map_err(|e| Error::ReadJxl(e.into()))?;
+            files.sort();
+            files
+        } else {
+            vec![(input.clone(), PathBuf::new())]
+        };
+
+        for (input, relative_dir) in files {
+            let output = args.output_dir.as_ref().map(|output_dir| {
+                let name = output_name(&input, &args.name_pattern, args.output_format);
+                output_dir.join(relative_dir).join(name)
+            });
+            if let Some(output) = &output {
+                if !output_paths.insert(output.clone()) {
+                    return Err(Error::Batch(format!(
+                        "output path {} is used by multiple inputs",
+                        output.display(),
+                    )));
+                }
+            }
+            jobs.push(BatchJob {
+                input,
+                output,
+                result: None,
+            });
+        }
+    }
+    Ok(jobs)
+}
+
+/// Collects JPEG XL files in `dir`, along with their directory relative to the input directory.
+fn collect_files(
+    dir: &Path,
+    relative_dir: &Path,
+    recursive: bool,
+    files: &mut Vec<(PathBuf, PathBuf)>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if recursive {
+                collect_files(&path, &relative_dir.join(entry.file_name()), true, files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("jxl"))
+        {
+            files.push((path, relative_dir.to_owned()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the output file name of `input`, by replacing placeholders in `pattern`.
+fn output_name(input: &Path, pattern: &str, format: OutputFormat) -> String {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    pattern
+        .replace("{stem}", &stem)
+        .replace("{ext}", format_extension(format))
+}
+
+fn format_extension(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Png | OutputFormat::Png8 | OutputFormat::Png16 => "png",
+        OutputFormat::Npy => "npy",
+        OutputFormat::Ppm => "pnm",
+        OutputFormat::Pam => "pam",
+        OutputFormat::Pfm => "pfm",
+        OutputFormat::Raw => "raw",
+        OutputFormat::Tiff
+        | OutputFormat::Tiff8
+        | OutputFormat::Tiff16
+        | OutputFormat::Tiff32
+        | OutputFormat::TiffFloat => "tiff",
+        OutputFormat::Exr | OutputFormat::ExrFloat => "exr",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::test_util::{self, SMALL_IMAGE};
+
+    /// Creates a temporary directory with files at the given relative paths.
+    fn temp_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = test_util::temp_dir(&format!("batch-{name}"));
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, SMALL_IMAGE).unwrap();
+        }
+        dir
+    }
+
+    fn parse_args(args: &[&OsStr]) -> DecodeArgs {
+        DecodeArgs::parse_from([OsStr::new("decode")].iter().chain(args))
+    }
+
+    fn collect(dir: &Path, recursive: bool) -> Vec<(PathBuf, PathBuf)> {
+        let mut files = Vec::new();
+        collect_files(dir, Path::new(""), recursive, &mut files).unwrap();
+        files.sort();
+        files
+            .into_iter()
+            .map(|(path, relative_dir)| (path.strip_prefix(dir).unwrap().to_owned(), relative_dir))
+            .collect()
+    }
+
+    #[test]
+    fn output_names() {
+        let input = Path::new("images/photo.final.JXL");
+        assert_eq!(
+            output_name(input, "{stem}.{ext}", OutputFormat::Png16),
+            "photo.final.png"
+        );
+        assert_eq!(
+            output_name(input, "{stem}-{stem}.{ext}", OutputFormat::Tiff32),
+            "photo.final-photo.final.tiff"
+        );
+        assert_eq!(
+            output_name(input, "out.{ext}", OutputFormat::ExrFloat),
+            "out.exr"
+        );
+        assert_eq!(
+            output_name(Path::new("noext"), "{stem}.{ext}", OutputFormat::Ppm),
+            "noext.pnm"
+        );
+    }
+
+    #[test]
+    fn collect_recursive() {
+        let dir = temp_dir(
+            "collect",
+            &["a.jxl", "b.JXL", "c.png", "sub/d.jxl", "sub/deep/e.jxl"],
+        );
+
+        assert_eq!(
+            collect(&dir, false),
+            [
+                (PathBuf::from("a.jxl"), PathBuf::new()),
+                (PathBuf::from("b.JXL"), PathBuf::new()),
+            ]
+        );
+        assert_eq!(
+            collect(&dir, true),
+            [
+                (PathBuf::from("a.jxl"), PathBuf::new()),
+                (PathBuf::from("b.JXL"), PathBuf::new()),
+                (Path::new("sub").join("d.jxl"), PathBuf::from("sub")),
+                (
+                    Path::new("sub").join("deep").join("e.jxl"),
+                    Path::new("sub").join("deep"),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn mirror_directories() {
+        let dir = temp_dir("mirror", &["in/a.jxl", "in/sub/b.jxl", "single.jxl"]);
+        let output_dir = dir.join("out");
+        let args = parse_args(&[
+            dir.join("in").as_os_str(),
+            dir.join("single.jxl").as_os_str(),
+            "--output-dir".as_ref(),
+            output_dir.as_os_str(),
+            "-r".as_ref(),
+            "-f".as_ref(),
+            "pam".as_ref(),
+        ]);
+
+        let mut outputs = plan_jobs(&args)
+            .unwrap()
+            .into_iter()
+            .map(|job| job.output.unwrap())
+            .collect::<Vec<_>>();
+        outputs.sort();
+        let expected = [
+            output_dir.join("a.pam"),
+            output_dir.join("single.pam"),
+            output_dir.join("sub").join("b.pam"),
+        ];
+        assert_eq!(outputs, expected);
+
+        handle_batch(&args, &JxlThreadPool::none()).unwrap();
+        for output in &expected {
+            assert!(output.is_file(), "{} is missing", output.display());
+        }
+    }
+
+    #[test]
+    fn duplicate_outputs() {
+        let dir = temp_dir("duplicate", &["a/x.jxl", "b/x.jxl"]);
+        let output_dir = dir.join("out");
+        let args = parse_args(&[
+            dir.join("a/x.jxl").as_os_str(),
+            dir.join("b/x.jxl").as_os_str(),
+            "--output-dir".as_ref(),
+            output_dir.as_os_str(),
+        ]);
+        assert!(matches!(plan_jobs(&args), Err(Error::Batch(_))));
+        assert!(matches!(
+            handle_batch(&args, &JxlThreadPool::none()),
+            Err(Error::Batch(_))
+        ));
+        assert!(!output_dir.exists());
+
+        // Input directories are not mirrored, so files with the same stem conflict.
+        let args = parse_args(&[
+            dir.join("a").as_os_str(),
+            dir.join("b").as_os_str(),
+            "--output-dir".as_ref(),
+            output_dir.as_os_str(),
+            "--name-pattern".as_ref(),
+            "{stem}-decoded.{ext}".as_ref(),
+        ]);
+        assert!(matches!(plan_jobs(&args), Err(Error::Batch(_))));
+
+        // Without output directory, nothing is written and nothing conflicts.
+        let args = parse_args(&[dir.join("a").as_os_str(), dir.join("b").as_os_str()]);
+        assert_eq!(plan_jobs(&args).unwrap().len(), 2);
+    }
+}
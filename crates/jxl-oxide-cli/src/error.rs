@@ -3,9 +3,12 @@
 pub enum Error {
     ReadJxl(Box<dyn std::error::Error + Send + Sync + 'static>),
     ReadIcc(std::io::Error),
+    ReadImage(Box<dyn std::error::Error + Send + Sync + 'static>),
     WriteIcc(std::io::Error),
     WriteImage(std::io::Error),
     Render(Box<dyn std::error::Error + Send + Sync + 'static>),
+    Compare(String),
+    Batch(String),
 }
 
 impl std::fmt::Display for Error {
@@ -13,9 +16,12 @@ impl std::fmt::Display for Error {
         match self {
             Error::ReadJxl(e) => write!(f, "failed reading JPEG XL image: {e}"),
             Error::ReadIcc(e) => write!(f, "failed reading ICC profile: {e}"),
+            Error::ReadImage(e) => write!(f, "failed reading image: {e}"),
             Error::WriteIcc(e) => write!(f, "failed writing ICC profile: {e}"),
             Error::WriteImage(e) => write!(f, "failed writing output image: {e}"),
             Error::Render(e) => write!(f, "failed to render image: {e}"),
+            Error::Compare(e) => write!(f, "cannot compare images: {e}"),
+            Error::Batch(e) => write!(f, "batch decoding failed: {e}"),
         }
     }
 }
@@ -25,9 +31,11 @@ impl std::error::Error for Error {
         match self {
             Error::ReadJxl(e) => Some(&**e),
             Error::ReadIcc(e) => Some(e),
+            Error::ReadImage(e) => Some(&**e),
             Error::WriteIcc(e) => Some(e),
             Error::WriteImage(e) => Some(e),
             Error::Render(e) => Some(&**e),
+            Error::Compare(_) | Error::Batch(_) => None,
         }
     }
 }
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use jxl_oxide::{
+    color::RenderingIntent, icc::IccSynthesisOptions, BitstreamKind, ContainerBoxType,
+    ContainerDetectingReader, EnumColourEncoding, JxlImage,
+};
+
+use crate::commands::extract::*;
+use crate::{output, Error, Result};
+
+pub fn handle_extract(args: ExtractArgs) -> Result<()> {
+    let _guard = tracing::trace_span!("Handle extract subcommand").entered();
+
+    let bytes = std::fs::read(&args.input).map_err(|e| Error::ReadJxl(e.into()))?;
+
+    // Read the whole file, as metadata boxes may come after the codestream.
+    let mut reader = ContainerDetectingReader::new();
+    reader
+        .feed_bytes(&bytes)
+        .map_err(|e| Error::ReadJxl(e.into()))?;
+    let codestream = reader.take_bytes();
+    reader.finish();
+    if reader.kind() == BitstreamKind::Invalid {
+        return Err(Error::ReadJxl("not a JPEG XL image".into()));
+    }
+
+    let stem = args
+        .input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("image"));
+    let mut writer = ExtractWriter {
+        output: args.output.clone(),
+        stem,
+        counts: HashMap::new(),
+    };
+    std::fs::create_dir_all(&args.output).map_err(Error::WriteImage)?;
+
+    let selected = |part: ExtractPart| args.parts.is_empty() || args.parts.contains(&part);
+
+    for (ty, data) in reader.aux_boxes() {
+        let (ty, data) = if *ty == ContainerBoxType::BROTLI_COMPRESSED {
+            match decompress_brob(data) {
+                Ok((inner_ty, data)) => {
+                    tracing::debug!(ty = %box_type_name(inner_ty), "Decompressed brob box");
+                    (inner_ty, std::borrow::Cow::Owned(data))
+                }
+                Err(e) => {
+                    tracing::warn!(%e, "Failed to decompress brob box");
+                    (*ty, std::borrow::Cow::Borrowed(&**data))
+                }
+            }
+        } else {
+            (*ty, std::borrow::Cow::Borrowed(&**data))
+        };
+
+        match ty {
+            ContainerBoxType::EXIF if selected(ExtractPart::Exif) => {
+                // Exif box starts with the offset to TIFF header.
+                let tiff = data
+                    .get(..4)
+                    .map(|offset| u32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]))
+                    .and_then(|offset| data.get(4 + offset as usize..));
+                let data = if let Some(tiff) = tiff {
+                    tiff
+                } else {
+                    tracing::warn!("Malformed Exif box; writing box contents as-is");
+                    &*data
+                };
+                writer.write("exif", data)?;
+            }
+            ContainerBoxType::XML if selected(ExtractPart::Xmp) => {
+                writer.write("xmp", &data)?;
+            }
+            ContainerBoxType::JUMBF if selected(ExtractPart::Jumbf) => {
+                writer.write("jumbf", &data)?;
+            }
+            ContainerBoxType::EXIF | ContainerBoxType::XML | ContainerBoxType::JUMBF => {}
+            ty if selected(ExtractPart::Boxes) => {
+                let suffix = format!("box-{}.bin", box_type_name(ty));
+                writer.write(&suffix, &data)?;
+            }
+            _ => {}
+        }
+    }
+
+    if selected(ExtractPart::Codestream) {
+        writer.write("codestream.jxl", &codestream)?;
+    }
+
+    if selected(ExtractPart::Icc) || selected(ExtractPart::Preview) {
+        let mut image = JxlImage::builder().read(&*bytes).map_err(Error::ReadJxl)?;
+
+        if selected(ExtractPart::Icc) {
+            if let Some(icc) = image.original_icc() {
+                writer.write_icc("original.icc", icc)?;
+            } else {
+                tracing::info!("No embedded ICC profile");
+            }
+            writer.write_icc("rendered.icc", &image.rendered_icc())?;
+        }
+
+        if selected(ExtractPart::Preview) {
+            if image.pixel_format().has_black() {
+                tracing::debug!("Input is CMYK; setting target color encoding to sRGB");
+                image.request_color_encoding(EnumColourEncoding::srgb(RenderingIntent::Relative));
+            }
+
+            match image.render_preview().map_err(Error::Render)? {
+                Some(preview) => {
+                    let stream = preview.stream();
+                    let (width, height) = (stream.width(), stream.height());
+                    tracing::info!("Preview dimension: {width}x{height}");
+
+                    let path = writer.next_path("preview.png");
+                    let output = std::fs::File::create(&path).map_err(Error::WriteImage)?;
+                    output::write_png(
+                        output,
+                        &image,
+                        std::slice::from_ref(&preview),
+                        image.pixel_format(),
+                        None,
+                        IccSynthesisOptions::default(),
+                        width,
+                        height,
+                    )
+                    .map_err(Error::WriteImage)?;
+                    tracing::info!(path = %path.display(), "Extracted");
+                }
+                None => tracing::info!("No preview frame"),
+            }
+        }
+    }
+
+    if writer.counts.is_empty() {
+        tracing::info!("Nothing to extract");
+    }
+
+    Ok(())
+}
+
+/// Decompresses the contents of `brob` box, returning the original box type and contents.
+fn decompress_brob(data: &[u8]) -> std::io::Result<(ContainerBoxType, Vec<u8>)> {
+    let [t0, t1, t2, t3, ref compressed @ ..] = *data else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "brob box too small",
+        ));
+    };
+    let ty = ContainerBoxType([t0, t1, t2, t3]);
+    if ty == ContainerBoxType::BROTLI_COMPRESSED
+        || ty == ContainerBoxType::CODESTREAM
+        || ty == ContainerBoxType::PARTIAL_CODESTREAM
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "invalid box type in brob box",
+        ));
+    }
+
+    let mut decompressed = Vec::new();
+    brotli_decompressor::Decompressor::new(compressed, 4096).read_to_end(&mut decompressed)?;
+    Ok((ty, decompressed))
+}
+
+/// Returns the box type as a string usable in file names.
+fn box_type_name(ty: ContainerBoxType) -> String {
+    String::from_utf8_lossy(&ty.0)
+        .trim_end()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+struct ExtractWriter {
+    output: PathBuf,
+    stem: String,
+    counts: HashMap<String, usize>,
+}
+
+impl ExtractWriter {
+    /// Returns the path of the next file with the suffix, numbering files with the same suffix.
+    fn next_path(&mut self, suffix: &str) -> PathBuf {
+        let count = self.counts.entry(suffix.to_owned()).or_default();
+        let file_name = if *count == 0 {
+            format!("{}.{suffix}", self.stem)
+        } else {
+            format!("{}-{count}.{suffix}", self.stem)
+        };
+        *count += 1;
+        self.output.join(file_name)
+    }
+
+    fn write(&mut self, suffix: &str, data: &[u8]) -> Result<()> {
+        let path = self.next_path(suffix);
+        write_file(&path, data).map_err(Error::WriteImage)
+    }
+
+    fn write_icc(&mut self, suffix: &str, data: &[u8]) -> Result<()> {
+        let path = self.next_path(suffix);
+        write_file(&path, data).map_err(Error::WriteIcc)
+    }
+}
+
+fn write_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, data)?;
+    tracing::info!(path = %path.display(), bytes = data.len(), "Extracted");
+    Ok(())
+}
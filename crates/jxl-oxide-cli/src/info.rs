@@ -1,4 +1,8 @@
-use jxl_oxide::{color::*, frame::*, ColorEncodingWithProfile, JxlImage};
+use jxl_oxide::{
+    color::*, frame::*, icc, image::BitDepth, BitstreamKind, ExtraChannelType, JxlImage,
+    TocGroupKind,
+};
+use serde_json::{json, Value};
 
 use crate::{commands::info::*, Error, Result};
 
@@ -8,6 +12,16 @@ pub fn handle_info(args: InfoArgs) -> Result<()> {
     let image = JxlImage::builder()
         .open(&args.input)
         .map_err(Error::ReadJxl)?;
+
+    match args.format {
+        InfoFormat::Text => print_info_text(&image, &args),
+        InfoFormat::Json => println!("{:#}", info_json(&image, &args)),
+    }
+
+    Ok(())
+}
+
+fn print_info_text(image: &JxlImage, args: &InfoArgs) {
     let image_size = &image.image_header().size;
     let image_meta = &image.image_header().metadata;
     let image_reader = image.reader();
@@ -55,9 +69,29 @@ pub fn handle_info(args: InfoArgs) -> Result<()> {
                 println!("Embedded ICC profile ({} bytes)", icc.len());
             }
 
-            if let Ok(encoding) = ColorEncodingWithProfile::with_icc(icc) {
-                if let ColourEncoding::Enum(encoding) = encoding.encoding() {
-                    print_colour_encoding(encoding, "      ");
+            match icc::inspect_icc(icc) {
+                Ok(inspection) => {
+                    if let Some(description) = inspection.description() {
+                        println!("      Description: {description}");
+                    }
+                    for (signature, e) in inspection.tag_errors() {
+                        println!(
+                            "      Invalid tag `{}`: {e}",
+                            String::from_utf8_lossy(signature)
+                        );
+                    }
+                }
+                Err(e) => println!("      Malformed ICC profile: {e}"),
+            }
+
+            if let Ok(matched) = icc::match_enum_encoding(icc, icc::IccMatchTolerance::APPROXIMATE)
+            {
+                print_colour_encoding(&matched.encoding, "      ");
+                if matched.trc_error > 0.0 || matched.chromaticity_error > 0.0 {
+                    println!(
+                        "      Approximated; max error of tone curves {:.6}, chromaticity {:.6}",
+                        matched.trc_error, matched.chromaticity_error
+                    );
                 }
             }
         }
@@ -170,8 +204,6 @@ pub fn handle_info(args: InfoArgs) -> Result<()> {
     if !image.is_loading_done() {
         println!("Partial file");
     }
-
-    Ok(())
 }
 
 fn print_colour_encoding(encoding: &EnumColourEncoding, indent: &str) {
@@ -228,3 +260,433 @@ fn print_colour_encoding(encoding: &EnumColourEncoding, indent: &str) {
         TransferFunction::Hlg => println!("Hybrid log-gamma (HDR)"),
     }
 }
+
+/// Builds the JSON representation of the image.
+///
+/// The schema is meant to be stable; add fields instead of changing existing ones.
+fn info_json(image: &JxlImage, args: &InfoArgs) -> Value {
+    let image_size = &image.image_header().size;
+    let image_meta = &image.image_header().metadata;
+    let image_reader = image.reader();
+
+    let kind = match image_reader.kind() {
+        BitstreamKind::Unknown => "unknown",
+        BitstreamKind::BareCodestream => "bare_codestream",
+        BitstreamKind::Container => "container",
+        BitstreamKind::Invalid => "invalid",
+    };
+
+    let colour_encoding = match &image_meta.colour_encoding {
+        ColourEncoding::Enum(colour_encoding) => {
+            let mut value = enum_colour_encoding_json(colour_encoding);
+            value["type"] = json!("enum");
+            value
+        }
+        ColourEncoding::IccProfile(colour_space) => {
+            let icc = image.original_icc().unwrap();
+            let description = icc::inspect_icc(icc)
+                .ok()
+                .and_then(|inspection| inspection.description().map(String::from));
+            let matched = icc::match_enum_encoding(icc, icc::IccMatchTolerance::APPROXIMATE)
+                .ok()
+                .map(|matched| {
+                    let mut value = enum_colour_encoding_json(&matched.encoding);
+                    value["trc_error"] = json!(matched.trc_error);
+                    value["chromaticity_error"] = json!(matched.chromaticity_error);
+                    value
+                });
+            json!({
+                "type": "icc",
+                "grayscale": *colour_space == ColourSpace::Grey,
+                "icc_size": icc.len(),
+                "description": description,
+                "matched_encoding": matched,
+            })
+        }
+    };
+
+    let extra_channels = image_meta
+        .ec_info
+        .iter()
+        .map(|ec| {
+            let mut value = json!({
+                "type": extra_channel_type_name(ec.ty),
+                "name": &*ec.name,
+                "bit_depth": bit_depth_json(ec.bit_depth),
+                "dim_shift": ec.dim_shift,
+            });
+            match ec.ty {
+                ExtraChannelType::Alpha { alpha_associated } => {
+                    value["alpha_associated"] = json!(alpha_associated);
+                }
+                ExtraChannelType::SpotColour {
+                    red,
+                    green,
+                    blue,
+                    solidity,
+                } => {
+                    value["spot_colour"] = json!([red, green, blue]);
+                    value["solidity"] = json!(solidity);
+                }
+                ExtraChannelType::Cfa { cfa_channel } => {
+                    value["cfa_channel"] = json!(cfa_channel);
+                }
+                _ => {}
+            }
+            value
+        })
+        .collect::<Vec<_>>();
+
+    let tone_mapping = &image_meta.tone_mapping;
+    let image_json = json!({
+        "width": image.width(),
+        "height": image.height(),
+        "encoded_width": image_size.width,
+        "encoded_height": image_size.height,
+        "orientation": image_meta.orientation,
+        "intrinsic_size": image_meta.intrinsic_size.as_ref().map(|size| json!({
+            "width": size.width,
+            "height": size.height,
+        })),
+        "bit_depth": bit_depth_json(image_meta.bit_depth),
+        "xyb_encoded": image_meta.xyb_encoded,
+        "colour_encoding": colour_encoding,
+        "extra_channels": extra_channels,
+        "animation": image_meta.animation.as_ref().map(|animation| json!({
+            "tps_numerator": animation.tps_numerator,
+            "tps_denominator": animation.tps_denominator,
+            "num_loops": animation.num_loops,
+            "have_timecodes": animation.have_timecodes,
+        })),
+        "tone_mapping": {
+            "intensity_target": tone_mapping.intensity_target,
+            "min_nits": tone_mapping.min_nits,
+            "relative_to_max_display": tone_mapping.relative_to_max_display,
+            "linear_below": tone_mapping.linear_below,
+        },
+        "preview": image_meta.preview.as_ref().map(|preview| json!({
+            "width": preview.width,
+            "height": preview.height,
+        })),
+    });
+
+    let animated = image_meta.animation.is_some();
+    let mut frames = Vec::new();
+    for idx in 0..image.num_loaded_frames() + 1 {
+        let Some(frame) = image.frame(idx) else {
+            break;
+        };
+        let frame_header = frame.header();
+        let is_keyframe = frame_header.is_keyframe();
+        if !args.all_frames && !is_keyframe {
+            continue;
+        }
+
+        let frame_type = match frame_header.frame_type {
+            FrameType::RegularFrame => "regular",
+            FrameType::LfFrame => "lf",
+            FrameType::ReferenceOnly => "reference_only",
+            FrameType::SkipProgressive => "skip_progressive",
+        };
+        let encoding = match frame_header.encoding {
+            Encoding::VarDct => "vardct",
+            Encoding::Modular => "modular",
+        };
+        let blending_info = &frame_header.blending_info;
+        let blend_mode = match blending_info.mode {
+            BlendMode::Replace => "replace",
+            BlendMode::Add => "add",
+            BlendMode::Blend => "blend",
+            BlendMode::MulAdd => "mul_add",
+            BlendMode::Mul => "mul",
+        };
+        let passes = &frame_header.passes;
+
+        let toc = frame.toc();
+        let groups = toc
+            .iter_bitstream_order()
+            .map(|group| {
+                let mut value = json!({
+                    "kind": "",
+                    "offset": group.offset,
+                    "size": group.size,
+                });
+                let kind = match group.kind {
+                    TocGroupKind::All => "all",
+                    TocGroupKind::LfGlobal => "lf_global",
+                    TocGroupKind::LfGroup(lf_group_idx) => {
+                        value["lf_group_idx"] = json!(lf_group_idx);
+                        "lf_group"
+                    }
+                    TocGroupKind::HfGlobal => "hf_global",
+                    TocGroupKind::GroupPass {
+                        pass_idx,
+                        group_idx,
+                    } => {
+                        value["pass_idx"] = json!(pass_idx);
+                        value["group_idx"] = json!(group_idx);
+                        "pass_group"
+                    }
+                };
+                value["kind"] = json!(kind);
+                value
+            })
+            .collect::<Vec<_>>();
+
+        frames.push(json!({
+            "index": idx,
+            "keyframe": is_keyframe,
+            "partial": !frame.is_loading_done(),
+            "name": &*frame_header.name,
+            "type": frame_type,
+            "encoding": encoding,
+            "lf_level": frame_header.lf_level,
+            "save_as_reference": frame_header.save_as_reference,
+            "width": frame_header.color_sample_width(),
+            "height": frame_header.color_sample_height(),
+            "x0": frame_header.x0,
+            "y0": frame_header.y0,
+            "upsampling": frame_header.upsampling,
+            "do_ycbcr": frame_header.do_ycbcr,
+            "jpeg_upsampling": frame_header.jpeg_upsampling,
+            "blending": {
+                "mode": blend_mode,
+                "alpha_channel": blending_info.alpha_channel,
+                "clamp": blending_info.clamp,
+                "source": blending_info.source,
+            },
+            "duration": (animated && frame_header.frame_type.is_normal_frame())
+                .then_some(frame_header.duration),
+            "is_last": frame_header.is_last,
+            "passes": {
+                "num_passes": passes.num_passes,
+                "shift": passes.shift,
+                "downsample": passes.downsample,
+                "last_pass": passes.last_pass,
+            },
+            "offset": image.frame_offset(idx),
+            "header_size": toc.bookmark(),
+            "data_size": toc.total_byte_size(),
+            "groups": groups,
+        }));
+    }
+
+    let boxes = image_reader
+        .boxes()
+        .iter()
+        .map(|info| {
+            json!({
+                "type": String::from_utf8_lossy(&info.box_type().0),
+                "offset": info.offset(),
+                "header_size": info.header_size(),
+                "size": info.size(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "kind": kind,
+        "partial": !image.is_loading_done(),
+        "image": image_json,
+        "frames": frames,
+        "boxes": boxes,
+    })
+}
+
+pub(crate) fn extra_channel_type_name(ty: ExtraChannelType) -> &'static str {
+    match ty {
+        ExtraChannelType::Alpha { .. } => "alpha",
+        ExtraChannelType::Depth => "depth",
+        ExtraChannelType::SpotColour { .. } => "spot_colour",
+        ExtraChannelType::SelectionMask => "selection_mask",
+        ExtraChannelType::Black => "black",
+        ExtraChannelType::Cfa { .. } => "cfa",
+        ExtraChannelType::Thermal => "thermal",
+        ExtraChannelType::NonOptional => "non_optional",
+        ExtraChannelType::Optional => "optional",
+    }
+}
+
+fn bit_depth_json(bit_depth: BitDepth) -> Value {
+    match bit_depth {
+        BitDepth::IntegerSample { bits_per_sample } => json!({
+            "type": "integer",
+            "bits_per_sample": bits_per_sample,
+        }),
+        BitDepth::FloatSample {
+            bits_per_sample,
+            exp_bits,
+        } => json!({
+            "type": "float",
+            "bits_per_sample": bits_per_sample,
+            "exp_bits": exp_bits,
+        }),
+    }
+}
+
+fn enum_colour_encoding_json(encoding: &EnumColourEncoding) -> Value {
+    let colour_space = match encoding.colour_space {
+        ColourSpace::Rgb => "rgb",
+        ColourSpace::Grey => "grey",
+        ColourSpace::Xyb => "xyb",
+        ColourSpace::Unknown => "unknown",
+    };
+
+    let white_point = match encoding.white_point {
+        WhitePoint::D65 => json!({ "name": "d65" }),
+        WhitePoint::Custom(xy) => json!({ "name": "custom", "xy": xy.as_float() }),
+        WhitePoint::E => json!({ "name": "e" }),
+        WhitePoint::Dci => json!({ "name": "dci" }),
+    };
+
+    let primaries = match encoding.primaries {
+        Primaries::Srgb => json!({ "name": "srgb" }),
+        Primaries::Custom { red, green, blue } => json!({
+            "name": "custom",
+            "red": red.as_float(),
+            "green": green.as_float(),
+            "blue": blue.as_float(),
+        }),
+        Primaries::Bt2100 => json!({ "name": "bt2100" }),
+        Primaries::P3 => json!({ "name": "p3" }),
+    };
+
+    let transfer_function = match encoding.tf {
+        TransferFunction::Gamma { g, inverted: false } => {
+            json!({ "name": "gamma", "gamma": g as f64 / 1e7 })
+        }
+        TransferFunction::Gamma { g, inverted: true } => {
+            json!({ "name": "gamma", "gamma": 1e7 / g as f64 })
+        }
+        TransferFunction::Bt709 => json!({ "name": "bt709" }),
+        TransferFunction::Unknown => json!({ "name": "unknown" }),
+        TransferFunction::Linear => json!({ "name": "linear" }),
+        TransferFunction::Srgb => json!({ "name": "srgb" }),
+        TransferFunction::Pq => json!({ "name": "pq" }),
+        TransferFunction::Dci => json!({ "name": "dci" }),
+        TransferFunction::Hlg => json!({ "name": "hlg" }),
+    };
+
+    let rendering_intent = match encoding.rendering_intent {
+        RenderingIntent::Perceptual => "perceptual",
+        RenderingIntent::Relative => "relative",
+        RenderingIntent::Saturation => "saturation",
+        RenderingIntent::Absolute => "absolute",
+    };
+
+    json!({
+        "colour_space": colour_space,
+        "white_point": white_point,
+        "primaries": primaries,
+        "transfer_function": transfer_function,
+        "rendering_intent": rendering_intent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::test_util::SMALL_IMAGE;
+
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut keys = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn json_schema() {
+        let image = JxlImage::builder().read(SMALL_IMAGE).unwrap();
+        let args = InfoArgs::parse_from(["info", "image.jxl", "--format", "json"]);
+        let info = info_json(&image, &args);
+
+        assert_eq!(keys(&info), ["boxes", "frames", "image", "kind", "partial"]);
+        assert_eq!(info["kind"], "bare_codestream");
+        assert_eq!(info["partial"], false);
+        assert_eq!(info["boxes"], json!([]));
+
+        let image_json = &info["image"];
+        assert_eq!(
+            keys(image_json),
+            [
+                "animation",
+                "bit_depth",
+                "colour_encoding",
+                "encoded_height",
+                "encoded_width",
+                "extra_channels",
+                "height",
+                "intrinsic_size",
+                "orientation",
+                "preview",
+                "tone_mapping",
+                "width",
+                "xyb_encoded",
+            ]
+        );
+        assert_eq!(image_json["width"], 240);
+        assert_eq!(image_json["height"], 135);
+        assert_eq!(image_json["orientation"], 1);
+        assert_eq!(image_json["animation"], Value::Null);
+        assert_eq!(image_json["colour_encoding"]["type"], "enum");
+        assert_eq!(
+            keys(&image_json["colour_encoding"]),
+            [
+                "colour_space",
+                "primaries",
+                "rendering_intent",
+                "transfer_function",
+                "type",
+                "white_point",
+            ]
+        );
+
+        let frames = info["frames"].as_array().unwrap();
+        assert_eq!(frames.len(), 1);
+        let frame = &frames[0];
+        assert_eq!(
+            keys(frame),
+            [
+                "blending",
+                "data_size",
+                "do_ycbcr",
+                "duration",
+                "encoding",
+                "groups",
+                "header_size",
+                "height",
+                "index",
+                "is_last",
+                "jpeg_upsampling",
+                "keyframe",
+                "lf_level",
+                "name",
+                "offset",
+                "partial",
+                "passes",
+                "save_as_reference",
+                "type",
+                "upsampling",
+                "width",
+                "x0",
+                "y0",
+            ]
+        );
+        assert_eq!(frame["index"], 0);
+        assert_eq!(frame["keyframe"], true);
+        assert_eq!(frame["is_last"], true);
+        assert_eq!(frame["encoding"], "modular");
+        let groups = frame["groups"].as_array().unwrap();
+        assert!(!groups.is_empty());
+        for group in groups {
+            assert!(group["kind"].is_string());
+        }
+    }
+}
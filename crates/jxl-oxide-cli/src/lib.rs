@@ -1,13 +1,18 @@
 pub mod commands;
+pub mod compare;
 pub mod decode;
 pub mod error;
+pub mod extract;
 #[cfg(feature = "__devtools")]
 pub mod generate_fixture;
 pub mod info;
 #[cfg(feature = "__devtools")]
 pub mod progressive;
+pub mod tiles;
 
 mod output;
+#[cfg(test)]
+mod test_util;
 
 pub use commands::{Args, Subcommands};
 pub use error::Error;
@@ -24,6 +24,7 @@ fn main() -> std::process::ExitCode {
         tracing_subscriber::fmt()
             .with_span_events(tracing_subscriber::fmt::format::FmtSpan::ACTIVE)
             .with_env_filter(env_filter)
+            .with_writer(std::io::stderr)
             .init();
     }
 
@@ -31,6 +32,9 @@ fn main() -> std::process::ExitCode {
         Some(Subcommands::Decode(args)) => jxl_oxide_cli::decode::handle_decode(args),
         None => jxl_oxide_cli::decode::handle_decode(decode.unwrap()),
         Some(Subcommands::Info(args)) => jxl_oxide_cli::info::handle_info(args),
+        Some(Subcommands::Tiles(args)) => jxl_oxide_cli::tiles::handle_tiles(args),
+        Some(Subcommands::Compare(args)) => jxl_oxide_cli::compare::handle_compare(args),
+        Some(Subcommands::Extract(args)) => jxl_oxide_cli::extract::handle_extract(args),
         #[cfg(feature = "__devtools")]
         Some(Subcommands::GenerateFixture(args)) => {
             jxl_oxide_cli::generate_fixture::handle_generate_fixture(args);
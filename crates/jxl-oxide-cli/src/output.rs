@@ -1,20 +1,36 @@
 use std::io::prelude::*;
 
-use jxl_oxide::{FrameBuffer, JxlImage, PixelFormat, Render};
+use jxl_oxide::{icc::IccSynthesisOptions, FrameBuffer, JxlImage, PixelFormat, Render};
+use serde_json::{json, Value};
 
+use crate::commands::decode::RawLayout;
+use crate::info::extra_channel_type_name;
+
+mod exr;
+mod tiff;
+
+pub(crate) use exr::{write_exr, ExrPixelType};
+pub(crate) use tiff::{write_tiff, TiffSampleFormat};
+
+/// Writes keyframes as PNG, or as APNG if the image is animated and there are multiple keyframes.
+///
+/// A single keyframe is written as a still image even if the image is animated.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn write_png<W: Write>(
     output: W,
     image: &JxlImage,
     keyframes: &[Render],
     pixfmt: PixelFormat,
     force_bit_depth: Option<png::BitDepth>,
+    icc_options: IccSynthesisOptions,
     width: u32,
     height: u32,
 ) -> std::io::Result<()> {
     // Color encoding information
-    let source_icc = image.rendered_icc();
+    let source_icc = image.rendered_icc_with_options(icc_options);
     let cicp = image.rendered_cicp();
     let metadata = &image.image_header().metadata;
+    let animation = metadata.animation.as_ref().filter(|_| keyframes.len() > 1);
 
     let mut encoder = png::Encoder::new(output, width, height);
 
@@ -23,9 +39,11 @@ pub(crate) fn write_png<W: Write>(
         PixelFormat::Graya => png::ColorType::GrayscaleAlpha,
         PixelFormat::Rgb => png::ColorType::Rgb,
         PixelFormat::Rgba => png::ColorType::Rgba,
-        _ => {
-            tracing::error!("Cannot output CMYK PNG");
-            panic!();
+        PixelFormat::Cmyk | PixelFormat::Cmyka => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "PNG output requires grayscale or RGB image",
+            ))
         }
     };
     encoder.set_color(color_type);
@@ -42,7 +60,7 @@ pub(crate) fn write_png<W: Write>(
         encoder.set_depth(png::BitDepth::Eight);
     }
 
-    if let Some(animation) = &metadata.animation {
+    if let Some(animation) = animation {
         let num_plays = animation.num_loops;
         encoder
             .set_animated(keyframes.len() as u32, num_plays)
@@ -66,7 +84,7 @@ pub(crate) fn write_png<W: Write>(
 
     tracing::debug!("Writing image data");
     for keyframe in keyframes {
-        if let Some(animation) = &metadata.animation {
+        if let Some(animation) = animation {
             let duration = keyframe.duration();
             let numer = animation.tps_denominator * duration;
             let denom = animation.tps_numerator;
@@ -148,3 +166,495 @@ pub(crate) fn write_npy<W: Write>(
     output.flush()?;
     Ok(())
 }
+
+/// Writes keyframes as PPM (PGM if grayscale), or as PAM if `pam` is set.
+///
+/// Keyframes are concatenated into a single multi-image stream. CMYK images can only be written
+/// as PAM.
+pub(crate) fn write_pnm<W: Write>(
+    output: W,
+    keyframes: &[Render],
+    pixfmt: PixelFormat,
+    pam: bool,
+    sixteen_bits: bool,
+) -> std::io::Result<()> {
+    if !pam && pixfmt.has_black() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "PPM output requires grayscale or RGB image",
+        ));
+    }
+
+    let mut output = std::io::BufWriter::new(output);
+    let maxval = if sixteen_bits { 65535 } else { 255 };
+
+    tracing::debug!("Writing image data");
+    for keyframe in keyframes {
+        let fb = keyframe.image_all_channels();
+        let width = fb.width();
+        let height = fb.height();
+        let channels = fb.channels();
+        let color_channels = keyframe.color_channels().len();
+
+        let depth = if pam {
+            writeln!(output, "P7")?;
+            writeln!(output, "WIDTH {width}")?;
+            writeln!(output, "HEIGHT {height}")?;
+            writeln!(output, "DEPTH {channels}")?;
+            writeln!(output, "MAXVAL {maxval}")?;
+            if let Some(tuple_type) = pam_tuple_type(keyframe) {
+                writeln!(output, "TUPLTYPE {tuple_type}")?;
+            }
+            writeln!(output, "ENDHDR")?;
+            channels
+        } else {
+            let magic = match color_channels {
+                1 => "P5",
+                3 => "P6",
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "PPM output requires grayscale or RGB image",
+                    ))
+                }
+            };
+            write!(output, "{magic}\n{width} {height}\n{maxval}\n")?;
+            color_channels
+        };
+
+        for pixel in fb.buf().chunks_exact(channels) {
+            for &s in &pixel[..depth] {
+                if sixteen_bits {
+                    let w = (s * 65535.0 + 0.5).clamp(0.0, 65535.0) as u16;
+                    output.write_all(&w.to_be_bytes())?;
+                } else {
+                    let b = (s * 255.0 + 0.5).clamp(0.0, 255.0) as u8;
+                    output.write_all(&[b])?;
+                }
+            }
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+fn pam_tuple_type(keyframe: &Render) -> Option<&'static str> {
+    let has_alpha = match keyframe.extra_channels().0 {
+        [] => false,
+        [ec] if ec.is_alpha() => true,
+        _ => return None,
+    };
+    Some(match (keyframe.color_channels().len(), has_alpha) {
+        (1, false) => "GRAYSCALE",
+        (1, true) => "GRAYSCALE_ALPHA",
+        (3, false) => "RGB",
+        (3, true) => "RGB_ALPHA",
+        _ => return None,
+    })
+}
+
+/// Writes color channels of a keyframe as PFM, without clamping samples.
+///
+/// PFM holds a single image, so writing multiple keyframes is an error.
+pub(crate) fn write_pfm<W: Write>(
+    output: W,
+    keyframes: &[Render],
+    pixfmt: PixelFormat,
+) -> std::io::Result<()> {
+    if pixfmt.has_black() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "PFM output requires grayscale or RGB image",
+        ));
+    }
+    if keyframes.len() > 1 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "PFM output cannot hold multiple keyframes; use `--frames each`",
+        ));
+    }
+
+    let mut output = std::io::BufWriter::new(output);
+
+    tracing::debug!("Writing image data");
+    for keyframe in keyframes {
+        let fb = keyframe.image_all_channels();
+        let width = fb.width();
+        let height = fb.height();
+        let channels = fb.channels();
+        let color_channels = keyframe.color_channels().len();
+
+        let magic = match color_channels {
+            1 => "Pf",
+            3 => "PF",
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "PFM output requires grayscale or RGB image",
+                ))
+            }
+        };
+        // Negative scale means little endian.
+        write!(output, "{magic}\n{width} {height}\n-1.0\n")?;
+
+        // Rows are stored from bottom to top.
+        for row in fb.buf().chunks_exact(width * channels).rev() {
+            for pixel in row.chunks_exact(channels) {
+                for s in &pixel[..color_channels] {
+                    output.write_all(&s.to_le_bytes())?;
+                }
+            }
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes all channels of keyframes as raw 32-bit float samples in little endian.
+pub(crate) fn write_raw<W: Write>(
+    output: W,
+    keyframes: &[Render],
+    layout: RawLayout,
+) -> std::io::Result<()> {
+    let mut output = std::io::BufWriter::new(output);
+
+    tracing::debug!("Writing image data");
+    for keyframe in keyframes {
+        let planes = match layout {
+            RawLayout::Interleaved => vec![keyframe.image_all_channels()],
+            RawLayout::Planar => keyframe.image_planar(),
+        };
+        for fb in planes {
+            for sample in fb.buf() {
+                output.write_all(&sample.to_le_bytes())?;
+            }
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Describes the layout of raw output, written next to the output as JSON.
+pub(crate) fn raw_description(image: &JxlImage, keyframes: &[Render], layout: RawLayout) -> Value {
+    let first_frame = keyframes.first().unwrap();
+    let stream = first_frame.stream();
+
+    let color_names: &[&str] = match first_frame.color_channels().len() {
+        1 => &["gray"],
+        3 if image.pixel_format().has_black() => &["cyan", "magenta", "yellow"],
+        _ => &["red", "green", "blue"],
+    };
+    let mut channels = color_names
+        .iter()
+        .map(|name| json!({ "type": "color", "name": name }))
+        .collect::<Vec<_>>();
+    for ec in first_frame.extra_channels().0 {
+        channels.push(json!({
+            "type": extra_channel_type_name(ec.ty()),
+            "name": ec.name(),
+        }));
+    }
+
+    let animation = image.image_header().metadata.animation.as_ref().map(|animation| {
+        json!({
+            "tps_numerator": animation.tps_numerator,
+            "tps_denominator": animation.tps_denominator,
+            "durations": keyframes.iter().map(|keyframe| keyframe.duration()).collect::<Vec<_>>(),
+        })
+    });
+
+    json!({
+        "sample_format": "f32le",
+        "layout": match layout {
+            RawLayout::Interleaved => "interleaved",
+            RawLayout::Planar => "planar",
+        },
+        "width": stream.width(),
+        "height": stream.height(),
+        "num_frames": keyframes.len(),
+        "channels": channels,
+        "animation": animation,
+    })
+}
+
+/// Describes keyframes written as separate files, including their blending information.
+///
+/// Written keyframes are already composited onto the full canvas, so blending information only
+/// records how each keyframe was blended while rendering; frame position and size are omitted.
+pub(crate) fn frames_description(
+    image: &JxlImage,
+    keyframes: &[Render],
+    paths: &[std::path::PathBuf],
+) -> Value {
+    let animation = image
+        .image_header()
+        .metadata
+        .animation
+        .as_ref()
+        .map(|animation| {
+            json!({
+                "tps_numerator": animation.tps_numerator,
+                "tps_denominator": animation.tps_denominator,
+                "num_loops": animation.num_loops,
+            })
+        });
+
+    let frames = keyframes
+        .iter()
+        .zip(paths)
+        .map(|(keyframe, path)| {
+            let header = image.frame_header(keyframe.keyframe_index());
+            json!({
+                "index": keyframe.keyframe_index(),
+                "path": path.to_string_lossy(),
+                "name": keyframe.name(),
+                "duration": keyframe.duration(),
+                "is_page": keyframe.duration() == 0xffffffff,
+                "blending_info": header.map(|header| &header.blending_info),
+                "ec_blending_info": header.map(|header| &header.ec_blending_info),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "animation": animation,
+        "frames": frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{ALPHA_IMAGE, SMALL_IMAGE};
+
+    /// 88x88 RGB image with five extra channels.
+    const MULTI_EC_IMAGE: &[u8] =
+        include_bytes!("../../jxl-oxide/tests/fuzz_findings/upsample_separate_ec.fuzz");
+
+    fn render(data: &[u8]) -> Render {
+        let image = JxlImage::builder().read(data).unwrap();
+        image.render_frame(0).unwrap()
+    }
+
+    /// Splits PAM output into header lines and sample data.
+    fn parse_pam(buf: &[u8]) -> (Vec<&str>, &[u8]) {
+        let end = buf.windows(7).position(|w| w == b"ENDHDR\n").unwrap() + 7;
+        let header = std::str::from_utf8(&buf[..end]).unwrap();
+        (header.lines().collect(), &buf[end..])
+    }
+
+    #[test]
+    fn pam_tuple_type() {
+        for (data, depth, tuple_type) in [
+            (SMALL_IMAGE, 3, Some("RGB")),
+            (ALPHA_IMAGE, 4, Some("RGB_ALPHA")),
+            (MULTI_EC_IMAGE, 8, None),
+        ] {
+            let keyframe = render(data);
+            let mut buf = Vec::new();
+            write_pnm(
+                &mut buf,
+                std::slice::from_ref(&keyframe),
+                PixelFormat::Rgb,
+                true,
+                false,
+            )
+            .unwrap();
+
+            let (header, samples) = parse_pam(&buf);
+            let fb = keyframe.image_all_channels();
+            assert_eq!(header[0], "P7");
+            assert!(header.contains(&&*format!("WIDTH {}", fb.width())));
+            assert!(header.contains(&&*format!("HEIGHT {}", fb.height())));
+            assert!(header.contains(&&*format!("DEPTH {depth}")));
+            assert!(header.contains(&"MAXVAL 255"));
+            let actual_type = header
+                .iter()
+                .find_map(|line| line.strip_prefix("TUPLTYPE "));
+            assert_eq!(actual_type, tuple_type);
+            assert_eq!(samples.len(), fb.width() * fb.height() * depth);
+        }
+    }
+
+    #[test]
+    fn ppm_drops_alpha() {
+        let keyframe = render(ALPHA_IMAGE);
+        let mut buf = Vec::new();
+        write_pnm(
+            &mut buf,
+            std::slice::from_ref(&keyframe),
+            PixelFormat::Rgba,
+            false,
+            true,
+        )
+        .unwrap();
+
+        // Alpha is dropped in PPM output.
+        let header = b"P6\n16 7\n65535\n";
+        assert_eq!(&buf[..header.len()], header);
+        assert_eq!(buf.len() - header.len(), 16 * 7 * 3 * 2);
+    }
+
+    #[test]
+    fn pfm_bottom_to_top() {
+        let keyframe = render(ALPHA_IMAGE);
+        let mut buf = Vec::new();
+        write_pfm(&mut buf, std::slice::from_ref(&keyframe), PixelFormat::Rgba).unwrap();
+
+        let header = b"PF\n16 7\n-1.0\n";
+        assert_eq!(&buf[..header.len()], header);
+        let samples = buf[header.len()..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), 16 * 7 * 3);
+
+        let fb = keyframe.image_all_channels();
+        for y in 0..7 {
+            for x in 0..16 {
+                for c in 0..3 {
+                    let expected = fb.buf()[(y * 16 + x) * 4 + c];
+                    let actual = samples[((6 - y) * 16 + x) * 3 + c];
+                    assert_eq!(
+                        expected.to_bits(),
+                        actual.to_bits(),
+                        "({x}, {y}) channel {c}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cmyk_netpbm() {
+        let keyframe = render(SMALL_IMAGE);
+        let keyframes = std::slice::from_ref(&keyframe);
+        let mut buf = Vec::new();
+        let err = write_pnm(&mut buf, keyframes, PixelFormat::Cmyk, false, false).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let err = write_pfm(&mut buf, keyframes, PixelFormat::Cmyk).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn pfm_multiple_keyframes() {
+        let keyframes = [render(SMALL_IMAGE), render(SMALL_IMAGE)];
+        let mut buf = Vec::new();
+        let err = write_pfm(&mut buf, &keyframes, PixelFormat::Rgb).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn raw_layout() {
+        let keyframe = render(ALPHA_IMAGE);
+        let keyframes = std::slice::from_ref(&keyframe);
+        let read_samples = |layout| {
+            let mut buf = Vec::new();
+            write_raw(&mut buf, keyframes, layout).unwrap();
+            buf.chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let interleaved = read_samples(RawLayout::Interleaved);
+        let planar = read_samples(RawLayout::Planar);
+
+        let (width, height, channels) = (16, 7, 4);
+        assert_eq!(interleaved.len(), width * height * channels);
+        assert_eq!(planar.len(), width * height * channels);
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..channels {
+                    let i = interleaved[(y * width + x) * channels + c];
+                    let p = planar[c * width * height + y * width + x];
+                    assert_eq!(i.to_bits(), p.to_bits(), "({x}, {y}) channel {c}");
+                }
+            }
+        }
+
+        let image = JxlImage::builder().read(ALPHA_IMAGE).unwrap();
+        let description = raw_description(&image, keyframes, RawLayout::Planar);
+        assert_eq!(description["layout"], "planar");
+        assert_eq!(description["width"], width);
+        assert_eq!(description["height"], height);
+        assert_eq!(description["channels"].as_array().unwrap().len(), channels);
+        assert_eq!(description["channels"][3]["type"], "alpha");
+    }
+
+    #[test]
+    fn frames_description_fields() {
+        let image = JxlImage::builder().read(SMALL_IMAGE).unwrap();
+        let keyframe = image.render_frame(0).unwrap();
+        let paths = [std::path::PathBuf::from("out-0.png")];
+        let description = frames_description(&image, std::slice::from_ref(&keyframe), &paths);
+
+        assert_eq!(description["animation"], Value::Null);
+        let frame = &description["frames"][0];
+        let mut keys = frame.as_object().unwrap().keys().collect::<Vec<_>>();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "blending_info",
+                "duration",
+                "ec_blending_info",
+                "index",
+                "is_page",
+                "name",
+                "path",
+            ]
+        );
+        assert_eq!(frame["index"], 0);
+        assert_eq!(frame["path"], "out-0.png");
+    }
+
+    #[test]
+    fn png_single_keyframe() {
+        let image = JxlImage::builder().read(ALPHA_IMAGE).unwrap();
+        let keyframe = image.render_frame(0).unwrap();
+        let mut buf = Vec::new();
+        write_png(
+            &mut buf,
+            &image,
+            std::slice::from_ref(&keyframe),
+            image.pixel_format(),
+            None,
+            IccSynthesisOptions::default(),
+            16,
+            7,
+        )
+        .unwrap();
+
+        let reader = png::Decoder::new(&buf[..]).read_info().unwrap();
+        let info = reader.info();
+        assert!(info.animation_control.is_none());
+        assert!(info.frame_control.is_none());
+        assert_eq!((info.width, info.height), (16, 7));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert!(info.icc_profile.is_some());
+    }
+
+    #[test]
+    fn cmyk_png() {
+        let image = JxlImage::builder().read(SMALL_IMAGE).unwrap();
+        let keyframe = image.render_frame(0).unwrap();
+        let mut buf = Vec::new();
+        let err = write_png(
+            &mut buf,
+            &image,
+            std::slice::from_ref(&keyframe),
+            PixelFormat::Cmyk,
+            None,
+            IccSynthesisOptions::default(),
+            240,
+            135,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+}
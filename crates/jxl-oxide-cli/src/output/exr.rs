@@ -0,0 +1,484 @@
+use std::io::prelude::*;
+
+use jxl_oxide::{ExtraChannelType, Render};
+
+use crate::info::extra_channel_type_name;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: u32 = 2;
+const FLAG_LONG_NAMES: u32 = 0x400;
+const FLAG_MULTIPART: u32 = 0x1000;
+
+/// Pixel type of OpenEXR output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ExrPixelType {
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::Half => 2,
+            Self::Float => 4,
+        }
+    }
+
+    fn chlist_value(self) -> i32 {
+        match self {
+            Self::Half => 1,
+            Self::Float => 2,
+        }
+    }
+
+    fn write_sample(self, out: &mut Vec<u8>, sample: f32) {
+        match self {
+            Self::Half => out.extend_from_slice(&f32_to_f16(sample).to_le_bytes()),
+            Self::Float => out.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+}
+
+/// Converts `f32` to the bit pattern of IEEE 754 binary16, rounding to nearest even.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7fffff;
+
+    if exp == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exp = exp - 127 + 15;
+    if exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let (half, round_bit, mantissa) = if exp <= 0 {
+        if exp < -10 {
+            return sign;
+        }
+        // Subnormal; shift the mantissa including the implicit leading bit.
+        let mantissa = mantissa | 0x800000;
+        let shift = (14 - exp) as u32;
+        (mantissa >> shift, 1u32 << (shift - 1), mantissa)
+    } else {
+        (
+            ((exp as u32) << 10) | (mantissa >> 13),
+            1u32 << 12,
+            mantissa,
+        )
+    };
+
+    // Round up if above halfway, or exactly halfway and the result is odd. Carry may propagate
+    // into the exponent, which is still correct.
+    let round_up =
+        (mantissa & round_bit) != 0 && (mantissa & ((round_bit << 1) | (round_bit - 1))) != 0;
+    sign | (half + round_up as u32) as u16
+}
+
+struct ExrChannel {
+    name: String,
+    /// Index of the channel in the interleaved framebuffer.
+    idx: usize,
+    /// Whether to premultiply samples with alpha.
+    premultiply: bool,
+}
+
+/// Maps channels of the keyframe to EXR channels, sorted by name.
+///
+/// Color channels are written as `R`, `G`, `B` (or `Y` if grayscale) and the first alpha channel
+/// as `A`. Other extra channels are written only if `extra_channels` is set, named after the
+/// channel name or type.
+fn exr_channels(keyframe: &Render, extra_channels: bool) -> (Vec<ExrChannel>, Option<usize>) {
+    let color_channels = keyframe.color_channels().len();
+    let ecs = keyframe.extra_channels().0;
+
+    let alpha_ec_idx = ecs.iter().position(|ec| ec.is_alpha());
+    let premultiply = alpha_ec_idx.is_some_and(|idx| {
+        matches!(
+            ecs[idx].ty(),
+            ExtraChannelType::Alpha {
+                alpha_associated: false
+            }
+        )
+    });
+
+    let color_names: &[&str] = if color_channels == 1 {
+        &["Y"]
+    } else {
+        &["R", "G", "B"]
+    };
+    let mut channels = color_names
+        .iter()
+        .enumerate()
+        .map(|(idx, &name)| ExrChannel {
+            name: String::from(name),
+            idx,
+            premultiply,
+        })
+        .collect::<Vec<_>>();
+
+    for (ec_idx, ec) in ecs.iter().enumerate() {
+        let name = if Some(ec_idx) == alpha_ec_idx {
+            String::from("A")
+        } else if !extra_channels || ec.is_black() {
+            // Black channel is merged into color channels while rendering.
+            continue;
+        } else if !ec.name().is_empty() {
+            ec.name().to_owned()
+        } else if ec.ty() == ExtraChannelType::Depth {
+            String::from("Z")
+        } else {
+            extra_channel_type_name(ec.ty()).to_owned()
+        };
+
+        let mut unique_name = name.clone();
+        let mut suffix = 1;
+        while channels.iter().any(|channel| channel.name == unique_name) {
+            unique_name = format!("{name}{suffix}");
+            suffix += 1;
+        }
+
+        channels.push(ExrChannel {
+            name: unique_name,
+            idx: color_channels + ec_idx,
+            premultiply: false,
+        });
+    }
+
+    channels.sort_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+    let alpha_idx = alpha_ec_idx.map(|idx| color_channels + idx);
+    (channels, alpha_idx)
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, ty: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(ty.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn floats(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn ints(values: &[i32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn too_large() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "image is too large for OpenEXR output",
+    )
+}
+
+/// Writes keyframes as an uncompressed scanline OpenEXR, with one scanline per chunk.
+///
+/// Multiple keyframes are written as a multi-part file, one part per keyframe. Samples are
+/// expected to be linear; color channels are premultiplied with alpha if alpha is unassociated.
+/// `chromaticities` are in the order of red, green, blue and white, and `white_luminance` is the
+/// luminance of sample value 1.0 in nits.
+pub(crate) fn write_exr<W: Write>(
+    output: W,
+    keyframes: &[Render],
+    pixel_type: ExrPixelType,
+    chromaticities: [[f32; 2]; 4],
+    white_luminance: f32,
+    extra_channels: bool,
+) -> std::io::Result<()> {
+    let multipart = keyframes.len() > 1;
+    let mut long_names = false;
+
+    let mut parts = Vec::with_capacity(keyframes.len());
+    for (idx, keyframe) in keyframes.iter().enumerate() {
+        let fb = keyframe.image_all_channels();
+        let width = i32::try_from(fb.width()).map_err(|_| too_large())?;
+        let height = i32::try_from(fb.height()).map_err(|_| too_large())?;
+        let (channels, alpha_idx) = exr_channels(keyframe, extra_channels);
+
+        let mut header = Vec::new();
+        let mut chlist = Vec::new();
+        for channel in &channels {
+            long_names |= channel.name.len() > 31;
+            chlist.extend_from_slice(channel.name.as_bytes());
+            chlist.push(0);
+            chlist.extend_from_slice(&pixel_type.chlist_value().to_le_bytes());
+            // pLinear and reserved bytes
+            chlist.extend_from_slice(&[0; 4]);
+            chlist.extend_from_slice(&ints(&[1, 1]));
+        }
+        chlist.push(0);
+
+        let window = ints(&[0, 0, width - 1, height - 1]);
+        write_attribute(&mut header, "channels", "chlist", &chlist);
+        write_attribute(
+            &mut header,
+            "chromaticities",
+            "chromaticities",
+            &floats(&chromaticities.concat()),
+        );
+        write_attribute(&mut header, "compression", "compression", &[0]);
+        write_attribute(&mut header, "dataWindow", "box2i", &window);
+        write_attribute(&mut header, "displayWindow", "box2i", &window);
+        write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        write_attribute(&mut header, "pixelAspectRatio", "float", &floats(&[1.0]));
+        write_attribute(
+            &mut header,
+            "screenWindowCenter",
+            "v2f",
+            &floats(&[0.0, 0.0]),
+        );
+        write_attribute(&mut header, "screenWindowWidth", "float", &floats(&[1.0]));
+        write_attribute(
+            &mut header,
+            "whiteLuminance",
+            "float",
+            &floats(&[white_luminance]),
+        );
+        if multipart {
+            let name = format!("frame{idx}");
+            write_attribute(&mut header, "name", "string", name.as_bytes());
+            write_attribute(&mut header, "type", "string", b"scanlineimage");
+            write_attribute(&mut header, "chunkCount", "int", &height.to_le_bytes());
+        }
+        header.push(0);
+
+        parts.push((fb, channels, alpha_idx, header));
+    }
+
+    let mut version = VERSION;
+    if multipart {
+        version |= FLAG_MULTIPART;
+    }
+    if long_names {
+        version |= FLAG_LONG_NAMES;
+    }
+
+    let mut output = std::io::BufWriter::new(output);
+    output.write_all(&MAGIC)?;
+    output.write_all(&version.to_le_bytes())?;
+    let mut pos = 8u64;
+    for (_, _, _, header) in &parts {
+        output.write_all(header)?;
+        pos += header.len() as u64;
+    }
+    if multipart {
+        output.write_all(&[0])?;
+        pos += 1;
+    }
+
+    // Offset tables of all parts come before chunks.
+    let chunk_header_len = if multipart { 12 } else { 8 };
+    let total_chunks = parts.iter().map(|(fb, ..)| fb.height() as u64).sum::<u64>();
+    let mut chunk_offset = pos + 8 * total_chunks;
+    for (fb, channels, ..) in &parts {
+        let line_len = fb.width() * channels.len() * pixel_type.bytes_per_sample();
+        for _ in 0..fb.height() {
+            output.write_all(&chunk_offset.to_le_bytes())?;
+            chunk_offset += (chunk_header_len + line_len) as u64;
+        }
+    }
+
+    tracing::debug!("Writing image data");
+    for (part_idx, (fb, channels, alpha_idx, _)) in parts.iter().enumerate() {
+        let width = fb.width();
+        let fb_channels = fb.channels();
+        let line_len = width * channels.len() * pixel_type.bytes_per_sample();
+        let line_len_i32 = i32::try_from(line_len).map_err(|_| too_large())?;
+
+        let mut line_buf = Vec::with_capacity(line_len);
+        for (y, row) in fb.buf().chunks_exact(width * fb_channels).enumerate() {
+            line_buf.clear();
+            for channel in channels {
+                for pixel in row.chunks_exact(fb_channels) {
+                    let mut sample = pixel[channel.idx];
+                    if let (true, Some(alpha_idx)) = (channel.premultiply, *alpha_idx) {
+                        sample *= pixel[alpha_idx];
+                    }
+                    pixel_type.write_sample(&mut line_buf, sample);
+                }
+            }
+
+            if multipart {
+                output.write_all(&(part_idx as i32).to_le_bytes())?;
+            }
+            output.write_all(&(y as i32).to_le_bytes())?;
+            output.write_all(&line_len_i32.to_le_bytes())?;
+            output.write_all(&line_buf)?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use jxl_oxide::JxlImage;
+
+    use super::*;
+    use crate::test_util::ALPHA_IMAGE;
+
+    #[test]
+    fn f16_edge_values() {
+        for (value, expected) in [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            // Largest finite value
+            (65504.0, 0x7bff),
+            // Halfway to the next value, rounded to even which overflows
+            (65520.0, 0x7c00),
+            (65519.996, 0x7bff),
+            (f32::INFINITY, 0x7c00),
+            (f32::NEG_INFINITY, 0xfc00),
+            // Smallest normal value
+            (2f32.powi(-14), 0x0400),
+            // Smallest subnormal value
+            (2f32.powi(-24), 0x0001),
+            // Halfway to the smallest subnormal, rounded to even
+            (2f32.powi(-25), 0x0000),
+            (2f32.powi(-25) * 1.5, 0x0001),
+            (-(2f32.powi(-25)), 0x8000),
+            (2f32.powi(-26), 0x0000),
+            // Halfway between subnormals, rounded to even
+            (2f32.powi(-24) * 1.5, 0x0002),
+            (2f32.powi(-24) * 2.5, 0x0002),
+            // Carry into the exponent
+            (2f32.powi(-14) - 2f32.powi(-26), 0x0400),
+            (1.0 + 2f32.powi(-11), 0x3c00),
+            (1.0 + 3.0 * 2f32.powi(-11), 0x3c02),
+        ] {
+            assert_eq!(
+                f32_to_f16(value),
+                expected,
+                "{value:e} should be {expected:#06x}"
+            );
+        }
+
+        let nan = f32_to_f16(f32::NAN);
+        assert_eq!(nan & 0x7c00, 0x7c00);
+        assert_ne!(nan & 0x3ff, 0);
+    }
+
+    fn u32_at(buf: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(buf[pos..][..4].try_into().unwrap())
+    }
+
+    fn read_cstr<'a>(buf: &'a [u8], pos: &mut usize) -> &'a str {
+        let len = buf[*pos..].iter().position(|&b| b == 0).unwrap();
+        let s = std::str::from_utf8(&buf[*pos..][..len]).unwrap();
+        *pos += len + 1;
+        s
+    }
+
+    /// Parses a header, returning attributes with their type and value.
+    fn parse_header(buf: &[u8], pos: &mut usize) -> BTreeMap<String, (String, Vec<u8>)> {
+        let mut attributes = BTreeMap::new();
+        loop {
+            let name = read_cstr(buf, pos);
+            if name.is_empty() {
+                return attributes;
+            }
+            let ty = read_cstr(buf, pos);
+            let len = u32_at(buf, *pos) as usize;
+            let value = buf[*pos + 4..][..len].to_vec();
+            *pos += 4 + len;
+            attributes.insert(name.to_owned(), (ty.to_owned(), value));
+        }
+    }
+
+    /// Parses channel names and pixel types of a `chlist` attribute.
+    fn parse_chlist(value: &[u8]) -> Vec<(String, u32)> {
+        let mut channels = Vec::new();
+        let mut pos = 0;
+        loop {
+            let name = read_cstr(value, &mut pos);
+            if name.is_empty() {
+                return channels;
+            }
+            channels.push((name.to_owned(), u32_at(value, pos)));
+            pos += 16;
+        }
+    }
+
+    #[test]
+    fn multipart_structure() {
+        let image = JxlImage::builder().read(ALPHA_IMAGE).unwrap();
+        let keyframes = [
+            image.render_frame(0).unwrap(),
+            image.render_frame(0).unwrap(),
+        ];
+        let chromaticities = [[0.64, 0.33], [0.3, 0.6], [0.15, 0.06], [0.3127, 0.329]];
+
+        let mut buf = Vec::new();
+        write_exr(
+            &mut buf,
+            &keyframes,
+            ExrPixelType::Half,
+            chromaticities,
+            203.0,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(buf[..4], MAGIC);
+        assert_eq!(u32_at(&buf, 4), VERSION | FLAG_MULTIPART);
+
+        let mut pos = 8;
+        let headers = [parse_header(&buf, &mut pos), parse_header(&buf, &mut pos)];
+        // Empty header terminates the header list.
+        assert_eq!(buf[pos], 0);
+        pos += 1;
+
+        for (idx, header) in headers.iter().enumerate() {
+            assert_eq!(
+                header["name"],
+                (String::from("string"), format!("frame{idx}").into_bytes())
+            );
+            assert_eq!(
+                header["type"],
+                (String::from("string"), b"scanlineimage".to_vec())
+            );
+            assert_eq!(
+                header["chunkCount"],
+                (String::from("int"), 7i32.to_le_bytes().to_vec())
+            );
+            assert_eq!(header["compression"].1, [0]);
+            assert_eq!(header["dataWindow"].1, ints(&[0, 0, 15, 6]));
+            assert_eq!(header["whiteLuminance"].1, floats(&[203.0]));
+            assert_eq!(header["chromaticities"].1, floats(&chromaticities.concat()));
+
+            let (ty, chlist) = &header["channels"];
+            assert_eq!(ty, "chlist");
+            let channels = parse_chlist(chlist);
+            let expected = ["A", "B", "G", "R"].map(|name| (String::from(name), 1));
+            assert_eq!(channels, expected);
+        }
+
+        // Offset tables of both parts, followed by chunks in order.
+        let line_len = 16 * 4 * 2;
+        let chunk_len = 12 + line_len;
+        let table_end = pos + 8 * 14;
+        for part_idx in 0..2 {
+            for y in 0..7 {
+                let entry = pos + 8 * (part_idx * 7 + y);
+                let offset = u64::from_le_bytes(buf[entry..][..8].try_into().unwrap()) as usize;
+                assert_eq!(offset, table_end + (part_idx * 7 + y) * chunk_len);
+                assert_eq!(u32_at(&buf, offset), part_idx as u32);
+                assert_eq!(u32_at(&buf, offset + 4), y as u32);
+                assert_eq!(u32_at(&buf, offset + 8), line_len as u32);
+            }
+        }
+        assert_eq!(buf.len(), table_end + 14 * chunk_len);
+    }
+}
@@ -0,0 +1,470 @@
+use std::io::prelude::*;
+
+use jxl_oxide::{ExtraChannelType, FrameBuffer, Render};
+
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+const TYPE_UNDEFINED: u16 = 7;
+
+const PHOTOMETRIC_BLACK_IS_ZERO: u16 = 1;
+const PHOTOMETRIC_RGB: u16 = 2;
+const PHOTOMETRIC_SEPARATED: u16 = 5;
+
+const TAG_STRIP_OFFSETS: u16 = 273;
+
+/// Sample format of TIFF output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum TiffSampleFormat {
+    U8,
+    U16,
+    U32,
+    F32,
+}
+
+impl TiffSampleFormat {
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 | Self::F32 => 4,
+        }
+    }
+
+    fn write_sample(self, out: &mut Vec<u8>, sample: f32) {
+        match self {
+            Self::U8 => out.push((sample * 255.0 + 0.5).clamp(0.0, 255.0) as u8),
+            Self::U16 => {
+                let w = (sample * 65535.0 + 0.5).clamp(0.0, 65535.0) as u16;
+                out.extend_from_slice(&w.to_le_bytes());
+            }
+            Self::U32 => {
+                let max = u32::MAX as f64;
+                let w = (sample as f64 * max + 0.5).clamp(0.0, max) as u32;
+                out.extend_from_slice(&w.to_le_bytes());
+            }
+            Self::F32 => out.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+}
+
+struct IfdEntry {
+    tag: u16,
+    ty: u16,
+    count: u32,
+    data: Vec<u8>,
+}
+
+impl IfdEntry {
+    fn shorts(tag: u16, values: &[u16]) -> Self {
+        Self {
+            tag,
+            ty: TYPE_SHORT,
+            count: values.len() as u32,
+            data: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    fn long(tag: u16, value: u32) -> Self {
+        Self {
+            tag,
+            ty: TYPE_LONG,
+            count: 1,
+            data: value.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn rational(tag: u16, numer: u32, denom: u32) -> Self {
+        let mut data = numer.to_le_bytes().to_vec();
+        data.extend_from_slice(&denom.to_le_bytes());
+        Self {
+            tag,
+            ty: TYPE_RATIONAL,
+            count: 1,
+            data,
+        }
+    }
+
+    fn undefined(tag: u16, data: &[u8]) -> Self {
+        Self {
+            tag,
+            ty: TYPE_UNDEFINED,
+            count: data.len() as u32,
+            data: data.to_vec(),
+        }
+    }
+}
+
+fn too_large() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "image is too large for TIFF output",
+    )
+}
+
+/// Writes keyframes as a multi-page little endian TIFF, with uncompressed samples in a single strip.
+///
+/// CMYK images are written as separated CMYK, and alpha and other extra channels are written as
+/// extra samples.
+pub(crate) fn write_tiff<W: Write>(
+    output: W,
+    keyframes: &[Render],
+    sample_format: TiffSampleFormat,
+    icc: &[u8],
+) -> std::io::Result<()> {
+    let mut output = std::io::BufWriter::new(output);
+    output.write_all(b"II")?;
+    output.write_all(&42u16.to_le_bytes())?;
+    output.write_all(&8u32.to_le_bytes())?;
+    let mut pos = 8u64;
+
+    tracing::debug!("Writing image data");
+    for (idx, keyframe) in keyframes.iter().enumerate() {
+        let fb = keyframe.image_all_channels();
+        let ec_types = keyframe
+            .extra_channels()
+            .0
+            .iter()
+            .map(|ec| ec.ty())
+            .collect::<Vec<_>>();
+        let page = TiffPage {
+            fb: &fb,
+            color_channels: keyframe.color_channels().len(),
+            ec_types: &ec_types,
+            is_last: idx + 1 == keyframes.len(),
+        };
+        pos = page.write(&mut output, pos, sample_format, icc)?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Single page of TIFF output, with samples of color channels followed by extra channels.
+struct TiffPage<'a> {
+    fb: &'a FrameBuffer,
+    color_channels: usize,
+    ec_types: &'a [ExtraChannelType],
+    is_last: bool,
+}
+
+impl TiffPage<'_> {
+    /// Writes the IFD and strip data of the page at `pos`, returning the end position.
+    fn write(
+        &self,
+        output: &mut impl Write,
+        pos: u64,
+        sample_format: TiffSampleFormat,
+        icc: &[u8],
+    ) -> std::io::Result<u64> {
+        let fb = self.fb;
+        let width = fb.width();
+        let height = fb.height();
+        let channels = fb.channels();
+        let color_channels = self.color_channels;
+        let ec_types = self.ec_types;
+
+        // TIFF requires ink samples to come first, so move black channel right after color
+        // channels.
+        let black_idx = ec_types
+            .iter()
+            .position(|ty| matches!(ty, ExtraChannelType::Black));
+        let mut order = (0..color_channels).collect::<Vec<_>>();
+        if let Some(black_idx) = black_idx {
+            order.push(color_channels + black_idx);
+        }
+        let mut extra_samples = Vec::new();
+        for (ec_idx, ty) in ec_types.iter().enumerate() {
+            if Some(ec_idx) == black_idx {
+                continue;
+            }
+            order.push(color_channels + ec_idx);
+            extra_samples.push(match ty {
+                ExtraChannelType::Alpha {
+                    alpha_associated: true,
+                } => 1,
+                ExtraChannelType::Alpha {
+                    alpha_associated: false,
+                } => 2,
+                _ => 0,
+            });
+        }
+
+        let is_cmyk = black_idx.is_some() && color_channels == 3;
+        let photometric = if is_cmyk {
+            PHOTOMETRIC_SEPARATED
+        } else if color_channels == 1 {
+            PHOTOMETRIC_BLACK_IS_ZERO
+        } else {
+            PHOTOMETRIC_RGB
+        };
+        let num_inks = if is_cmyk { 4 } else { 0 };
+
+        let samples_per_pixel = order.len();
+        let strip_len = width * height * samples_per_pixel * sample_format.bytes_per_sample();
+        let bits_per_sample = (sample_format.bytes_per_sample() * 8) as u16;
+        let tiff_sample_format = if sample_format == TiffSampleFormat::F32 {
+            3
+        } else {
+            1
+        };
+
+        let mut entries = vec![
+            IfdEntry::long(256, width as u32),
+            IfdEntry::long(257, height as u32),
+            IfdEntry::shorts(258, &vec![bits_per_sample; samples_per_pixel]),
+            IfdEntry::shorts(259, &[1]),
+            IfdEntry::shorts(262, &[photometric]),
+            IfdEntry::long(TAG_STRIP_OFFSETS, 0),
+            IfdEntry::shorts(277, &[samples_per_pixel as u16]),
+            IfdEntry::long(278, height as u32),
+            IfdEntry::long(279, u32::try_from(strip_len).map_err(|_| too_large())?),
+            IfdEntry::rational(282, 72, 1),
+            IfdEntry::rational(283, 72, 1),
+            IfdEntry::shorts(284, &[1]),
+            IfdEntry::shorts(296, &[2]),
+            IfdEntry::shorts(339, &vec![tiff_sample_format; samples_per_pixel]),
+        ];
+        if is_cmyk {
+            // InkSet: CMYK
+            entries.push(IfdEntry::shorts(332, &[1]));
+        }
+        if !extra_samples.is_empty() {
+            entries.push(IfdEntry::shorts(338, &extra_samples));
+        }
+        if !icc.is_empty() {
+            entries.push(IfdEntry::undefined(34675, icc));
+        }
+        entries.sort_by_key(|entry| entry.tag);
+
+        // Layout: IFD, out-of-line values, strip data.
+        let ifd_offset = pos;
+        let ifd_len = 2 + 12 * entries.len() as u64 + 4;
+        let mut values = Vec::new();
+        let mut ifd = Vec::with_capacity(ifd_len as usize);
+        ifd.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for entry in &entries {
+            ifd.extend_from_slice(&entry.tag.to_le_bytes());
+            ifd.extend_from_slice(&entry.ty.to_le_bytes());
+            ifd.extend_from_slice(&entry.count.to_le_bytes());
+            if entry.data.len() <= 4 {
+                let mut inline = [0u8; 4];
+                inline[..entry.data.len()].copy_from_slice(&entry.data);
+                ifd.extend_from_slice(&inline);
+            } else {
+                let offset = ifd_offset + ifd_len + values.len() as u64;
+                let offset = u32::try_from(offset).map_err(|_| too_large())?;
+                ifd.extend_from_slice(&offset.to_le_bytes());
+                values.extend_from_slice(&entry.data);
+                if values.len() & 1 != 0 {
+                    values.push(0);
+                }
+            }
+        }
+
+        let strip_offset = ifd_offset + ifd_len + values.len() as u64;
+        // IFDs should begin on a word boundary.
+        let strip_padding = strip_len & 1;
+        let strip_end = strip_offset + (strip_len + strip_padding) as u64;
+        let next_ifd_offset = if self.is_last { 0 } else { strip_end };
+        let next_ifd_offset = u32::try_from(next_ifd_offset).map_err(|_| too_large())?;
+        ifd.extend_from_slice(&next_ifd_offset.to_le_bytes());
+
+        let strip_offset_entry = entries
+            .iter()
+            .position(|entry| entry.tag == TAG_STRIP_OFFSETS)
+            .unwrap();
+        let value_pos = 2 + 12 * strip_offset_entry + 8;
+        let strip_offset = u32::try_from(strip_offset).map_err(|_| too_large())?;
+        ifd[value_pos..][..4].copy_from_slice(&strip_offset.to_le_bytes());
+
+        output.write_all(&ifd)?;
+        output.write_all(&values)?;
+
+        let mut row_buf = Vec::with_capacity(width * samples_per_pixel * 4);
+        for row in fb.buf().chunks_exact(width * channels) {
+            row_buf.clear();
+            for pixel in row.chunks_exact(channels) {
+                for (sample_idx, &channel_idx) in order.iter().enumerate() {
+                    let mut sample = pixel[channel_idx];
+                    if sample_idx < num_inks {
+                        // 0 means full ink in JPEG XL, but no ink in TIFF.
+                        sample = 1.0 - sample;
+                    }
+                    sample_format.write_sample(&mut row_buf, sample);
+                }
+            }
+            output.write_all(&row_buf)?;
+        }
+        output.write_all(&[0u8; 1][..strip_padding])?;
+
+        Ok(strip_end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use jxl_oxide::JxlImage;
+
+    use super::*;
+    use crate::test_util::ALPHA_IMAGE;
+
+    const TAG_BITS_PER_SAMPLE: u16 = 258;
+    const TAG_PHOTOMETRIC: u16 = 262;
+    const TAG_SAMPLES_PER_PIXEL: u16 = 277;
+    const TAG_STRIP_BYTE_COUNTS: u16 = 279;
+    const TAG_INK_SET: u16 = 332;
+    const TAG_EXTRA_SAMPLES: u16 = 338;
+    const TAG_SAMPLE_FORMAT: u16 = 339;
+    const TAG_ICC_PROFILE: u16 = 34675;
+
+    fn u16_at(buf: &[u8], pos: usize) -> u16 {
+        u16::from_le_bytes(buf[pos..][..2].try_into().unwrap())
+    }
+
+    fn u32_at(buf: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(buf[pos..][..4].try_into().unwrap())
+    }
+
+    /// Parses every IFD in the file, returning entries as raw bytes of their values.
+    fn parse_ifds(buf: &[u8]) -> Vec<BTreeMap<u16, (u16, Vec<u8>)>> {
+        assert_eq!(&buf[..4], b"II\x2a\x00");
+        let mut ifds = Vec::new();
+        let mut offset = u32_at(buf, 4) as usize;
+        while offset != 0 {
+            assert_eq!(offset & 1, 0, "IFD should begin on a word boundary");
+            let num_entries = u16_at(buf, offset) as usize;
+            let mut entries = BTreeMap::new();
+            let mut last_tag = 0;
+            for idx in 0..num_entries {
+                let entry = offset + 2 + 12 * idx;
+                let tag = u16_at(buf, entry);
+                assert!(tag > last_tag, "IFD entries should be sorted by tag");
+                last_tag = tag;
+
+                let ty = u16_at(buf, entry + 2);
+                let count = u32_at(buf, entry + 4) as usize;
+                let len = count
+                    * match ty {
+                        TYPE_SHORT => 2,
+                        TYPE_LONG => 4,
+                        TYPE_RATIONAL => 8,
+                        TYPE_UNDEFINED => 1,
+                        _ => panic!("unexpected type {ty}"),
+                    };
+                let value_pos = if len <= 4 {
+                    entry + 8
+                } else {
+                    u32_at(buf, entry + 8) as usize
+                };
+                entries.insert(tag, (ty, buf[value_pos..][..len].to_vec()));
+            }
+            ifds.push(entries);
+            offset = u32_at(buf, offset + 2 + 12 * num_entries) as usize;
+        }
+        ifds
+    }
+
+    fn shorts(entries: &BTreeMap<u16, (u16, Vec<u8>)>, tag: u16) -> Vec<u16> {
+        let (ty, data) = &entries[&tag];
+        assert_eq!(*ty, TYPE_SHORT);
+        data.chunks_exact(2)
+            .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+            .collect()
+    }
+
+    fn long(entries: &BTreeMap<u16, (u16, Vec<u8>)>, tag: u16) -> u32 {
+        let (ty, data) = &entries[&tag];
+        assert_eq!(*ty, TYPE_LONG);
+        u32::from_le_bytes(data[..].try_into().unwrap())
+    }
+
+    #[test]
+    fn rgba_pages() {
+        let image = JxlImage::builder().read(ALPHA_IMAGE).unwrap();
+        let keyframes = [
+            image.render_frame(0).unwrap(),
+            image.render_frame(0).unwrap(),
+        ];
+        let alpha_associated = match keyframes[0].extra_channels().0[0].ty() {
+            ExtraChannelType::Alpha { alpha_associated } => alpha_associated,
+            ty => panic!("unexpected extra channel type {ty:?}"),
+        };
+        let icc = [1u8, 2, 3, 4, 5];
+
+        let mut buf = Vec::new();
+        write_tiff(&mut buf, &keyframes, TiffSampleFormat::U16, &icc).unwrap();
+
+        let ifds = parse_ifds(&buf);
+        assert_eq!(ifds.len(), 2);
+        let fb = keyframes[0].image_all_channels();
+        for entries in &ifds {
+            assert_eq!(long(entries, 256), 16);
+            assert_eq!(long(entries, 257), 7);
+            assert_eq!(shorts(entries, TAG_BITS_PER_SAMPLE), [16; 4]);
+            assert_eq!(shorts(entries, TAG_PHOTOMETRIC), [PHOTOMETRIC_RGB]);
+            assert_eq!(shorts(entries, TAG_SAMPLES_PER_PIXEL), [4]);
+            assert_eq!(shorts(entries, TAG_SAMPLE_FORMAT), [1; 4]);
+            assert_eq!(
+                shorts(entries, TAG_EXTRA_SAMPLES),
+                [if alpha_associated { 1 } else { 2 }]
+            );
+            assert!(!entries.contains_key(&TAG_INK_SET));
+            assert_eq!(entries[&TAG_ICC_PROFILE], (TYPE_UNDEFINED, icc.to_vec()));
+
+            let strip_offset = long(entries, TAG_STRIP_OFFSETS) as usize;
+            let strip_len = long(entries, TAG_STRIP_BYTE_COUNTS) as usize;
+            assert_eq!(strip_len, 16 * 7 * 4 * 2);
+            let strip = &buf[strip_offset..][..strip_len];
+            for (bytes, &sample) in strip.chunks_exact(2).zip(fb.buf()) {
+                let expected = (sample * 65535.0 + 0.5).clamp(0.0, 65535.0) as u16;
+                assert_eq!(u16::from_le_bytes(bytes.try_into().unwrap()), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn cmyk_ink_set() {
+        // C, M, Y, alpha, K for two pixels.
+        let samples = [0.0, 0.25, 0.5, 1.0, 0.75, 1.0, 0.5, 0.0, 0.5, 0.25];
+        let mut fb = FrameBuffer::new(2, 1, 5);
+        fb.buf_mut().copy_from_slice(&samples);
+        let ec_types = [
+            ExtraChannelType::Alpha {
+                alpha_associated: false,
+            },
+            ExtraChannelType::Black,
+        ];
+        let page = TiffPage {
+            fb: &fb,
+            color_channels: 3,
+            ec_types: &ec_types,
+            is_last: true,
+        };
+
+        let mut buf = b"II\x2a\x00\x08\x00\x00\x00".to_vec();
+        let end = page.write(&mut buf, 8, TiffSampleFormat::F32, &[]).unwrap();
+        assert_eq!(end as usize, buf.len());
+
+        let ifds = parse_ifds(&buf);
+        assert_eq!(ifds.len(), 1);
+        let entries = &ifds[0];
+        assert_eq!(shorts(entries, TAG_PHOTOMETRIC), [PHOTOMETRIC_SEPARATED]);
+        assert_eq!(shorts(entries, TAG_INK_SET), [1]);
+        assert_eq!(shorts(entries, TAG_SAMPLES_PER_PIXEL), [5]);
+        assert_eq!(shorts(entries, TAG_BITS_PER_SAMPLE), [32; 5]);
+        assert_eq!(shorts(entries, TAG_SAMPLE_FORMAT), [3; 5]);
+        assert_eq!(shorts(entries, TAG_EXTRA_SAMPLES), [2]);
+        assert!(!entries.contains_key(&TAG_ICC_PROFILE));
+
+        // Inks are inverted and black comes before alpha.
+        let strip_offset = long(entries, TAG_STRIP_OFFSETS) as usize;
+        let strip = buf[strip_offset..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(strip, [1.0, 0.75, 0.5, 0.25, 1.0, 0.0, 0.5, 1.0, 0.75, 0.5]);
+    }
+}
@@ -12,7 +12,7 @@ pub fn handle_progressive(args: ProgressiveArgs) -> Result<()> {
     #[cfg(feature = "rayon")]
     let pool = JxlThreadPool::rayon(args.num_threads);
     #[cfg(not(feature = "rayon"))]
-    let pool = JxlThreadPool::none();
+    let pool = JxlThreadPool::std_threads(args.num_threads);
 
     let mut uninit_image = JxlImage::builder().pool(pool.clone()).build_uninit();
 
@@ -140,6 +140,7 @@ fn run_once(image: &mut JxlImage, current_iter: usize, output_dir: Option<&Path>
         &[render],
         image.pixel_format(),
         None,
+        Default::default(),
         width,
         height,
     )
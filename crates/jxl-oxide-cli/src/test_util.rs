@@ -0,0 +1,24 @@
+//! Fixtures shared by unit tests.
+
+use std::path::PathBuf;
+
+/// 240x135 modular RGB image.
+pub(crate) const SMALL_IMAGE: &[u8] = &[
+    0xff, 0x0a, 0x30, 0x54, 0x10, 0x09, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b, 0x38, 0x41, 0x3c,
+    0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48, 0x45, 0x84, 0x1b,
+    0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84, 0x01,
+];
+
+/// 16x7 RGBA image.
+pub(crate) const ALPHA_IMAGE: &[u8] =
+    include_bytes!("../../jxl-oxide/tests/fuzz_findings/squeeze_tendency_overflow.fuzz");
+
+/// Creates an empty temporary directory, removing the one left by previous runs if any.
+///
+/// `name` should be unique across tests, as tests run in parallel.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("jxl-oxide-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,653 @@
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use jxl_oxide::{
+    CropInfo, EnumColourEncoding, FrameBuffer, JxlImage, JxlThreadPool, PixelFormat, Render,
+};
+use serde_json::json;
+
+use crate::commands::tiles::*;
+use crate::{Error, Result};
+
+/// Levels at or below this scale are rendered from LF data.
+const LF_SCALE: u32 = 8;
+
+pub fn handle_tiles(args: TilesArgs) -> Result<()> {
+    let _guard = tracing::trace_span!("Handle tiles subcommand").entered();
+
+    #[cfg(feature = "rayon")]
+    let pool = JxlThreadPool::rayon(args.num_threads);
+    #[cfg(not(feature = "rayon"))]
+    let pool = JxlThreadPool::std_threads(args.num_threads);
+
+    let mut image = JxlImage::builder()
+        .pool(pool)
+        .open(&args.input)
+        .map_err(Error::ReadJxl)?;
+    if !image.is_loading_done() {
+        tracing::warn!("Partial image");
+    }
+    match image.num_loaded_keyframes() {
+        0 => return Err(Error::Render("no keyframes are decoded".into())),
+        1 => {}
+        _ => tracing::warn!("Image has multiple keyframes; exporting the first keyframe only"),
+    }
+
+    if args.format == TileFormat::Png {
+        image.request_color_encoding(EnumColourEncoding::srgb(
+            jxl_oxide::color::RenderingIntent::Relative,
+        ));
+    }
+
+    let pixel_format = image.pixel_format();
+    if args.format == TileFormat::Png && pixel_format.has_black() {
+        return Err(Error::WriteImage(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "PNG tiles cannot be written from CMYK image",
+        )));
+    }
+
+    let width = image.width();
+    let height = image.height();
+    tracing::info!("Image dimension: {width}x{height}");
+
+    let name = args
+        .input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("image"));
+    let mut writer = TileWriter {
+        layout: args.layout,
+        format: args.format,
+        pixel_format,
+        output: args.output.clone(),
+        name,
+        width,
+        height,
+        tile_size: args.tile_size,
+        pending: HashMap::new(),
+    };
+    let scales = writer.scales();
+    tracing::debug!(?scales, "Pyramid levels");
+
+    std::fs::create_dir_all(&args.output).map_err(Error::WriteImage)?;
+    writer
+        .write_manifest(args.iiif_id.as_deref())
+        .map_err(Error::WriteImage)?;
+
+    // Blocks are large enough to produce whole tiles down to 1/8 scale.
+    let block_size = args.tile_size * LF_SCALE;
+    let full_scales: Vec<_> = scales
+        .iter()
+        .copied()
+        .filter(|&scale| scale < LF_SCALE)
+        .collect();
+    let has_lf_scales = scales.iter().any(|&scale| scale >= LF_SCALE);
+
+    let mut base = None;
+    if has_lf_scales && !args.no_lf {
+        base = render_lf_base(&mut image, block_size)?;
+    }
+    let fill_base = has_lf_scales && base.is_none();
+    if fill_base {
+        base = Some(FrameBuffer::new(
+            width.div_ceil(LF_SCALE) as usize,
+            height.div_ceil(LF_SCALE) as usize,
+            image.pixel_format().channels(),
+        ));
+    }
+
+    if !full_scales.is_empty() || fill_base {
+        tracing::info!("Rendering full resolution levels");
+        for_each_block(&mut image, block_size, |crop, fb| {
+            for &scale in &full_scales {
+                let level = downsample(&fb, scale as usize);
+                writer
+                    .write_tiles(&level, scale, crop.left / scale, crop.top / scale)
+                    .map_err(Error::WriteImage)?;
+            }
+            if let (Some(base), true) = (&mut base, fill_base) {
+                let downsampled = downsample(&fb, LF_SCALE as usize);
+                paste(
+                    base,
+                    &downsampled,
+                    (crop.left / LF_SCALE) as usize,
+                    (crop.top / LF_SCALE) as usize,
+                );
+            }
+            Ok(())
+        })?;
+    }
+
+    if let Some(mut level) = base {
+        let mut current_scale = LF_SCALE;
+        for &scale in scales.iter().filter(|&&scale| scale >= LF_SCALE) {
+            while current_scale < scale {
+                level = downsample(&level, 2);
+                current_scale *= 2;
+            }
+            writer
+                .write_tiles(&level, scale, 0, 0)
+                .map_err(Error::WriteImage)?;
+        }
+    }
+
+    tracing::info!("Wrote tiles to {}", args.output.display());
+    Ok(())
+}
+
+/// Renders the first keyframe at 1/8 scale from LF data.
+///
+/// The LF image is used directly if the keyframe supports it. Otherwise the keyframe is rendered
+/// from LF coefficients at full resolution and downsampled, block by block. Returns `None` if the
+/// keyframe can't be rendered from LF data.
+fn render_lf_base(image: &mut JxlImage, block_size: u32) -> Result<Option<FrameBuffer>> {
+    if let Some(render) = image.render_frame_lf(0).map_err(Error::Render)? {
+        tracing::info!("Rendering lower levels from LF image");
+        return Ok(Some(render_to_buffer(&render)));
+    }
+
+    let frame_header = image.frame_header(0).unwrap();
+    if frame_header.encoding != jxl_oxide::frame::Encoding::VarDct {
+        return Ok(None);
+    }
+
+    tracing::info!("Rendering lower levels from LF coefficients");
+    let mut base = FrameBuffer::new(
+        image.width().div_ceil(LF_SCALE) as usize,
+        image.height().div_ceil(LF_SCALE) as usize,
+        image.pixel_format().channels(),
+    );
+    image.set_lf_only(true);
+    let result = for_each_block(image, block_size, |crop, fb| {
+        let downsampled = downsample(&fb, LF_SCALE as usize);
+        paste(
+            &mut base,
+            &downsampled,
+            (crop.left / LF_SCALE) as usize,
+            (crop.top / LF_SCALE) as usize,
+        );
+        Ok(())
+    });
+    image.set_lf_only(false);
+    result.map(|_| Some(base))
+}
+
+/// Renders the first keyframe in blocks of at least `block_size`, in raster order.
+///
+/// Block size is rounded up to LF groups, see [`JxlImage::render_tiles`].
+fn for_each_block(
+    image: &mut JxlImage,
+    block_size: u32,
+    mut f: impl FnMut(CropInfo, FrameBuffer) -> Result<()>,
+) -> Result<()> {
+    image
+        .render_tiles(0, block_size, |crop, render| {
+            tracing::debug!(?crop, "Rendered block");
+            f(crop, render_to_buffer(render)).map_err(Into::into)
+        })
+        .map_err(|e| match e.downcast::<Error>() {
+            // Error returned by `f`
+            Ok(e) => *e,
+            Err(e) => Error::Render(e),
+        })
+}
+
+fn render_to_buffer(render: &Render) -> FrameBuffer {
+    let mut stream = render.stream();
+    let mut fb = FrameBuffer::new(
+        stream.width() as usize,
+        stream.height() as usize,
+        stream.channels() as usize,
+    );
+    stream.write_to_buffer(fb.buf_mut());
+    fb
+}
+
+/// Downsamples the image by averaging `factor` by `factor` boxes.
+fn downsample(fb: &FrameBuffer, factor: usize) -> FrameBuffer {
+    if factor == 1 {
+        return fb.clone();
+    }
+
+    let channels = fb.channels();
+    let width = fb.width().div_ceil(factor);
+    let height = fb.height().div_ceil(factor);
+    let mut out = FrameBuffer::new(width, height, channels);
+    let buf = fb.buf();
+    let out_buf = out.buf_mut();
+    for y in 0..height {
+        let rows = (y * factor)..((y + 1) * factor).min(fb.height());
+        for x in 0..width {
+            let cols = (x * factor)..((x + 1) * factor).min(fb.width());
+            let count = (rows.len() * cols.len()) as f32;
+            let out_sample = &mut out_buf[(y * width + x) * channels..][..channels];
+            for sy in rows.clone() {
+                for sx in cols.clone() {
+                    let sample = &buf[(sy * fb.width() + sx) * channels..][..channels];
+                    for (out, &s) in out_sample.iter_mut().zip(sample) {
+                        *out += s;
+                    }
+                }
+            }
+            for out in out_sample {
+                *out /= count;
+            }
+        }
+    }
+    out
+}
+
+/// Copies `src` into `dst` at the given position, clipping at the edges of `dst`.
+fn paste(dst: &mut FrameBuffer, src: &FrameBuffer, left: usize, top: usize) {
+    let channels = dst.channels();
+    let dst_width = dst.width();
+    let width = src.width().min(dst_width.saturating_sub(left));
+    let height = src.height().min(dst.height().saturating_sub(top));
+    let dst_buf = dst.buf_mut();
+    for y in 0..height {
+        let src_row = &src.buf()[y * src.width() * channels..][..width * channels];
+        let dst_row = &mut dst_buf[((top + y) * dst_width + left) * channels..][..width * channels];
+        dst_row.copy_from_slice(src_row);
+    }
+}
+
+/// Crops the image to the given region.
+fn crop(fb: &FrameBuffer, left: usize, top: usize, width: usize, height: usize) -> FrameBuffer {
+    let mut out = FrameBuffer::new(width, height, fb.channels());
+    paste_from(&mut out, fb, left, top);
+    out
+}
+
+fn paste_from(dst: &mut FrameBuffer, src: &FrameBuffer, left: usize, top: usize) {
+    let channels = dst.channels();
+    let row_len = dst.width() * channels;
+    let height = dst.height();
+    let src_stride = src.width() * channels;
+    let dst_buf = dst.buf_mut();
+    for y in 0..height {
+        let src_row = &src.buf()[(top + y) * src_stride + left * channels..][..row_len];
+        dst_buf[y * row_len..][..row_len].copy_from_slice(src_row);
+    }
+}
+
+struct TileWriter {
+    layout: TileLayout,
+    format: TileFormat,
+    pixel_format: PixelFormat,
+    output: PathBuf,
+    name: String,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    /// Tiles spanning multiple blocks, keyed by scale, column and row.
+    pending: HashMap<(u32, u32, u32), PendingTile>,
+}
+
+struct PendingTile {
+    tile: FrameBuffer,
+    pixels_left: usize,
+}
+
+impl TileWriter {
+    /// Returns downsampling factors of pyramid levels, from the largest level.
+    fn scales(&self) -> Vec<u32> {
+        let max_dim = self.width.max(self.height);
+        let mut scales = vec![1u32];
+        match self.layout {
+            TileLayout::Dzi => {
+                // Deep Zoom pyramid goes down to 1x1.
+                let max_level = max_dim.next_power_of_two().ilog2();
+                scales.extend((1..=max_level).map(|level| 1 << level));
+            }
+            TileLayout::Iiif => {
+                let mut scale = 1u32;
+                while max_dim.div_ceil(scale) > self.tile_size {
+                    scale *= 2;
+                    scales.push(scale);
+                }
+            }
+        }
+        scales
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.format {
+            TileFormat::Png => "png",
+            TileFormat::Raw => "raw",
+        }
+    }
+
+    fn write_manifest(&self, iiif_id: Option<&str>) -> std::io::Result<()> {
+        match self.layout {
+            TileLayout::Dzi => {
+                let manifest = format!(
+                    concat!(
+                        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                        "<Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" ",
+                        "Format=\"{format}\" Overlap=\"0\" TileSize=\"{tile_size}\">\n",
+                        "  <Size Width=\"{width}\" Height=\"{height}\"/>\n",
+                        "</Image>\n",
+                    ),
+                    format = self.extension(),
+                    tile_size = self.tile_size,
+                    width = self.width,
+                    height = self.height,
+                );
+                let path = self.output.join(format!("{}.dzi", self.name));
+                std::fs::write(path, manifest)
+            }
+            TileLayout::Iiif => {
+                let id = match iiif_id {
+                    Some(id) => id.to_owned(),
+                    None => self
+                        .output
+                        .file_name()
+                        .map(|s| s.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                };
+                let manifest = json!({
+                    "@context": "http://iiif.io/api/image/3/context.json",
+                    "id": id,
+                    "type": "ImageService3",
+                    "protocol": "http://iiif.io/api/image",
+                    "profile": "level0",
+                    "width": self.width,
+                    "height": self.height,
+                    "tiles": [{
+                        "width": self.tile_size,
+                        "height": self.tile_size,
+                        "scaleFactors": self.scales(),
+                    }],
+                    "preferredFormats": [self.extension()],
+                });
+                let manifest = format!("{manifest:#}\n");
+                std::fs::write(self.output.join("info.json"), manifest)
+            }
+        }
+    }
+
+    /// Writes tiles covered by `level`, which is a part of the level at the given scale starting
+    /// at (`left`, `top`) in level coordinates.
+    ///
+    /// Tiles which are only partially covered are kept until the rest of them are given.
+    fn write_tiles(
+        &mut self,
+        level: &FrameBuffer,
+        scale: u32,
+        left: u32,
+        top: u32,
+    ) -> std::io::Result<()> {
+        if level.width() == 0 || level.height() == 0 {
+            return Ok(());
+        }
+
+        let tile_size = self.tile_size;
+        let level_width = self.width.div_ceil(scale);
+        let level_height = self.height.div_ceil(scale);
+        let right = left + level.width() as u32;
+        let bottom = top + level.height() as u32;
+        for row in (top / tile_size)..bottom.div_ceil(tile_size) {
+            for col in (left / tile_size)..right.div_ceil(tile_size) {
+                let x = col * tile_size;
+                let y = row * tile_size;
+                let w = tile_size.min(level_width - x);
+                let h = tile_size.min(level_height - y);
+
+                // Part of the tile covered by `level`
+                let covered_left = x.max(left);
+                let covered_top = y.max(top);
+                let covered_width = (x + w).min(right) - covered_left;
+                let covered_height = (y + h).min(bottom) - covered_top;
+                let part = crop(
+                    level,
+                    (covered_left - left) as usize,
+                    (covered_top - top) as usize,
+                    covered_width as usize,
+                    covered_height as usize,
+                );
+
+                let tile = if covered_width == w && covered_height == h {
+                    part
+                } else {
+                    let key = (scale, col, row);
+                    let pending = self.pending.entry(key).or_insert_with(|| PendingTile {
+                        tile: FrameBuffer::new(w as usize, h as usize, level.channels()),
+                        pixels_left: (w * h) as usize,
+                    });
+                    paste(
+                        &mut pending.tile,
+                        &part,
+                        (covered_left - x) as usize,
+                        (covered_top - y) as usize,
+                    );
+                    pending.pixels_left -= part.width() * part.height();
+                    if pending.pixels_left > 0 {
+                        continue;
+                    }
+                    self.pending.remove(&key).unwrap().tile
+                };
+
+                let path = self.tile_path(scale, col, row, w, h);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                self.write_tile(&path, &tile)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn tile_path(&self, scale: u32, col: u32, row: u32, width: u32, height: u32) -> PathBuf {
+        let ext = self.extension();
+        match self.layout {
+            TileLayout::Dzi => {
+                let max_level = self.width.max(self.height).next_power_of_two().ilog2();
+                let level = max_level - scale.ilog2();
+                self.output
+                    .join(format!("{}_files", self.name))
+                    .join(level.to_string())
+                    .join(format!("{col}_{row}.{ext}"))
+            }
+            TileLayout::Iiif => {
+                // Region is in full resolution coordinates.
+                let region_size = self.tile_size * scale;
+                let x = col * region_size;
+                let y = row * region_size;
+                let region_width = region_size.min(self.width - x);
+                let region_height = region_size.min(self.height - y);
+                self.output
+                    .join(format!("{x},{y},{region_width},{region_height}"))
+                    .join(format!("{width},{height}"))
+                    .join("0")
+                    .join(format!("default.{ext}"))
+            }
+        }
+    }
+
+    fn write_tile(&self, path: &Path, tile: &FrameBuffer) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        let mut file = std::io::BufWriter::new(file);
+        match self.format {
+            TileFormat::Png => {
+                let color_type = match self.pixel_format {
+                    PixelFormat::Gray => png::ColorType::Grayscale,
+                    PixelFormat::Graya => png::ColorType::GrayscaleAlpha,
+                    PixelFormat::Rgb => png::ColorType::Rgb,
+                    PixelFormat::Rgba => png::ColorType::Rgba,
+                    PixelFormat::Cmyk | PixelFormat::Cmyka => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "PNG tiles cannot be written from CMYK image",
+                        ))
+                    }
+                };
+                if tile.channels() != color_type.samples() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "tile has unexpected number of channels",
+                    ));
+                }
+
+                let mut encoder =
+                    png::Encoder::new(&mut file, tile.width() as u32, tile.height() as u32);
+                encoder.set_color(color_type);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_srgb(png::SrgbRenderingIntent::RelativeColorimetric);
+                let mut writer = encoder.write_header()?;
+
+                let buf = tile
+                    .buf()
+                    .iter()
+                    .map(|&s| (s * 255.0 + 0.5).clamp(0.0, 255.0) as u8)
+                    .collect::<Vec<_>>();
+                writer.write_image_data(&buf)?;
+                writer.finish()?;
+            }
+            TileFormat::Raw => {
+                for sample in tile.buf() {
+                    file.write_all(&sample.to_le_bytes())?;
+                }
+            }
+        }
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::test_util::{temp_dir, SMALL_IMAGE};
+
+    fn count_files(dir: &Path) -> usize {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                if entry.file_type().unwrap().is_dir() {
+                    count_files(&entry.path())
+                } else {
+                    1
+                }
+            })
+            .sum()
+    }
+
+    fn writer(dir: &Path, layout: TileLayout, width: u32, height: u32) -> TileWriter {
+        TileWriter {
+            layout,
+            format: TileFormat::Raw,
+            pixel_format: PixelFormat::Gray,
+            output: dir.to_owned(),
+            name: String::from("image"),
+            width,
+            height,
+            tile_size: 16,
+            pending: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn dzi_pyramid() {
+        let dir = temp_dir("tiles-dzi");
+        let input = dir.join("image.jxl");
+        std::fs::write(&input, SMALL_IMAGE).unwrap();
+        let output = dir.join("out");
+
+        let args = TilesArgs::parse_from([
+            "tiles".as_ref(),
+            input.as_os_str(),
+            "-o".as_ref(),
+            output.as_os_str(),
+            "--tile-size".as_ref(),
+            "64".as_ref(),
+        ]);
+        handle_tiles(args).unwrap();
+
+        let manifest = std::fs::read_to_string(output.join("image.dzi")).unwrap();
+        assert!(manifest.contains(r#"Format="png" Overlap="0" TileSize="64""#));
+        assert!(manifest.contains(r#"<Size Width="240" Height="135"/>"#));
+
+        // Levels 0 to 8; 240x135 at level 8, 1x1 at level 0.
+        let files = output.join("image_files");
+        assert_eq!(std::fs::read_dir(&files).unwrap().count(), 9);
+        assert_eq!(count_files(&files.join("8")), 12);
+        assert_eq!(count_files(&files.join("7")), 4);
+        for level in 0..=6 {
+            assert_eq!(count_files(&files.join(level.to_string())), 1);
+        }
+        assert!(files.join("8/3_2.png").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn iiif_manifest() {
+        let dir = temp_dir("tiles-iiif");
+        let writer = writer(&dir, TileLayout::Iiif, 100, 50);
+        writer
+            .write_manifest(Some("https://example.com/\"image\""))
+            .unwrap();
+
+        let manifest = std::fs::read_to_string(dir.join("info.json")).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["id"], "https://example.com/\"image\"");
+        assert_eq!(manifest["profile"], "level0");
+        assert_eq!(manifest["width"], 100);
+        assert_eq!(manifest["height"], 50);
+        assert_eq!(manifest["tiles"][0]["width"], 16);
+        assert_eq!(
+            manifest["tiles"][0]["scaleFactors"],
+            serde_json::json!([1, 2, 4, 8])
+        );
+        assert_eq!(manifest["preferredFormats"], serde_json::json!(["raw"]));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tiles_spanning_blocks() {
+        let dir = temp_dir("tiles-span");
+        let mut writer = writer(&dir, TileLayout::Iiif, 40, 20);
+
+        // Image where each sample is its x coordinate, given in two blocks split at x = 24.
+        let mut level = FrameBuffer::new(40, 20, 1);
+        for (idx, sample) in level.buf_mut().iter_mut().enumerate() {
+            *sample = (idx % 40) as f32;
+        }
+        let left_block = crop(&level, 0, 0, 24, 20);
+        let right_block = crop(&level, 24, 0, 16, 20);
+        writer.write_tiles(&left_block, 1, 0, 0).unwrap();
+        assert_eq!(writer.pending.len(), 2);
+        writer.write_tiles(&right_block, 1, 24, 0).unwrap();
+        assert!(writer.pending.is_empty());
+        assert_eq!(count_files(&dir), 6);
+
+        // Tile at column 1, row 0 spans both blocks.
+        let tile = std::fs::read(dir.join("16,0,16,16/16,16/0/default.raw")).unwrap();
+        let row = tile[..16 * 4]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(row, (16..32).map(|x| x as f32).collect::<Vec<_>>());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cmyk_png_tiles() {
+        let dir = temp_dir("tiles-cmyk");
+        let mut writer = writer(&dir, TileLayout::Iiif, 16, 16);
+        writer.format = TileFormat::Png;
+        writer.pixel_format = PixelFormat::Cmyk;
+
+        let level = FrameBuffer::new(16, 16, 4);
+        let err = writer.write_tiles(&level, 1, 0, 0).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,6 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
 use jxl_oxide::{AllocTracker, JxlImage, JxlThreadPool};
 
 pub fn fuzz_decode(data: &[u8], dimension_limit: u32, alloc_limit: usize) {
@@ -18,3 +21,179 @@ pub fn fuzz_decode(data: &[u8], dimension_limit: u32, alloc_limit: usize) {
         }
     }
 }
+
+/// Runs [`fuzz_decode`], and checks that heap usage stays within the allocation limit.
+///
+/// Heap usage is measured by [`CountingAllocator`], which should be set as the global allocator.
+/// Untracked allocations, such as copies of the input, are allowed up to `untracked_limit` bytes.
+pub fn fuzz_decode_memory_limit(
+    data: &[u8],
+    dimension_limit: u32,
+    alloc_limit: usize,
+    untracked_limit: usize,
+) {
+    reset_peak_heap_usage();
+    fuzz_decode(data, dimension_limit, alloc_limit);
+    let peak = peak_heap_usage();
+    let limit = alloc_limit + untracked_limit;
+    assert!(
+        peak <= limit,
+        "heap usage exceeded the limit: peak {peak} bytes, limit {limit} bytes"
+    );
+}
+
+thread_local! {
+    static CURRENT_HEAP_USAGE: Cell<isize> = const { Cell::new(0) };
+    static PEAK_HEAP_USAGE: Cell<isize> = const { Cell::new(0) };
+    static BASELINE_HEAP_USAGE: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Global allocator which records heap usage of the current thread.
+///
+/// Decoding should be done with [`JxlThreadPool::none`] so that every allocation is counted.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn record(diff: isize) {
+        let _ = CURRENT_HEAP_USAGE.try_with(|current| {
+            let usage = current.get() + diff;
+            current.set(usage);
+            let _ = PEAK_HEAP_USAGE.try_with(|peak| peak.set(peak.get().max(usage)));
+        });
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+/// Resets peak heap usage of the current thread.
+pub fn reset_peak_heap_usage() {
+    let current = CURRENT_HEAP_USAGE.with(|current| current.get());
+    BASELINE_HEAP_USAGE.with(|baseline| baseline.set(current));
+    PEAK_HEAP_USAGE.with(|peak| peak.set(current));
+}
+
+/// Returns peak heap usage of the current thread since the last reset, relative to the usage at
+/// the time of the reset.
+pub fn peak_heap_usage() -> usize {
+    let baseline = BASELINE_HEAP_USAGE.with(|baseline| baseline.get());
+    let peak = PEAK_HEAP_USAGE.with(|peak| peak.get());
+    (peak - baseline).max(0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static ALLOC: CountingAllocator = CountingAllocator;
+
+    /// Writes bits in the order of the JPEG XL bitstream.
+    #[derive(Default)]
+    struct BitWriter {
+        buf: Vec<u8>,
+        num_bits: usize,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, bits: usize) {
+            for idx in 0..bits {
+                if self.num_bits & 7 == 0 {
+                    self.buf.push(0);
+                }
+                let bit = ((value >> idx) & 1) as u8;
+                *self.buf.last_mut().unwrap() |= bit << (self.num_bits & 7);
+                self.num_bits += 1;
+            }
+        }
+
+        fn zero_pad_to_byte(&mut self) {
+            self.num_bits = self.buf.len() * 8;
+        }
+    }
+
+    /// Creates an 8x8 image with a single frame, whose TOC claims that the frame data is about
+    /// 1 GiB long.
+    fn huge_toc_image(data_len: usize) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.write(0xaff, 16); // signature
+        w.write(1, 1); // SizeHeader.div8
+        w.write(0, 5); // SizeHeader.h_div8 - 1
+        w.write(1, 3); // SizeHeader.ratio, 1:1
+        w.write(1, 1); // ImageMetadata.all_default
+        w.write(1, 1); // ImageMetadata.default_m
+        w.zero_pad_to_byte();
+        w.write(1, 1); // FrameHeader.all_default
+        w.write(0, 1); // TOC is not permuted
+        w.zero_pad_to_byte();
+        w.write(3, 2); // TOC entry, 4211712 + u(30)
+        w.write((1 << 30) - 1, 30);
+        w.zero_pad_to_byte();
+
+        let mut out = w.buf;
+        out.resize(out.len() + data_len, 0x55);
+        out
+    }
+
+    #[test]
+    fn huge_toc_size_is_not_reserved() {
+        let data = huge_toc_image(4096);
+        reset_peak_heap_usage();
+        let image = JxlImage::builder()
+            .pool(JxlThreadPool::none())
+            .alloc_tracker(AllocTracker::with_limit(64 << 20))
+            .read(std::io::Cursor::new(&data))
+            .unwrap();
+        assert!(!image.is_loading_done());
+        drop(image);
+        assert!(peak_heap_usage() < 16 << 20);
+    }
+
+    #[test]
+    fn frame_data_exceeding_limit() {
+        let data = huge_toc_image(256 * 1024);
+        let tracker = AllocTracker::with_limit(64 * 1024);
+        let result = JxlImage::builder()
+            .pool(JxlThreadPool::none())
+            .alloc_tracker(tracker.clone())
+            .read(std::io::Cursor::new(&data));
+        assert!(result.is_err());
+
+        let stats = tracker.stats();
+        assert!(stats.failed_allocs > 0);
+        assert!(stats.total.peak_bytes <= 64 * 1024);
+    }
+
+    #[test]
+    fn memory_limit() {
+        fuzz_decode_memory_limit(&huge_toc_image(64 * 1024), 65536, 1 << 20, 16 << 20);
+    }
+}
@@ -140,31 +140,39 @@
 //! ```
 //!
 //! # Feature flags
-//! - `rayon`: Enable multithreading with Rayon. (*default*)
+//! - `rayon`: Enable multithreading with Rayon. (*default*) Without this feature, a thread pool
+//!   using only the standard library can be created with [`JxlThreadPool::std_threads`].
 //! - `lcms2`: Enable integration with Little CMS 2.
+//! - `serde`: Implement `Serialize` and `Deserialize` for image headers, frame headers and TOC.
 use std::sync::Arc;
 
 use image::BitDepth;
-use jxl_bitstream::ContainerDetectingReader;
 use jxl_bitstream::Name;
 use jxl_bitstream::{Bitstream, Bundle};
-use jxl_frame::FrameContext;
 use jxl_render::ImageBuffer;
 use jxl_render::ImageWithRegion;
 use jxl_render::Region;
 use jxl_render::{IndexedFrame, RenderContext};
 
-pub use jxl_bitstream::Lz77Mode;
+pub use jxl_bitstream::{
+    BitstreamKind, ContainerBoxInfo, ContainerBoxType, ContainerDetectingReader, Lz77Mode,
+};
 pub use jxl_color::header as color;
+pub use jxl_color::icc;
 pub use jxl_color::{
-    ColorEncodingWithProfile, ColorManagementSystem, EnumColourEncoding, NullCms, RenderingIntent,
+    convert_color, convert_to_xyb, ColorEncodingWithProfile, ColorManagementSystem,
+    EnumColourEncoding, NullCms, RenderingIntent,
 };
+pub use jxl_frame::data::{Toc, TocGroup, TocGroupKind};
 pub use jxl_frame::header as frame;
 pub use jxl_frame::{Frame, FrameHeader};
-pub use jxl_grid::{AlignedGrid, AllocTracker};
+pub use jxl_grid::{AlignedGrid, AllocCategory, AllocCategoryStats, AllocStats, AllocTracker};
 pub use jxl_image as image;
 pub use jxl_image::{ExtraChannelType, ImageHeader};
-pub use jxl_threadpool::JxlThreadPool;
+pub use jxl_render::{
+    CancellationToken, DamagedGroup, Error as RenderError, GroupKind, RenderProgress,
+};
+pub use jxl_threadpool::{JxlExecutor, JxlExecutorScope, JxlScopedJob, JxlThreadPool};
 
 mod fb;
 #[cfg(feature = "lcms2")]
@@ -191,6 +199,8 @@ fn default_pool() -> JxlThreadPool {
 pub struct JxlImageBuilder {
     pool: Option<JxlThreadPool>,
     tracker: Option<AllocTracker>,
+    buffer_pool: Option<usize>,
+    icc_match_tolerance: icc::IccMatchTolerance,
     lz77_mode: Lz77Mode,
 }
 
@@ -207,6 +217,27 @@ impl JxlImageBuilder {
         self
     }
 
+    /// Enables the buffer pool, which keeps up to `max_idle_bytes` bytes of released grid buffers
+    /// for reuse across frames and renders.
+    ///
+    /// Idle buffers are recorded in the allocation tracker, and they're released when the memory
+    /// limit is reached. An allocation tracker without limit is created if none is set.
+    pub fn buffer_pool(mut self, max_idle_bytes: usize) -> Self {
+        self.buffer_pool = Some(max_idle_bytes);
+        self
+    }
+
+    /// Sets the tolerance used when matching embedded ICC profile to an enum color encoding.
+    ///
+    /// Images with ICC profiles matched to enum color encodings are converted without using
+    /// CMS. Only exact matches are used by default; use
+    /// [`IccMatchTolerance::APPROXIMATE`][icc::IccMatchTolerance::APPROXIMATE] to also match
+    /// sampled tone curves and slightly different parameters.
+    pub fn icc_match_tolerance(mut self, tolerance: icc::IccMatchTolerance) -> Self {
+        self.icc_match_tolerance = tolerance;
+        self
+    }
+
     #[doc(hidden)]
     pub fn lz77_mode(mut self, lz77_mode: Lz77Mode) -> Self {
         self.lz77_mode = lz77_mode;
@@ -215,9 +246,19 @@ impl JxlImageBuilder {
 
     /// Consumes the builder, and creates an empty, uninitialized JPEG XL image decoder.
     pub fn build_uninit(self) -> UninitializedJxlImage {
+        let tracker = match self.buffer_pool {
+            Some(max_idle_bytes) => Some(
+                self.tracker
+                    .unwrap_or_else(|| AllocTracker::with_limit(usize::MAX))
+                    .with_buffer_pool(max_idle_bytes),
+            ),
+            None => self.tracker,
+        };
+
         UninitializedJxlImage {
             pool: self.pool.unwrap_or_else(default_pool),
-            tracker: self.tracker,
+            tracker,
+            icc_match_tolerance: self.icc_match_tolerance,
             reader: ContainerDetectingReader::new(),
             buffer: Vec::new(),
             lz77_mode: self.lz77_mode,
@@ -296,6 +337,7 @@ impl JxlImageBuilder {
 pub struct UninitializedJxlImage {
     pool: JxlThreadPool,
     tracker: Option<AllocTracker>,
+    icc_match_tolerance: icc::IccMatchTolerance,
     reader: ContainerDetectingReader,
     buffer: Vec<u8>,
     lz77_mode: Lz77Mode,
@@ -335,34 +377,54 @@ impl UninitializedJxlImage {
             }
         };
 
-        let embedded_icc = if image_header.metadata.colour_encoding.want_icc() {
-            let icc = match jxl_color::icc::read_icc(&mut bitstream) {
-                Ok(x) => x,
-                Err(e) if e.unexpected_eof() => {
-                    return Ok(InitializeResult::NeedMoreData(self));
-                }
-                Err(e) => {
-                    return Err(e.into());
-                }
+        let (embedded_icc, mut icc_alloc_handle) =
+            if image_header.metadata.colour_encoding.want_icc() {
+                let (icc, _stream_alloc_handle) = match jxl_color::icc::read_icc_with_tracker(
+                    &mut bitstream,
+                    self.tracker.as_ref(),
+                ) {
+                    Ok(x) => x,
+                    Err(e) if e.unexpected_eof() => {
+                        return Ok(InitializeResult::NeedMoreData(self));
+                    }
+                    Err(e) => {
+                        return Err(e.into());
+                    }
+                };
+                tracing::debug!("Image has an embedded ICC profile");
+                let (icc, alloc_handle) =
+                    jxl_color::icc::decode_icc_with_tracker(&icc, self.tracker.as_ref())?;
+                (Some(icc), alloc_handle)
+            } else {
+                (None, None)
             };
-            tracing::debug!("Image has an embedded ICC profile");
-            let icc = jxl_color::icc::decode_icc(&icc)?;
-            Some(icc)
-        } else {
-            None
-        };
         bitstream.zero_pad_to_byte()?;
 
         let image_header = Arc::new(image_header);
-        let skip_bytes = if image_header.metadata.preview.is_some() {
-            let frame = match Frame::parse(
-                &mut bitstream,
-                FrameContext {
-                    image_header: image_header.clone(),
-                    tracker: self.tracker.as_ref(),
-                    pool: self.pool.clone(),
-                },
-            ) {
+        let (preview, skip_bytes) = if let Some(preview_header) = &image_header.metadata.preview {
+            // Preview frame is coded with the dimension of the preview image.
+            let mut header_bitstream = Bitstream::new(&self.buffer);
+            let mut preview_image_header = ImageHeader::parse(&mut header_bitstream, ())?;
+            preview_image_header.size.width = preview_header.width;
+            preview_image_header.size.height = preview_header.height;
+            let preview_image_header = Arc::new(preview_image_header);
+
+            let mut builder = RenderContext::builder()
+                .pool(self.pool.clone())
+                .icc_match_tolerance(self.icc_match_tolerance);
+            if let Some(icc) = &embedded_icc {
+                // The preview context holds its own copy of the profile.
+                if let Some(handle) = &mut icc_alloc_handle {
+                    handle.grow::<u8>(icc.len())?;
+                }
+                builder = builder.embedded_icc(icc.clone());
+            }
+            if let Some(tracker) = &self.tracker {
+                builder = builder.alloc_tracker(tracker.clone());
+            }
+            let mut ctx = builder.build(preview_image_header.clone())?;
+
+            let frame = match ctx.load_frame_header(&mut bitstream) {
                 Ok(x) => x,
                 Err(e) if e.unexpected_eof() => {
                     return Ok(InitializeResult::NeedMoreData(self));
@@ -377,10 +439,16 @@ impl UninitializedJxlImage {
             if self.buffer.len() < bytes_read + x {
                 return Ok(InitializeResult::NeedMoreData(self));
             }
+            frame.feed_bytes(&self.buffer[bytes_read..][..x])?;
+            ctx.finalize_current_frame();
 
-            x
+            let preview = Preview {
+                image_header: preview_image_header,
+                ctx,
+            };
+            (Some(preview), x)
         } else {
-            0usize
+            (None, 0usize)
         };
 
         let bytes_read = bitstream.num_read_bits() / 8 + skip_bytes;
@@ -388,7 +456,9 @@ impl UninitializedJxlImage {
 
         let render_spot_color = !image_header.metadata.grayscale();
 
-        let mut builder = RenderContext::builder().pool(self.pool.clone());
+        let mut builder = RenderContext::builder()
+            .pool(self.pool.clone())
+            .icc_match_tolerance(self.icc_match_tolerance);
         if let Some(icc) = embedded_icc {
             builder = builder.embedded_icc(icc);
         }
@@ -411,6 +481,8 @@ impl UninitializedJxlImage {
             buffer_offset: bytes_read,
             frame_offsets: Vec::new(),
             lz77_mode: self.lz77_mode,
+            preview,
+            _icc_alloc_handle: icc_alloc_handle,
         };
         image.feed_bytes_inner(&self.buffer)?;
 
@@ -439,6 +511,16 @@ pub struct JxlImage {
     buffer_offset: usize,
     frame_offsets: Vec<usize>,
     lz77_mode: Lz77Mode,
+    preview: Option<Preview>,
+    /// Allocation handle recording the embedded ICC profile held by the render contexts.
+    _icc_alloc_handle: Option<jxl_grid::AllocHandle>,
+}
+
+/// Preview frame, loaded into a separate render context.
+#[derive(Debug)]
+struct Preview {
+    image_header: Arc<ImageHeader>,
+    ctx: RenderContext,
 }
 
 impl JxlImage {
@@ -468,7 +550,7 @@ impl JxlImage {
         if let Some(loading_frame) = self.ctx.current_loading_frame() {
             debug_assert!(self.buffer.is_empty());
             let len = buf.len();
-            buf = loading_frame.feed_bytes(buf);
+            buf = loading_frame.feed_bytes(buf)?;
             let count = len - buf.len();
             self.buffer_offset += count;
 
@@ -508,7 +590,7 @@ impl JxlImage {
             let read_bytes = bitstream.num_read_bits() / 8;
             buf = &buf[read_bytes..];
             let len = buf.len();
-            buf = frame.feed_bytes(buf);
+            buf = frame.feed_bytes(buf)?;
             let read_bytes = read_bytes + (len - buf.len());
             self.buffer_offset += read_bytes;
 
@@ -574,6 +656,26 @@ impl JxlImage {
         }
     }
 
+    /// Returns the ICC profile that describes rendered images, synthesized with the given
+    /// options.
+    ///
+    /// If `hdr_peak_luminance` of the options is not set, intensity target of the image is used.
+    /// ICC profile requested using [`request_icc`][Self::request_icc] is returned as-is.
+    pub fn rendered_icc_with_options(&self, options: icc::IccSynthesisOptions) -> Vec<u8> {
+        let encoding = self.ctx.requested_color_encoding();
+        match encoding.encoding() {
+            jxl_color::ColourEncoding::Enum(encoding) => {
+                let mut options = options;
+                if options.hdr_peak_luminance.is_none() {
+                    let intensity_target = self.image_header.metadata.tone_mapping.intensity_target;
+                    options.hdr_peak_luminance = Some(intensity_target);
+                }
+                icc::colour_encoding_to_icc_with_options(encoding, options)
+            }
+            jxl_color::ColourEncoding::IccProfile(_) => encoding.icc_profile().to_vec(),
+        }
+    }
+
     /// Returns the CICP tag of the color encoding of rendered images, if there's any.
     #[inline]
     pub fn rendered_cicp(&self) -> Option<[u8; 4]> {
@@ -639,6 +741,89 @@ impl JxlImage {
             .request_color_encoding(ColorEncodingWithProfile::new(color_encoding))
     }
 
+    /// Requests the decoder to render unclamped, linear samples in absolute luminance.
+    ///
+    /// Linear sample value of 1.0 in rendered images represents `reference_white` nits, computed
+    /// from the intensity target of the image. No tone mapping or gamut mapping is done, so
+    /// samples may be negative or larger than 1.0. Transfer function of `color_encoding` is
+    /// ignored; only the color space, primaries and white point are used.
+    ///
+    /// For scRGB output, use [`EnumColourEncoding::srgb_linear`] with `reference_white` of `80.0`.
+    pub fn request_extended_linear(
+        &mut self,
+        color_encoding: EnumColourEncoding,
+        reference_white: f32,
+    ) {
+        self.ctx
+            .request_extended_linear(color_encoding, reference_white)
+    }
+
+    /// Sets the nominal peak luminance of the target display, in nits, used by HLG OOTF.
+    ///
+    /// System gamma of HLG OOTF is derived from this value as described in Rec. ITU-R BT.2100.
+    /// This affects rendering from and to color encodings with HLG transfer function. If `None`
+    /// is given, which is the default, intensity target of the image is used.
+    #[inline]
+    pub fn set_hlg_display_luminance(&mut self, luminance: Option<f32>) {
+        self.ctx.set_hlg_display_luminance(luminance);
+    }
+
+    /// Sets whether HLG signals are treated as scene-referred.
+    ///
+    /// If `true`, HLG OOTF is not applied, and rendering to HLG produces scene-referred signals.
+    #[inline]
+    pub fn set_hlg_scene_referred(&mut self, value: bool) {
+        self.ctx.set_hlg_scene_referred(value);
+    }
+
+    /// Sets whether VarDCT frames are rendered using LF coefficients only.
+    ///
+    /// HF coefficients are not decoded, so images are rendered faster with the quality of 8x
+    /// downsampled images, or LF frames if the image has ones. This is useful for generating
+    /// thumbnails. Frames with extra channels stored in pass groups are rendered in full.
+    #[inline]
+    pub fn set_lf_only(&mut self, value: bool) {
+        self.ctx.set_lf_only(value);
+    }
+
+    /// Sets whether rendering tolerates groups which fail to decode or are missing.
+    ///
+    /// In error-tolerant mode, a damaged pass group is rendered from the passes before the damaged
+    /// one, and a damaged LF group is filled with neutral gray. Rendering succeeds as long as frame headers and global data are
+    /// intact, and damaged groups are listed in [`Render::damaged_groups`]. Disabled by default.
+    #[inline]
+    pub fn set_error_tolerant(&mut self, value: bool) {
+        self.ctx.set_error_tolerant(value);
+    }
+
+    /// Sets the cancellation token checked while rendering.
+    ///
+    /// Cancellation is checked between groups and rendering stages. Once the token is cancelled,
+    /// rendering fails with [`RenderError::Cancelled`]. Progress made so far is kept, so rendering
+    /// again with a new token resumes from there.
+    #[inline]
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.ctx.set_cancellation_token(token);
+    }
+
+    /// Sets the callback which receives the number of completed groups of frames being rendered.
+    ///
+    /// Reference frames and LF frames needed by the keyframe report progress with their own frame
+    /// indices. The callback may be called from multiple threads concurrently.
+    #[inline]
+    pub fn set_progress_callback(
+        &mut self,
+        callback: impl Fn(RenderProgress) + Send + Sync + 'static,
+    ) {
+        self.ctx.set_progress_callback(callback);
+    }
+
+    /// Removes the progress callback.
+    #[inline]
+    pub fn clear_progress_callback(&mut self) {
+        self.ctx.clear_progress_callback();
+    }
+
     /// Returns whether the spot color channels will be rendered.
     #[inline]
     pub fn render_spot_color(&self) -> bool {
@@ -683,6 +868,46 @@ impl JxlImage {
         self.end_of_image
     }
 
+    /// Returns the number of progressive stages of the currently loading frame which are fully
+    /// loaded.
+    ///
+    /// Stages are, in order, LF global data, LF groups with HF global data, and each pass of pass
+    /// groups. Returns 0 if no frame is being loaded or the frame consists of a single group, as
+    /// such frames are not rendered progressively.
+    pub fn loading_frame_stage(&self) -> usize {
+        let Some(frame) = self.ctx.frame(self.ctx.loaded_frames()) else {
+            return 0;
+        };
+        let toc = frame.toc();
+        if toc.is_single_entry() {
+            return 0;
+        }
+
+        let frame_header = frame.header();
+        let mut lf_global = false;
+        let mut hf_global = false;
+        let mut lf_groups = 0u32;
+        let mut pass_groups = vec![0u32; frame_header.passes.num_passes as usize];
+        for group in toc.iter_bitstream_order().take(frame.num_loaded_groups()) {
+            match group.kind {
+                TocGroupKind::LfGlobal => lf_global = true,
+                TocGroupKind::LfGroup(_) => lf_groups += 1,
+                TocGroupKind::HfGlobal => hf_global = true,
+                TocGroupKind::GroupPass { pass_idx, .. } => pass_groups[pass_idx as usize] += 1,
+                TocGroupKind::All => {}
+            }
+        }
+
+        if !lf_global {
+            0
+        } else if !hf_global || lf_groups < frame_header.num_lf_groups() {
+            1
+        } else {
+            let num_groups = frame_header.num_groups();
+            2 + pass_groups.iter().take_while(|&&n| n == num_groups).count()
+        }
+    }
+
     /// Returns frame data by keyframe index.
     pub fn frame_by_keyframe(&self, keyframe_index: usize) -> Option<&IndexedFrame> {
         self.ctx.keyframe(keyframe_index)
@@ -740,6 +965,7 @@ impl JxlImage {
             target_frame_region,
             color_bit_depth: self.image_header.metadata.bit_depth,
             render_spot_color: self.render_spot_color,
+            damaged_groups: self.ctx.damaged_groups(),
         };
         Ok(result)
     }
@@ -774,10 +1000,212 @@ impl JxlImage {
             target_frame_region,
             color_bit_depth: self.image_header.metadata.bit_depth,
             render_spot_color: self.render_spot_color,
+            damaged_groups: self.ctx.damaged_groups(),
         };
         Ok(result)
     }
 
+    /// Returns whether the image has a preview frame.
+    #[inline]
+    pub fn has_preview(&self) -> bool {
+        self.preview.is_some()
+    }
+
+    /// Renders the preview frame, or returns `None` if the image doesn't have one.
+    ///
+    /// The preview is rendered in full with the color encoding, color management system and HLG
+    /// options of the main image; image region is not applied.
+    pub fn render_preview(&mut self) -> Result<Option<Render>> {
+        let extra_channels = self.convert_ec_info();
+        let Some(preview) = &mut self.preview else {
+            return Ok(None);
+        };
+        preview.ctx.copy_output_options(&self.ctx);
+        let image = preview.ctx.render_keyframe(0)?;
+
+        let image_region = preview
+            .ctx
+            .image_region()
+            .apply_orientation(&preview.image_header);
+        let frame = preview.ctx.keyframe(0).unwrap();
+        let frame_header = frame.header();
+        let target_frame_region = image_region.translate(-frame_header.x0, -frame_header.y0);
+
+        let result = Render {
+            keyframe_index: 0,
+            name: frame_header.name.clone(),
+            duration: frame_header.duration,
+            orientation: self.image_header.metadata.orientation,
+            image,
+            extra_channels,
+            target_frame_region,
+            color_bit_depth: self.image_header.metadata.bit_depth,
+            render_spot_color: self.render_spot_color,
+            damaged_groups: preview.ctx.damaged_groups(),
+        };
+        Ok(Some(result))
+    }
+
+    /// Renders the LF image of the given keyframe, at 1/8 of the image dimension.
+    ///
+    /// HF coefficients are not decoded, so this is much faster than rendering the keyframe and
+    /// downsampling it. Restoration filters, splines and noise are not applied. The whole keyframe
+    /// is rendered regardless of the image region.
+    ///
+    /// Returns `Ok(None)` if the keyframe can't be rendered from its LF image alone, such as
+    /// modular frames, or frames with blending, patches or extra channels.
+    pub fn render_frame_lf(&self, keyframe_index: usize) -> Result<Option<Render>> {
+        let Some(image) = self.ctx.render_keyframe_lf(keyframe_index)? else {
+            return Ok(None);
+        };
+
+        let frame = self.ctx.keyframe(keyframe_index).unwrap();
+        let frame_header = frame.header();
+        let target_frame_region = Region::with_size(
+            frame_header.width.div_ceil(8),
+            frame_header.height.div_ceil(8),
+        );
+
+        let result = Render {
+            keyframe_index,
+            name: frame_header.name.clone(),
+            duration: frame_header.duration,
+            orientation: self.image_header.metadata.orientation,
+            image,
+            extra_channels: Vec::new(),
+            target_frame_region,
+            color_bit_depth: self.image_header.metadata.bit_depth,
+            render_spot_color: self.render_spot_color,
+            damaged_groups: self.ctx.damaged_groups(),
+        };
+        Ok(Some(result))
+    }
+
+    /// Renders the given keyframe in horizontal strips, passing each strip to `sink` as soon as it
+    /// is rendered.
+    ///
+    /// Strips cover the current image region from top to bottom. The sink receives the row offset
+    /// of the strip relative to the top of the region, and the strip itself, with orientation
+    /// applied. Returning an error from the sink stops rendering.
+    ///
+    /// Each strip is a separate cropped render of the keyframe, as if the image region were set to
+    /// the strip and [`render_frame_cropped`][Self::render_frame_cropped] were called. Strip
+    /// boundaries follow group boundaries of the keyframe, taking frame offset and orientation into
+    /// account, so that strips don't decode groups more than needed.
+    ///
+    /// This is not a streaming decoder. Global and LF group data are decoded once and kept until
+    /// all strips are rendered, so peak memory is that of rendering a single strip plus the LF
+    /// data of the region. Pass groups and filter borders on strip boundaries are decoded again
+    /// for adjacent strips.
+    ///
+    /// The render cache is reset while rendering, and the image region is restored afterwards.
+    pub fn render_frame_strips(
+        &mut self,
+        keyframe_index: usize,
+        mut sink: impl FnMut(u32, &Render) -> Result<()>,
+    ) -> Result<()> {
+        let frame_header = self
+            .frame_header(keyframe_index)
+            .ok_or(jxl_render::Error::IncompleteFrame)?;
+        let strip_height = frame_header.group_dim() * frame_header.upsampling;
+        let row_origin = self.group_row_origin(frame_header);
+
+        let region = self.ctx.image_region();
+        let strips = split_region(region, region.width, strip_height, row_origin);
+        self.render_subregions(keyframe_index, strips, |strip, render| {
+            sink((strip.top - region.top) as u32, render)
+        })
+    }
+
+    /// Returns a row of the oriented image where a group row of the frame starts.
+    fn group_row_origin(&self, frame_header: &FrameHeader) -> i32 {
+        let metadata = &self.image_header.metadata;
+        let width = self.image_header.width_with_orientation();
+        let height = self.image_header.height_with_orientation();
+        let to_frame = |top: i32| {
+            let (_, _, left, top) = metadata.apply_orientation(width, height, 0, top, true);
+            (left - frame_header.x0, top - frame_header.y0)
+        };
+
+        // Rows of the oriented image run along one of the axes of the frame, in either direction.
+        let (x0, y0) = to_frame(0);
+        let (x1, y1) = to_frame(1);
+        let (start, step) = if x0 != x1 {
+            (x0, x1 - x0)
+        } else {
+            (y0, y1 - y0)
+        };
+        if step > 0 {
+            -start
+        } else {
+            start + 1
+        }
+    }
+
+    /// Renders the given keyframe in tiles, passing each tile to `sink` as soon as it is rendered.
+    ///
+    /// `tile_size` is rounded up to a multiple of the LF group size of the keyframe. Tiles cover the
+    /// current image region in raster order, starting from its top-left corner. The sink receives
+    /// the region of the tile within the image, and the tile itself, with orientation applied.
+    /// Returning an error from the sink stops rendering.
+    ///
+    /// Each tile is a separate cropped render of the keyframe. Global and LF group data are decoded
+    /// once and kept until all tiles are rendered, and frames used as patch sources are rendered in
+    /// full once. Peak memory is that of rendering a single tile plus the LF data of the region,
+    /// but pass groups and filter borders on tile boundaries are decoded again for adjacent tiles.
+    ///
+    /// The render cache is reset while rendering, and the image region is restored afterwards.
+    pub fn render_tiles(
+        &mut self,
+        keyframe_index: usize,
+        tile_size: u32,
+        mut sink: impl FnMut(CropInfo, &Render) -> Result<()>,
+    ) -> Result<()> {
+        let frame_header = self
+            .frame_header(keyframe_index)
+            .ok_or(jxl_render::Error::IncompleteFrame)?;
+        let lf_group_size = frame_header.lf_group_dim() * frame_header.upsampling;
+        let tile_size = tile_size.max(1).next_multiple_of(lf_group_size);
+
+        let region = self.ctx.image_region();
+        let tiles = split_region(region, tile_size, tile_size, region.top);
+        self.render_subregions(keyframe_index, tiles, |tile, render| {
+            let crop = CropInfo {
+                width: tile.width,
+                height: tile.height,
+                left: tile.left as u32,
+                top: tile.top as u32,
+            };
+            sink(crop, render)
+        })
+    }
+
+    fn render_subregions(
+        &mut self,
+        keyframe_index: usize,
+        subregions: Vec<Region>,
+        mut sink: impl FnMut(Region, &Render) -> Result<()>,
+    ) -> Result<()> {
+        let region = self.ctx.image_region();
+        let retain_render_cache = self.ctx.retain_render_cache();
+        self.ctx.set_retain_render_cache(true);
+        let mut result = Ok(());
+        for subregion in subregions {
+            tracing::debug!(?subregion, "Rendering subregion");
+            self.ctx.request_image_region(subregion);
+            result = self
+                .render_frame_cropped(keyframe_index)
+                .and_then(|render| sink(subregion, &render));
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.ctx.set_retain_render_cache(retain_render_cache);
+        self.ctx.request_image_region(region);
+        result
+    }
+
     fn convert_ec_info(&self) -> Vec<ExtraChannel> {
         self.image_header
             .metadata
@@ -892,6 +1320,7 @@ pub struct Render {
     target_frame_region: Region,
     color_bit_depth: BitDepth,
     render_spot_color: bool,
+    damaged_groups: Vec<DamagedGroup>,
 }
 
 impl Render {
@@ -919,6 +1348,15 @@ impl Render {
         self.orientation
     }
 
+    /// Returns groups which failed to decode or were missing, if rendered in error-tolerant mode.
+    ///
+    /// The list covers every frame rendered since the render cache was last reset, including
+    /// reference frames; [`DamagedGroup::frame_index`] tells which frame the group belongs to.
+    #[inline]
+    pub fn damaged_groups(&self) -> &[DamagedGroup] {
+        &self.damaged_groups
+    }
+
     /// Creates a stream that writes to borrowed buffer.
     ///
     /// The stream will include black and alpha channels, if exists, in addition to color channels.
@@ -1042,6 +1480,45 @@ impl ExtraChannel {
     }
 }
 
+/// Splits the region into a grid of subregions of at most `width` by `height`.
+///
+/// Columns start at `region.left`, and rows are aligned to multiples of `height` from
+/// `row_origin`. Zero `width` or `height` doesn't split the region along the axis.
+fn split_region(region: Region, width: u32, height: u32, row_origin: i32) -> Vec<Region> {
+    fn split_axis(start: i32, len: u32, size: u32, origin: i32) -> Vec<(i32, u32)> {
+        if len == 0 {
+            return Vec::new();
+        }
+        if size == 0 {
+            return vec![(start, len)];
+        }
+
+        let end = start + len as i32;
+        let size = size as i32;
+        let mut out = Vec::new();
+        let mut pos = start;
+        while pos < end {
+            let next = (origin + ((pos - origin).div_euclid(size) + 1) * size).min(end);
+            out.push((pos, (next - pos) as u32));
+            pos = next;
+        }
+        out
+    }
+
+    let columns = split_axis(region.left, region.width, width, region.left);
+    split_axis(region.top, region.height, height, row_origin)
+        .into_iter()
+        .flat_map(|(top, height)| {
+            columns.iter().map(move |&(left, width)| Region {
+                left,
+                top,
+                width,
+                height,
+            })
+        })
+        .collect()
+}
+
 /// Cropping region information.
 #[derive(Debug, Default, Copy, Clone)]
 pub struct CropInfo {
@@ -0,0 +1,65 @@
+use jxl_oxide::{BitstreamKind, ContainerBoxType, ContainerDetectingReader, JxlImage};
+
+const CODESTREAM: &[u8] = &[
+    0xff, 0x0a, 0x30, 0x54, 0x10, 0x09, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b, 0x38, 0x41, 0x3c,
+    0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48, 0x45, 0x84, 0x1b,
+    0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84, 0x01,
+];
+
+const EXIF: &[u8] = &[0, 0, 0, 0, b'I', b'I', 42, 0];
+const XMP: &[u8] = b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>";
+
+fn push_box(out: &mut Vec<u8>, ty: &[u8; 4], data: &[u8], to_end: bool) {
+    let size = if to_end { 0 } else { data.len() as u32 + 8 };
+    out.extend_from_slice(&size.to_be_bytes());
+    out.extend_from_slice(ty);
+    out.extend_from_slice(data);
+}
+
+fn container() -> Vec<u8> {
+    let mut out = vec![0, 0, 0, 0xc, b'J', b'X', b'L', b' ', 0xd, 0xa, 0x87, 0xa];
+    push_box(&mut out, b"ftyp", b"jxl \0\0\0\0jxl ", false);
+    push_box(&mut out, b"Exif", EXIF, false);
+    push_box(&mut out, b"jxlc", CODESTREAM, false);
+    push_box(&mut out, b"xml ", XMP, true);
+    out
+}
+
+#[test]
+fn aux_boxes() {
+    let data = container();
+    let mut reader = ContainerDetectingReader::new();
+    let mut codestream = Vec::new();
+    for chunk in data.chunks(7) {
+        reader.feed_bytes(chunk).unwrap();
+        codestream.extend(reader.take_bytes());
+    }
+    assert_eq!(reader.kind(), BitstreamKind::Container);
+    assert_eq!(codestream, CODESTREAM);
+
+    // `ftyp` box is not a codestream box, so it's returned too.
+    let types = reader
+        .aux_boxes()
+        .iter()
+        .map(|(ty, _)| *ty)
+        .collect::<Vec<_>>();
+    assert_eq!(types, [ContainerBoxType::FILE_TYPE, ContainerBoxType::EXIF]);
+    assert_eq!(reader.aux_boxes()[1].1, EXIF);
+
+    // The last box extends to the end of the file.
+    reader.finish();
+    let (ty, xml) = reader.aux_boxes().last().unwrap();
+    assert_eq!(*ty, ContainerBoxType::XML);
+    assert_eq!(xml, XMP);
+}
+
+#[test]
+fn decode_container() {
+    let data = container();
+    let image = JxlImage::builder().read(&*data).unwrap();
+    let expected = JxlImage::builder().read(CODESTREAM).unwrap();
+    assert_eq!(
+        image.render_frame(0).unwrap().image_all_channels().buf(),
+        expected.render_frame(0).unwrap().image_all_channels().buf(),
+    );
+}
@@ -1,6 +1,7 @@
 use std::io::Cursor;
+use std::sync::{Arc, Mutex};
 
-use jxl_oxide::{CropInfo, JxlImage, Render};
+use jxl_oxide::{CancellationToken, CropInfo, JxlImage, Render, RenderError};
 use rand::prelude::*;
 
 mod util;
@@ -186,6 +187,220 @@ testcase_with_crop! {
     crop_bike_0: bike[CropInfo { width: 936, height: 137, left: 877, top: 2353 }],
 }
 
+fn test_strips(buf: &[u8], crop: Option<CropInfo>) {
+    let mut image = JxlImage::builder()
+        .read(Cursor::new(buf))
+        .expect("Failed to open file");
+    if let Some(crop) = crop {
+        image.set_image_region(crop);
+    }
+
+    let num_frames = image.num_loaded_keyframes();
+    for idx in 0..num_frames {
+        eprintln!("Testing frame #{idx}");
+        let expected = image
+            .render_frame_cropped(idx)
+            .expect("Failed to render image")
+            .image_all_channels();
+        let row_len = expected.width() * expected.channels();
+
+        let mut next_row = 0usize;
+        image
+            .render_frame_strips(idx, |top, render| {
+                assert_eq!(top as usize, next_row);
+                let strip = render.image_all_channels();
+                assert_eq!(strip.width(), expected.width());
+                assert_eq!(strip.channels(), expected.channels());
+
+                let expected_rows = &expected.buf()[top as usize * row_len..][..strip.buf().len()];
+                for (expected, actual) in expected_rows.iter().zip(strip.buf()) {
+                    assert!((expected - actual).abs() <= 1e-6);
+                }
+                next_row += strip.height();
+                Ok(())
+            })
+            .expect("Failed to render image in strips");
+        assert_eq!(next_row, expected.height());
+    }
+}
+
+macro_rules! testcase_strips {
+    {$($(#[$attr:meta])* $name:ident: $testimage:ident $([$crop:expr])?),* $(,)?} => {
+        $(
+            #[test]
+            $(#[$attr])*
+            fn $name() {
+                let path = util::conformance_path(stringify!($testimage));
+                let buf = std::fs::read(path).expect("Failed to open file");
+                #[allow(unused_mut, unused_assignments)]
+                let mut crop = None;
+                $(crop = Some($crop);)?
+                test_strips(&buf, crop);
+            }
+        )*
+    };
+}
+
+testcase_strips! {
+    strips_bicycles: bicycles,
+    strips_alpha_triangles: alpha_triangles,
+    strips_progressive: progressive,
+    strips_upsampling: upsampling,
+    strips_patches_lossless: patches_lossless,
+    strips_noise: noise,
+    strips_blendmodes: blendmodes,
+    strips_crop_progressive: progressive[CropInfo { width: 1159, height: 359, left: 776, top: 1745 }],
+}
+
+/// 240x135 modular image.
+const SMALL_IMAGE: &[u8] = &[
+    0xff, 0x0a, 0x30, 0x54, 0x10, 0x09, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b, 0x38, 0x41, 0x3c,
+    0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48, 0x45, 0x84, 0x1b,
+    0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84, 0x01,
+];
+
+#[test]
+fn strips_crop_offset() {
+    // Strips span the whole width of the region, even if it doesn't start at the left edge.
+    test_strips(
+        SMALL_IMAGE,
+        Some(CropInfo {
+            width: 200,
+            height: 100,
+            left: 30,
+            top: 10,
+        }),
+    );
+}
+
+fn test_tiles(buf: &[u8], tile_size: u32) {
+    let mut image = JxlImage::builder()
+        .read(Cursor::new(buf))
+        .expect("Failed to open file");
+
+    let num_frames = image.num_loaded_keyframes();
+    for idx in 0..num_frames {
+        eprintln!("Testing frame #{idx}");
+        let expected = image
+            .render_frame(idx)
+            .expect("Failed to render image")
+            .image_all_channels();
+        let channels = expected.channels();
+        let row_len = expected.width() * channels;
+
+        let mut covered = 0usize;
+        image
+            .render_tiles(idx, tile_size, |crop, render| {
+                let tile = render.image_all_channels();
+                assert_eq!(tile.width(), crop.width as usize);
+                assert_eq!(tile.height(), crop.height as usize);
+                assert_eq!(tile.channels(), channels);
+
+                let tile_row_len = crop.width as usize * channels;
+                for (y, actual_row) in tile.buf().chunks_exact(tile_row_len).enumerate() {
+                    let expected_row = &expected.buf()[(crop.top as usize + y) * row_len..]
+                        [crop.left as usize * channels..][..tile_row_len];
+                    for (expected, actual) in expected_row.iter().zip(actual_row) {
+                        assert!((expected - actual).abs() <= 1e-6);
+                    }
+                }
+                covered += tile.width() * tile.height();
+                Ok(())
+            })
+            .expect("Failed to render tiles");
+        assert_eq!(covered, expected.width() * expected.height());
+    }
+}
+
+macro_rules! testcase_tiles {
+    {$($(#[$attr:meta])* $name:ident: $testimage:ident [$tile_size:expr]),* $(,)?} => {
+        $(
+            #[test]
+            $(#[$attr])*
+            fn $name() {
+                let path = util::conformance_path(stringify!($testimage));
+                let buf = std::fs::read(path).expect("Failed to open file");
+                test_tiles(&buf, $tile_size);
+            }
+        )*
+    };
+}
+
+testcase_tiles! {
+    tiles_bicycles: bicycles[256],
+    tiles_progressive: progressive[2048],
+    tiles_upsampling: upsampling[256],
+    tiles_patches_lossless: patches_lossless[256],
+    tiles_blendmodes: blendmodes[256],
+    tiles_animation_spline: animation_spline[256],
+}
+
+fn test_cancel(buf: &[u8]) {
+    let expected = JxlImage::builder()
+        .read(Cursor::new(buf))
+        .expect("Failed to open file")
+        .render_frame(0)
+        .expect("Failed to render image")
+        .image_all_channels();
+
+    let mut image = JxlImage::builder()
+        .read(Cursor::new(buf))
+        .expect("Failed to open file");
+
+    let token = CancellationToken::new();
+    token.cancel();
+    image.set_cancellation_token(token);
+    let err = image
+        .render_frame(0)
+        .expect_err("Render should be cancelled");
+    assert!(matches!(
+        err.downcast_ref::<RenderError>(),
+        Some(RenderError::Cancelled)
+    ));
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    image.set_cancellation_token(CancellationToken::new());
+    image.set_progress_callback({
+        let reports = Arc::clone(&reports);
+        move |progress| reports.lock().unwrap().push(progress)
+    });
+    let actual = image
+        .render_frame(0)
+        .expect("Failed to render image")
+        .image_all_channels();
+    assert_eq!(expected.buf(), actual.buf());
+
+    let reports = reports.lock().unwrap();
+    assert!(!reports.is_empty());
+    for report in &*reports {
+        assert!(report.completed_groups <= report.total_groups);
+        assert!(reports
+            .iter()
+            .any(|done| done.frame_index == report.frame_index
+                && done.completed_groups == report.total_groups));
+    }
+}
+
+macro_rules! testcase_cancel {
+    {$($(#[$attr:meta])* $name:ident: $testimage:ident),* $(,)?} => {
+        $(
+            #[test]
+            $(#[$attr])*
+            fn $name() {
+                let path = util::conformance_path(stringify!($testimage));
+                let buf = std::fs::read(path).expect("Failed to open file");
+                test_cancel(&buf);
+            }
+        )*
+    };
+}
+
+testcase_cancel! {
+    cancel_bicycles: bicycles,
+    cancel_progressive: progressive,
+    cancel_patches_lossless: patches_lossless,
+}
+
 fn write_npy(render: &Render, path: impl AsRef<std::path::Path>) {
     use std::io::prelude::*;
 
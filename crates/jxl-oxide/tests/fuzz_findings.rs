@@ -83,3 +83,47 @@ test_by_include!(
     upsample_separate_ec,
     lz77_num_to_copy_overflow,
 );
+
+fn tolerant_decode(data: &[u8]) {
+    let open = || {
+        JxlImage::builder()
+            .alloc_tracker(AllocTracker::with_limit(128 * 1024 * 1024))
+            .read(std::io::Cursor::new(data))
+            .unwrap()
+    };
+
+    let image = open();
+    assert!(image.render_frame(0).is_err());
+
+    let mut image = open();
+    image.set_error_tolerant(true);
+    let render = image.render_frame(0).unwrap();
+    let damaged_groups = render.damaged_groups();
+    assert!(!damaged_groups.is_empty());
+    assert!(damaged_groups.iter().any(|group| group.error.is_some()));
+}
+
+// Images with a damaged group, which can be rendered in error-tolerant mode.
+macro_rules! test_tolerant_by_include {
+    ($($(#[$attr:meta])* $name:ident),* $(,)?) => {
+        mod tolerant {
+            use super::*;
+
+            $(
+                #[test]
+                $(#[$attr])*
+                fn $name() {
+                    let data =
+                        include_bytes!(concat!("fuzz_findings/", stringify!($name), ".fuzz"));
+                    tolerant_decode(data);
+                }
+            )*
+        }
+    }
+}
+
+test_tolerant_by_include!(
+    hf_coeff_non_zeros,
+    hf_coeff_out_of_zeros,
+    hfmul_non_positive
+);
@@ -0,0 +1,93 @@
+use jxl_oxide::{EnumColourEncoding, InitializeResult, JxlImage, RenderingIntent};
+
+/// 240x135 image without preview.
+const NO_PREVIEW: &[u8] = &[
+    0xff, 0x0a, 0x30, 0x54, 0x10, 0x09, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b, 0x38, 0x41, 0x3c,
+    0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48, 0x45, 0x84, 0x1b,
+    0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84, 0x01,
+];
+
+/// Same image as `NO_PREVIEW`, with a 240x135 preview frame which has the same contents as the
+/// main frame. Preview width is signalled using aspect ratio.
+const WITH_PREVIEW: &[u8] = &[
+    0xff, 0x0a, 0x30, 0x54, 0xa1, 0x8c, 0x0a, 0x31, 0x01, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b,
+    0x38, 0x41, 0x3c, 0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48,
+    0x45, 0x84, 0x1b, 0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84, 0x01, 0x08, 0x06, 0x01,
+    0x00, 0x78, 0x00, 0x4b, 0x38, 0x41, 0x3c, 0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85,
+    0xb8, 0x27, 0x1a, 0x48, 0x45, 0x84, 0x1b, 0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84,
+    0x01,
+];
+
+#[test]
+fn preview_header() {
+    let image = JxlImage::builder().read(WITH_PREVIEW).unwrap();
+    let preview = image.image_header().metadata.preview.as_ref().unwrap();
+    assert_eq!((preview.width, preview.height), (240, 135));
+    assert!(image.has_preview());
+    assert_eq!(image.num_loaded_keyframes(), 1);
+    assert_eq!((image.width(), image.height()), (240, 135));
+}
+
+#[test]
+fn render_preview() {
+    let mut image = JxlImage::builder().read(WITH_PREVIEW).unwrap();
+    let expected = image.render_frame(0).unwrap().image_all_channels();
+    let preview = image
+        .render_preview()
+        .unwrap()
+        .unwrap()
+        .image_all_channels();
+    assert_eq!(preview.width(), 240);
+    assert_eq!(preview.height(), 135);
+    assert_eq!(preview.channels(), expected.channels());
+    assert_eq!(preview.buf(), expected.buf());
+}
+
+#[test]
+fn render_preview_with_output_options() {
+    let mut image = JxlImage::builder().read(WITH_PREVIEW).unwrap();
+    image.request_color_encoding(EnumColourEncoding::srgb_linear(RenderingIntent::Relative));
+    let expected = image.render_frame(0).unwrap().image_all_channels();
+    let preview = image
+        .render_preview()
+        .unwrap()
+        .unwrap()
+        .image_all_channels();
+    assert_eq!(preview.buf(), expected.buf());
+
+    let srgb = JxlImage::builder()
+        .read(WITH_PREVIEW)
+        .unwrap()
+        .render_frame(0)
+        .unwrap()
+        .image_all_channels();
+    assert_ne!(preview.buf(), srgb.buf());
+}
+
+#[test]
+fn no_preview() {
+    let mut image = JxlImage::builder().read(NO_PREVIEW).unwrap();
+    assert!(!image.has_preview());
+    assert!(image.render_preview().unwrap().is_none());
+}
+
+#[test]
+fn preview_incremental() {
+    let mut uninit = JxlImage::builder().build_uninit();
+    let mut image = None;
+    for &b in WITH_PREVIEW {
+        uninit.feed_bytes(&[b]).unwrap();
+        match uninit.try_init().unwrap() {
+            InitializeResult::NeedMoreData(x) => uninit = x,
+            InitializeResult::Initialized(x) => {
+                image = Some(x);
+                break;
+            }
+        }
+    }
+
+    // Preview frame is loaded before the image is initialized.
+    let mut image = image.unwrap();
+    assert!(image.has_preview());
+    assert!(image.render_preview().unwrap().is_some());
+}
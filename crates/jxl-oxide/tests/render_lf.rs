@@ -0,0 +1,52 @@
+use jxl_oxide::JxlImage;
+
+/// 16x7 VarDCT image without extra channels.
+const VARDCT: &[u8] = include_bytes!("fuzz_findings/hf_coeff_out_of_zeros.fuzz");
+
+/// 240x135 modular image.
+const MODULAR: &[u8] = &[
+    0xff, 0x0a, 0x30, 0x54, 0x10, 0x09, 0x08, 0x06, 0x01, 0x00, 0x78, 0x00, 0x4b, 0x38, 0x41, 0x3c,
+    0xb6, 0x3a, 0x51, 0xfe, 0x00, 0x47, 0x1e, 0xa0, 0x85, 0xb8, 0x27, 0x1a, 0x48, 0x45, 0x84, 0x1b,
+    0x71, 0x4f, 0xa8, 0x3e, 0x8e, 0x30, 0x03, 0x92, 0x84, 0x01,
+];
+
+#[test]
+fn render_lf_matches_lf_only() {
+    let mut image = JxlImage::builder().read(VARDCT).unwrap();
+    let lf = image
+        .render_frame_lf(0)
+        .unwrap()
+        .expect("VarDCT frame should be rendered from LF image")
+        .image_all_channels();
+    assert_eq!((lf.width(), lf.height()), (2, 1));
+
+    // Averaging 8x8 blocks of the LF-only render yields the LF image.
+    image.set_lf_only(true);
+    let full = image.render_frame(0).unwrap().image_all_channels();
+    let channels = full.channels();
+    assert_eq!(lf.channels(), channels);
+    for bx in 0..lf.width() {
+        for c in 0..channels {
+            let mut sum = 0f32;
+            let mut count = 0f32;
+            for y in 0..full.height() {
+                for x in (bx * 8)..((bx + 1) * 8).min(full.width()) {
+                    sum += full.buf()[(y * full.width() + x) * channels + c];
+                    count += 1.0;
+                }
+            }
+            let expected = sum / count;
+            let actual = lf.buf()[bx * channels + c];
+            assert!(
+                (expected - actual).abs() < 1e-4,
+                "block {bx} channel {c}: expected {expected}, got {actual}"
+            );
+        }
+    }
+}
+
+#[test]
+fn render_lf_modular() {
+    let image = JxlImage::builder().read(MODULAR).unwrap();
+    assert!(image.render_frame_lf(0).unwrap().is_none());
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "serde")]
+
+use jxl_oxide::{FrameHeader, ImageHeader, JxlImage, Toc};
+
+macro_rules! assert_roundtrip {
+    ($value:expr, $ty:ty) => {{
+        let json = serde_json::to_string($value).unwrap();
+        let decoded: $ty = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+    }};
+}
+
+fn roundtrip_headers(data: &[u8]) {
+    let image = JxlImage::builder()
+        .read(std::io::Cursor::new(data))
+        .unwrap();
+    assert_roundtrip!(image.image_header(), ImageHeader);
+
+    let mut frame_idx = 0;
+    while let Some(frame) = image.frame(frame_idx) {
+        assert_roundtrip!(frame.header(), FrameHeader);
+        assert_roundtrip!(frame.toc(), Toc);
+        frame_idx += 1;
+    }
+    assert!(frame_idx > 0);
+}
+
+macro_rules! test_roundtrip_by_include {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                let data = include_bytes!(concat!("fuzz_findings/", stringify!($name), ".fuzz"));
+                roundtrip_headers(data);
+            }
+        )*
+    }
+}
+
+test_roundtrip_by_include!(hf_coeff_out_of_zeros, ma_tree_multiple_frames_0);
@@ -5,7 +5,7 @@ use jxl_frame::{
     header::{BlendMode as FrameBlendMode, BlendingInfo},
     Frame,
 };
-use jxl_grid::{AlignedGrid, MutableSubgrid, SharedSubgrid};
+use jxl_grid::{AlignedGrid, AllocCategory, MutableSubgrid, SharedSubgrid};
 use jxl_image::ImageHeader;
 use jxl_modular::Sample;
 use jxl_threadpool::JxlThreadPool;
@@ -182,7 +182,10 @@ pub(crate) fn blend<S: Sample>(
     pool: &JxlThreadPool,
 ) -> Result<ImageWithRegion> {
     let header = new_frame.header();
-    let tracker = new_frame.alloc_tracker();
+    let tracker = new_frame
+        .alloc_tracker()
+        .map(|tracker| tracker.with_category(AllocCategory::ReferenceFrame));
+    let tracker = tracker.as_ref();
 
     let full_frame_region = Region::with_size(header.width, header.height);
     let output_image_region = output_frame_region.translate(header.x0, header.y0);
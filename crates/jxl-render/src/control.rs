@@ -0,0 +1,291 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use crate::{Error, Result};
+
+/// Token which cancels rendering in progress.
+///
+/// Clones of a token share the same state, so the token can be cancelled from another thread
+/// while rendering. Once cancelled, a token cannot be reset; set a new token to render again.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a new token which is not cancelled.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels rendering which uses this token.
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the token is cancelled.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Progress of rendering a frame, reported to the progress callback.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RenderProgress {
+    /// Index of the frame being rendered, including non-keyframes.
+    pub frame_index: usize,
+    /// Number of groups completed so far.
+    pub completed_groups: usize,
+    /// Total number of groups to be processed to render the frame.
+    pub total_groups: usize,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(RenderProgress) + Send + Sync + 'static>;
+
+/// Group of a frame which failed to decode or is missing, recorded in error-tolerant mode.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DamagedGroup {
+    /// Index of the frame, including non-keyframes.
+    pub frame_index: usize,
+    /// Kind and index of the group.
+    pub kind: GroupKind,
+    /// Error occurred while decoding the group, or `None` if the group is missing.
+    pub error: Option<Arc<Error>>,
+}
+
+/// Kind of a group in a frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GroupKind {
+    /// HF global data. If damaged, HF coefficients of the entire frame are not decoded.
+    HfGlobal,
+    /// LF group with the given index. If damaged, color channels of the group are filled with
+    /// neutral gray.
+    LfGroup(u32),
+    /// Pass group with the given pass and group index. If damaged, HF coefficients of the group
+    /// from the pass onward are discarded, and the group is rendered from previous passes.
+    PassGroup { pass_idx: u32, group_idx: u32 },
+}
+
+/// Cancellation token, progress callback and damage report used while rendering.
+#[derive(Clone, Default)]
+pub(crate) struct RenderControl {
+    pub(crate) cancellation: CancellationToken,
+    pub(crate) progress: Option<ProgressCallback>,
+    /// Damaged groups recorded so far; `Some` if rendering is error-tolerant.
+    pub(crate) damaged_groups: Option<Arc<Mutex<Vec<DamagedGroup>>>>,
+}
+
+impl std::fmt::Debug for RenderControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RenderControl")
+            .field("cancellation", &self.cancellation)
+            .field("progress", &self.progress.is_some())
+            .field("damaged_groups", &self.damaged_groups)
+            .finish()
+    }
+}
+
+impl RenderControl {
+    #[inline]
+    pub(crate) fn check(&self) -> Result<()> {
+        self.cancellation.check()
+    }
+
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    #[inline]
+    pub(crate) fn is_error_tolerant(&self) -> bool {
+        self.damaged_groups.is_some()
+    }
+
+    /// Records the group which failed to decode if rendering is error-tolerant, or returns the
+    /// error back otherwise.
+    pub(crate) fn tolerate(&self, frame_index: usize, kind: GroupKind, error: Error) -> Result<()> {
+        let Some(damaged_groups) = &self.damaged_groups else {
+            return Err(error);
+        };
+
+        tracing::warn!(frame_index, ?kind, %error, "Group failed to decode");
+        damaged_groups.lock().unwrap().push(DamagedGroup {
+            frame_index,
+            kind,
+            error: Some(Arc::new(error)),
+        });
+        Ok(())
+    }
+
+    /// Records the missing group if rendering is error-tolerant.
+    pub(crate) fn record_missing(&self, frame_index: usize, kind: GroupKind) {
+        if let Some(damaged_groups) = &self.damaged_groups {
+            tracing::debug!(frame_index, ?kind, "Group is missing");
+            damaged_groups.lock().unwrap().push(DamagedGroup {
+                frame_index,
+                kind,
+                error: None,
+            });
+        }
+    }
+
+    /// Returns indices of damaged LF groups of the frame.
+    pub(crate) fn damaged_lf_groups(&self, frame_index: usize) -> Vec<u32> {
+        let Some(damaged_groups) = &self.damaged_groups else {
+            return Vec::new();
+        };
+
+        damaged_groups
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|group| group.frame_index == frame_index)
+            .filter_map(|group| match group.kind {
+                GroupKind::LfGroup(idx) => Some(idx),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Forgets damaged groups of the frame, as it is about to be rendered again.
+    pub(crate) fn clear_damage(&self, frame_index: usize) {
+        if let Some(damaged_groups) = &self.damaged_groups {
+            damaged_groups
+                .lock()
+                .unwrap()
+                .retain(|group| group.frame_index != frame_index);
+        }
+    }
+
+    /// Starts tracking progress of `total_groups` groups of the frame.
+    pub(crate) fn track_groups(
+        &self,
+        frame_index: usize,
+        total_groups: usize,
+    ) -> GroupProgress<'_> {
+        let progress = GroupProgress {
+            control: self,
+            frame_index,
+            completed_groups: AtomicUsize::new(0),
+            total_groups,
+        };
+        progress.report(0);
+        progress
+    }
+}
+
+/// Counts completed groups of a frame, reporting each completion to the progress callback.
+#[derive(Debug)]
+pub(crate) struct GroupProgress<'a> {
+    control: &'a RenderControl,
+    frame_index: usize,
+    completed_groups: AtomicUsize,
+    total_groups: usize,
+}
+
+impl GroupProgress<'_> {
+    /// Checks whether rendering is cancelled.
+    #[inline]
+    pub(crate) fn check(&self) -> Result<()> {
+        self.control.check()
+    }
+
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+
+    /// Marks a group as completed.
+    pub(crate) fn complete_group(&self) {
+        let completed_groups = self.completed_groups.fetch_add(1, Ordering::Relaxed) + 1;
+        self.report(completed_groups);
+    }
+
+    fn report(&self, completed_groups: usize) {
+        if let Some(progress) = &self.control.progress {
+            progress(RenderProgress {
+                frame_index: self.frame_index,
+                completed_groups,
+                total_groups: self.total_groups,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn cancellation_token() {
+        let token = CancellationToken::new();
+        let cloned = token.clone();
+        assert!(token.check().is_ok());
+        cloned.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn group_progress() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let control = RenderControl {
+            cancellation: CancellationToken::new(),
+            progress: Some({
+                let reports = Arc::clone(&reports);
+                Arc::new(move |progress: RenderProgress| {
+                    reports.lock().unwrap().push(progress.completed_groups);
+                })
+            }),
+            damaged_groups: None,
+        };
+
+        let progress = control.track_groups(3, 2);
+        progress.complete_group();
+        progress.complete_group();
+        assert_eq!(*reports.lock().unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn damage_report() {
+        let control = RenderControl::default();
+        let result = control.tolerate(0, GroupKind::HfGlobal, Error::IncompleteFrame);
+        assert!(matches!(result, Err(Error::IncompleteFrame)));
+
+        let control = RenderControl {
+            damaged_groups: Some(Default::default()),
+            ..Default::default()
+        };
+        control.record_missing(0, GroupKind::LfGroup(1));
+        let kind = GroupKind::PassGroup {
+            pass_idx: 0,
+            group_idx: 2,
+        };
+        assert!(control.tolerate(1, kind, Error::IncompleteFrame).is_ok());
+        assert_eq!(control.damaged_lf_groups(0), [1]);
+        assert!(control.damaged_lf_groups(1).is_empty());
+        control.clear_damage(0);
+        assert!(control.damaged_lf_groups(0).is_empty());
+
+        let damaged_groups = control.damaged_groups.as_ref().unwrap().lock().unwrap();
+        assert_eq!(damaged_groups.len(), 1);
+        assert_eq!(damaged_groups[0].frame_index, 1);
+        assert!(damaged_groups[0].error.is_some());
+    }
+}
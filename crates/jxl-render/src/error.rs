@@ -13,6 +13,7 @@ pub enum Error {
     InvalidReference(u32),
     NotReady,
     NotSupported(&'static str),
+    Cancelled,
 }
 
 impl From<jxl_bitstream::Error> for Error {
@@ -70,6 +71,7 @@ impl std::fmt::Display for Error {
             InvalidReference(idx) => write!(f, "invalid reference {idx}"),
             NotReady => write!(f, "image is not ready to be rendered"),
             NotSupported(msg) => write!(f, "not supported: {}", msg),
+            Cancelled => write!(f, "rendering is cancelled"),
         }
     }
 }
@@ -1,7 +1,7 @@
 use std::num::Wrapping;
 
 use jxl_frame::{data::NoiseParameters, FrameHeader};
-use jxl_grid::{AlignedGrid, AllocTracker, PaddedGrid};
+use jxl_grid::{AlignedGrid, AllocCategory, AllocTracker, PaddedGrid};
 
 use crate::{ImageWithRegion, Region, Result};
 
@@ -14,7 +14,9 @@ pub fn render_noise(
     params: &NoiseParameters,
 ) -> Result<()> {
     let (region, shift) = grid.regions_and_shifts()[0];
-    let tracker = grid.alloc_tracker().cloned();
+    let tracker = grid
+        .alloc_tracker()
+        .map(|tracker| tracker.with_category(AllocCategory::FilterScratch));
     let [grid_r, grid_g, grid_b] = grid.as_color_floats_mut();
 
     let full_frame_region = Region::with_size(header.width, header.height);
@@ -1,4 +1,4 @@
-use jxl_grid::{AlignedGrid, AllocTracker, PaddedGrid, SharedSubgrid};
+use jxl_grid::{AlignedGrid, AllocCategory, AllocTracker, PaddedGrid, SharedSubgrid};
 
 use crate::Region;
 
@@ -54,7 +54,10 @@ fn upsample_inner<const K: usize, const NW: usize>(
 
     // 5x5 kernel
     const PADDING: usize = 2;
-    let mut padded = PaddedGrid::with_alloc_tracker(grid_width, grid_height, PADDING, tracker)?;
+    let scratch_tracker =
+        tracker.map(|tracker| tracker.with_category(AllocCategory::FilterScratch));
+    let mut padded =
+        PaddedGrid::with_alloc_tracker(grid_width, grid_height, PADDING, scratch_tracker.as_ref())?;
     let padded_width = grid_width + PADDING * 2;
 
     let padded_buf = padded.buf_padded_mut();
@@ -1,18 +1,22 @@
 //! This crate is the core of jxl-oxide that provides JPEG XL renderer.
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use jxl_bitstream::{Bitstream, Bundle};
 use jxl_color::{
-    ColorEncodingWithProfile, ColorManagementSystem, ColourEncoding, ColourSpace,
-    EnumColourEncoding,
+    icc::IccMatchTolerance, ColorEncodingWithProfile, ColorManagementSystem, ColourEncoding,
+    ColourSpace, EnumColourEncoding,
+};
+use jxl_frame::{
+    header::{BlendMode, Encoding, FrameType},
+    Frame, FrameContext,
 };
-use jxl_frame::{header::FrameType, Frame, FrameContext};
 use jxl_grid::AllocTracker;
 use jxl_image::{ImageHeader, ImageMetadata};
 use jxl_modular::Sample;
 use jxl_threadpool::JxlThreadPool;
 
 mod blend;
+mod control;
 mod error;
 mod features;
 mod filter;
@@ -24,6 +28,8 @@ mod state;
 mod util;
 mod vardct;
 
+use control::RenderControl;
+pub use control::{CancellationToken, DamagedGroup, GroupKind, ProgressCallback, RenderProgress};
 pub use error::{Error, Result};
 pub use features::render_spot_color;
 pub use image::{ImageBuffer, ImageWithRegion};
@@ -50,8 +56,15 @@ pub struct RenderContext {
     pub(crate) loading_region: Option<Region>,
     requested_image_region: Region,
     embedded_icc: Vec<u8>,
+    icc_match_tolerance: IccMatchTolerance,
     requested_color_encoding: ColorEncodingWithProfile,
-    cms: Box<dyn ColorManagementSystem + Send + Sync>,
+    extended_linear: Option<f32>,
+    hlg_display_luminance: Option<f32>,
+    hlg_scene_referred: bool,
+    lf_only: bool,
+    retain_render_cache: bool,
+    control: Arc<Mutex<RenderControl>>,
+    cms: Arc<dyn ColorManagementSystem + Send + Sync>,
 }
 
 impl std::fmt::Debug for RenderContext {
@@ -69,6 +82,7 @@ impl RenderContext {
 #[derive(Debug, Default)]
 pub struct RenderContextBuilder {
     embedded_icc: Vec<u8>,
+    icc_match_tolerance: IccMatchTolerance,
     pool: Option<JxlThreadPool>,
     tracker: Option<AllocTracker>,
 }
@@ -79,6 +93,12 @@ impl RenderContextBuilder {
         self
     }
 
+    /// Sets the tolerance used when matching embedded ICC profile to an enum color encoding.
+    pub fn icc_match_tolerance(mut self, tolerance: IccMatchTolerance) -> Self {
+        self.icc_match_tolerance = tolerance;
+        self
+    }
+
     pub fn pool(mut self, pool: JxlThreadPool) -> Self {
         self.pool = Some(pool);
         self
@@ -101,7 +121,10 @@ impl RenderContextBuilder {
             let ColourEncoding::IccProfile(color_space) = color_encoding else {
                 unreachable!();
             };
-            match ColorEncodingWithProfile::with_icc(&self.embedded_icc) {
+            match ColorEncodingWithProfile::with_icc_tolerance(
+                &self.embedded_icc,
+                self.icc_match_tolerance,
+            ) {
                 Ok(parsed_icc) => {
                     let header_is_gray = *color_space == ColourSpace::Grey;
                     let icc_is_gray = parsed_icc.is_grayscale();
@@ -151,8 +174,15 @@ impl RenderContextBuilder {
             loading_region: None,
             requested_image_region: full_image_region,
             embedded_icc: self.embedded_icc,
+            icc_match_tolerance: self.icc_match_tolerance,
             requested_color_encoding,
-            cms: Box::new(jxl_color::NullCms),
+            extended_linear: None,
+            hlg_display_luminance: None,
+            hlg_scene_referred: false,
+            lf_only: false,
+            retain_render_cache: false,
+            control: Arc::new(Mutex::new(RenderControl::default())),
+            cms: Arc::new(jxl_color::NullCms),
         })
     }
 }
@@ -167,12 +197,180 @@ impl RenderContext {
 impl RenderContext {
     #[inline]
     pub fn set_cms(&mut self, cms: impl ColorManagementSystem + Send + Sync + 'static) {
-        self.cms = Box::new(cms);
+        self.cms = Arc::new(cms);
+    }
+
+    /// Copies the requested color encoding, color management system and HLG options from
+    /// `other`.
+    ///
+    /// This is useful for rendering another frame sequence of the same image, such as the preview
+    /// frame, with the same output settings.
+    pub fn copy_output_options(&mut self, other: &RenderContext) {
+        self.cms = Arc::clone(&other.cms);
+        self.requested_color_encoding = other.requested_color_encoding.clone();
+        self.extended_linear = other.extended_linear;
+        self.hlg_display_luminance = other.hlg_display_luminance;
+        self.hlg_scene_referred = other.hlg_scene_referred;
     }
 
     #[inline]
     pub fn request_color_encoding(&mut self, encoding: ColorEncodingWithProfile) {
         self.requested_color_encoding = encoding;
+        self.extended_linear = None;
+    }
+
+    /// Requests unclamped linear output in the given color encoding, where linear sample value of
+    /// 1.0 represents `reference_white` nits.
+    ///
+    /// Transfer function of `encoding` is ignored and set to linear.
+    pub fn request_extended_linear(&mut self, encoding: EnumColourEncoding, reference_white: f32) {
+        let encoding = EnumColourEncoding {
+            tf: jxl_color::TransferFunction::Linear,
+            ..encoding
+        };
+        self.requested_color_encoding = ColorEncodingWithProfile::new(encoding);
+        self.extended_linear = Some(reference_white);
+    }
+
+    /// Returns the reference white luminance of extended range linear output, if requested.
+    #[inline]
+    pub fn extended_linear(&self) -> Option<f32> {
+        self.extended_linear
+    }
+
+    /// Sets the nominal peak luminance of the display used by HLG OOTF.
+    ///
+    /// If `None` is given, intensity target of the image is used.
+    #[inline]
+    pub fn set_hlg_display_luminance(&mut self, luminance: Option<f32>) {
+        self.hlg_display_luminance = luminance;
+    }
+
+    /// Returns the nominal peak luminance of the display used by HLG OOTF, if set.
+    #[inline]
+    pub fn hlg_display_luminance(&self) -> Option<f32> {
+        self.hlg_display_luminance
+    }
+
+    /// Sets whether HLG signals are treated as scene-referred, skipping HLG OOTF.
+    #[inline]
+    pub fn set_hlg_scene_referred(&mut self, value: bool) {
+        self.hlg_scene_referred = value;
+    }
+
+    /// Returns whether HLG signals are treated as scene-referred.
+    #[inline]
+    pub fn hlg_scene_referred(&self) -> bool {
+        self.hlg_scene_referred
+    }
+
+    /// Sets whether VarDCT frames are rendered using LF coefficients only.
+    ///
+    /// HF coefficients are not decoded, so the image is rendered faster with the quality of an 8x
+    /// downsampled image. Frames with extra channels stored in pass groups are rendered in full.
+    /// Render cache is reset if the value is changed.
+    pub fn set_lf_only(&mut self, value: bool) {
+        if self.lf_only != value {
+            self.lf_only = value;
+            self.reset_cache();
+        }
+    }
+
+    /// Returns whether VarDCT frames are rendered using LF coefficients only.
+    #[inline]
+    pub fn lf_only(&self) -> bool {
+        self.lf_only
+    }
+
+    /// Sets whether decoded global and LF group data of frames are kept when the requested image
+    /// region changes.
+    ///
+    /// Rendering the same frame for several regions in turn then decodes those once, at the cost
+    /// of keeping LF group data of every region rendered so far. Retained data is dropped when the
+    /// value is turned off.
+    pub fn set_retain_render_cache(&mut self, value: bool) {
+        self.retain_render_cache = value;
+        if !value {
+            for handle in &self.renders_wide {
+                handle.take_retained_cache();
+            }
+            for handle in &self.renders_narrow {
+                handle.take_retained_cache();
+            }
+        }
+    }
+
+    /// Returns whether decoded global and LF group data are kept when the requested image region
+    /// changes.
+    #[inline]
+    pub fn retain_render_cache(&self) -> bool {
+        self.retain_render_cache
+    }
+
+    /// Sets the cancellation token checked while rendering.
+    ///
+    /// Rendering fails with [`Error::Cancelled`] once the token is cancelled. Progress made so far
+    /// is kept, so setting a new token and rendering again resumes from there.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.control.lock().unwrap().cancellation = token;
+    }
+
+    /// Sets the callback which receives the number of completed groups of the frames being
+    /// rendered.
+    ///
+    /// The callback may be called from multiple threads concurrently.
+    pub fn set_progress_callback(
+        &mut self,
+        callback: impl Fn(RenderProgress) + Send + Sync + 'static,
+    ) {
+        self.control.lock().unwrap().progress = Some(Arc::new(callback));
+    }
+
+    /// Removes the progress callback.
+    pub fn clear_progress_callback(&mut self) {
+        self.control.lock().unwrap().progress = None;
+    }
+
+    /// Sets whether rendering tolerates groups which fail to decode or are missing.
+    ///
+    /// In error-tolerant mode, damaged groups are filled from previous passes or lower resolution
+    /// data, or with neutral gray if there's none, and recorded instead of failing the render. Use [`damaged_groups`] to get
+    /// the list of them. Render cache is reset if the value is changed.
+    ///
+    /// [`damaged_groups`]: Self::damaged_groups
+    pub fn set_error_tolerant(&mut self, value: bool) {
+        if self.error_tolerant() != value {
+            self.control.lock().unwrap().damaged_groups = value.then(Default::default);
+            self.reset_cache();
+        }
+    }
+
+    /// Returns whether rendering tolerates groups which fail to decode or are missing.
+    #[inline]
+    pub fn error_tolerant(&self) -> bool {
+        self.render_control().is_error_tolerant()
+    }
+
+    /// Returns groups which failed to decode or were missing in the frames rendered since the
+    /// cache was last reset, if rendering is error-tolerant.
+    pub fn damaged_groups(&self) -> Vec<DamagedGroup> {
+        match &self.render_control().damaged_groups {
+            Some(damaged_groups) => damaged_groups.lock().unwrap().clone(),
+            None => Vec::new(),
+        }
+    }
+
+    fn render_control(&self) -> RenderControl {
+        self.control.lock().unwrap().clone()
+    }
+
+    /// Returns [`Error::Cancelled`] in place of `err` if rendering is cancelled.
+    fn map_cancelled(&self, err: Error) -> Error {
+        if self.render_control().is_cancelled() {
+            Error::Cancelled
+        } else {
+            err
+        }
     }
 
     #[inline]
@@ -183,7 +381,7 @@ impl RenderContext {
     #[inline]
     pub fn request_image_region(&mut self, image_region: Region) {
         self.requested_image_region = image_region;
-        self.reset_cache();
+        self.reset_region_cache();
     }
 
     #[inline]
@@ -296,7 +494,7 @@ impl RenderContext {
             };
             let refs = reference_frames.refs.clone();
 
-            let render_op = self.render_op::<i16>(Arc::clone(&frame), reference_frames);
+            let render_op = self.render_op::<i16>(Arc::clone(&frame), reference_frames, None);
             let handle = if let Some(cache) = self.loading_render_cache_narrow.take() {
                 FrameRenderHandle::from_cache(
                     Arc::clone(&frame),
@@ -324,7 +522,7 @@ impl RenderContext {
             };
             let refs = reference_frames.refs.clone();
 
-            let render_op = self.render_op::<i32>(Arc::clone(&frame), reference_frames);
+            let render_op = self.render_op::<i32>(Arc::clone(&frame), reference_frames, None);
             let handle = if let Some(cache) = self.loading_render_cache_wide.take() {
                 FrameRenderHandle::from_cache(
                     Arc::clone(&frame),
@@ -441,23 +639,27 @@ impl RenderContext {
         &self,
         frame: Arc<IndexedFrame>,
         reference_frames: ReferenceFrames<S>,
+        retained_cache: Option<RetainedCache<S>>,
     ) -> RenderOp<S> {
         let prev_frame_visibility = self.get_previous_frames_visibility(&frame);
 
         let pool = self.pool.clone();
+        let lf_only = self.lf_only;
+        let control = Arc::clone(&self.control);
         Arc::new(move |mut state, image_region| {
+            let control = control.lock().unwrap().clone();
             if let Some(lf) = &reference_frames.lf {
                 tracing::trace!(idx = lf.frame.idx, "Spawn LF frame renderer");
                 let lf_handle = Arc::clone(&lf.image);
                 pool.spawn(move || {
-                    lf_handle.run(image_region);
+                    lf_handle.run(lf_handle.image_region);
                 });
             }
             for grid in reference_frames.refs.iter().flatten() {
                 tracing::trace!(idx = grid.frame.idx, "Spawn reference frame renderer");
                 let ref_handle = Arc::clone(&grid.image);
                 pool.spawn(move || {
-                    ref_handle.run(image_region);
+                    ref_handle.run(ref_handle.image_region);
                 });
             }
 
@@ -479,9 +681,18 @@ impl RenderContext {
                 image_region,
                 pool.clone(),
                 prev_frame_visibility,
+                lf_only,
+                &control,
             );
             match result {
-                Ok(grid) => FrameRender::Done(grid),
+                Ok(grid) => {
+                    if let Some(retained_cache) = &retained_cache {
+                        *retained_cache.lock().unwrap() = Some(cache);
+                    }
+                    FrameRender::Done(grid)
+                }
+                // Keep the progress so that rendering can be resumed with another token.
+                Err(_) if control.is_cancelled() => FrameRender::InProgress(cache),
                 Err(e) if e.unexpected_eof() || matches!(e, Error::IncompleteFrame) => {
                     if frame.is_loading_done() {
                         FrameRender::Err(e)
@@ -541,15 +752,16 @@ impl RenderContext {
     }
 
     fn render_by_index(&self, index: usize) -> Result<Arc<ImageWithRegion>> {
-        if self.narrow_modular() {
+        let result = if self.narrow_modular() {
             Arc::clone(&self.renders_narrow[index])
-                .run_with_image()?
-                .blend(None, &self.pool)
+                .run_with_image()
+                .and_then(|image| image.blend(None, &self.pool))
         } else {
             Arc::clone(&self.renders_wide[index])
-                .run_with_image()?
-                .blend(None, &self.pool)
-        }
+                .run_with_image()
+                .and_then(|image| image.blend(None, &self.pool))
+        };
+        result.map_err(|e| self.map_cancelled(e))
     }
 
     /// Renders the first keyframe.
@@ -574,10 +786,81 @@ impl RenderContext {
         self.postprocess_keyframe(frame, grid)
     }
 
+    /// Renders the LF image of the keyframe, at 1/8 of the image dimension.
+    ///
+    /// HF coefficients are not decoded, and restoration filters, splines and noise are not
+    /// applied. The whole keyframe is rendered regardless of the requested image region.
+    ///
+    /// Returns `None` if the keyframe can't be rendered from its LF image alone: if it's not a
+    /// VarDCT frame covering the whole image without blending, or has upsampling, chroma
+    /// subsampling, patches or extra channels.
+    pub fn render_keyframe_lf(&self, keyframe_idx: usize) -> Result<Option<Arc<ImageWithRegion>>> {
+        let idx = *self
+            .keyframes
+            .get(keyframe_idx)
+            .ok_or(Error::IncompleteFrame)?;
+        let frame = &*self.frames[idx];
+        let frame_header = frame.header();
+        let image_size = &self.image_header.size;
+        let supported = frame_header.encoding == Encoding::VarDct
+            && frame_header.x0 == 0
+            && frame_header.y0 == 0
+            && frame_header.width == image_size.width
+            && frame_header.height == image_size.height
+            && frame_header.blending_info.mode == BlendMode::Replace
+            && frame_header.upsampling == 1
+            && frame_header.jpeg_upsampling == [0; 3]
+            && !frame_header.flags.patches()
+            && self.image_header.metadata.ec_info.is_empty();
+        if !supported {
+            return Ok(None);
+        }
+
+        let full_image_region = Region::with_size(
+            self.image_header.width_with_orientation(),
+            self.image_header.height_with_orientation(),
+        );
+        let lf_idx = self.frame_deps[idx].lf;
+        let control = self.render_control();
+        let image = if self.narrow_modular() {
+            let lf_frame = (lf_idx != usize::MAX).then(|| Reference {
+                frame: Arc::clone(&self.frames[lf_idx]),
+                image: Arc::clone(&self.renders_narrow[lf_idx]),
+            });
+            // LF frame should have been rendered in full.
+            if lf_frame
+                .as_ref()
+                .is_some_and(|lf| lf.image.image_region != full_image_region)
+            {
+                return Ok(None);
+            }
+            let mut cache = RenderCache::new(frame);
+            vardct::render_vardct_lf(frame, lf_frame.as_ref(), &mut cache, &self.pool, &control)
+        } else {
+            let lf_frame = (lf_idx != usize::MAX).then(|| Reference {
+                frame: Arc::clone(&self.frames[lf_idx]),
+                image: Arc::clone(&self.renders_wide[lf_idx]),
+            });
+            if lf_frame
+                .as_ref()
+                .is_some_and(|lf| lf.image.image_region != full_image_region)
+            {
+                return Ok(None);
+            }
+            let mut cache = RenderCache::new(frame);
+            vardct::render_vardct_lf(frame, lf_frame.as_ref(), &mut cache, &self.pool, &control)
+        };
+        let image = image.map_err(|e| self.map_cancelled(e))?;
+
+        self.postprocess_keyframe(frame, Arc::new(image)).map(Some)
+    }
+
     pub fn render_loading_keyframe(&mut self) -> Result<(&IndexedFrame, Arc<ImageWithRegion>)> {
         let mut current_frame_grid = None;
         if self.loading_frame().is_some() {
-            let ret = self.render_loading_frame();
+            let ret = self
+                .render_loading_frame()
+                .map_err(|e| self.map_cancelled(e));
             match ret {
                 Ok(grid) => current_frame_grid = Some(grid),
                 Err(Error::IncompleteFrame) => {}
@@ -601,18 +884,65 @@ impl RenderContext {
     }
 
     pub fn reset_cache(&mut self) {
-        let image_region = self.requested_image_region;
+        if let Some(damaged_groups) = &self.render_control().damaged_groups {
+            damaged_groups.lock().unwrap().clear();
+        }
+        self.rebuild_render_handles(false);
+    }
+
+    /// Resets render cache of frames which depend on the requested image region.
+    ///
+    /// Frames rendered in full regardless of the region, such as patch sources, keep their
+    /// renders, so that they aren't rendered again every time the region changes.
+    fn reset_region_cache(&mut self) {
+        let kept = self.rebuild_render_handles(true);
+        if let Some(damaged_groups) = &self.render_control().damaged_groups {
+            damaged_groups
+                .lock()
+                .unwrap()
+                .retain(|group| kept.get(group.frame_index).copied().unwrap_or(false));
+        }
+    }
+
+    /// Creates new render handles, and returns which frames kept their handles.
+    fn rebuild_render_handles(&mut self, keep_full_region: bool) -> Vec<bool> {
+        let full_image_region = Region::with_size(
+            self.image_header.width_with_orientation(),
+            self.image_header.height_with_orientation(),
+        );
+        let needs_full_region = self.frames_needing_full_region();
+        let narrow_modular = self.narrow_modular();
+        // Decoded data is carried over only when the region changes.
+        let retain_render_cache = keep_full_region && self.retain_render_cache;
+        let mut kept = vec![false; self.frames.len()];
 
         self.loading_region = None;
         self.loading_render_cache_wide = None;
         self.loading_render_cache_narrow = None;
         for (idx, frame) in self.frames.iter().enumerate() {
             if frame.header().frame_type == FrameType::ReferenceOnly {
+                kept[idx] = true;
                 continue;
             }
+            if keep_full_region && needs_full_region[idx] {
+                let handle_region = if narrow_modular {
+                    self.renders_narrow[idx].image_region
+                } else {
+                    self.renders_wide[idx].image_region
+                };
+                if handle_region == full_image_region {
+                    kept[idx] = true;
+                    continue;
+                }
+            }
 
+            let image_region = if needs_full_region[idx] {
+                full_image_region
+            } else {
+                self.requested_image_region
+            };
             let deps = self.frame_deps[idx];
-            if self.narrow_modular() {
+            if narrow_modular {
                 let reference_frames = ReferenceFrames {
                     lf: (deps.lf != usize::MAX).then(|| Reference {
                         frame: Arc::clone(&self.frames[deps.lf]),
@@ -627,10 +957,33 @@ impl RenderContext {
                 };
                 let refs = reference_frames.refs.clone();
 
-                let render_op = self.render_op::<i16>(Arc::clone(frame), reference_frames);
-                let handle =
-                    FrameRenderHandle::new(Arc::clone(frame), image_region, render_op, refs);
-                self.renders_narrow[idx] = Arc::new(handle);
+                if !retain_render_cache {
+                    let render_op =
+                        self.render_op::<i16>(Arc::clone(frame), reference_frames, None);
+                    let handle =
+                        FrameRenderHandle::new(Arc::clone(frame), image_region, render_op, refs);
+                    self.renders_narrow[idx] = Arc::new(handle);
+                    continue;
+                }
+
+                let retained_cache = RetainedCache::default();
+                let render_op = self.render_op::<i16>(
+                    Arc::clone(frame),
+                    reference_frames,
+                    Some(Arc::clone(&retained_cache)),
+                );
+                let handle = if let Some(cache) = self.renders_narrow[idx].take_retained_cache() {
+                    FrameRenderHandle::from_cache(
+                        Arc::clone(frame),
+                        image_region,
+                        *cache,
+                        render_op,
+                        refs,
+                    )
+                } else {
+                    FrameRenderHandle::new(Arc::clone(frame), image_region, render_op, refs)
+                };
+                self.renders_narrow[idx] = Arc::new(handle.with_retained_cache(retained_cache));
             } else {
                 let reference_frames = ReferenceFrames {
                     lf: (deps.lf != usize::MAX).then(|| Reference {
@@ -646,12 +999,60 @@ impl RenderContext {
                 };
                 let refs = reference_frames.refs.clone();
 
-                let render_op = self.render_op::<i32>(Arc::clone(frame), reference_frames);
-                let handle =
-                    FrameRenderHandle::new(Arc::clone(frame), image_region, render_op, refs);
-                self.renders_wide[idx] = Arc::new(handle);
+                if !retain_render_cache {
+                    let render_op =
+                        self.render_op::<i32>(Arc::clone(frame), reference_frames, None);
+                    let handle =
+                        FrameRenderHandle::new(Arc::clone(frame), image_region, render_op, refs);
+                    self.renders_wide[idx] = Arc::new(handle);
+                    continue;
+                }
+
+                let retained_cache = RetainedCache::default();
+                let render_op = self.render_op::<i32>(
+                    Arc::clone(frame),
+                    reference_frames,
+                    Some(Arc::clone(&retained_cache)),
+                );
+                let handle = if let Some(cache) = self.renders_wide[idx].take_retained_cache() {
+                    FrameRenderHandle::from_cache(
+                        Arc::clone(frame),
+                        image_region,
+                        *cache,
+                        render_op,
+                        refs,
+                    )
+                } else {
+                    FrameRenderHandle::new(Arc::clone(frame), image_region, render_op, refs)
+                };
+                self.renders_wide[idx] = Arc::new(handle.with_retained_cache(retained_cache));
+            }
+        }
+        kept
+    }
+
+    /// Returns which frames should be rendered in full regardless of the requested region.
+    ///
+    /// Patches may be copied from anywhere in the reference frame, so frames used as patch sources
+    /// are rendered in full, along with the frames they depend on. Reference-only frames are
+    /// always rendered in full, and are not included.
+    fn frames_needing_full_region(&self) -> Vec<bool> {
+        let mut needs_full_region = vec![false; self.frames.len()];
+        for (idx, frame) in self.frames.iter().enumerate().rev() {
+            let has_patches = frame.header().flags.patches();
+            let deps = self.frame_deps[idx];
+            if needs_full_region[idx] && deps.lf != usize::MAX {
+                needs_full_region[deps.lf] = true;
+            }
+            if has_patches || needs_full_region[idx] {
+                for r in deps.ref_slots {
+                    if r != usize::MAX {
+                        needs_full_region[r] = true;
+                    }
+                }
             }
         }
+        needs_full_region
     }
 
     fn render_loading_frame(&mut self) -> Result<ImageWithRegion> {
@@ -686,6 +1087,7 @@ impl RenderContext {
         }
 
         tracing::debug!(?image_region, ?frame_region, "Rendering loading frame");
+        let control = self.render_control();
         let image = if self.narrow_modular() {
             let mut cache = self.loading_render_cache_narrow.take().unwrap_or_else(|| {
                 let frame = self.loading_frame().unwrap();
@@ -713,6 +1115,8 @@ impl RenderContext {
                 image_region,
                 self.pool.clone(),
                 self.get_previous_frames_visibility(frame),
+                self.lf_only,
+                &control,
             );
             match image_result {
                 Ok(image) => image,
@@ -748,6 +1152,8 @@ impl RenderContext {
                 image_region,
                 self.pool.clone(),
                 self.get_previous_frames_visibility(frame),
+                self.lf_only,
+                &control,
             );
             match image_result {
                 Ok(image) => image,
@@ -771,6 +1177,7 @@ impl RenderContext {
         frame: &IndexedFrame,
         grid: Arc<ImageWithRegion>,
     ) -> Result<Arc<ImageWithRegion>> {
+        self.render_control().check()?;
         let frame_header = frame.header();
         let metadata = self.metadata();
 
@@ -783,7 +1190,10 @@ impl RenderContext {
             } else if let ColourEncoding::Enum(encoding) = header_color_encoding {
                 ColorEncodingWithProfile::new(encoding.clone())
             } else {
-                ColorEncodingWithProfile::with_icc(&self.embedded_icc)?
+                ColorEncodingWithProfile::with_icc_tolerance(
+                    &self.embedded_icc,
+                    self.icc_match_tolerance,
+                )?
             };
             tracing::trace!(?frame_color_encoding);
             tracing::trace!(requested_color_encoding = ?self.requested_color_encoding);
@@ -795,6 +1205,9 @@ impl RenderContext {
 
             let mut transform = jxl_color::ColorTransform::builder();
             transform.set_srgb_icc(!self.cms.supports_linear_tf());
+            transform.set_extended_linear(self.extended_linear);
+            transform.set_hlg_display_luminance(self.hlg_display_luminance);
+            transform.set_hlg_scene_referred(self.hlg_scene_referred);
             let transform = transform.build(
                 &frame_color_encoding,
                 &self.requested_color_encoding,
@@ -1,13 +1,18 @@
 use jxl_frame::{data::GlobalModular, FrameHeader};
+use jxl_grid::AllocCategory;
 use jxl_modular::{image::TransformedModularSubimage, Sample};
 
-use crate::{util, Error, ImageWithRegion, IndexedFrame, Region, RenderCache, Result};
+use crate::{
+    control::RenderControl, util, Error, GroupKind, ImageWithRegion, IndexedFrame, Region,
+    RenderCache, Result,
+};
 
 pub(crate) fn render_modular<S: Sample>(
     frame: &IndexedFrame,
     cache: &mut RenderCache<S>,
     region: Region,
     pool: &jxl_threadpool::JxlThreadPool,
+    control: &RenderControl,
 ) -> Result<ImageWithRegion> {
     let image_header = frame.image_header();
     let frame_header = frame.header();
@@ -43,6 +48,7 @@ pub(crate) fn render_modular<S: Sample>(
                     lf_group_image,
                     modular_region.downsample(3),
                     pool,
+                    control,
                 );
                 if let Err(e) = r {
                     *result.write().unwrap() = Err(e);
@@ -91,6 +97,8 @@ pub(crate) fn render_modular<S: Sample>(
                 })
                 .collect::<Vec<_>>();
 
+            let progress = control.track_groups(frame.idx, jobs.len());
+            let progress = &progress;
             pool.for_each_vec(
                 jobs,
                 |PassGroupJob {
@@ -98,13 +106,32 @@ pub(crate) fn render_modular<S: Sample>(
                      group_idx,
                      modular,
                  }| {
+                    if progress.is_cancelled() {
+                        return;
+                    }
+
                     let bitstream = match frame.pass_group_bitstream(pass_idx, group_idx) {
                         Some(Ok(bitstream)) => bitstream,
                         Some(Err(e)) => {
-                            *result.write().unwrap() = Err(e.into());
+                            let kind = GroupKind::PassGroup {
+                                pass_idx,
+                                group_idx,
+                            };
+                            if let Err(e) = control.tolerate(frame.idx, kind, e.into()) {
+                                *result.write().unwrap() = Err(e);
+                            }
+                            progress.complete_group();
+                            return;
+                        }
+                        None => {
+                            let kind = GroupKind::PassGroup {
+                                pass_idx,
+                                group_idx,
+                            };
+                            control.record_missing(frame.idx, kind);
+                            progress.complete_group();
                             return;
                         }
-                        None => return,
                     };
 
                     let allow_partial = bitstream.partial;
@@ -122,12 +149,21 @@ pub(crate) fn render_modular<S: Sample>(
                         tracker,
                         pool,
                     );
-                    if !allow_partial && r.is_err() {
-                        *result.write().unwrap() = r.map_err(From::from);
+                    if let (false, Err(e)) = (allow_partial, r) {
+                        // Decoded part of the group is kept in error-tolerant mode.
+                        let kind = GroupKind::PassGroup {
+                            pass_idx,
+                            group_idx,
+                        };
+                        if let Err(e) = control.tolerate(frame.idx, kind, e.into()) {
+                            *result.write().unwrap() = Err(e);
+                        }
                     }
+                    progress.complete_group();
                 },
             );
         });
+        control.check()?;
         result.into_inner().unwrap()
     })?;
 
@@ -135,7 +171,8 @@ pub(crate) fn render_modular<S: Sample>(
         modular_image.prepare_subimage().unwrap().finish(pool);
     });
 
-    let mut fb = ImageWithRegion::new(frame_header.encoded_color_channels(), tracker);
+    let tracker = tracker.map(|tracker| tracker.with_category(AllocCategory::ColorBuffer));
+    let mut fb = ImageWithRegion::new(frame_header.encoded_color_channels(), tracker.as_ref());
     fb.extend_from_gmodular(gmodular);
 
     if xyb_encoded {
@@ -2,15 +2,89 @@ use jxl_frame::{
     filter::{EdgePreservingFilter, Gabor},
     header::Encoding,
 };
-use jxl_grid::AlignedGrid;
+use jxl_grid::{AlignedGrid, AllocCategory};
 use jxl_modular::Sample;
 use jxl_threadpool::JxlThreadPool;
 
 use crate::{
-    blend, features, filter, modular, state::RenderCache, util, vardct, Error, ImageWithRegion,
-    IndexedFrame, Reference, ReferenceFrames, Region, Result,
+    blend, control::RenderControl, features, filter, modular, state::RenderCache, util, vardct,
+    Error, ImageWithRegion, IndexedFrame, Reference, ReferenceFrames, Region, Result,
 };
 
+/// Fills color channels of damaged LF groups with neutral gray, in error-tolerant mode.
+fn fill_damaged_lf_groups(
+    frame: &IndexedFrame,
+    fb: &mut ImageWithRegion,
+    control: &RenderControl,
+) -> Result<()> {
+    let damaged_lf_groups = control.damaged_lf_groups(frame.idx);
+    if damaged_lf_groups.is_empty() {
+        return Ok(());
+    }
+
+    let metadata = &frame.image_header().metadata;
+    let frame_header = frame.header();
+    let neutral = if frame_header.do_ycbcr {
+        // Luma and chroma are centered at zero.
+        [0.0; 3]
+    } else if metadata.xyb_encoded {
+        neutral_xyb(
+            metadata.opsin_inverse_matrix.opsin_bias,
+            metadata.tone_mapping.intensity_target,
+        )
+    } else {
+        [0.5; 3]
+    };
+    // Integer samples are converted to float so that neutral value can be written directly.
+    fb.convert_modular_color(metadata.bit_depth)?;
+
+    let lf_group_dim = frame_header.group_dim() * 8;
+    let lf_groups_per_row = frame_header.lf_groups_per_row();
+    for lf_group_idx in damaged_lf_groups {
+        let lf_group_region = Region {
+            left: ((lf_group_idx % lf_groups_per_row) * lf_group_dim) as i32,
+            top: ((lf_group_idx / lf_groups_per_row) * lf_group_dim) as i32,
+            width: lf_group_dim,
+            height: lf_group_dim,
+        };
+        tracing::debug!(lf_group_idx, "Filling damaged LF group");
+
+        for (idx, value) in neutral.into_iter().enumerate().take(fb.color_channels()) {
+            let (region, shift) = fb.regions_and_shifts()[idx];
+            let target = lf_group_region.intersection(region);
+            if target.is_empty() {
+                continue;
+            }
+            let target = target
+                .translate(-region.left, -region.top)
+                .downsample_with_shift(shift);
+
+            let grid = fb.buffer_mut()[idx].as_float_mut().unwrap();
+            let width = grid.width();
+            let right = (target.right() as usize).min(width);
+            let bottom = (target.bottom() as usize).min(grid.height());
+            for row in grid
+                .buf_mut()
+                .chunks_exact_mut(width)
+                .take(bottom)
+                .skip(target.top as usize)
+            {
+                row[target.left as usize..right].fill(value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns XYB value of linear gray which is 0.5 in sRGB transfer function.
+fn neutral_xyb(opsin_bias: [f32; 3], intensity_target: f32) -> [f32; 3] {
+    let itscale = 255.0 / intensity_target;
+    let [_, y, b] = opsin_bias.map(|ob| (0.21404114 / itscale - ob).cbrt() + ob.cbrt());
+    [0.0, y, b]
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn render_frame<S: Sample>(
     frame: &IndexedFrame,
     reference_frames: ReferenceFrames<S>,
@@ -18,7 +92,11 @@ pub(crate) fn render_frame<S: Sample>(
     image_region: Region,
     pool: JxlThreadPool,
     frame_visibility: (usize, usize),
+    lf_only: bool,
+    control: &RenderControl,
 ) -> Result<ImageWithRegion> {
+    control.check()?;
+    control.clear_damage(frame.idx);
     let frame_region = util::image_region_to_frame(frame, image_region, false);
     tracing::debug!(
         index = frame.idx,
@@ -44,7 +122,9 @@ pub(crate) fn render_frame<S: Sample>(
         .intersection(full_frame_region);
 
     let mut fb = match frame_header.encoding {
-        Encoding::Modular => modular::render_modular(frame, cache, color_padded_region, &pool)?,
+        Encoding::Modular => {
+            modular::render_modular(frame, cache, color_padded_region, &pool, control)?
+        }
         Encoding::VarDct => {
             let result = vardct::render_vardct(
                 frame,
@@ -52,6 +132,8 @@ pub(crate) fn render_frame<S: Sample>(
                 cache,
                 color_padded_region,
                 &pool,
+                lf_only,
+                control,
             );
             match (result, reference_frames.lf) {
                 (Ok(grid), _) => grid,
@@ -65,6 +147,9 @@ pub(crate) fn render_frame<S: Sample>(
         }
     };
 
+    control.check()?;
+    fill_damaged_lf_groups(frame, &mut fb, control)?;
+
     if frame_header.do_ycbcr {
         fb.upsample_jpeg(color_padded_region, image_header.metadata.bit_depth)?;
     }
@@ -79,7 +164,10 @@ pub(crate) fn render_frame<S: Sample>(
 
         fb.convert_modular_color(image_header.metadata.bit_depth)?;
         let mut fb_scratch = {
-            let tracker = fb.alloc_tracker();
+            let tracker = fb
+                .alloc_tracker()
+                .map(|tracker| tracker.with_category(AllocCategory::FilterScratch));
+            let tracker = tracker.as_ref();
             let width = color_padded_region.width as usize;
             let height = color_padded_region.height as usize;
             [
@@ -108,7 +196,10 @@ pub(crate) fn render_frame<S: Sample>(
         let fb_scratch = if let Some(buffer) = scratch_buffer {
             buffer
         } else {
-            let tracker = fb.alloc_tracker();
+            let tracker = fb
+                .alloc_tracker()
+                .map(|tracker| tracker.with_category(AllocCategory::FilterScratch));
+            let tracker = tracker.as_ref();
             let width = color_padded_region.width as usize;
             let height = color_padded_region.height as usize;
             [
@@ -128,6 +219,8 @@ pub(crate) fn render_frame<S: Sample>(
         );
     }
 
+    control.check()?;
+
     // Truncate cloned gray channels.
     fb.remove_color_channels(color_channels);
 
@@ -144,6 +237,7 @@ pub(crate) fn render_frame<S: Sample>(
         &pool,
     )?;
 
+    control.check()?;
     fb.upsample_nonseparable(image_header, frame_header, upsampling_valid_region, false)?;
 
     if !frame_header.save_before_ct && !frame_header.is_last {
@@ -220,3 +314,24 @@ fn render_features<S: Sample>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neutral_xyb_is_gray() {
+        let opsin_bias = [-0.003793073; 3];
+        for intensity_target in [255.0, 10000.0] {
+            let [x, y, b] = neutral_xyb(opsin_bias, intensity_target);
+            assert_eq!(x, 0.0);
+            assert_eq!(y, b);
+
+            // Inverse XYB, as in `jxl_color`.
+            let cbrt_ob = opsin_bias[1].cbrt();
+            let lms = (y - cbrt_ob).powi(3) + opsin_bias[1];
+            let linear = lms * 255.0 / intensity_target;
+            assert!((linear - 0.21404114).abs() < 1e-5);
+        }
+    }
+}
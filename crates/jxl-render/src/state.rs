@@ -13,6 +13,10 @@ use crate::{
 pub type RenderOp<S> =
     Arc<dyn Fn(FrameRender<S>, Region) -> FrameRender<S> + Send + Sync + 'static>;
 
+/// Slot which receives the render cache of a frame after it's rendered, so that decoded global and
+/// LF group data can be reused when the frame is rendered again for another region.
+pub type RetainedCache<S> = Arc<Mutex<Option<Box<RenderCache<S>>>>>;
+
 #[derive(Debug)]
 pub struct RenderCache<S: Sample> {
     pub(crate) lf_global: Option<LfGlobal<S>>,
@@ -76,6 +80,7 @@ pub struct FrameRenderHandle<S: Sample> {
     pub(crate) condvar: Condvar,
     pub(crate) render_op: RenderOp<S>,
     pub(crate) refs: [Option<Reference<S>>; 4],
+    pub(crate) retained_cache: Option<RetainedCache<S>>,
 }
 
 impl<S: Sample> std::fmt::Debug for FrameRenderHandle<S> {
@@ -102,6 +107,7 @@ impl<S: Sample> FrameRenderHandle<S> {
             condvar: Condvar::new(),
             render_op,
             refs,
+            retained_cache: None,
         }
     }
 
@@ -121,9 +127,21 @@ impl<S: Sample> FrameRenderHandle<S> {
             condvar: Condvar::new(),
             render_op,
             refs,
+            retained_cache: None,
         }
     }
 
+    #[inline]
+    pub fn with_retained_cache(mut self, retained_cache: RetainedCache<S>) -> Self {
+        self.retained_cache = Some(retained_cache);
+        self
+    }
+
+    /// Takes the render cache retained after rendering, if any.
+    pub fn take_retained_cache(&self) -> Option<Box<RenderCache<S>>> {
+        self.retained_cache.as_ref()?.lock().unwrap().take()
+    }
+
     pub fn run_with_image(self: Arc<Self>) -> Result<RenderedImage<S>> {
         let _guard = tracing::trace_span!("Run with image", index = self.frame.idx).entered();
 
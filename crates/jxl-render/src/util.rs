@@ -7,13 +7,14 @@ use jxl_frame::{
     header::FrameType,
     Frame, FrameHeader,
 };
-use jxl_grid::{AlignedGrid, MutableSubgrid};
+use jxl_grid::{AlignedGrid, AllocCategory, MutableSubgrid};
 use jxl_image::ImageHeader;
 use jxl_modular::{image::TransformedModularSubimage, ChannelShift, Sample};
 use jxl_threadpool::JxlThreadPool;
 
 use crate::{
-    image::ImageBuffer, vardct::copy_lf_dequant, ImageWithRegion, IndexedFrame, Region, Result,
+    control::RenderControl, image::ImageBuffer, vardct::copy_lf_dequant, GroupKind,
+    ImageWithRegion, IndexedFrame, Region, Result,
 };
 
 pub(crate) fn image_region_to_frame(
@@ -126,6 +127,7 @@ pub(crate) fn load_lf_groups<S: Sample>(
     mlf_groups: Vec<TransformedModularSubimage<S>>,
     lf_region: Region,
     pool: &JxlThreadPool,
+    control: &RenderControl,
 ) -> Result<Option<ImageWithRegion>> {
     #[derive(Default)]
     struct LfGroupJob<'modular, 'xyb, S: Sample> {
@@ -143,7 +145,10 @@ pub(crate) fn load_lf_groups<S: Sample>(
     let shifts_cbycr: [_; 3] =
         std::array::from_fn(|idx| ChannelShift::from_jpeg_upsampling(jpeg_upsampling, idx));
     let mut lf_xyb = if lf_global_vardct.is_some() && !frame_header.flags.use_lf_frame() {
-        let tracker = frame.alloc_tracker();
+        let tracker = frame
+            .alloc_tracker()
+            .map(|tracker| tracker.with_category(AllocCategory::ColorBuffer));
+        let tracker = tracker.as_ref();
         let mut out = ImageWithRegion::new(3, tracker);
         let Region { width, height, .. } = lf_region;
         for shift in shifts_cbycr {
@@ -218,7 +223,16 @@ pub(crate) fn load_lf_groups<S: Sample>(
                 })
             });
 
+            // Groups with modular data are parsed again, as the data is decoded into the
+            // modular image of this render.
+            let lf_group = if modular.as_ref().map(|m| m.is_empty()).unwrap_or(true) {
+                lf_groups.remove(&idx)
+            } else {
+                None
+            };
+
             Some(LfGroupJob {
+                lf_group,
                 modular,
                 lf_xyb,
                 ..job
@@ -244,10 +258,14 @@ pub(crate) fn load_lf_groups<S: Sample>(
                     *lf_group = Some(g);
                 }
                 Some(Err(e)) => {
-                    *result.write().unwrap() = Err(e.into());
+                    let r = control.tolerate(frame.idx, GroupKind::LfGroup(idx), e.into());
+                    if let Err(e) = r {
+                        *result.write().unwrap() = Err(e);
+                    }
                     return;
                 }
                 None => {
+                    control.record_missing(frame.idx, GroupKind::LfGroup(idx));
                     return;
                 }
             }
@@ -4,7 +4,7 @@ use jxl_frame::{
     data::{HfGlobal, LfGlobal, LfGroup, PassGroupParams, PassGroupParamsVardct},
     FrameHeader,
 };
-use jxl_grid::{AlignedGrid, MutableSubgrid, SharedSubgrid};
+use jxl_grid::{AlignedGrid, AllocCategory, MutableSubgrid, SharedSubgrid};
 use jxl_image::ImageHeader;
 use jxl_modular::{ChannelShift, Sample};
 use jxl_threadpool::JxlThreadPool;
@@ -13,8 +13,8 @@ use jxl_vardct::{
 };
 
 use crate::{
-    image::ImageBuffer, modular, util, Error, ImageWithRegion, IndexedFrame, Reference, Region,
-    RenderCache, Result,
+    control::RenderControl, image::ImageBuffer, modular, util, Error, GroupKind, ImageWithRegion,
+    IndexedFrame, Reference, Region, RenderCache, Result,
 };
 
 mod dct_common;
@@ -43,12 +43,87 @@ mod generic;
 )))]
 use generic as impls;
 
+/// Renders LF image of the frame, at 1/8 of the frame dimension.
+///
+/// HF coefficients are not decoded, and the image is left in the color space of the frame.
+/// Frames with chroma subsampling are not supported.
+pub(crate) fn render_vardct_lf<S: Sample>(
+    frame: &IndexedFrame,
+    lf_frame: Option<&Reference<S>>,
+    cache: &mut RenderCache<S>,
+    pool: &JxlThreadPool,
+    control: &RenderControl,
+) -> Result<ImageWithRegion> {
+    let span = tracing::span!(tracing::Level::TRACE, "Render VarDCT LF");
+    let _guard = span.enter();
+
+    if let Some(x) = lf_frame {
+        let lf_frame = std::sync::Arc::clone(&x.image).run_with_image()?;
+        return lf_frame.blend(None, pool)?.try_clone();
+    }
+
+    let frame_header = frame.header();
+    let lf_global = if let Some(x) = &cache.lf_global {
+        x
+    } else {
+        let lf_global = frame
+            .try_parse_lf_global()
+            .ok_or(Error::IncompleteFrame)??;
+        cache.lf_global = Some(lf_global);
+        cache.lf_global.as_ref().unwrap()
+    };
+    let mut gmodular = lf_global.gmodular.try_clone()?;
+    let lf_global_vardct = lf_global.vardct.as_ref().unwrap();
+
+    let lf_region = Region::with_size(
+        frame_header.color_sample_width().div_ceil(8),
+        frame_header.color_sample_height().div_ceil(8),
+    );
+    let mut modular_image = gmodular.modular.image_mut();
+    let lf_group_image = modular_image
+        .as_mut()
+        .map(|x| x.prepare_groups(frame.pass_shifts()))
+        .transpose()?
+        .map(|x| x.lf_groups)
+        .unwrap_or_default();
+
+    let lf_xyb = tracing::trace_span!("Load LF groups").in_scope(|| {
+        util::load_lf_groups(
+            frame,
+            lf_global,
+            &mut cache.lf_groups,
+            lf_group_image,
+            lf_region,
+            pool,
+            control,
+        )
+    })?;
+    let mut lf_xyb = lf_xyb.ok_or(Error::IncompleteFrame)?;
+
+    tracing::trace_span!("LF CfL").in_scope(|| {
+        chroma_from_luma_lf(lf_xyb.as_color_floats_mut(), &lf_global_vardct.lf_chan_corr);
+    });
+    if !frame_header.flags.skip_adaptive_lf_smoothing() {
+        tracing::trace_span!("Adaptive LF smoothing").in_scope(|| {
+            adaptive_lf_smoothing(
+                lf_xyb.as_color_floats_mut(),
+                &lf_global.lf_dequant,
+                &lf_global_vardct.quantizer,
+            )
+        })?;
+    }
+
+    Ok(lf_xyb)
+}
+
 pub(crate) fn render_vardct<S: Sample>(
     frame: &IndexedFrame,
     lf_frame: Option<&Reference<S>>,
     cache: &mut RenderCache<S>,
     region: Region,
     pool: &JxlThreadPool,
+    lf_only: bool,
+    control: &RenderControl,
 ) -> Result<ImageWithRegion> {
     let span = tracing::span!(tracing::Level::TRACE, "Render VarDCT");
     let _guard = span.enter();
@@ -135,6 +210,11 @@ pub(crate) fn render_vardct<S: Sample>(
         ret.resize_with(passes, Vec::new);
         ret
     });
+    // HF coefficients can be skipped only if pass groups don't contain modular data.
+    let skip_hf = lf_only && pass_group_image.iter().all(|pass| pass.is_empty());
+    if lf_only && !skip_hf {
+        tracing::debug!("Pass groups contain modular data, decoding HF coefficients");
+    }
 
     let hf_global = &mut cache.hf_global;
     let lf_groups = &mut cache.lf_groups;
@@ -142,10 +222,17 @@ pub(crate) fn render_vardct<S: Sample>(
 
     let result = std::sync::RwLock::new(Result::Ok(()));
     let (mut fb, lf_xyb) = pool.scope(|scope| -> Result<_> {
-        if hf_global.is_none() {
+        if hf_global.is_none() && !skip_hf {
             scope.spawn(|_| {
                 let ret = tracing::trace_span!("Parse HfGlobal").in_scope(|| -> Result<_> {
-                    *hf_global = frame.try_parse_hf_global(Some(lf_global)).transpose()?;
+                    match frame.try_parse_hf_global(Some(lf_global)) {
+                        Some(Ok(parsed)) => *hf_global = Some(parsed),
+                        // HF coefficients are not decoded if HfGlobal is damaged.
+                        Some(Err(e)) => {
+                            control.tolerate(frame.idx, GroupKind::HfGlobal, e.into())?
+                        }
+                        None => control.record_missing(frame.idx, GroupKind::HfGlobal),
+                    }
                     Ok(())
                 });
                 if let Err(e) = ret {
@@ -162,6 +249,7 @@ pub(crate) fn render_vardct<S: Sample>(
                 lf_group_image,
                 modular_lf_region,
                 pool,
+                control,
             )
         })?;
 
@@ -202,6 +290,8 @@ pub(crate) fn render_vardct<S: Sample>(
             });
             let Region { width, height, .. } = modular_region;
 
+            let tracker = tracker.map(|tracker| tracker.with_category(AllocCategory::ColorBuffer));
+            let tracker = tracker.as_ref();
             let mut fb = ImageWithRegion::new(3, tracker);
             for shift in shifts_cbycr {
                 let (width, height) = shift.shift_size((width, height));
@@ -215,8 +305,9 @@ pub(crate) fn render_vardct<S: Sample>(
         Ok((fb, lf_xyb))
     })?;
     result.into_inner().unwrap()?;
+    control.check()?;
 
-    let hf_global = cache.hf_global.as_ref();
+    let hf_global = cache.hf_global.as_ref().filter(|_| !skip_hf);
     let lf_groups = &mut cache.lf_groups;
 
     let it = tracing::trace_span!("Prepare PassGroup").in_scope(|| {
@@ -231,6 +322,17 @@ pub(crate) fn render_vardct<S: Sample>(
             .collect::<Vec<_>>()
     });
 
+    // Each group is decoded once per pass, and then transformed.
+    let num_pass_jobs = if hf_global.is_some() {
+        pass_group_image.len() * it.len()
+    } else {
+        0
+    };
+    let progress = control.track_groups(frame.idx, num_pass_jobs + it.len());
+    let progress = &progress;
+    // Groups which failed to decode in error-tolerant mode, mapped to the first pass which failed.
+    let damaged_groups = std::sync::Mutex::new(HashMap::<u32, u32>::new());
+
     tracing::trace_span!("Decode PassGroup").in_scope(|| {
         let Some(hf_global) = hf_global else {
             return Ok(());
@@ -253,16 +355,28 @@ pub(crate) fn render_vardct<S: Sample>(
                         .map(|grid| unsafe { grid.as_shared().as_atomic_i32() });
 
                     if lf_group.hf_meta.is_none() {
+                        progress.complete_group();
                         continue;
                     }
 
+                    let kind = GroupKind::PassGroup {
+                        pass_idx,
+                        group_idx,
+                    };
                     let bitstream = match frame.pass_group_bitstream(pass_idx, group_idx) {
                         Some(Ok(bitstream)) => bitstream,
                         Some(Err(e)) => {
-                            *result.write().unwrap() = Err(e.into());
+                            if let Err(e) = control.tolerate(frame.idx, kind, e.into()) {
+                                *result.write().unwrap() = Err(e);
+                            }
+                            progress.complete_group();
+                            continue;
+                        }
+                        None => {
+                            control.record_missing(frame.idx, kind);
+                            progress.complete_group();
                             continue;
                         }
-                        None => continue,
                     };
                     let allow_partial = bitstream.partial;
                     let mut bitstream = bitstream.bitstream;
@@ -272,7 +386,12 @@ pub(crate) fn render_vardct<S: Sample>(
                         .map(|(_, modular)| modular);
 
                     let result = &result;
+                    let damaged_groups = &damaged_groups;
                     scope.spawn(move |_| {
+                        if progress.is_cancelled() {
+                            return;
+                        }
+
                         let vardct = Some(PassGroupParamsVardct {
                             lf_vardct: lf_global_vardct,
                             hf_global,
@@ -294,21 +413,37 @@ pub(crate) fn render_vardct<S: Sample>(
                                 pool,
                             },
                         );
-                        if !allow_partial && r.is_err() {
-                            *result.write().unwrap() = r.map_err(From::from);
+                        if let (false, Err(e)) = (allow_partial, r) {
+                            match control.tolerate(frame.idx, kind, e.into()) {
+                                Ok(()) => {
+                                    let mut damaged_groups = damaged_groups.lock().unwrap();
+                                    let failed_pass =
+                                        damaged_groups.entry(group_idx).or_insert(pass_idx);
+                                    *failed_pass = (*failed_pass).min(pass_idx);
+                                }
+                                Err(e) => *result.write().unwrap() = Err(e),
+                            }
                         }
+                        progress.complete_group();
                     });
                 }
             }
         });
 
+        progress.check()?;
         result.into_inner().unwrap()
     })?;
 
+    let damaged_groups = damaged_groups.into_inner().unwrap();
+
     tracing::trace_span!("Dequant and transform").in_scope(|| {
         let groups_per_row = frame_header.groups_per_row();
 
         pool.for_each_vec(it, |job| {
+            if progress.is_cancelled() {
+                return;
+            }
+
             let (group_idx, mut grid_xyb, lf_group) = job;
             let grid_xyb = &mut grid_xyb;
             let group_x = group_idx % groups_per_row;
@@ -327,8 +462,42 @@ pub(crate) fn render_vardct<S: Sample>(
                 !group_region.intersection(aligned_region).is_empty()
             };
 
+            if let Some(&failed_pass) = damaged_groups.get(&group_idx) {
+                // Coefficients of all passes are accumulated in the same grid, so the passes
+                // before the failed one are decoded again to discard the failed ones.
+                let restored = failed_pass > 0
+                    && redecode_passes(
+                        frame,
+                        lf_group,
+                        group_idx,
+                        failed_pass,
+                        gmodular.ma_config.as_ref(),
+                        lf_global_vardct,
+                        hf_global.unwrap(),
+                        grid_xyb,
+                        pool,
+                    );
+                if !restored {
+                    for grid in grid_xyb.iter_mut() {
+                        for y in 0..grid.height() {
+                            grid.get_row_mut(y).fill(0.0);
+                        }
+                    }
+                    transform_with_lf_grouped(
+                        &lf_xyb,
+                        grid_xyb,
+                        group_idx,
+                        frame_header,
+                        lf_groups,
+                    );
+                    progress.complete_group();
+                    return;
+                }
+            }
+
             if lf_group.hf_meta.is_none() || hf_global.is_none() || !transform_hf {
                 transform_with_lf_grouped(&lf_xyb, grid_xyb, group_idx, frame_header, lf_groups);
+                progress.complete_group();
                 return;
             }
 
@@ -363,8 +532,10 @@ pub(crate) fn render_vardct<S: Sample>(
             }
 
             transform_with_lf_grouped(&lf_xyb, grid_xyb, group_idx, frame_header, lf_groups);
+            progress.complete_group();
         });
     });
+    control.check()?;
 
     if let Some(modular_image) = modular_image {
         tracing::trace_span!("Extra channel inverse transform").in_scope(|| {
@@ -376,6 +547,61 @@ pub(crate) fn render_vardct<S: Sample>(
     Ok(fb)
 }
 
+/// Decodes HF coefficients of passes before `failed_pass` into the zeroed coefficient grid of the
+/// group. Returns `false` if any of the passes fails to decode.
+#[allow(clippy::too_many_arguments)]
+fn redecode_passes<S: Sample>(
+    frame: &IndexedFrame,
+    lf_group: &LfGroup<S>,
+    group_idx: u32,
+    failed_pass: u32,
+    global_ma_config: Option<&jxl_modular::MaConfig>,
+    lf_vardct: &jxl_frame::data::LfGlobalVarDct,
+    hf_global: &HfGlobal,
+    grid_xyb: &mut [MutableSubgrid<f32>; 3],
+    pool: &JxlThreadPool,
+) -> bool {
+    for grid in grid_xyb.iter_mut() {
+        for y in 0..grid.height() {
+            grid.get_row_mut(y).fill(0.0);
+        }
+    }
+
+    // SAFETY: `grid_xyb` is borrowed exclusively, and it's accessed only through the atomic views
+    // until they are dropped.
+    let hf_coeff_output = grid_xyb
+        .each_ref()
+        .map(|grid| unsafe { grid.as_shared().as_atomic_i32() });
+    (0..failed_pass).all(|pass_idx| {
+        let Some(Ok(bitstream)) = frame.pass_group_bitstream(pass_idx, group_idx) else {
+            // The pass was skipped in the first place.
+            return true;
+        };
+        let allow_partial = bitstream.partial;
+        let mut bitstream = bitstream.bitstream;
+        let r = jxl_frame::data::decode_pass_group(
+            &mut bitstream,
+            PassGroupParams {
+                frame_header: frame.header(),
+                lf_group,
+                pass_idx,
+                group_idx,
+                global_ma_config,
+                modular: None,
+                vardct: Some(PassGroupParamsVardct {
+                    lf_vardct,
+                    hf_global,
+                    hf_coeff_output: &hf_coeff_output,
+                }),
+                allow_partial,
+                tracker: frame.alloc_tracker(),
+                pool,
+            },
+        );
+        allow_partial || r.is_ok()
+    })
+}
+
 pub fn copy_lf_dequant<S: Sample>(
     grid: &mut MutableSubgrid<f32>,
     quantizer: &Quantizer,
@@ -414,7 +640,9 @@ pub fn adaptive_lf_smoothing(
     let lf_b = (512.0 * lf_dequant.m_b_lf as f64 / scale_inv as f64) as f32;
 
     let [in_x, in_y, in_b] = lf_image;
-    let tracker = in_x.tracker();
+    let tracker = in_x
+        .tracker()
+        .map(|tracker| tracker.with_category(AllocCategory::FilterScratch));
     let width = in_x.width();
     let height = in_x.height();
 
@@ -0,0 +1,166 @@
+/// Job run in a fork-join scope, which may borrow data that outlives `'scope`.
+///
+/// The job is given the scope so that it can spawn more jobs.
+pub type JxlScopedJob<'scope, 'a> =
+    Box<dyn for<'r> FnOnce(&'r dyn JxlExecutorScope<'scope>) + Send + 'a>;
+
+/// Job executor provided by the application.
+///
+/// Implement this trait to run jobs of jxl-oxide on an existing thread pool or job system, and
+/// wrap it with [`JxlThreadPool::with_executor`][crate::JxlThreadPool::with_executor].
+pub trait JxlExecutor: Send + Sync {
+    /// Returns the number of jobs that can run concurrently.
+    ///
+    /// The thread pool is considered single-threaded if this returns 1 or less.
+    fn num_threads(&self) -> usize;
+
+    /// Runs the job, possibly on another thread.
+    fn spawn(&self, job: Box<dyn FnOnce() + Send + 'static>);
+
+    /// Creates a fork-join scope, and runs `op` with the scope.
+    ///
+    /// This method must not return until `op` and every job spawned in the scope, including jobs
+    /// spawned by other jobs, complete.
+    fn scope<'scope>(&self, op: JxlScopedJob<'scope, '_>);
+
+    /// Runs `op` for each index in `0..num_jobs`, and waits for all of them to complete.
+    ///
+    /// Default implementation spawns a job for each index in a fork-join scope.
+    fn for_each(&self, num_jobs: usize, op: &(dyn Fn(usize) + Sync)) {
+        self.scope(Box::new(move |scope| {
+            for idx in 0..num_jobs {
+                scope.spawn(Box::new(move |_| op(idx)));
+            }
+        }));
+    }
+}
+
+/// Fork-join scope created by [`JxlExecutor::scope`].
+pub trait JxlExecutorScope<'scope>: Sync {
+    /// Spawns the job in the scope.
+    fn spawn(&self, job: JxlScopedJob<'scope, 'scope>);
+}
+
+impl std::fmt::Debug for dyn JxlExecutor + '_ {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JxlExecutor")
+            .field("num_threads", &self.num_threads())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'scope> std::fmt::Debug for dyn JxlExecutorScope<'scope> + '_ {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JxlExecutorScope").finish_non_exhaustive()
+    }
+}
+
+/// Runs `op` for each item of the iterator on the executor.
+pub(crate) fn for_each<I>(executor: &dyn JxlExecutor, it: I, op: impl Fn(I::Item) + Sync)
+where
+    I: ExactSizeIterator + Send,
+    I::Item: Send,
+{
+    let num_jobs = it.len();
+    let it = std::sync::Mutex::new(it);
+    executor.for_each(num_jobs, &|_| {
+        let item = it.lock().unwrap().next();
+        if let Some(item) = item {
+            op(item);
+        }
+    });
+}
+
+/// Runs `op` for each item of the iterator on the executor, with state cloned from `init` for
+/// each worker.
+pub(crate) fn for_each_with<I, U>(
+    executor: &dyn JxlExecutor,
+    it: I,
+    init: U,
+    op: impl Fn(&mut U, I::Item) + Sync,
+) where
+    I: ExactSizeIterator + Send,
+    I::Item: Send,
+    U: Send + Clone,
+{
+    let num_workers = it.len().min(executor.num_threads().max(1));
+    let states = std::sync::Mutex::new(vec![init; num_workers]);
+    let it = std::sync::Mutex::new(it);
+    executor.for_each(num_workers, &|_| {
+        let Some(mut state) = states.lock().unwrap().pop() else {
+            return;
+        };
+        loop {
+            let item = it.lock().unwrap().next();
+            let Some(item) = item else {
+                break;
+            };
+            op(&mut state, item);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::JxlThreadPool;
+
+    /// Executor which spawns a thread for each job.
+    struct ThreadPerJob;
+
+    struct ThreadPerJobScope<'s, 'env>(&'s std::thread::Scope<'s, 'env>);
+
+    impl<'scope, 's, 'env> JxlExecutorScope<'scope> for ThreadPerJobScope<'s, 'env>
+    where
+        'scope: 's,
+    {
+        fn spawn(&self, job: JxlScopedJob<'scope, 'scope>) {
+            let scope = self.0;
+            scope.spawn(move || job(&ThreadPerJobScope(scope)));
+        }
+    }
+
+    impl JxlExecutor for ThreadPerJob {
+        fn num_threads(&self) -> usize {
+            4
+        }
+
+        fn spawn(&self, job: Box<dyn FnOnce() + Send + 'static>) {
+            std::thread::spawn(job);
+        }
+
+        fn scope<'scope>(&self, op: JxlScopedJob<'scope, '_>) {
+            std::thread::scope(|scope| op(&ThreadPerJobScope(scope)));
+        }
+    }
+
+    #[test]
+    fn custom_executor() {
+        let pool = JxlThreadPool::with_executor(Arc::new(ThreadPerJob));
+        assert!(pool.is_multithreaded());
+
+        let sum = AtomicUsize::new(0);
+        pool.for_each_vec((1..=100).collect(), |v| {
+            sum.fetch_add(v, Ordering::Relaxed);
+        });
+        assert_eq!(sum.load(Ordering::Relaxed), 5050);
+
+        let mut v = vec![0usize; 100];
+        pool.for_each_mut_slice_with(&mut v, 1usize, |inc, v| *v += *inc);
+        assert!(v.iter().all(|&v| v == 1));
+
+        let ret = pool.scope(|scope| {
+            scope.spawn(|scope| {
+                scope.spawn(|_| {
+                    sum.fetch_add(1, Ordering::Relaxed);
+                });
+            });
+            42
+        });
+        assert_eq!(ret, 42);
+        assert_eq!(sum.load(Ordering::Relaxed), 5051);
+    }
+}
@@ -2,19 +2,29 @@
 //!
 //! [`JxlThreadPool`] is re-exported by `jxl-oxide`.
 
+mod executor;
+mod std_threads;
+
+pub use executor::{JxlExecutor, JxlExecutorScope, JxlScopedJob};
+
+use std::sync::Arc;
+
 /// Thread pool wrapper.
 ///
 /// This struct wraps internal thread pool implementation and provides interfaces to access it. If
-/// `rayon` feature is enabled, users can create an actual thread pool backed by Rayon; if not,
-/// this struct won't have any multithreading capability, and every spawn operation will just run
-/// the given closure in place.
+/// `rayon` feature is enabled, users can create an actual thread pool backed by Rayon. Thread pool
+/// using only the standard library can be created with [`std_threads`][Self::std_threads], and
+/// users can also provide their own executor by implementing [`JxlExecutor`]. Thread pools created with
+/// [`none`][Self::none] don't have any multithreading capability, and every spawn operation will
+/// just run the given closure in place.
 #[derive(Debug, Clone)]
 pub struct JxlThreadPool(JxlThreadPoolImpl);
 
 #[derive(Debug, Clone)]
 enum JxlThreadPoolImpl {
     #[cfg(feature = "rayon")]
-    Rayon(std::sync::Arc<rayon_core::ThreadPool>),
+    Rayon(Arc<rayon_core::ThreadPool>),
+    Executor(Arc<dyn JxlExecutor>),
     None,
 }
 
@@ -26,6 +36,7 @@ pub struct JxlScope<'r, 'scope>(JxlScopeInner<'r, 'scope>);
 enum JxlScopeInner<'r, 'scope> {
     #[cfg(feature = "rayon")]
     Rayon(&'r rayon_core::Scope<'scope>),
+    Executor(&'r dyn JxlExecutorScope<'scope>),
     None(std::marker::PhantomData<&'r &'scope ()>),
 }
 
@@ -39,10 +50,15 @@ impl JxlThreadPool {
 
     /// Creates a thread pool backed by Rayon [`ThreadPool`][rayon_core::ThreadPool].
     #[cfg(feature = "rayon")]
-    pub fn with_rayon_thread_pool(pool: std::sync::Arc<rayon_core::ThreadPool>) -> Self {
+    pub fn with_rayon_thread_pool(pool: Arc<rayon_core::ThreadPool>) -> Self {
         Self(JxlThreadPoolImpl::Rayon(pool))
     }
 
+    /// Creates a thread pool backed by the given [`JxlExecutor`].
+    pub fn with_executor(executor: Arc<dyn JxlExecutor>) -> Self {
+        Self(JxlThreadPoolImpl::Executor(executor))
+    }
+
     /// Creates a thread pool backed by Rayon.
     ///
     /// If `num_threads_requested` is `None` or zero, this method queries available paralleism and
@@ -67,7 +83,7 @@ impl JxlThreadPool {
         let inner = rayon_core::ThreadPoolBuilder::new()
             .num_threads(num_threads)
             .build()
-            .map(|pool| JxlThreadPoolImpl::Rayon(std::sync::Arc::new(pool)));
+            .map(|pool| JxlThreadPoolImpl::Rayon(Arc::new(pool)));
 
         match inner {
             Ok(inner) => {
@@ -81,12 +97,56 @@ impl JxlThreadPool {
         }
     }
 
+    /// Creates a thread pool backed by persistent worker threads, using only the standard library.
+    ///
+    /// If `num_threads_requested` is `None` or zero, this method queries available paralleism and
+    /// uses it.
+    pub fn std_threads(num_threads_requested: Option<usize>) -> Self {
+        let num_threads_requested = num_threads_requested.unwrap_or(0);
+
+        let num_threads = if num_threads_requested == 0 {
+            let num_threads = std::thread::available_parallelism();
+            match num_threads {
+                Ok(num_threads) => num_threads.into(),
+                Err(e) => {
+                    tracing::warn!(%e, "Failed to query available parallelism; falling back to single-threaded");
+                    return Self::none();
+                }
+            }
+        } else {
+            num_threads_requested
+        };
+
+        if num_threads <= 1 {
+            return Self::none();
+        }
+
+        match std_threads::StdThreadPool::new(num_threads) {
+            Ok(pool) => {
+                tracing::debug!(num_threads, "Initialized thread pool");
+                Self::with_executor(Arc::new(pool))
+            }
+            Err(e) => {
+                tracing::warn!(%e, "Failed to initialize thread pool; falling back to single-threaded");
+                Self::none()
+            }
+        }
+    }
+
     /// Returns the reference to Rayon thread pool, if exists.
     #[cfg(feature = "rayon")]
     pub fn as_rayon_pool(&self) -> Option<&rayon_core::ThreadPool> {
         match &self.0 {
             JxlThreadPoolImpl::Rayon(pool) => Some(&**pool),
-            JxlThreadPoolImpl::None => None,
+            _ => None,
+        }
+    }
+
+    /// Returns the reference to the executor, if the thread pool is backed by [`JxlExecutor`].
+    pub fn as_executor(&self) -> Option<&dyn JxlExecutor> {
+        match &self.0 {
+            JxlThreadPoolImpl::Executor(executor) => Some(&**executor),
+            _ => None,
         }
     }
 
@@ -95,6 +155,7 @@ impl JxlThreadPool {
         match self.0 {
             #[cfg(feature = "rayon")]
             JxlThreadPoolImpl::Rayon(_) => true,
+            JxlThreadPoolImpl::Executor(ref executor) => executor.num_threads() > 1,
             JxlThreadPoolImpl::None => false,
         }
     }
@@ -106,6 +167,7 @@ impl JxlThreadPool {
         match &self.0 {
             #[cfg(feature = "rayon")]
             JxlThreadPoolImpl::Rayon(pool) => pool.spawn(op),
+            JxlThreadPoolImpl::Executor(executor) => executor.spawn(Box::new(op)),
             JxlThreadPoolImpl::None => op(),
         }
     }
@@ -121,6 +183,15 @@ impl JxlThreadPool {
                 let scope = JxlScope(JxlScopeInner::Rayon(scope));
                 op(scope)
             }),
+            JxlThreadPoolImpl::Executor(executor) => {
+                let mut ret = None;
+                let ret_mut = &mut ret;
+                executor.scope(Box::new(move |scope| {
+                    let scope = JxlScope(JxlScopeInner::Executor(scope));
+                    *ret_mut = Some(op(scope));
+                }));
+                ret.expect("executor returned before running the scope")
+            }
             JxlThreadPoolImpl::None => op(JxlScope(JxlScopeInner::None(Default::default()))),
         }
     }
@@ -130,6 +201,9 @@ impl JxlThreadPool {
         match &self.0 {
             #[cfg(feature = "rayon")]
             JxlThreadPoolImpl::Rayon(pool) => pool.install(|| par_for_each(v, op)),
+            JxlThreadPoolImpl::Executor(executor) => {
+                executor::for_each(&**executor, v.into_iter(), op)
+            }
             JxlThreadPoolImpl::None => v.into_iter().for_each(op),
         }
     }
@@ -144,6 +218,9 @@ impl JxlThreadPool {
         match &self.0 {
             #[cfg(feature = "rayon")]
             JxlThreadPoolImpl::Rayon(pool) => pool.install(|| par_for_each_with(v, init, op)),
+            JxlThreadPoolImpl::Executor(executor) => {
+                executor::for_each_with(&**executor, v.into_iter(), init, op)
+            }
             JxlThreadPoolImpl::None => {
                 let mut init = init;
                 v.into_iter().for_each(|item| op(&mut init, item))
@@ -160,6 +237,9 @@ impl JxlThreadPool {
         match &self.0 {
             #[cfg(feature = "rayon")]
             JxlThreadPoolImpl::Rayon(pool) => pool.install(|| par_for_each(v, op)),
+            JxlThreadPoolImpl::Executor(executor) => {
+                executor::for_each(&**executor, v.iter_mut(), op)
+            }
             JxlThreadPoolImpl::None => v.iter_mut().for_each(op),
         }
     }
@@ -174,6 +254,9 @@ impl JxlThreadPool {
         match &self.0 {
             #[cfg(feature = "rayon")]
             JxlThreadPoolImpl::Rayon(pool) => pool.install(|| par_for_each_with(v, init, op)),
+            JxlThreadPoolImpl::Executor(executor) => {
+                executor::for_each_with(&**executor, v.iter_mut(), init, op)
+            }
             JxlThreadPoolImpl::None => {
                 let mut init = init;
                 v.iter_mut().for_each(|item| op(&mut init, item))
@@ -210,6 +293,10 @@ impl<'scope> JxlScope<'_, 'scope> {
                 let scope = JxlScope(JxlScopeInner::Rayon(scope));
                 op(scope)
             }),
+            JxlScopeInner::Executor(scope) => scope.spawn(Box::new(|scope| {
+                let scope = JxlScope(JxlScopeInner::Executor(scope));
+                op(scope)
+            })),
             JxlScopeInner::None(_) => op(JxlScope(JxlScopeInner::None(Default::default()))),
         }
     }
@@ -0,0 +1,229 @@
+//! Thread pool backed by persistent worker threads, using only the standard library.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::{JxlExecutor, JxlExecutorScope, JxlScopedJob};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Job>,
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    cv: Condvar,
+}
+
+impl Shared {
+    fn push(&self, job: Job) {
+        self.state.lock().unwrap().queue.push_back(job);
+        self.cv.notify_all();
+    }
+
+    /// Runs queued jobs until `done` returns true.
+    ///
+    /// Threads waiting for a scope run other jobs in the meantime, so that nested scopes don't
+    /// deadlock when every worker is waiting.
+    fn run_until(&self, done: impl Fn() -> bool) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if done() {
+                return;
+            }
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().unwrap();
+            } else {
+                state = self.cv.wait(state).unwrap();
+            }
+        }
+    }
+
+    fn worker_loop(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().unwrap();
+            } else if state.shutdown {
+                return;
+            } else {
+                state = self.cv.wait(state).unwrap();
+            }
+        }
+    }
+}
+
+/// [`JxlExecutor`] backed by persistent worker threads.
+pub(crate) struct StdThreadPool {
+    shared: Arc<Shared>,
+    num_threads: usize,
+}
+
+impl StdThreadPool {
+    /// Spawns `num_threads` worker threads.
+    pub(crate) fn new(num_threads: usize) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared::default());
+        let pool = Self {
+            shared: Arc::clone(&shared),
+            num_threads,
+        };
+
+        for idx in 0..num_threads {
+            let shared = Arc::clone(&shared);
+            // Workers are detached; they exit after the pool is dropped and the queue is drained.
+            std::thread::Builder::new()
+                .name(format!("jxl-oxide-worker-{idx}"))
+                .spawn(move || shared.worker_loop())?;
+        }
+
+        Ok(pool)
+    }
+}
+
+impl Drop for StdThreadPool {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.cv.notify_all();
+    }
+}
+
+impl JxlExecutor for StdThreadPool {
+    fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    fn spawn(&self, job: Box<dyn FnOnce() + Send + 'static>) {
+        self.shared.push(Box::new(move || {
+            if std::panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                tracing::error!("Job spawned on thread pool panicked");
+            }
+        }));
+    }
+
+    fn scope<'scope>(&self, op: JxlScopedJob<'scope, '_>) {
+        let scope = StdScope {
+            shared: &self.shared,
+            state: Arc::new(ScopeState::default()),
+            _marker: PhantomData,
+        };
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| op(&scope)));
+        self.shared
+            .run_until(|| scope.state.pending.load(Ordering::Acquire) == 0);
+
+        if let Err(payload) = result {
+            std::panic::resume_unwind(payload);
+        }
+        let payload = scope.state.panic.lock().unwrap().take();
+        if let Some(payload) = payload {
+            std::panic::resume_unwind(payload);
+        }
+    }
+}
+
+#[derive(Default)]
+struct ScopeState {
+    pending: AtomicUsize,
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+struct StdScope<'a, 'scope> {
+    shared: &'a Arc<Shared>,
+    state: Arc<ScopeState>,
+    _marker: PhantomData<fn(&'scope ()) -> &'scope ()>,
+}
+
+impl<'scope> JxlExecutorScope<'scope> for StdScope<'_, 'scope> {
+    fn spawn(&self, job: JxlScopedJob<'scope, 'scope>) {
+        self.state.pending.fetch_add(1, Ordering::AcqRel);
+
+        let shared = Arc::clone(self.shared);
+        let state = Arc::clone(&self.state);
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            let scope = StdScope {
+                shared: &shared,
+                state: Arc::clone(&state),
+                _marker: PhantomData,
+            };
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| job(&scope)));
+            if let Err(payload) = result {
+                state.panic.lock().unwrap().get_or_insert(payload);
+            }
+
+            // Decrement while holding the lock, so that waiters don't miss the wakeup.
+            let guard = shared.state.lock().unwrap();
+            state.pending.fetch_sub(1, Ordering::AcqRel);
+            drop(guard);
+            shared.cv.notify_all();
+        });
+
+        // SAFETY: `StdThreadPool::scope` doesn't return until every job spawned in the scope
+        // completes, so data borrowed for `'scope` outlives the job.
+        let job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(job) };
+        self.shared.push(job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::JxlThreadPool;
+
+    #[test]
+    fn nested_scopes() {
+        let pool = JxlThreadPool::std_threads(Some(2));
+        assert!(pool.is_multithreaded());
+
+        // Every worker waits for a nested scope; waiting threads should run queued jobs.
+        let count = AtomicUsize::new(0);
+        pool.scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|_| {
+                    pool.scope(|scope| {
+                        for _ in 0..8 {
+                            scope.spawn(|_| {
+                                count.fetch_add(1, Ordering::Relaxed);
+                            });
+                        }
+                    });
+                });
+            }
+        });
+        assert_eq!(count.load(Ordering::Relaxed), 64);
+
+        let mut v = (0..1000).collect::<Vec<usize>>();
+        pool.for_each_mut_slice_with(&mut v, 2usize, |mul, v| *v *= *mul);
+        assert!(v.iter().enumerate().all(|(idx, &v)| v == idx * 2));
+    }
+
+    #[test]
+    fn panic_in_scope() {
+        let pool = JxlThreadPool::std_threads(Some(2));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            pool.scope(|scope| {
+                scope.spawn(|_| panic!("job panicked"));
+            });
+        }));
+        assert!(result.is_err());
+
+        // The pool is still usable after a panic.
+        let count = AtomicUsize::new(0);
+        pool.for_each_vec(vec![1usize; 16], |v| {
+            count.fetch_add(v, Ordering::Relaxed);
+        });
+        assert_eq!(count.load(Ordering::Relaxed), 16);
+    }
+}
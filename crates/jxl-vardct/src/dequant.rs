@@ -1,5 +1,5 @@
 use jxl_bitstream::{Bitstream, Bundle, BundleDefault};
-use jxl_grid::AllocTracker;
+use jxl_grid::{AllocCategory, AllocTracker};
 use jxl_modular::{Modular, ModularParams};
 
 use crate::{Result, TransformType};
@@ -482,6 +482,8 @@ impl Bundle<DequantMatrixSetParams<'_, '_, '_>> for DequantMatrixParams {
             tracker,
             pool,
         } = params;
+        let tracker = tracker.map(|tracker| tracker.with_category(AllocCategory::VarDctCoeff));
+        let tracker = tracker.as_ref();
 
         let encoding_mode = bitstream.read_bits(3)?;
         if encoding_mode != 0 {
@@ -1,7 +1,7 @@
 use std::sync::atomic::AtomicI32;
 
 use jxl_bitstream::Bitstream;
-use jxl_grid::{AllocTracker, SharedSubgrid};
+use jxl_grid::{AllocCategory, AllocTracker, SharedSubgrid};
 use jxl_modular::{ChannelShift, Sample};
 
 use crate::{BlockInfo, HfBlockContext, HfPass, Result};
@@ -47,6 +47,8 @@ pub fn write_hf_coeff<S: Sample>(
         coeff_shift,
         tracker,
     } = params;
+    let tracker = tracker.map(|tracker| tracker.with_category(AllocCategory::VarDctCoeff));
+    let tracker = tracker.as_ref();
     let mut dist = hf_pass.clone_decoder();
 
     let HfBlockContext {
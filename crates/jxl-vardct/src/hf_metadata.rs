@@ -1,5 +1,5 @@
 use jxl_bitstream::{Bitstream, Bundle};
-use jxl_grid::{AlignedGrid, AllocTracker};
+use jxl_grid::{AlignedGrid, AllocCategory, AllocTracker};
 use jxl_modular::{MaConfig, Modular, ModularChannelParams, ModularParams};
 
 use crate::{Result, TransformType};
@@ -65,6 +65,8 @@ impl Bundle<HfMetadataParams<'_, '_, '_>> for HfMetadata {
             tracker,
             pool,
         } = params;
+        let tracker = tracker.map(|tracker| tracker.with_category(AllocCategory::VarDctCoeff));
+        let tracker = tracker.as_ref();
 
         let mut bw = ((lf_width + 7) / 8) as usize;
         let mut bh = ((lf_height + 7) / 8) as usize;
@@ -1,18 +1,25 @@
 use jxl_bitstream::{read_bits, Bitstream, Bundle};
 use jxl_coding::Decoder;
+use jxl_grid::{AllocHandle, AllocTracker};
 
 /// Parameters for decoding `HfPass`.
 #[derive(Debug, Copy, Clone)]
 pub struct HfPassParams<'a> {
     hf_block_ctx: &'a crate::HfBlockContext,
     num_hf_presets: u32,
+    tracker: Option<&'a AllocTracker>,
 }
 
 impl<'a> HfPassParams<'a> {
-    pub fn new(hf_block_ctx: &'a crate::HfBlockContext, num_hf_presets: u32) -> Self {
+    pub fn new(
+        hf_block_ctx: &'a crate::HfBlockContext,
+        num_hf_presets: u32,
+        tracker: Option<&'a AllocTracker>,
+    ) -> Self {
         Self {
             hf_block_ctx,
             num_hf_presets,
+            tracker,
         }
     }
 }
@@ -25,6 +32,7 @@ impl<'a> HfPassParams<'a> {
 pub struct HfPass {
     permutation: [[Vec<(u16, u16)>; 3]; 13],
     hf_dist: Decoder,
+    _alloc_handle: Option<AllocHandle>,
 }
 
 impl Bundle<HfPassParams<'_>> for HfPass {
@@ -34,6 +42,7 @@ impl Bundle<HfPassParams<'_>> for HfPass {
         let HfPassParams {
             hf_block_ctx,
             num_hf_presets,
+            tracker,
         } = params;
         let mut used_orders = read_bits!(bitstream, U32(0x5F, 0x13, 0x00, u(13)))?;
         let mut decoder = (used_orders != 0)
@@ -62,14 +71,20 @@ impl Bundle<HfPassParams<'_>> for HfPass {
             decoder.finalize()?;
         }
 
-        let hf_dist = Decoder::parse(
-            bitstream,
-            495 * num_hf_presets * hf_block_ctx.num_block_clusters,
-        )?;
+        let num_dist = 495u64 * num_hf_presets as u64 * hf_block_ctx.num_block_clusters as u64;
+        let num_dist = u32::try_from(num_dist).map_err(|_| {
+            jxl_bitstream::Error::ProfileConformance("too many HF coefficient distributions")
+        })?;
+        // The cluster map of the distribution has an entry for each context.
+        let alloc_handle = tracker
+            .map(|tracker| tracker.alloc::<u8>(num_dist as usize))
+            .transpose()?;
+        let hf_dist = Decoder::parse(bitstream, num_dist)?;
 
         Ok(Self {
             permutation,
             hf_dist,
+            _alloc_handle: alloc_handle,
         })
     }
 }
@@ -1,5 +1,5 @@
 use jxl_bitstream::{define_bundle, read_bits, Bitstream, Bundle};
-use jxl_grid::AllocTracker;
+use jxl_grid::{AllocCategory, AllocTracker};
 use jxl_modular::{ChannelShift, MaConfig, Modular, ModularParams, Sample};
 
 use crate::Result;
@@ -151,6 +151,8 @@ impl<S: Sample> Bundle<LfCoeffParams<'_, '_, '_>> for LfCoeff<S> {
             tracker,
             pool,
         } = params;
+        let tracker = tracker.map(|tracker| tracker.with_category(AllocCategory::VarDctCoeff));
+        let tracker = tracker.as_ref();
 
         let extra_precision = bitstream.read_bits(2)? as u8;
 
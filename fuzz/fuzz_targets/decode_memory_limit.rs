@@ -0,0 +1,16 @@
+#![no_main]
+
+const DIM_LIMIT: u32 = 65536;
+
+// 128 MiB
+const SIZE_LIMIT: usize = 128 * 1024 * 1024;
+
+// Allocations not tracked by `AllocTracker`, such as the input buffer and small metadata.
+const UNTRACKED_LIMIT: usize = 32 * 1024 * 1024;
+
+#[global_allocator]
+static ALLOC: jxl_oxide_fuzz::CountingAllocator = jxl_oxide_fuzz::CountingAllocator;
+
+libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+    jxl_oxide_fuzz::fuzz_decode_memory_limit(data, DIM_LIMIT, SIZE_LIMIT, UNTRACKED_LIMIT);
+});